@@ -1,12 +1,13 @@
 use axum::{
     extract::{Json, State, ws::{WebSocketUpgrade, WebSocket, Message}},
     response::IntoResponse,
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
 };
 use serde_json::json;
-use shared::{RoomConfig, ChatMessage, ServerMessage, Participant, ClientMessage};
+use shared::{RoomConfig, ChatMessage, ServerMessage, Participant, ClientMessage, AuditAction, AuditEntry, RecordingConsent, CaptionSegment, FileAttachment, ContentFlag, ContentKind, Question, sort_questions};
 use std::sync::Arc;
 use crate::AppState;
+use crate::chat;
 use futures::{sink::SinkExt, stream::StreamExt};
 
 pub async fn health_check() -> impl IntoResponse {
@@ -35,6 +36,14 @@ pub async fn create_room(
         let mut polls = state.polls.lock().unwrap();
         polls.clear();
     }
+    {
+        let mut clocks = state.quiz_clocks.lock().unwrap();
+        clocks.clear();
+    }
+    {
+        let mut questions = state.questions.lock().unwrap();
+        questions.clear();
+    }
     {
         let mut wb = state.whiteboard.lock().unwrap();
         wb.clear();
@@ -43,6 +52,10 @@ pub async fn create_room(
         let mut ch = state.chat_history.lock().unwrap();
         ch.clear();
     }
+    {
+        let mut rs = state.resumable_sessions.lock().unwrap();
+        rs.clear();
+    }
     {
         let mut br = state.breakout_rooms.lock().unwrap();
         br.clear();
@@ -59,6 +72,19 @@ pub async fn create_room(
         let mut s = state.speaking_start_times.lock().unwrap();
         s.clear();
     }
+    {
+        let mut c = state.recording_consents.lock().unwrap();
+        c.clear();
+    }
+    {
+        let mut a = state.audit_log.lock().unwrap();
+        a.clear();
+    }
+    {
+        let mut c = state.captions.lock().unwrap();
+        c.clear();
+    }
+    crate::files::purge(&state.files).await;
 
     let room_id = format!("room-{}", uuid::Uuid::new_v4());
 
@@ -71,6 +97,297 @@ pub async fn create_room(
     (StatusCode::CREATED, Json(response))
 }
 
+pub async fn audit_log(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+    let log = state.audit_log.lock().unwrap().clone();
+    Json(log).into_response()
+}
+
+// How long the token of a closed connection can resume its ID or export the meeting
+const RESUMABLE_SESSION_TTL_MS: u64 = 60 * 60 * 1000;
+
+fn still_resumable(left_at: u64, now_ms: u64) -> bool {
+    now_ms.saturating_sub(left_at) < RESUMABLE_SESSION_TTL_MS
+}
+
+fn bearer_token(headers: &HeaderMap) -> Result<&str, StatusCode> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// Resolves the `Authorization: Bearer <session token>` header of a REST call.
+/// Tokens are valid while the participant's socket is open. Returns the participant ID.
+pub fn require_session(state: &AppState, headers: &HeaderMap) -> Result<String, StatusCode> {
+    let token = bearer_token(headers)?;
+    let tokens = state.session_tokens.lock().unwrap();
+    tokens.get(token).cloned().ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// Like `require_session`, but also accepts the token of a participant who left
+/// within `RESUMABLE_SESSION_TTL_MS`, so transcripts and chat can still be exported
+/// after the meeting.
+pub fn require_export_session(state: &AppState, headers: &HeaderMap) -> Result<String, StatusCode> {
+    match require_session(state, headers) {
+        Err(StatusCode::UNAUTHORIZED) => {
+            let token = bearer_token(headers)?;
+            let now = chrono::Utc::now().timestamp_millis() as u64;
+            let resumable = state.resumable_sessions.lock().unwrap();
+            resumable
+                .get(token)
+                .filter(|(_, left_at)| still_resumable(*left_at, now))
+                .map(|(id, _)| id.clone())
+                .ok_or(StatusCode::UNAUTHORIZED)
+        },
+        other => other,
+    }
+}
+
+/// Like `require_session`, but the token must belong to the current host.
+pub fn require_moderator(state: &AppState, headers: &HeaderMap) -> Result<String, StatusCode> {
+    let participant_id = require_session(state, headers)?;
+    let is_host = state.room_config.lock().unwrap().host_id.as_deref() == Some(participant_id.as_str());
+    if is_host {
+        Ok(participant_id)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn issue_session_token(state: &AppState, participant_id: &str) -> String {
+    let token = uuid::Uuid::new_v4().to_string();
+    state.session_tokens.lock().unwrap().insert(token.clone(), participant_id.to_string());
+    token
+}
+
+/// Everyone who sees messages in `key`: both sides of a private conversation,
+/// or whoever is in the room right now.
+fn chat_audience(state: &AppState, key: &chat::HistoryKey) -> Vec<Participant> {
+    let ids: Vec<String> = match key {
+        chat::HistoryKey::Private(a, b) => vec![a.clone(), b.clone()],
+        _ => {
+            let room_id = key.room_id();
+            let locations = state.participant_locations.lock().unwrap();
+            locations.iter().filter(|(_, loc)| **loc == room_id).map(|(id, _)| id.clone()).collect()
+        },
+    };
+    let participants = state.participants.lock().unwrap();
+    ids.iter().filter_map(|id| participants.get(id)).cloned().collect()
+}
+
+async fn send_latest_chat_page(state: &AppState, key: &chat::HistoryKey, my_id: &str, internal_tx: &tokio::sync::mpsc::Sender<ServerMessage>) {
+    let (messages, parents, has_more) = {
+        let histories = state.chat_history.lock().unwrap();
+        let history = histories.get(key);
+        let (page, has_more) = chat::history_page(history, None, chat::CHAT_PAGE_SIZE);
+        let parents = chat::missing_parents(history, &page);
+        (page, parents, has_more)
+    };
+    if !messages.is_empty() {
+        let scope = key.scope_for(my_id);
+        let _ = internal_tx.send(ServerMessage::ChatHistory { messages, parents, before: None, has_more, scope }).await;
+    }
+}
+
+/// Sends the latest page of the main room and of each of the participant's private conversations.
+async fn send_chat_history(state: &AppState, my_id: &str, internal_tx: &tokio::sync::mpsc::Sender<ServerMessage>) {
+    send_latest_chat_page(state, &chat::HistoryKey::Main, my_id, internal_tx).await;
+    let conversations = state.chat_history.lock().unwrap().private_conversations(my_id);
+    for key in conversations {
+        send_latest_chat_page(state, &key, my_id, internal_tx).await;
+    }
+}
+
+/// Decides whether a broadcast message is delivered to a participant, based on
+/// the room they are in, private recipients and host-only messages.
+fn should_forward(msg: &ServerMessage, my_id: &str, my_loc: &Option<String>, is_host: bool) -> bool {
+    match msg {
+        ServerMessage::Chat { message, room_id } => {
+            if room_id != my_loc {
+                false
+            } else if let Some(target) = &message.recipient_id {
+                target == my_id || message.user_id == my_id
+            } else {
+                true
+            }
+        },
+        // Private messages can be changed from anywhere, but only their two participants hear of it
+        ServerMessage::ChatMessageUpdated { message, room_id } => match &message.recipient_id {
+            Some(target) => target == my_id || message.user_id == my_id,
+            None => room_id == my_loc,
+        },
+        ServerMessage::PeerTyping { room_id, .. } => room_id == my_loc,
+        ServerMessage::Caption(segment) => &segment.room_id == my_loc,
+        ServerMessage::ConnectionQualityReport(_) | ServerMessage::RecordingConsents(_) | ServerMessage::ContentFlagged(_) | ServerMessage::PollResults(_) => is_host,
+        ServerMessage::ForceMute(target) => target == my_id,
+        ServerMessage::RtcSignal { to, .. } => to == my_id,
+        _ => true,
+    }
+}
+
+fn record_audit(state: &AppState, actor_id: &str, action: AuditAction) {
+    let actor_name = {
+        let participants = state.participants.lock().unwrap();
+        participants.get(actor_id).map(|p| p.name.clone()).unwrap_or_default()
+    };
+    let mut log = state.audit_log.lock().unwrap();
+    log.push(AuditEntry {
+        timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        actor_id: actor_id.to_string(),
+        actor_name,
+        action,
+    });
+}
+
+fn recording_consent_list(state: &AppState) -> Vec<RecordingConsent> {
+    let consents = state.recording_consents.lock().unwrap();
+    let mut list: Vec<RecordingConsent> = consents.values().cloned().collect();
+    list.sort_by_key(|c| c.timestamp);
+    list
+}
+
+/// Runs user-supplied text through the content filter. Flagged matches are reported to the
+/// moderators; a rejected text is reported to the sender and yields `None`.
+async fn filter_content(state: &AppState, kind: ContentKind, text: &str, user_id: &str, internal_tx: &tokio::sync::mpsc::Sender<ServerMessage>) -> Option<String> {
+    let outcome = state.content_filter.run(kind, text);
+    if outcome.rejected {
+        let _ = internal_tx.send(ServerMessage::Error(format!("This {} is not allowed", kind.label()))).await;
+        return None;
+    }
+    if !outcome.flagged.is_empty() {
+        let user_name = {
+            let participants = state.participants.lock().unwrap();
+            participants.get(user_id).map(|p| p.name.clone())
+        };
+        let _ = state.tx.send(ServerMessage::ContentFlagged(ContentFlag {
+            kind,
+            user_id: user_id.to_string(),
+            // Someone still joining is only known by the name being checked
+            user_name: user_name.unwrap_or_else(|| outcome.text.clone()),
+            text: text.to_string(),
+            matches: outcome.flagged,
+            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        }));
+    }
+    Some(outcome.text)
+}
+
+/// Filters the text of a text or sticky note element in place. False if it was rejected.
+async fn filter_whiteboard_text(state: &AppState, element: &mut shared::whiteboard::WhiteboardElement, user_id: &str, internal_tx: &tokio::sync::mpsc::Sender<ServerMessage>) -> bool {
+    let Some(text) = element.shape.text_mut() else { return true };
+    match filter_content(state, ContentKind::Whiteboard, text, user_id, internal_tx).await {
+        Some(filtered) => {
+            *text = filtered;
+            true
+        },
+        None => false,
+    }
+}
+
+/// Brings a newly admitted participant up to date with the room.
+async fn send_join_snapshot(state: &Arc<AppState>, my_id: &str, internal_tx: &tokio::sync::mpsc::Sender<ServerMessage>) {
+    // The config carries the recording state, so late joiners learn a recording is running
+    let config = state.room_config.lock().unwrap().clone();
+    let is_host = config.host_id.as_deref() == Some(my_id);
+    let _ = internal_tx.send(ServerMessage::RoomUpdated(config)).await;
+
+    let current_list: Vec<Participant> = {
+        let participants = state.participants.lock().unwrap();
+        participants.values().cloned().collect()
+    };
+    let _ = internal_tx.send(ServerMessage::ParticipantList(current_list)).await;
+
+    let knocking_list: Vec<Participant> = {
+        let knocking = state.knocking_participants.lock().unwrap();
+        knocking.values().map(|(p, _)| p.clone()).collect()
+    };
+    for p in knocking_list {
+        let _ = internal_tx.send(ServerMessage::KnockingParticipant(p)).await;
+    }
+
+    let all_rooms: Vec<shared::BreakoutRoom> = {
+        let rooms = state.breakout_rooms.lock().unwrap();
+        rooms.values().cloned().collect()
+    };
+    if !all_rooms.is_empty() {
+        let _ = internal_tx.send(ServerMessage::BreakoutRoomsList(all_rooms)).await;
+    }
+
+    let history: Vec<shared::whiteboard::WhiteboardElement> = {
+        let wb = state.whiteboard.lock().unwrap();
+        wb.clone()
+    };
+    if !history.is_empty() {
+        let _ = internal_tx.send(ServerMessage::WhiteboardHistory(history)).await;
+    }
+
+    let shared_url = {
+        state.shared_video_url.lock().unwrap().clone()
+    };
+    if let Some(url) = shared_url {
+        let _ = internal_tx.send(ServerMessage::VideoShared(url)).await;
+    }
+
+    let polls = {
+        let polls = state.polls.lock().unwrap();
+        crate::polls::poll_snapshot(polls.values(), my_id, is_host)
+    };
+    for msg in polls {
+        let _ = internal_tx.send(msg).await;
+    }
+    let questions = {
+        let mut questions: Vec<Question> = state.questions.lock().unwrap().values().cloned().collect();
+        sort_questions(&mut questions);
+        questions
+    };
+    let _ = internal_tx.send(ServerMessage::QuestionList(questions)).await;
+
+    let leaderboard = crate::polls::leaderboard_message(state);
+    if !matches!(&leaderboard, ServerMessage::QuizLeaderboard(scores) if scores.is_empty()) {
+        let _ = internal_tx.send(leaderboard).await;
+    }
+
+    if is_host {
+        let _ = internal_tx.send(ServerMessage::RecordingConsents(recording_consent_list(state))).await;
+    }
+}
+
+fn spawn_broadcast_forwarder(
+    state: &Arc<AppState>,
+    my_id: String,
+    forward_tx: tokio::sync::mpsc::Sender<ServerMessage>,
+) -> tokio::task::JoinHandle<()> {
+    let mut rx = state.tx.subscribe();
+    let state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => {
+                    let my_loc = {
+                        let locs = state.participant_locations.lock().unwrap();
+                        locs.get(&my_id).cloned().flatten()
+                    };
+                    let is_host = {
+                        state.room_config.lock().unwrap().host_id.as_deref() == Some(my_id.as_str())
+                    };
+
+                    if should_forward(&msg, &my_id, &my_loc, is_host)
+                        && forward_tx.send(msg).await.is_err() { break; }
+                },
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
 pub async fn chat_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
@@ -115,6 +432,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     let knocking_mutex = state.knocking_participants.clone();
     let room_config_mutex = state.room_config.clone();
     let polls_mutex = state.polls.clone();
+    let questions_mutex = state.questions.clone();
     let whiteboard_mutex = state.whiteboard.clone();
     let chat_history_mutex = state.chat_history.clone();
     let breakout_rooms_mutex = state.breakout_rooms.clone();
@@ -125,8 +443,14 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     // We don't have an ID yet
     let mut my_id: Option<String> = None;
     let mut knocking_id: Option<String> = None;
+    // ID of an earlier connection that the next Join takes over
+    let mut resume_id: Option<String> = None;
     // Track my current room locally for quick access
     let mut my_room_id: Option<String> = None;
+    // Start of the utterance currently being captioned
+    let mut caption_started_at: Option<u64> = None;
+    // When we last sent a chat message, for slow mode
+    let mut last_chat_sent_at: Option<u64> = None;
     let mut broadcast_task: Option<tokio::task::JoinHandle<()>> = None;
 
     // Send initial breakout rooms list
@@ -262,6 +586,18 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         }
                                     }
                                 },
+                                ClientMessage::ResumeSession(token) => {
+                                    if my_id.is_none() && knocking_id.is_none() {
+                                        let now = chrono::Utc::now().timestamp_millis() as u64;
+                                        let previous = state.resumable_sessions.lock().unwrap().remove(&token)
+                                            .filter(|(_, left_at)| still_resumable(*left_at, now))
+                                            .map(|(id, _)| id);
+                                        // Only take over an ID nobody is using, e.g. from a second tab
+                                        resume_id = previous.filter(|id| {
+                                            !participants_mutex.lock().unwrap().contains_key(id) && !knocking_mutex.lock().unwrap().contains_key(id)
+                                        });
+                                    }
+                                },
                                 ClientMessage::Join(name) => {
                                     if my_id.is_some() || knocking_id.is_some() { continue; } // Already joined or knocking
 
@@ -276,13 +612,19 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         continue;
                                     }
 
-                                    let id = uuid::Uuid::new_v4().to_string();
+                                    let id = resume_id.take().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                                    let Some(name) = filter_content(&state, ContentKind::DisplayName, &name, &id, &internal_tx).await else {
+                                        // Trying again with another name keeps the same ID
+                                        resume_id = Some(id);
+                                        continue;
+                                    };
                                     let me = Participant {
                                         id: id.clone(),
                                         name,
                                         is_hand_raised: false,
                                         is_sharing_screen: false,
                                         speaking_time: 0,
+                                        video_quality: shared::VideoQuality::default(),
                                     };
 
                                     if is_lobby && host_exists {
@@ -347,22 +689,15 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
                                     // Send Welcome with own ID
                                     let _ = internal_tx.send(ServerMessage::Welcome { id: id.clone() }).await;
+                                    let _ = internal_tx.send(ServerMessage::SessionToken(issue_session_token(&state, &id))).await;
 
-                                    // Send Chat History
-                                    let history: Vec<shared::ChatMessage> = {
-                                        let history = chat_history_mutex.lock().unwrap();
-                                        history.clone()
-                                    };
-                                    if !history.is_empty() {
-                                        let _ = internal_tx.send(ServerMessage::ChatHistory(history)).await;
-                                    }
+                                    send_chat_history(&state, &id, &internal_tx).await;
 
                                     if new_host_assigned {
                                         let new_config = {
                                             room_config_mutex.lock().unwrap().clone()
                                         };
-                                        let _ = tx.send(ServerMessage::RoomUpdated(new_config.clone()));
-                                        let _ = internal_tx.send(ServerMessage::RoomUpdated(new_config)).await;
+                                        let _ = tx.send(ServerMessage::RoomUpdated(new_config));
                                     }
 
                                     // Register initial location (Main Room)
@@ -371,109 +706,219 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         locations.insert(id.clone(), None);
                                     }
 
-                                    let mut rx = tx.subscribe();
-                                    let forward_tx = internal_tx.clone();
-                                    let my_id_clone = id.clone();
-                                    let locations_clone = participant_locations_mutex.clone();
-
-                                    broadcast_task = Some(tokio::spawn(async move {
-                                        loop {
-                                            match rx.recv().await {
-                                                Ok(msg) => {
-                                                    // Filter based on room and recipient
-                                                    let should_send = match &msg {
-                                                        ServerMessage::Chat { message, room_id } => {
-                                                            let my_loc = {
-                                                                let locs = locations_clone.lock().unwrap();
-                                                                locs.get(&my_id_clone).cloned().flatten()
-                                                            };
-                                                            if *room_id != my_loc {
-                                                                false
-                                                            } else if let Some(target) = &message.recipient_id {
-                                                                *target == my_id_clone || message.user_id == my_id_clone
-                                                            } else {
-                                                                true
-                                                            }
-                                                        },
-                                                        ServerMessage::PeerTyping { room_id, .. } => {
-                                                            let my_loc = {
-                                                                let locs = locations_clone.lock().unwrap();
-                                                                locs.get(&my_id_clone).cloned().flatten()
-                                                            };
-                                                            *room_id == my_loc
-                                                        },
-                                                        _ => true,
-                                                    };
-
-                                                    if should_send
-                                                        && forward_tx.send(msg).await.is_err() { break; }
-                                                },
-                                                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
-                                                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
-                                            }
-                                        }
-                                    }));
+                                    broadcast_task = Some(spawn_broadcast_forwarder(&state, id.clone(), internal_tx.clone()));
 
                                     let _ = tx.send(ServerMessage::ParticipantJoined(me));
 
-                                    let current_list: Vec<Participant> = {
-                                        let participants = participants_mutex.lock().unwrap();
-                                        participants.values().cloned().collect()
-                                    };
-                                    let _ = internal_tx.send(ServerMessage::ParticipantList(current_list)).await;
-
-                                    let knocking_list: Vec<Participant> = {
-                                        let knocking = knocking_mutex.lock().unwrap();
-                                        knocking.values().map(|(p, _)| p.clone()).collect()
-                                    };
-                                    for p in knocking_list {
-                                        let _ = internal_tx.send(ServerMessage::KnockingParticipant(p)).await;
-                                    }
-
-                                    let history: Vec<shared::DrawAction> = {
-                                        let wb = whiteboard_mutex.lock().unwrap();
-                                        wb.clone()
-                                    };
-                                    if !history.is_empty() {
-                                        let _ = internal_tx.send(ServerMessage::WhiteboardHistory(history)).await;
-                                    }
-
-                                    // Send Shared Video State
-                                    let shared_url = {
-                                        shared_video_mutex.lock().unwrap().clone()
-                                    };
-                                    if let Some(url) = shared_url {
-                                        let _ = internal_tx.send(ServerMessage::VideoShared(url)).await;
-                                    }
+                                    send_join_snapshot(&state, &id, &internal_tx).await;
                                 },
-                                ClientMessage::Chat { content, recipient_id, attachment } => {
-                                    if let Some(att) = &attachment {
-                                        // Bug 5: Don't trust att.size from client.
-                                        // Max encoded size for 2MB file: 2 * 1024 * 1024 * 4 / 3 = 2,796,202 bytes.
-                                        // Round up to 3MB (3 * 1024 * 1024) for safety.
-                                        if att.content_base64.len() > 3 * 1024 * 1024 {
-                                            let _ = internal_tx.send(ServerMessage::Error("File too large".to_string())).await;
+                                ClientMessage::Chat { content, recipient_id, mut attachment, parent_id } => {
+                                    if let Some(uid) = &my_id {
+                                        let now = chrono::Utc::now().timestamp_millis() as u64;
+                                        let allowed = chat::check_can_send(&room_config_mutex.lock().unwrap(), uid, last_chat_sent_at, now);
+                                        if let Err(reason) = allowed {
+                                            let _ = internal_tx.send(ServerMessage::Error(reason)).await;
                                             continue;
                                         }
                                     }
+                                    if let Err(e) = chat::check_content(&content) {
+                                        let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await;
+                                        continue;
+                                    }
+                                    if let Some(att) = attachment.take() {
+                                        // Only the name and thumbnail come from the client; size, type and hash are what the store recorded
+                                        let stored = state.files.lock().unwrap().get(&att.id).map(|f| f.attachment.clone());
+                                        match stored {
+                                            Some(stored) => {
+                                                let thumbnail = att.thumbnail.filter(|t| stored.mime_type.starts_with("image/") && crate::files::is_valid_thumbnail(t));
+                                                attachment = Some(FileAttachment { filename: crate::files::sanitize_filename(&att.filename), thumbnail, ..stored });
+                                            },
+                                            None => {
+                                                let _ = internal_tx.send(ServerMessage::Error("File not found".to_string())).await;
+                                                continue;
+                                            },
+                                        }
+                                    }
                                     if let Some(uid) = &my_id {
+                                        // Private histories are only started with someone who is here
+                                        if let Some(recipient) = &recipient_id {
+                                            let known = recipient != uid && participants_mutex.lock().unwrap().contains_key(recipient);
+                                            if !known {
+                                                let _ = internal_tx.send(ServerMessage::Error("Recipient not found".to_string())).await;
+                                                continue;
+                                            }
+                                        }
+                                        let key = match &recipient_id {
+                                            Some(recipient) => chat::HistoryKey::private(uid, recipient),
+                                            None => chat::HistoryKey::room(&my_room_id),
+                                        };
+                                        // Replies must point at a message in the same conversation
+                                        if let Some(pid) = &parent_id {
+                                            let known = chat_history_mutex.lock().unwrap().get(&key).iter().any(|m| &m.id == pid);
+                                            if !known {
+                                                let _ = internal_tx.send(ServerMessage::Error(chat::ChatEditError::NotFound.message().to_string())).await;
+                                                continue;
+                                            }
+                                        }
+                                        let Some(content) = filter_content(&state, ContentKind::Chat, &content, uid, &internal_tx).await else { continue };
+                                        // Only people who will see the message can be mentioned in it
+                                        let audience = chat_audience(&state, &key);
+                                        let mentions = shared::mentions::find_mentions(&content, audience.iter().filter(|p| p.id != *uid));
+                                        // Kept with the history so exports can name people who have left
+                                        let names: Vec<(String, String)> = {
+                                            let participants = participants_mutex.lock().unwrap();
+                                            std::iter::once(uid).chain(recipient_id.as_ref())
+                                                .filter_map(|id| participants.get(id))
+                                                .map(|p| (p.id.clone(), p.name.clone()))
+                                                .collect()
+                                        };
                                         let chat_msg = ChatMessage {
+                                            id: uuid::Uuid::new_v4().to_string(),
                                             user_id: uid.clone(),
                                             content,
                                             recipient_id: recipient_id.clone(),
                                             timestamp: chrono::Utc::now().timestamp_millis() as u64,
                                             attachment,
+                                            edited_at: None,
+                                            deleted: false,
+                                            reactions: Default::default(),
+                                            parent_id,
+                                            mentions,
                                         };
-                                        if recipient_id.is_none() && my_room_id.is_none() {
-                                            let mut history = chat_history_mutex.lock().unwrap();
-                                            history.push(chat_msg.clone());
+                                        {
+                                            let mut histories = chat_history_mutex.lock().unwrap();
+                                            histories.push(key, chat_msg.clone(), state.chat_history_limit);
+                                            for (id, name) in &names {
+                                                histories.remember_name(id, name);
+                                            }
                                         }
+                                        last_chat_sent_at = Some(chat_msg.timestamp);
                                         let _ = tx.send(ServerMessage::Chat {
                                             message: chat_msg,
                                             room_id: my_room_id.clone()
                                         });
                                     }
                                 },
+                                ClientMessage::Caption { text, is_final, lang } => {
+                                    if let Some(uid) = &my_id {
+                                        let text: String = text.trim().chars().take(crate::captions::MAX_CAPTION_CHARS).collect();
+                                        if text.is_empty() {
+                                            continue;
+                                        }
+                                        if !crate::captions::is_valid_lang(&lang) {
+                                            let _ = internal_tx.send(ServerMessage::Error("Invalid caption language".to_string())).await;
+                                            continue;
+                                        }
+                                        let speaker_name = {
+                                            let participants = participants_mutex.lock().unwrap();
+                                            participants.get(uid).map(|p| p.name.clone()).unwrap_or_default()
+                                        };
+                                        let now = chrono::Utc::now().timestamp_millis() as u64;
+                                        let start_ms = *caption_started_at.get_or_insert(now);
+                                        let segment = CaptionSegment {
+                                            id: uuid::Uuid::new_v4().to_string(),
+                                            user_id: uid.clone(),
+                                            speaker_name,
+                                            text,
+                                            is_final,
+                                            lang,
+                                            start_ms,
+                                            end_ms: now,
+                                            room_id: my_room_id.clone(),
+                                        };
+                                        if is_final {
+                                            caption_started_at = None;
+                                            let mut captions = state.captions.lock().unwrap();
+                                            crate::captions::store_caption(&mut captions, segment.clone());
+                                        }
+                                        let _ = tx.send(ServerMessage::Caption(segment));
+                                    }
+                                },
+                                ClientMessage::FetchChatHistory { before, limit, scope } => {
+                                    if let Some(uid) = &my_id {
+                                        let key = chat::HistoryKey::from_scope(&scope, uid);
+                                        if !key.is_visible_to(uid, &my_room_id) {
+                                            continue;
+                                        }
+                                        let limit = (limit as usize).clamp(1, chat::MAX_CHAT_PAGE_SIZE);
+                                        let (messages, parents, has_more) = {
+                                            let histories = chat_history_mutex.lock().unwrap();
+                                            let history = histories.get(&key);
+                                            let (page, has_more) = chat::history_page(history, before.as_deref(), limit);
+                                            let parents = chat::missing_parents(history, &page);
+                                            (page, parents, has_more)
+                                        };
+                                        let _ = internal_tx.send(ServerMessage::ChatHistory { messages, parents, before, has_more, scope }).await;
+                                    }
+                                },
+                                ClientMessage::EditMessage { message_id, content } => {
+                                    if let Some(uid) = &my_id {
+                                        let (is_host, restriction) = {
+                                            let config = room_config_mutex.lock().unwrap();
+                                            let is_host = config.host_id == Some(uid.clone());
+                                            (is_host, config.chat_restriction(uid, is_host))
+                                        };
+                                        // Editing would otherwise be a way around a mute
+                                        if let Some(restriction) = restriction {
+                                            let _ = internal_tx.send(ServerMessage::Error(restriction.message().to_string())).await;
+                                            continue;
+                                        }
+                                        if let Err(e) = chat::check_content(&content) {
+                                            let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await;
+                                            continue;
+                                        }
+                                        let Some(content) = filter_content(&state, ContentKind::Chat, &content, uid, &internal_tx).await else { continue };
+                                        let located = chat_history_mutex.lock().unwrap().locate(&message_id, uid, &my_room_id);
+                                        let Some(key) = located else {
+                                            let _ = internal_tx.send(ServerMessage::Error(chat::ChatEditError::NotFound.message().to_string())).await;
+                                            continue;
+                                        };
+                                        // Mentions follow the new text
+                                        let audience = chat_audience(&state, &key);
+                                        let now = chrono::Utc::now().timestamp_millis() as u64;
+                                        let result = {
+                                            let mut histories = chat_history_mutex.lock().unwrap();
+                                            chat::edit_message(histories.get_mut(&key), &message_id, uid, is_host, content, &audience, now).map(|m| (m, key.room_id()))
+                                        };
+                                        match result {
+                                            Ok((message, room_id)) => { let _ = tx.send(ServerMessage::ChatMessageUpdated { message, room_id }); },
+                                            Err(e) => { let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await; },
+                                        }
+                                    }
+                                },
+                                ClientMessage::DeleteMessage(message_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        let result = {
+                                            let mut histories = chat_history_mutex.lock().unwrap();
+                                            match histories.locate(&message_id, uid, &my_room_id) {
+                                                Some(key) => chat::delete_message(histories.get_mut(&key), &message_id, uid, is_host).map(|m| (m, key.room_id())),
+                                                None => Err(chat::ChatEditError::NotFound),
+                                            }
+                                        };
+                                        match result {
+                                            Ok((message, room_id)) => { let _ = tx.send(ServerMessage::ChatMessageUpdated { message, room_id }); },
+                                            Err(e) => { let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await; },
+                                        }
+                                    }
+                                },
+                                ClientMessage::ToggleMessageReaction { message_id, emoji } => {
+                                    if let Some(uid) = &my_id {
+                                        let result = {
+                                            let mut histories = chat_history_mutex.lock().unwrap();
+                                            match histories.locate(&message_id, uid, &my_room_id) {
+                                                Some(key) => chat::toggle_reaction(histories.get_mut(&key), &message_id, uid, &emoji).map(|m| (m, key.room_id())),
+                                                None => Err(chat::ChatEditError::NotFound),
+                                            }
+                                        };
+                                        match result {
+                                            Ok((message, room_id)) => { let _ = tx.send(ServerMessage::ChatMessageUpdated { message, room_id }); },
+                                            Err(e) => { let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await; },
+                                        }
+                                    }
+                                },
                                 ClientMessage::ToggleRoomLock => {
                                     if let Some(uid) = &my_id {
                                         let is_host = {
@@ -500,66 +945,463 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                                 config.is_recording = !config.is_recording;
                                                 config.clone()
                                             };
+                                            // Consent is collected per recording; the host consents by starting it
+                                            {
+                                                let mut consents = state.recording_consents.lock().unwrap();
+                                                consents.clear();
+                                                if new_config.is_recording {
+                                                    consents.insert(uid.clone(), RecordingConsent {
+                                                        user_id: uid.clone(),
+                                                        accepted: true,
+                                                        timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                                                    });
+                                                }
+                                            }
+                                            let action = if new_config.is_recording { AuditAction::RecordingStarted } else { AuditAction::RecordingStopped };
+                                            record_audit(&state, uid, action);
                                             let _ = tx.send(ServerMessage::RoomUpdated(new_config));
+                                            let _ = tx.send(ServerMessage::RecordingConsents(recording_consent_list(&state)));
                                         }
                                     }
                                 },
-                                ClientMessage::CreatePoll(mut poll) => {
+                                ClientMessage::RecordingConsent(accepted) => {
+                                    if let Some(uid) = &my_id {
+                                        let (is_recording, auto_mute) = {
+                                            let config = room_config_mutex.lock().unwrap();
+                                            (config.is_recording, config.auto_mute_decliners)
+                                        };
+                                        if !is_recording {
+                                            continue;
+                                        }
+                                        {
+                                            let mut consents = state.recording_consents.lock().unwrap();
+                                            consents.insert(uid.clone(), RecordingConsent {
+                                                user_id: uid.clone(),
+                                                accepted,
+                                                timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                                            });
+                                        }
+                                        let action = if accepted { AuditAction::RecordingConsentGiven } else { AuditAction::RecordingConsentDeclined };
+                                        record_audit(&state, uid, action);
+                                        let _ = tx.send(ServerMessage::RecordingConsents(recording_consent_list(&state)));
+                                        if !accepted && auto_mute {
+                                            let _ = tx.send(ServerMessage::ForceMute(uid.clone()));
+                                        }
+                                    }
+                                },
+                                ClientMessage::ToggleAutoMuteDecliners => {
                                     if let Some(uid) = &my_id {
                                         let is_host = {
                                             room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
                                         };
                                         if is_host {
-                                            if poll.id.is_empty() {
-                                                poll.id = uuid::Uuid::new_v4().to_string();
+                                            let new_config = {
+                                                let mut config = room_config_mutex.lock().unwrap();
+                                                config.auto_mute_decliners = !config.auto_mute_decliners;
+                                                config.clone()
+                                            };
+                                            let _ = tx.send(ServerMessage::RoomUpdated(new_config.clone()));
+                                            // Apply to people who already declined
+                                            if new_config.auto_mute_decliners {
+                                                for consent in recording_consent_list(&state) {
+                                                    if !consent.accepted {
+                                                        let _ = tx.send(ServerMessage::ForceMute(consent.user_id));
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::SetChatMode(mode) => {
+                                    if let Some(uid) = &my_id {
+                                        let new_config = {
+                                            let mut config = room_config_mutex.lock().unwrap();
+                                            if config.host_id == Some(uid.clone()) {
+                                                config.chat_mode = mode;
+                                                Some(config.clone())
+                                            } else {
+                                                None
+                                            }
+                                        };
+                                        if let Some(new_config) = new_config {
+                                            let _ = tx.send(ServerMessage::RoomUpdated(new_config));
+                                        }
+                                    }
+                                },
+                                ClientMessage::SetChatSlowMode(secs) => {
+                                    if let Some(uid) = &my_id {
+                                        let new_config = {
+                                            let mut config = room_config_mutex.lock().unwrap();
+                                            if config.host_id == Some(uid.clone()) {
+                                                config.chat_slow_mode_secs = secs.min(chat::MAX_SLOW_MODE_SECS);
+                                                Some(config.clone())
+                                            } else {
+                                                None
+                                            }
+                                        };
+                                        if let Some(new_config) = new_config {
+                                            let _ = tx.send(ServerMessage::RoomUpdated(new_config));
+                                        }
+                                    }
+                                },
+                                ClientMessage::SetChatMuted { participant_id, muted } => {
+                                    if let Some(uid) = &my_id {
+                                        let new_config = {
+                                            let mut config = room_config_mutex.lock().unwrap();
+                                            if config.host_id == Some(uid.clone()) && participant_id != *uid {
+                                                config.chat_muted.retain(|id| *id != participant_id);
+                                                if muted {
+                                                    config.chat_muted.push(participant_id);
+                                                }
+                                                Some(config.clone())
+                                            } else {
+                                                None
+                                            }
+                                        };
+                                        if let Some(new_config) = new_config {
+                                            let _ = tx.send(ServerMessage::RoomUpdated(new_config));
+                                        }
+                                    }
+                                },
+                                ClientMessage::SetTimer(secs) => {
+                                    if let Some(uid) = &my_id {
+                                        let new_config = {
+                                            let mut config = room_config_mutex.lock().unwrap();
+                                            if config.host_id == Some(uid.clone()) {
+                                                let now = chrono::Utc::now().timestamp_millis() as u64;
+                                                config.timer_ends_at = (secs > 0).then(|| now + u64::from(secs.min(shared::MAX_TIMER_SECS)) * 1000);
+                                                Some(config.clone())
+                                            } else {
+                                                None
+                                            }
+                                        };
+                                        if let Some(new_config) = new_config {
+                                            let _ = tx.send(ServerMessage::RoomUpdated(new_config));
+                                        }
+                                    }
+                                },
+                                ClientMessage::CreatePoll(poll) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let now = chrono::Utc::now().timestamp_millis() as u64;
+                                            let mut poll = match crate::polls::new_poll(poll, now) {
+                                                Ok(poll) => poll,
+                                                Err(e) => {
+                                                    let _ = internal_tx.send(ServerMessage::Error(e.message())).await;
+                                                    continue;
+                                                },
+                                            };
+                                            let Some(question) = filter_content(&state, ContentKind::PollQuestion, &poll.question, uid, &internal_tx).await else { continue };
+                                            poll.question = question;
+                                            let mut rejected = false;
+                                            for option in &mut poll.options {
+                                                match filter_content(&state, ContentKind::PollQuestion, &option.text, uid, &internal_tx).await {
+                                                    Some(text) => option.text = text,
+                                                    None => { rejected = true; break; },
+                                                }
+                                            }
+                                            if rejected {
+                                                continue;
                                             }
 
                                             {
                                                 let mut polls = polls_mutex.lock().unwrap();
                                                 polls.insert(poll.id.clone(), poll.clone());
                                             }
-                                            let _ = tx.send(ServerMessage::PollCreated(poll));
+                                            if poll.quiz {
+                                                state.quiz_clocks.lock().unwrap().insert(poll.id.clone(), crate::polls::QuizClock::new(now));
+                                            }
+                                            if let Some(closes_at) = poll.closes_at {
+                                                crate::polls::schedule_close(state.clone(), poll.id.clone(), closes_at);
+                                            }
+                                            crate::polls::broadcast_poll(&tx, &poll, true);
                                         }
                                     }
                                 },
-                                ClientMessage::Vote { poll_id, option_id } => {
+                                ClientMessage::Vote { poll_id, option_ids } => {
                                     if let Some(uid) = &my_id {
-                                        let updated_poll = {
+                                        let allow_change = room_config_mutex.lock().unwrap().poll_vote_changes;
+                                        let now = chrono::Utc::now().timestamp_millis() as u64;
+                                        let name = participants_mutex.lock().unwrap().get(uid).map(|p| p.name.clone());
+                                        let result = {
                                             let mut polls = polls_mutex.lock().unwrap();
-                                            if let Some(poll) = polls.get_mut(&poll_id) {
-                                                if poll.voters.contains(uid) {
-                                                    None
-                                                } else {
-                                                    poll.voters.insert(uid.clone());
-                                                    for opt in &mut poll.options {
-                                                        if opt.id == option_id {
-                                                            opt.votes += 1;
-                                                        }
+                                            match polls.get_mut(&poll_id) {
+                                                Some(poll) => crate::polls::cast_vote(poll, uid, &option_ids, allow_change, now).map(|chosen| {
+                                                    if let Some(name) = name {
+                                                        poll.voter_names.insert(uid.clone(), name);
+                                                    }
+                                                    if let Some(clock) = state.quiz_clocks.lock().unwrap().get_mut(&poll.id) {
+                                                        clock.answered(uid, now);
                                                     }
-                                                    Some(poll.clone())
+                                                    (poll.clone(), chosen)
+                                                }),
+                                                None => Err(crate::polls::PollError::NotFound),
+                                            }
+                                        };
+
+                                        match result {
+                                            Ok((poll, option_ids)) => {
+                                                let _ = internal_tx.send(ServerMessage::PollVoteRecorded { poll_id, option_ids }).await;
+                                                crate::polls::broadcast_poll(&tx, &poll, false);
+                                            },
+                                            Err(e) => {
+                                                let _ = internal_tx.send(ServerMessage::Error(e.message())).await;
+                                            },
+                                        }
+                                    }
+                                },
+                                ClientMessage::ClosePoll(poll_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let closed = {
+                                                let mut polls = polls_mutex.lock().unwrap();
+                                                polls.get_mut(&poll_id).filter(|p| !p.closed).map(|poll| {
+                                                    poll.closed = true;
+                                                    poll.clone()
+                                                })
+                                            };
+                                            if let Some(poll) = closed {
+                                                crate::polls::broadcast_poll(&tx, &poll, false);
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::ReopenPoll(poll_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            // The time limit, if any, starts over
+                                            let now = chrono::Utc::now().timestamp_millis() as u64;
+                                            let reopened = {
+                                                let mut polls = polls_mutex.lock().unwrap();
+                                                // Revealed quiz answers can't be taken back
+                                                polls.get_mut(&poll_id).filter(|p| !p.is_open(now) && !p.revealed).map(|poll| {
+                                                    poll.closed = false;
+                                                    poll.closes_at = poll.time_limit_secs.map(|secs| now + u64::from(secs) * 1000);
+                                                    poll.clone()
+                                                })
+                                            };
+                                            if let Some(poll) = reopened {
+                                                if let Some(closes_at) = poll.closes_at {
+                                                    crate::polls::schedule_close(state.clone(), poll.id.clone(), closes_at);
                                                 }
+                                                crate::polls::broadcast_poll(&tx, &poll, false);
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::DeletePoll(poll_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let removed = polls_mutex.lock().unwrap().remove(&poll_id);
+                                            state.quiz_clocks.lock().unwrap().remove(&poll_id);
+                                            if let Some(poll) = removed {
+                                                let _ = tx.send(ServerMessage::PollDeleted(poll_id));
+                                                if poll.quiz && poll.revealed {
+                                                    let _ = tx.send(crate::polls::leaderboard_message(&state));
+                                                }
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::RevealPollAnswers(poll_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let revealed = {
+                                                let mut polls = polls_mutex.lock().unwrap();
+                                                polls.get_mut(&poll_id).filter(|p| p.quiz && !p.revealed).map(|poll| {
+                                                    poll.revealed = true;
+                                                    poll.closed = true;
+                                                    poll.clone()
+                                                })
+                                            };
+                                            if let Some(poll) = revealed {
+                                                crate::polls::broadcast_poll(&tx, &poll, false);
+                                                let _ = tx.send(crate::polls::leaderboard_message(&state));
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::AskQuestion { text, anonymous } => {
+                                    if let Some(uid) = &my_id {
+                                        let author = participants_mutex.lock().unwrap().get(uid).cloned();
+                                        let Some(author) = author else { continue };
+                                        let now = chrono::Utc::now().timestamp_millis() as u64;
+                                        let mut question = match crate::questions::new_question(&text, &author, anonymous, now) {
+                                            Ok(question) => question,
+                                            Err(e) => {
+                                                let _ = internal_tx.send(ServerMessage::Error(e.message())).await;
+                                                continue;
+                                            },
+                                        };
+                                        let Some(text) = filter_content(&state, ContentKind::Question, &question.text, uid, &internal_tx).await else { continue };
+                                        question.text = text;
+                                        questions_mutex.lock().unwrap().insert(question.id.clone(), question.clone());
+                                        let _ = tx.send(ServerMessage::QuestionAsked(question));
+                                    }
+                                },
+                                ClientMessage::UpvoteQuestion(question_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let updated = {
+                                            let mut questions = questions_mutex.lock().unwrap();
+                                            questions.get_mut(&question_id).map(|question| {
+                                                crate::questions::toggle_upvote(question, uid);
+                                                question.clone()
+                                            })
+                                        };
+                                        match updated {
+                                            Some(question) => { let _ = tx.send(ServerMessage::QuestionUpdated(question)); },
+                                            None => {
+                                                let _ = internal_tx.send(ServerMessage::Error(crate::questions::QuestionError::NotFound.message())).await;
+                                            },
+                                        }
+                                    }
+                                },
+                                ClientMessage::SetQuestionAnswered { question_id, answered } => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let updated = {
+                                                let mut questions = questions_mutex.lock().unwrap();
+                                                questions.get_mut(&question_id).map(|question| {
+                                                    question.answered = answered;
+                                                    question.clone()
+                                                })
+                                            };
+                                            if let Some(question) = updated {
+                                                let _ = tx.send(ServerMessage::QuestionUpdated(question));
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::SetQuestionPinned { question_id, pinned } => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let updated = {
+                                                let mut questions = questions_mutex.lock().unwrap();
+                                                questions.get_mut(&question_id).map(|question| {
+                                                    question.pinned = pinned;
+                                                    question.clone()
+                                                })
+                                            };
+                                            if let Some(question) = updated {
+                                                let _ = tx.send(ServerMessage::QuestionUpdated(question));
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::DismissQuestion(question_id) => {
+                                    if let Some(uid) = &my_id {
+                                        let is_host = {
+                                            room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
+                                        };
+                                        if is_host {
+                                            let removed = questions_mutex.lock().unwrap().remove(&question_id).is_some();
+                                            if removed {
+                                                let _ = tx.send(ServerMessage::QuestionDismissed(question_id));
+                                            }
+                                        }
+                                    }
+                                },
+                                ClientMessage::TogglePollVoteChanges => {
+                                    if let Some(uid) = &my_id {
+                                        let new_config = {
+                                            let mut config = room_config_mutex.lock().unwrap();
+                                            if config.host_id == Some(uid.clone()) {
+                                                config.poll_vote_changes = !config.poll_vote_changes;
+                                                Some(config.clone())
                                             } else {
                                                 None
                                             }
                                         };
-
-                                        if let Some(poll) = updated_poll {
-                                            let _ = tx.send(ServerMessage::PollUpdated(poll));
+                                        if let Some(new_config) = new_config {
+                                            let _ = tx.send(ServerMessage::RoomUpdated(new_config));
                                         }
                                     }
                                 },
-                                ClientMessage::Draw(mut action) => {
+                                ClientMessage::AddWhiteboardElement(mut element) => {
                                     if let Some(uid) = &my_id {
-                                        action.sender_id = uid.clone();
-                                        {
+                                        element.id = uuid::Uuid::new_v4().to_string();
+                                        element.sender_id = uid.clone();
+                                        if !filter_whiteboard_text(&state, &mut element, uid, &internal_tx).await {
+                                            continue;
+                                        }
+                                        let added = element.validate().and_then(|()| {
                                             let mut wb = whiteboard_mutex.lock().unwrap();
-                                            wb.push(action.clone());
+                                            if wb.len() >= shared::whiteboard::MAX_ELEMENTS {
+                                                return Err(shared::whiteboard::WhiteboardError::Full);
+                                            }
+                                            wb.push(element.clone());
+                                            Ok(())
+                                        });
+                                        match added {
+                                            Ok(()) => { let _ = tx.send(ServerMessage::WhiteboardElementAdded(element)); },
+                                            Err(e) => { let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await; },
+                                        }
+                                    }
+                                },
+                                ClientMessage::UpdateWhiteboardElement(mut element) => {
+                                    if let Some(uid) = &my_id {
+                                        // Moving an element doesn't run its text through the filter again
+                                        let text_changed = {
+                                            let wb = whiteboard_mutex.lock().unwrap();
+                                            wb.iter().find(|e| e.id == element.id).is_none_or(|e| e.shape.text() != element.shape.text())
+                                        };
+                                        if text_changed && !filter_whiteboard_text(&state, &mut element, uid, &internal_tx).await {
+                                            continue;
+                                        }
+                                        let updated = element.validate().and_then(|()| {
+                                            let mut wb = whiteboard_mutex.lock().unwrap();
+                                            let existing = wb.iter_mut().find(|e| e.id == element.id).ok_or(shared::whiteboard::WhiteboardError::NotFound)?;
+                                            // Whoever moves or edits it, it stays the original author's
+                                            element.sender_id = existing.sender_id.clone();
+                                            *existing = element.clone();
+                                            Ok(())
+                                        });
+                                        match updated {
+                                            Ok(()) => { let _ = tx.send(ServerMessage::WhiteboardElementUpdated(element)); },
+                                            Err(e) => { let _ = internal_tx.send(ServerMessage::Error(e.message().to_string())).await; },
+                                        }
+                                    }
+                                },
+                                ClientMessage::DeleteWhiteboardElement(element_id) => {
+                                    if my_id.is_some() {
+                                        let removed = {
+                                            let mut wb = whiteboard_mutex.lock().unwrap();
+                                            let before = wb.len();
+                                            wb.retain(|e| e.id != element_id);
+                                            wb.len() != before
+                                        };
+                                        if removed {
+                                            let _ = tx.send(ServerMessage::WhiteboardElementDeleted(element_id));
                                         }
-                                        let _ = tx.send(ServerMessage::Draw(action));
                                     }
                                 },
                                 ClientMessage::Reaction(emoji) => {
                                     if let Some(uid) = &my_id {
+                                        if !chat::is_valid_reaction(&emoji) {
+                                            let _ = internal_tx.send(ServerMessage::Error(chat::ChatEditError::InvalidReaction.message().to_string())).await;
+                                            continue;
+                                        }
                                         let _ = tx.send(ServerMessage::Reaction {
                                             sender_id: uid.clone(),
                                             emoji,
@@ -568,6 +1410,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 },
                                 ClientMessage::UpdateProfile(new_name) => {
                                     if let Some(uid) = &my_id {
+                                        let Some(new_name) = filter_content(&state, ContentKind::DisplayName, &new_name, uid, &internal_tx).await else { continue };
                                         let updated_participant = {
                                             let mut participants = participants_mutex.lock().unwrap();
                                             if let Some(p) = participants.get_mut(uid) {
@@ -600,6 +1443,23 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         }
                                     }
                                 },
+                                ClientMessage::SetVideoQuality(quality) => {
+                                    if let Some(uid) = &my_id {
+                                        let updated_participant = {
+                                            let mut participants = participants_mutex.lock().unwrap();
+                                            if let Some(p) = participants.get_mut(uid) {
+                                                p.video_quality = quality;
+                                                Some(p.clone())
+                                            } else {
+                                                None
+                                            }
+                                        };
+
+                                        if let Some(p) = updated_participant {
+                                            let _ = tx.send(ServerMessage::ParticipantUpdated(p));
+                                        }
+                                    }
+                                },
                                 ClientMessage::ToggleRaiseHand => {
                                     if let Some(uid) = &my_id {
                                         let updated_participant = {
@@ -632,6 +1492,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                             room_config_mutex.lock().unwrap().host_id == Some(uid.clone())
                                         };
                                         if is_host {
+                                            let Some(name) = filter_content(&state, ContentKind::BreakoutRoomName, &name, uid, &internal_tx).await else { continue };
                                             let id = uuid::Uuid::new_v4().to_string();
                                             let room = shared::BreakoutRoom {
                                                 id: id.clone(),
@@ -657,16 +1518,8 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         }
                                         my_room_id = room_id.clone();
 
-                                        // If joining Main Room (None), resend global chat history
-                                        if my_room_id.is_none() {
-                                            let history: Vec<shared::ChatMessage> = {
-                                                let history = chat_history_mutex.lock().unwrap();
-                                                history.clone()
-                                            };
-                                            if !history.is_empty() {
-                                                let _ = internal_tx.send(ServerMessage::ChatHistory(history)).await;
-                                            }
-                                        }
+                                        // Bring the chat of the room we entered up to date
+                                        send_latest_chat_page(&state, &chat::HistoryKey::room(&my_room_id), uid, &internal_tx).await;
                                     }
                                 },
                                 ClientMessage::StartShareVideo(url) => {
@@ -739,6 +1592,20 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                 },
                                 ClientMessage::Ping => {
                                     let _ = internal_tx.send(ServerMessage::Pong { timestamp: chrono::Utc::now().timestamp_millis() as u64 }).await;
+                                },
+                                ClientMessage::RtcSignal { to, signal } => {
+                                    if let Some(uid) = &my_id {
+                                        let known = participants_mutex.lock().unwrap().contains_key(&to);
+                                        if known && to != *uid {
+                                            let _ = tx.send(ServerMessage::RtcSignal { from: uid.clone(), to, signal });
+                                        }
+                                    }
+                                },
+                                ClientMessage::ConnectionQuality(mut report) => {
+                                    if let Some(uid) = &my_id {
+                                        report.user_id = uid.clone();
+                                        let _ = tx.send(ServerMessage::ConnectionQualityReport(report));
+                                    }
                                 }
                             }
                         }
@@ -780,22 +1647,15 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             my_id = Some(id.clone());
 
                             let _ = internal_tx.send(ServerMessage::Welcome { id: id.clone() }).await;
+                            let _ = internal_tx.send(ServerMessage::SessionToken(issue_session_token(&state, &id))).await;
 
-                            // Send Chat History
-                            let history: Vec<shared::ChatMessage> = {
-                                let history = chat_history_mutex.lock().unwrap();
-                                history.clone()
-                            };
-                            if !history.is_empty() {
-                                let _ = internal_tx.send(ServerMessage::ChatHistory(history)).await;
-                            }
+                            send_chat_history(&state, &id, &internal_tx).await;
 
                             if new_host_assigned {
                                 let new_config = {
                                     room_config_mutex.lock().unwrap().clone()
                                 };
-                                let _ = tx.send(ServerMessage::RoomUpdated(new_config.clone()));
-                                let _ = internal_tx.send(ServerMessage::RoomUpdated(new_config)).await;
+                                let _ = tx.send(ServerMessage::RoomUpdated(new_config));
                             }
 
                              // Register initial location (Main Room)
@@ -805,89 +1665,11 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             }
 
                             // Subscribe to broadcast
-                            let mut rx = tx.subscribe();
-                            let forward_tx = internal_tx.clone();
-                            let my_id_clone = id.clone();
-                            let locations_clone = participant_locations_mutex.clone();
-
-                            broadcast_task = Some(tokio::spawn(async move {
-                                loop {
-                                    match rx.recv().await {
-                                        Ok(msg) => {
-                                            // Filter based on room and recipient
-                                            let should_send = match &msg {
-                                                ServerMessage::Chat { message, room_id } => {
-                                                    let my_loc = {
-                                                        let locs = locations_clone.lock().unwrap();
-                                                        locs.get(&my_id_clone).cloned().flatten()
-                                                    };
-                                                    if *room_id != my_loc {
-                                                        false
-                                                    } else if let Some(target) = &message.recipient_id {
-                                                        *target == my_id_clone || message.user_id == my_id_clone
-                                                    } else {
-                                                        true
-                                                    }
-                                                },
-                                                ServerMessage::PeerTyping { room_id, .. } => {
-                                                    let my_loc = {
-                                                        let locs = locations_clone.lock().unwrap();
-                                                        locs.get(&my_id_clone).cloned().flatten()
-                                                    };
-                                                    *room_id == my_loc
-                                                },
-                                                _ => true,
-                                            };
-
-                                            if should_send
-                                                && forward_tx.send(msg).await.is_err() { break; }
-                                        },
-                                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
-                                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
-                                    }
-                                }
-                            }));
+                            broadcast_task = Some(spawn_broadcast_forwarder(&state, id.clone(), internal_tx.clone()));
 
                             let _ = tx.send(ServerMessage::ParticipantJoined(me));
 
-                            let current_list: Vec<Participant> = {
-                                let participants = participants_mutex.lock().unwrap();
-                                participants.values().cloned().collect()
-                            };
-                            let _ = internal_tx.send(ServerMessage::ParticipantList(current_list)).await;
-
-                            let knocking_list: Vec<Participant> = {
-                                let knocking = knocking_mutex.lock().unwrap();
-                                knocking.values().map(|(p, _)| p.clone()).collect()
-                            };
-                            for p in knocking_list {
-                                let _ = internal_tx.send(ServerMessage::KnockingParticipant(p)).await;
-                            }
-
-                            // Send Breakout Rooms List
-                            let all_rooms: Vec<shared::BreakoutRoom> = {
-                                let rooms = breakout_rooms_mutex.lock().unwrap();
-                                rooms.values().cloned().collect()
-                            };
-                            if !all_rooms.is_empty() {
-                                let _ = internal_tx.send(ServerMessage::BreakoutRoomsList(all_rooms)).await;
-                            }
-
-                            let history: Vec<shared::DrawAction> = {
-                                let wb = whiteboard_mutex.lock().unwrap();
-                                wb.clone()
-                            };
-                            if !history.is_empty() {
-                                let _ = internal_tx.send(ServerMessage::WhiteboardHistory(history)).await;
-                            }
-
-                            // Send Shared Video State
-                            let shared_url = {
-                                shared_video_mutex.lock().unwrap().clone()
-                            };
-                            if let Some(url) = shared_url {
-                                let _ = internal_tx.send(ServerMessage::VideoShared(url)).await;
-                            }
+                            send_join_snapshot(&state, &id, &internal_tx).await;
                         } else {
                             let _ = internal_tx.send(ServerMessage::AccessDenied).await;
                         }
@@ -957,6 +1739,21 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
             let mut locations = participant_locations_mutex.lock().unwrap();
             locations.remove(&id);
         }
+        // Keep the tokens so a reconnect can resume this ID and its private chats
+        {
+            let now = chrono::Utc::now().timestamp_millis() as u64;
+            let mut tokens = state.session_tokens.lock().unwrap();
+            let mut resumable = state.resumable_sessions.lock().unwrap();
+            resumable.retain(|_, (_, left_at)| still_resumable(*left_at, now));
+            tokens.retain(|token, owner| {
+                if *owner == id {
+                    resumable.insert(token.clone(), (owner.clone(), now));
+                    false
+                } else {
+                    true
+                }
+            });
+        }
     } else if let Some(kid) = knocking_id {
         // If disconnected while knocking
         let removed = {
@@ -978,6 +1775,34 @@ mod tests {
     use axum::Router;
     use axum::routing::get;
 
+    fn test_state() -> AppState {
+        let (tx, _rx) = tokio::sync::broadcast::channel(100);
+        AppState {
+            tx,
+            participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            knocking_participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            room_config: Arc::new(std::sync::Mutex::new(shared::RoomConfig::default())),
+            polls: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            quiz_clocks: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            questions: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            whiteboard: Arc::new(std::sync::Mutex::new(Vec::new())),
+            chat_history: Arc::new(std::sync::Mutex::new(chat::ChatHistories::default())),
+            breakout_rooms: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            participant_locations: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            shared_video_url: Arc::new(std::sync::Mutex::new(None)),
+            speaking_start_times: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            session_tokens: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            resumable_sessions: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            recordings: Arc::new(tokio::sync::Mutex::new(crate::recordings::RecordingStore::new(std::env::temp_dir()))),
+            recording_consents: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            audit_log: Arc::new(std::sync::Mutex::new(Vec::new())),
+            captions: Arc::new(std::sync::Mutex::new(Vec::new())),
+            chat_history_limit: 1000,
+            files: Arc::new(std::sync::Mutex::new(crate::files::FileStore::new(std::env::temp_dir(), crate::files::DEFAULT_QUOTA_BYTES))),
+            content_filter: Arc::new(crate::filter::FilterPipeline::default()),
+        }
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let app = Router::new().route("/health", get(health_check));
@@ -993,20 +1818,7 @@ mod tests {
     #[tokio::test]
     async fn test_create_room() {
         use shared::RoomConfig;
-        let (tx, _rx) = tokio::sync::broadcast::channel(100);
-        let app_state = Arc::new(AppState {
-            tx,
-            participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            knocking_participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            room_config: Arc::new(std::sync::Mutex::new(RoomConfig::default())),
-            polls: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            whiteboard: Arc::new(std::sync::Mutex::new(Vec::new())),
-            chat_history: Arc::new(std::sync::Mutex::new(Vec::new())),
-            breakout_rooms: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            participant_locations: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            shared_video_url: Arc::new(std::sync::Mutex::new(None)),
-            speaking_start_times: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-        });
+        let app_state = Arc::new(test_state());
 
         let config = RoomConfig::default();
         let response = create_room(State(app_state), Json(config)).await.into_response();
@@ -1016,20 +1828,7 @@ mod tests {
     #[tokio::test]
     async fn test_create_room_with_limit() {
         use shared::RoomConfig;
-        let (tx, _rx) = tokio::sync::broadcast::channel(100);
-        let app_state = Arc::new(AppState {
-            tx,
-            participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            knocking_participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            room_config: Arc::new(std::sync::Mutex::new(RoomConfig::default())),
-            polls: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            whiteboard: Arc::new(std::sync::Mutex::new(Vec::new())),
-            chat_history: Arc::new(std::sync::Mutex::new(Vec::new())),
-            breakout_rooms: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            participant_locations: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            shared_video_url: Arc::new(std::sync::Mutex::new(None)),
-            speaking_start_times: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-        });
+        let app_state = Arc::new(test_state());
 
         let config = RoomConfig {
             max_participants: 10,
@@ -1051,39 +1850,30 @@ mod tests {
 
     #[tokio::test]
     async fn test_chat_history() {
-        let (tx, _rx) = tokio::sync::broadcast::channel(100);
-        let history = Arc::new(std::sync::Mutex::new(Vec::new()));
-
-        let _app_state = Arc::new(AppState {
-            tx,
-            participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            knocking_participants: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            room_config: Arc::new(std::sync::Mutex::new(shared::RoomConfig::default())),
-            polls: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            whiteboard: Arc::new(std::sync::Mutex::new(Vec::new())),
-            chat_history: history.clone(),
-            breakout_rooms: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            participant_locations: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            shared_video_url: Arc::new(std::sync::Mutex::new(None)),
-            speaking_start_times: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-        });
+        let history = test_state().chat_history;
 
         // Simulate adding a message (like the websocket handler would)
         {
             let mut h = history.lock().unwrap();
-            h.push(shared::ChatMessage {
+            h.push(chat::HistoryKey::Main, shared::ChatMessage {
+                id: "m1".to_string(),
                 user_id: "user1".to_string(),
                 content: "Hello".to_string(),
                 recipient_id: None,
                 timestamp: 1234567890,
                 attachment: None,
-            });
+                edited_at: None,
+                deleted: false,
+                reactions: Default::default(),
+                parent_id: None,
+                mentions: Vec::new(),
+            }, 1000);
         }
 
         // Verify history contains the message
         let h = history.lock().unwrap();
-        assert_eq!(h.len(), 1);
-        assert_eq!(h[0].content, "Hello");
+        assert_eq!(h.get(&chat::HistoryKey::Main).len(), 1);
+        assert_eq!(h.get(&chat::HistoryKey::Main)[0].content, "Hello");
     }
 
     #[tokio::test]
@@ -1137,6 +1927,7 @@ mod tests {
                 is_hand_raised: false,
                 is_sharing_screen: false,
                 speaking_time: 0,
+                video_quality: shared::VideoQuality::default(),
             });
         }
 
@@ -1196,18 +1987,26 @@ mod tests {
         let (tx, mut rx) = tokio::sync::broadcast::channel(100);
 
         let attachment = shared::FileAttachment {
+            id: "f1".to_string(),
             filename: "doc.txt".to_string(),
             mime_type: "text/plain".to_string(),
             size: 100,
-            content_base64: "base64content".to_string(),
+            sha256: "0".repeat(64),
+            thumbnail: None,
         };
 
         let chat_msg = shared::ChatMessage {
+            id: "m1".to_string(),
             user_id: "user1".to_string(),
             content: "File sent".to_string(),
             recipient_id: None,
             timestamp: 1234567890,
             attachment: Some(attachment.clone()),
+            edited_at: None,
+            deleted: false,
+            reactions: Default::default(),
+            parent_id: None,
+            mentions: Vec::new(),
         };
 
         // Send logic
@@ -1229,6 +2028,138 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_should_forward_filters() {
+        let private = shared::ServerMessage::Chat {
+            message: shared::ChatMessage {
+                id: "m1".to_string(),
+                user_id: "alice".to_string(),
+                content: "psst".to_string(),
+                recipient_id: Some("bob".to_string()),
+                timestamp: 1234567890,
+                attachment: None,
+                edited_at: None,
+                deleted: false,
+                reactions: Default::default(),
+                parent_id: None,
+                mentions: Vec::new(),
+            },
+            room_id: None,
+        };
+        assert!(should_forward(&private, "bob", &None, false));
+        assert!(should_forward(&private, "alice", &None, false));
+        assert!(!should_forward(&private, "carol", &None, false));
+        assert!(!should_forward(&private, "bob", &Some("breakout".to_string()), false));
+
+        let shared::ServerMessage::Chat { message, .. } = private else { unreachable!() };
+        let updated = shared::ServerMessage::ChatMessageUpdated { message: message.clone(), room_id: None };
+        assert!(should_forward(&updated, "bob", &Some("breakout".to_string()), false));
+        assert!(!should_forward(&updated, "carol", &None, false));
+        let updated = shared::ServerMessage::ChatMessageUpdated {
+            message: shared::ChatMessage { recipient_id: None, ..message },
+            room_id: Some("breakout".to_string()),
+        };
+        assert!(should_forward(&updated, "carol", &Some("breakout".to_string()), false));
+        assert!(!should_forward(&updated, "carol", &None, false));
+
+        let report = shared::ServerMessage::ConnectionQualityReport(shared::ConnectionQuality::summarize(&[]));
+        assert!(should_forward(&report, "host", &None, true));
+        assert!(!should_forward(&report, "guest", &None, false));
+
+        let consents = shared::ServerMessage::RecordingConsents(Vec::new());
+        assert!(should_forward(&consents, "host", &None, true));
+        assert!(!should_forward(&consents, "guest", &None, false));
+
+        let mute = shared::ServerMessage::ForceMute("guest".to_string());
+        assert!(should_forward(&mute, "guest", &None, false));
+        assert!(!should_forward(&mute, "other", &None, false));
+
+        let signal = shared::ServerMessage::RtcSignal {
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+            signal: shared::RtcSignal::Answer { sdp: "v=0".to_string() },
+        };
+        assert!(should_forward(&signal, "bob", &None, false));
+        assert!(!should_forward(&signal, "carol", &None, false));
+
+        let caption = shared::ServerMessage::Caption(shared::CaptionSegment {
+            id: "c1".to_string(),
+            user_id: "alice".to_string(),
+            speaker_name: "Alice".to_string(),
+            text: "Hello".to_string(),
+            is_final: true,
+            lang: "en".to_string(),
+            start_ms: 0,
+            end_ms: 0,
+            room_id: Some("breakout".to_string()),
+        });
+        assert!(should_forward(&caption, "bob", &Some("breakout".to_string()), false));
+        assert!(!should_forward(&caption, "bob", &None, false));
+    }
+
+    #[tokio::test]
+    async fn test_whiteboard_text_is_filtered() {
+        use shared::whiteboard::{Point, Shape, WhiteboardElement};
+        let config: crate::filter::FilterConfig = serde_json::from_str(r#"{"rules": [
+            {"words": ["darn"], "action": "mask", "applies_to": ["whiteboard"]},
+            {"words": ["banned"], "action": "reject", "applies_to": ["whiteboard"]}
+        ]}"#).unwrap();
+        let mut state = test_state();
+        state.content_filter = Arc::new(crate::filter::FilterPipeline::default().with_stage(crate::filter::RuleFilter::new(config).unwrap()));
+        let (internal_tx, mut internal_rx) = tokio::sync::mpsc::channel(10);
+        let note = |text: &str| WhiteboardElement {
+            id: "e1".to_string(),
+            shape: Shape::StickyNote { at: Point { x: 0.0, y: 0.0 }, text: text.to_string() },
+            color: "#000000".to_string(),
+            width: 1.0,
+            sender_id: "alice".to_string(),
+        };
+
+        let mut masked = note("darn it");
+        assert!(filter_whiteboard_text(&state, &mut masked, "alice", &internal_tx).await);
+        assert_eq!(masked.shape.text(), Some("**** it"));
+
+        assert!(!filter_whiteboard_text(&state, &mut note("banned words"), "alice", &internal_tx).await);
+        match internal_rx.try_recv() {
+            Ok(ServerMessage::Error(e)) => assert_eq!(e, "This whiteboard text is not allowed"),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+
+        // Shapes without text pass untouched
+        let mut rect = WhiteboardElement { shape: Shape::Rect { from: Point { x: 0.0, y: 0.0 }, to: Point { x: 1.0, y: 1.0 } }, ..note("") };
+        assert!(filter_whiteboard_text(&state, &mut rect, "alice", &internal_tx).await);
+    }
+
+    #[test]
+    fn test_require_moderator() {
+        let state = test_state();
+        state.room_config.lock().unwrap().host_id = Some("host".to_string());
+        let host_token = issue_session_token(&state, "host");
+        let guest_token = issue_session_token(&state, "guest");
+
+        let bearer = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            headers
+        };
+
+        assert_eq!(require_moderator(&state, &bearer(&host_token)), Ok("host".to_string()));
+        assert_eq!(require_moderator(&state, &bearer(&guest_token)), Err(StatusCode::FORBIDDEN));
+        assert_eq!(require_moderator(&state, &bearer("made-up")), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(require_moderator(&state, &HeaderMap::new()), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(require_session(&state, &bearer(&guest_token)), Ok("guest".to_string()));
+
+        // A guest who left can still export for a while, but nothing else
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let owner = state.session_tokens.lock().unwrap().remove(&guest_token).unwrap();
+        state.resumable_sessions.lock().unwrap().insert(guest_token.clone(), (owner, now));
+        assert_eq!(require_session(&state, &bearer(&guest_token)), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(require_export_session(&state, &bearer(&guest_token)), Ok("guest".to_string()));
+        assert_eq!(require_export_session(&state, &bearer("made-up")), Err(StatusCode::UNAUTHORIZED));
+        state.resumable_sessions.lock().unwrap().insert(guest_token.clone(), ("guest".to_string(), now - RESUMABLE_SESSION_TTL_MS));
+        assert_eq!(require_export_session(&state, &bearer(&guest_token)), Err(StatusCode::UNAUTHORIZED));
+    }
+
     #[test]
     fn test_speaking_time_accumulation_logic() {
         // Test the logic used in handle_socket for speaking time
@@ -1245,6 +2176,7 @@ mod tests {
                 is_hand_raised: false,
                 is_sharing_screen: false,
                 speaking_time: 0,
+                video_quality: shared::VideoQuality::default(),
             });
         }
 
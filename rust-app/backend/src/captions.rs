@@ -0,0 +1,267 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use shared::CaptionSegment;
+use std::sync::Arc;
+use crate::AppState;
+use crate::api::require_export_session;
+
+// Longer texts are cut off; captions are meant to be short lines
+pub const MAX_CAPTION_CHARS: usize = 500;
+// Oldest final segments are dropped beyond this
+pub const MAX_STORED_CAPTIONS: usize = 10_000;
+// Enough for tags like "zh-Hant-TW"
+const MAX_LANG_CHARS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Vtt,
+    Srt,
+    Json,
+}
+
+impl TranscriptFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            TranscriptFormat::Vtt => "text/vtt; charset=utf-8",
+            TranscriptFormat::Srt => "application/x-subrip; charset=utf-8",
+            TranscriptFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Vtt => "vtt",
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Json => "json",
+        }
+    }
+}
+
+fn cue_time(ms: u64, decimal_separator: char) -> String {
+    let hours = ms / 3_600_000;
+    let minutes = (ms / 60_000) % 60;
+    let seconds = (ms / 1000) % 60;
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, seconds, decimal_separator, ms % 1000)
+}
+
+// Cue text must not contain blank lines, which would end the cue early
+fn cue_text(text: &str) -> String {
+    text.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+fn vtt_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Cue times are relative to `origin_ms`. Cues with no duration get one second so players show them.
+fn cue_range(segment: &CaptionSegment, origin_ms: u64) -> (u64, u64) {
+    let start = segment.start_ms.saturating_sub(origin_ms);
+    let end = segment.end_ms.saturating_sub(origin_ms).max(start + 1000);
+    (start, end)
+}
+
+pub fn to_webvtt(segments: &[CaptionSegment], origin_ms: u64) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for (i, segment) in segments.iter().enumerate() {
+        let (start, end) = cue_range(segment, origin_ms);
+        out.push_str(&format!(
+            "\n{}\n{} --> {}\n<v {}>{}\n",
+            i + 1,
+            cue_time(start, '.'),
+            cue_time(end, '.'),
+            vtt_escape(&segment.speaker_name),
+            vtt_escape(&cue_text(&segment.text)),
+        ));
+    }
+    out
+}
+
+pub fn to_srt(segments: &[CaptionSegment], origin_ms: u64) -> String {
+    let mut out = String::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let (start, end) = cue_range(segment, origin_ms);
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}: {}\n",
+            i + 1,
+            cue_time(start, ','),
+            cue_time(end, ','),
+            segment.speaker_name,
+            cue_text(&segment.text),
+        ));
+    }
+    out
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: TranscriptFormat,
+    // Breakout room ID; the main room when omitted
+    #[serde(default)]
+    pub room: Option<String>,
+    #[serde(default)]
+    pub lang: Option<String>,
+    // Epoch ms that cue times are relative to; defaults to the first caption
+    #[serde(default)]
+    pub start: Option<u64>,
+}
+
+/// Whether `tag` looks like a BCP 47 language tag: a 2 or 3 letter language,
+/// then subtags of 1 to 8 letters or digits, separated by hyphens.
+pub fn is_valid_lang(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    tag.len() <= MAX_LANG_CHARS
+        && (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Final caption segments of one room, optionally limited to one language.
+pub fn transcript(captions: &[CaptionSegment], room: &Option<String>, lang: Option<&str>) -> Vec<CaptionSegment> {
+    captions
+        .iter()
+        .filter(|c| &c.room_id == room && lang.is_none_or(|l| c.lang == l))
+        .cloned()
+        .collect()
+}
+
+/// Keeps a final segment, trimming the transcript to `MAX_STORED_CAPTIONS`.
+pub fn store_caption(captions: &mut Vec<CaptionSegment>, segment: CaptionSegment) {
+    captions.push(segment);
+    if captions.len() > MAX_STORED_CAPTIONS {
+        let excess = captions.len() - MAX_STORED_CAPTIONS;
+        captions.drain(..excess);
+    }
+}
+
+pub async fn export_captions(
+    State(state): State<Arc<AppState>>,
+    Path(_room_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let participant_id = match require_export_session(&state, &headers) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    // Like the chat export, participants get the room they are in; only the host may pick another
+    let is_host = state.room_config.lock().unwrap().host_id.as_deref() == Some(participant_id.as_str());
+    if !is_host {
+        let location = state.participant_locations.lock().unwrap().get(&participant_id).cloned().flatten();
+        if query.room != location {
+            return (StatusCode::FORBIDDEN, "Only the host can export another room's captions").into_response();
+        }
+    }
+
+    let segments = {
+        let captions = state.captions.lock().unwrap();
+        transcript(&captions, &query.room, query.lang.as_deref())
+    };
+    if segments.is_empty() && query.format != TranscriptFormat::Json {
+        return (StatusCode::NOT_FOUND, "No captions recorded").into_response();
+    }
+    let origin = query.start.or_else(|| segments.first().map(|s| s.start_ms)).unwrap_or(0);
+
+    let body = match query.format {
+        TranscriptFormat::Vtt => to_webvtt(&segments, origin),
+        TranscriptFormat::Srt => to_srt(&segments, origin),
+        TranscriptFormat::Json => match serde_json::to_string_pretty(&segments) {
+            Ok(json) => json,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"transcript.{}\"", query.format.extension())),
+        ],
+        body,
+    ).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(name: &str, text: &str, start_ms: u64, end_ms: u64) -> CaptionSegment {
+        CaptionSegment {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: name.to_lowercase(),
+            speaker_name: name.to_string(),
+            text: text.to_string(),
+            is_final: true,
+            lang: "en".to_string(),
+            start_ms,
+            end_ms,
+            room_id: None,
+        }
+    }
+
+    #[test]
+    fn test_webvtt_export() {
+        let segments = vec![
+            segment("Alice", "Hello <everyone>", 10_000, 12_500),
+            segment("Bob", "Hi\n\nAlice", 3_671_000, 3_671_000),
+        ];
+        let vtt = to_webvtt(&segments, 9_000);
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:03.500\n<v Alice>Hello &lt;everyone&gt;\n\n2\n01:01:02.000 --> 01:01:03.000\n<v Bob>Hi\nAlice\n"
+        );
+    }
+
+    #[test]
+    fn test_srt_export() {
+        let segments = vec![
+            segment("Alice", "Hello", 10_000, 12_500),
+            segment("Bob", "Hi", 13_000, 14_250),
+        ];
+        let srt = to_srt(&segments, 10_000);
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:02,500\nAlice: Hello\n\n2\n00:00:03,000 --> 00:00:04,250\nBob: Hi\n"
+        );
+    }
+
+    #[test]
+    fn test_transcript_filters_and_retention() {
+        let mut captions = Vec::new();
+        store_caption(&mut captions, segment("Alice", "Main", 0, 1000));
+        let mut breakout = segment("Bob", "Breakout", 0, 1000);
+        breakout.room_id = Some("room-1".to_string());
+        store_caption(&mut captions, breakout);
+        let mut german = segment("Carol", "Hallo", 0, 1000);
+        german.lang = "de".to_string();
+        store_caption(&mut captions, german);
+
+        assert_eq!(transcript(&captions, &None, None).len(), 2);
+        assert_eq!(transcript(&captions, &None, Some("de"))[0].text, "Hallo");
+        assert_eq!(transcript(&captions, &Some("room-1".to_string()), None)[0].text, "Breakout");
+
+        for i in 0..MAX_STORED_CAPTIONS {
+            store_caption(&mut captions, segment("Alice", &i.to_string(), 0, 0));
+        }
+        assert_eq!(captions.len(), MAX_STORED_CAPTIONS);
+        assert_eq!(captions[0].text, "0");
+    }
+
+    #[test]
+    fn test_is_valid_lang() {
+        for tag in ["en", "de-CH", "zh-Hant-TW", "es-419"] {
+            assert!(is_valid_lang(tag), "{}", tag);
+        }
+        for tag in ["", "e", "english", "en-", "en_US", "en-US<script>", "de-abcdefghi", &format!("en{}", "-x".repeat(10))] {
+            assert!(!is_valid_lang(tag), "{}", tag);
+        }
+    }
+}
@@ -0,0 +1,778 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize, Serializer};
+use shared::{ChatMessage, ChatScope, Participant, RoomConfig, MAX_CHAT_CHARS};
+use shared::mentions::find_mentions;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use crate::AppState;
+use crate::api::require_export_session;
+
+// Messages sent on join and per FetchChatHistory by default
+pub const CHAT_PAGE_SIZE: usize = 50;
+pub const MAX_CHAT_PAGE_SIZE: usize = 200;
+
+/// Returns up to `limit` messages older than the message `before` (or the newest ones),
+/// oldest first, and whether older messages remain. An unknown `before` ID yields nothing.
+pub fn history_page(history: &[ChatMessage], before: Option<&str>, limit: usize) -> (Vec<ChatMessage>, bool) {
+    let end = match before {
+        Some(id) => match history.iter().position(|m| m.id == id) {
+            Some(pos) => pos,
+            None => return (Vec::new(), false),
+        },
+        None => history.len(),
+    };
+    let start = end.saturating_sub(limit);
+    (history[start..end].to_vec(), start > 0)
+}
+
+/// Ancestors of replies in `page` that are not part of it, in history order.
+pub fn missing_parents(history: &[ChatMessage], page: &[ChatMessage]) -> Vec<ChatMessage> {
+    let positions: HashMap<&str, usize> = history.iter().enumerate().map(|(i, m)| (m.id.as_str(), i)).collect();
+    let in_page: HashSet<&str> = page.iter().map(|m| m.id.as_str()).collect();
+    let mut found = HashSet::new();
+    for message in page {
+        let mut parent = message.parent_id.as_deref();
+        while let Some(id) = parent {
+            if in_page.contains(id) {
+                break;
+            }
+            // Stop at parents lost to retention, and at ones already collected
+            let Some(&pos) = positions.get(id) else { break };
+            if !found.insert(pos) {
+                break;
+            }
+            parent = history[pos].parent_id.as_deref();
+        }
+    }
+    let mut found: Vec<usize> = found.into_iter().collect();
+    found.sort_unstable();
+    found.into_iter().map(|pos| history[pos].clone()).collect()
+}
+
+/// Appends to one scope's history, dropping the oldest messages beyond `limit`.
+pub fn push_history(history: &mut Vec<ChatMessage>, message: ChatMessage, limit: usize) {
+    history.push(message);
+    if history.len() > limit {
+        let excess = history.len() - limit;
+        history.drain(..excess);
+    }
+}
+
+/// Identifies one stored history: a room, or the conversation between two participants.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HistoryKey {
+    Main,
+    Breakout(String),
+    Private(String, String), // Participant IDs, sorted
+}
+
+impl HistoryKey {
+    pub fn room(room_id: &Option<String>) -> Self {
+        match room_id {
+            Some(id) => HistoryKey::Breakout(id.clone()),
+            None => HistoryKey::Main,
+        }
+    }
+
+    pub fn private(a: &str, b: &str) -> Self {
+        if a <= b {
+            HistoryKey::Private(a.to_string(), b.to_string())
+        } else {
+            HistoryKey::Private(b.to_string(), a.to_string())
+        }
+    }
+
+    /// Where a message sent from `room_id` is kept.
+    pub fn of_message(message: &ChatMessage, room_id: &Option<String>) -> Self {
+        match &message.recipient_id {
+            Some(recipient) => HistoryKey::private(&message.user_id, recipient),
+            None => HistoryKey::room(room_id),
+        }
+    }
+
+    /// The history a participant asks for, as seen from their side.
+    pub fn from_scope(scope: &ChatScope, participant_id: &str) -> Self {
+        match scope {
+            ChatScope::Main => HistoryKey::Main,
+            ChatScope::Breakout(id) => HistoryKey::Breakout(id.clone()),
+            ChatScope::Private(other) => HistoryKey::private(participant_id, other),
+        }
+    }
+
+    pub fn scope_for(&self, participant_id: &str) -> ChatScope {
+        match self {
+            HistoryKey::Main => ChatScope::Main,
+            HistoryKey::Breakout(id) => ChatScope::Breakout(id.clone()),
+            HistoryKey::Private(a, b) => ChatScope::Private(if a == participant_id { b.clone() } else { a.clone() }),
+        }
+    }
+
+    /// Participants read the room they are in and their own private conversations.
+    pub fn is_visible_to(&self, participant_id: &str, room_id: &Option<String>) -> bool {
+        match self {
+            HistoryKey::Private(a, b) => a == participant_id || b == participant_id,
+            room => room == &HistoryKey::room(room_id),
+        }
+    }
+
+    // Room that updates to this history are broadcast to
+    pub fn room_id(&self) -> Option<String> {
+        match self {
+            HistoryKey::Breakout(id) => Some(id.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Chat histories of the meeting, one per room and private conversation.
+#[derive(Debug, Default)]
+pub struct ChatHistories {
+    scopes: HashMap<HistoryKey, Vec<ChatMessage>>,
+    // Participant ID -> display name when they last chatted, for exports after they left
+    names: HashMap<String, String>,
+}
+
+impl ChatHistories {
+    pub fn get(&self, key: &HistoryKey) -> &[ChatMessage] {
+        self.scopes.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, key: &HistoryKey) -> &mut [ChatMessage] {
+        self.scopes.get_mut(key).map(Vec::as_mut_slice).unwrap_or_default()
+    }
+
+    pub fn push(&mut self, key: HistoryKey, message: ChatMessage, limit: usize) {
+        push_history(self.scopes.entry(key).or_default(), message, limit);
+    }
+
+    /// The history holding `message_id`, among those the participant can read.
+    pub fn locate(&self, message_id: &str, participant_id: &str, room_id: &Option<String>) -> Option<HistoryKey> {
+        self.scopes
+            .iter()
+            .find(|(key, messages)| key.is_visible_to(participant_id, room_id) && messages.iter().any(|m| m.id == message_id))
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Whether a message the participant can read carries the file.
+    pub fn shares_file(&self, file_id: &str, participant_id: &str, room_id: &Option<String>) -> bool {
+        self.scopes.iter().any(|(key, messages)| {
+            key.is_visible_to(participant_id, room_id) && messages.iter().any(|m| m.attachment.as_ref().is_some_and(|a| a.id == file_id))
+        })
+    }
+
+    pub fn private_conversations(&self, participant_id: &str) -> Vec<HistoryKey> {
+        self.scopes
+            .keys()
+            .filter(|key| matches!(key, HistoryKey::Private(..)) && key.is_visible_to(participant_id, &None))
+            .cloned()
+            .collect()
+    }
+
+    pub fn remember_name(&mut self, participant_id: &str, name: &str) {
+        self.names.insert(participant_id.to_string(), name.to_string());
+    }
+
+    pub fn clear(&mut self) {
+        self.scopes.clear();
+        self.names.clear();
+    }
+}
+
+// Longest emoji sequence accepted as a reaction
+const MAX_REACTION_CHARS: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub enum ChatEditError {
+    NotFound,
+    Forbidden,
+    Deleted,
+    InvalidReaction,
+    TooLong,
+}
+
+impl ChatEditError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ChatEditError::NotFound => "Message not found",
+            ChatEditError::Forbidden => "Only the author or a moderator can change this message",
+            ChatEditError::Deleted => "Message was deleted",
+            ChatEditError::InvalidReaction => "Invalid reaction",
+            ChatEditError::TooLong => "Message is too long",
+        }
+    }
+}
+
+/// Rejects message text over `MAX_CHAT_CHARS`, before it is filtered, stored or rendered.
+pub fn check_content(content: &str) -> Result<(), ChatEditError> {
+    if content.chars().count() > MAX_CHAT_CHARS {
+        return Err(ChatEditError::TooLong);
+    }
+    Ok(())
+}
+
+/// Whether `emoji` is short enough to be a reaction.
+pub fn is_valid_reaction(emoji: &str) -> bool {
+    !emoji.is_empty() && emoji.chars().count() <= MAX_REACTION_CHARS
+}
+
+fn find_live<'a>(history: &'a mut [ChatMessage], message_id: &str) -> Result<&'a mut ChatMessage, ChatEditError> {
+    let message = history.iter_mut().find(|m| m.id == message_id).ok_or(ChatEditError::NotFound)?;
+    if message.deleted {
+        return Err(ChatEditError::Deleted);
+    }
+    Ok(message)
+}
+
+/// Replaces the text of a message. Authors and moderators only. Mentions are
+/// found again in the new text, among the `audience` who see the message.
+pub fn edit_message(history: &mut [ChatMessage], message_id: &str, actor_id: &str, is_moderator: bool, content: String, audience: &[Participant], now: u64) -> Result<ChatMessage, ChatEditError> {
+    let message = find_live(history, message_id)?;
+    if message.user_id != actor_id && !is_moderator {
+        return Err(ChatEditError::Forbidden);
+    }
+    message.mentions = find_mentions(&content, audience.iter().filter(|p| p.id != message.user_id));
+    message.content = content;
+    message.edited_at = Some(now);
+    Ok(message.clone())
+}
+
+/// Clears a message but keeps its entry so its position in the history stays stable.
+pub fn delete_message(history: &mut [ChatMessage], message_id: &str, actor_id: &str, is_moderator: bool) -> Result<ChatMessage, ChatEditError> {
+    let message = find_live(history, message_id)?;
+    if message.user_id != actor_id && !is_moderator {
+        return Err(ChatEditError::Forbidden);
+    }
+    message.deleted = true;
+    message.content.clear();
+    message.attachment = None;
+    message.reactions.clear();
+    message.mentions.clear();
+    Ok(message.clone())
+}
+
+/// Adds the user's reaction, or removes it if they already reacted with that emoji.
+pub fn toggle_reaction(history: &mut [ChatMessage], message_id: &str, user_id: &str, emoji: &str) -> Result<ChatMessage, ChatEditError> {
+    let emoji = emoji.trim();
+    if !is_valid_reaction(emoji) {
+        return Err(ChatEditError::InvalidReaction);
+    }
+    let message = find_live(history, message_id)?;
+    let users = message.reactions.entry(emoji.to_string()).or_default();
+    if let Some(pos) = users.iter().position(|u| u == user_id) {
+        users.remove(pos);
+        if users.is_empty() {
+            message.reactions.remove(emoji);
+        }
+    } else {
+        users.push(user_id.to_string());
+    }
+    Ok(message.clone())
+}
+
+pub const MAX_SLOW_MODE_SECS: u32 = 3600;
+
+/// Checks the room's chat moderation settings for a new message from `sender_id`,
+/// who last sent one at `last_sent_ms`. Moderators are exempt from all of them.
+pub fn check_can_send(config: &RoomConfig, sender_id: &str, last_sent_ms: Option<u64>, now_ms: u64) -> Result<(), String> {
+    let is_moderator = config.host_id.as_deref() == Some(sender_id);
+    if let Some(restriction) = config.chat_restriction(sender_id, is_moderator) {
+        return Err(restriction.message().to_string());
+    }
+    let wait_ms = if is_moderator { 0 } else { config.slow_mode_wait_ms(last_sent_ms, now_ms) };
+    if wait_ms > 0 {
+        return Err(format!("Slow mode is on, you can send another message in {} s", wait_ms.div_ceil(1000)));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatExportFormat {
+    Txt,
+    Html,
+    Json,
+}
+
+impl ChatExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ChatExportFormat::Txt => "text/plain; charset=utf-8",
+            ChatExportFormat::Html => "text/html; charset=utf-8",
+            ChatExportFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ChatExportFormat::Txt => "txt",
+            ChatExportFormat::Html => "html",
+            ChatExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatExportQuery {
+    pub format: ChatExportFormat,
+    // Requester's offset from UTC in minutes, east positive; UTC when omitted
+    #[serde(default)]
+    pub tz_offset: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedMessage {
+    pub id: String,
+    #[serde(serialize_with = "rfc3339")]
+    pub time: DateTime<FixedOffset>,
+    pub sender_id: String,
+    pub sender_name: String,
+    pub recipient_id: Option<String>, // Set on private messages
+    pub recipient_name: Option<String>,
+    pub content: String,
+    pub parent_id: Option<String>,
+    pub edited: bool,
+    pub deleted: bool,
+    pub attachment: Option<ExportedAttachment>,
+}
+
+fn rfc3339<S: Serializer>(time: &DateTime<FixedOffset>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.to_rfc3339())
+}
+
+const UNKNOWN_NAME: &str = "Unknown participant";
+
+/// What `participant_id` can read: the room they are in and their own private
+/// conversations, oldest first. Names come from `current_names`, then from the
+/// names remembered when people chatted.
+pub fn export_messages(
+    histories: &ChatHistories,
+    participant_id: &str,
+    room_id: &Option<String>,
+    current_names: &HashMap<String, String>,
+    offset: FixedOffset,
+) -> Vec<ExportedMessage> {
+    let name_of = |id: &str| {
+        current_names.get(id).or_else(|| histories.names.get(id)).cloned().unwrap_or_else(|| UNKNOWN_NAME.to_string())
+    };
+    let mut messages: Vec<&ChatMessage> = histories.get(&HistoryKey::room(room_id)).iter().collect();
+    for key in histories.private_conversations(participant_id) {
+        messages.extend(histories.get(&key));
+    }
+    messages.sort_by_key(|m| m.timestamp);
+
+    messages
+        .into_iter()
+        .map(|m| ExportedMessage {
+            id: m.id.clone(),
+            time: DateTime::from_timestamp_millis(m.timestamp as i64).unwrap_or_default().with_timezone(&offset),
+            sender_id: m.user_id.clone(),
+            sender_name: name_of(&m.user_id),
+            recipient_id: m.recipient_id.clone(),
+            recipient_name: m.recipient_id.as_deref().map(name_of),
+            content: m.content.clone(),
+            parent_id: m.parent_id.clone(),
+            edited: m.edited_at.is_some(),
+            deleted: m.deleted,
+            attachment: m.attachment.as_ref().map(|a| ExportedAttachment {
+                filename: a.filename.clone(),
+                mime_type: a.mime_type.clone(),
+                size: a.size,
+                sha256: a.sha256.clone(),
+            }),
+        })
+        .collect()
+}
+
+const EXPORT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+// Downloads need a live session token, so exports describe files rather than link them
+const ATTACHMENT_NOTE: &str = "Attachments are listed by name, size and SHA-256 checksum. The files are not part of the export.";
+
+fn has_attachments(messages: &[ExportedMessage]) -> bool {
+    messages.iter().any(|m| m.attachment.is_some())
+}
+
+// "notes.pdf (42 bytes, SHA-256 …)", shared by the text and HTML exports
+fn describe_attachment(attachment: &ExportedAttachment) -> String {
+    format!("{} ({} bytes, SHA-256 {})", attachment.filename, attachment.size, attachment.sha256)
+}
+
+// "to Bob (private)", "(edited)" and so on, shared by the text and HTML exports
+fn message_notes(message: &ExportedMessage) -> (String, String) {
+    let to = message.recipient_name.as_ref().map(|name| format!(" to {} (private)", name)).unwrap_or_default();
+    let edited = if message.edited && !message.deleted { " (edited)" } else { "" };
+    (to, edited.to_string())
+}
+
+pub fn to_text(messages: &[ExportedMessage], offset: FixedOffset) -> String {
+    let mut out = format!("Chat export, times in UTC{}\n", offset);
+    if has_attachments(messages) {
+        out.push_str(ATTACHMENT_NOTE);
+        out.push('\n');
+    }
+    out.push('\n');
+    for message in messages {
+        let (to, edited) = message_notes(message);
+        let content = if message.deleted { "(message deleted)".to_string() } else { message.content.replace('\n', "\n    ") };
+        out.push_str(&format!("[{}] {}{}: {}{}\n", message.time.format(EXPORT_TIME_FORMAT), message.sender_name, to, content, edited));
+        if let Some(attachment) = &message.attachment {
+            out.push_str(&format!("    Attachment: {}\n", describe_attachment(attachment)));
+        }
+    }
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+pub fn to_html(messages: &[ExportedMessage], offset: FixedOffset) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Chat export</title></head>\n<body>\n");
+    out.push_str(&format!("<h1>Chat export</h1>\n<p>Times in UTC{}</p>\n", offset));
+    if has_attachments(messages) {
+        out.push_str(&format!("<p>{}</p>\n", ATTACHMENT_NOTE));
+    }
+    out.push_str("<ul>\n");
+    for message in messages {
+        let (to, edited) = message_notes(message);
+        let content = if message.deleted {
+            "<em>(message deleted)</em>".to_string()
+        } else {
+            html_escape(&message.content).replace('\n', "<br>")
+        };
+        out.push_str(&format!(
+            "<li><time datetime=\"{}\">{}</time> <strong>{}</strong>{}: {}{}",
+            message.time.to_rfc3339(),
+            message.time.format(EXPORT_TIME_FORMAT),
+            html_escape(&message.sender_name),
+            html_escape(&to),
+            content,
+            edited,
+        ));
+        if let Some(attachment) = &message.attachment {
+            out.push_str(&format!("<br>Attachment: {}", html_escape(&describe_attachment(attachment))));
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n</body>\n</html>\n");
+    out
+}
+
+pub async fn export_chat(
+    State(state): State<Arc<AppState>>,
+    Path(_room_id): Path<String>,
+    Query(query): Query<ChatExportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let participant_id = match require_export_session(&state, &headers) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(offset) = query.tz_offset.checked_mul(60).and_then(FixedOffset::east_opt) else {
+        return (StatusCode::BAD_REQUEST, "Invalid timezone offset").into_response();
+    };
+
+    // Participants who already left export the main room, like they saw it on rejoining
+    let location = state.participant_locations.lock().unwrap().get(&participant_id).cloned().flatten();
+    let current_names: HashMap<String, String> = {
+        let participants = state.participants.lock().unwrap();
+        participants.values().map(|p| (p.id.clone(), p.name.clone())).collect()
+    };
+    let messages = {
+        let histories = state.chat_history.lock().unwrap();
+        export_messages(&histories, &participant_id, &location, &current_names, offset)
+    };
+    if messages.is_empty() && query.format != ChatExportFormat::Json {
+        return (StatusCode::NOT_FOUND, "No chat messages").into_response();
+    }
+
+    let body = match query.format {
+        ChatExportFormat::Txt => to_text(&messages, offset),
+        ChatExportFormat::Html => to_html(&messages, offset),
+        ChatExportFormat::Json => match serde_json::to_string_pretty(&messages) {
+            Ok(json) => json,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"chat.{}\"", query.format.extension())),
+        ],
+        body,
+    ).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(i: usize) -> ChatMessage {
+        ChatMessage {
+            id: format!("m{}", i),
+            user_id: "user1".to_string(),
+            content: i.to_string(),
+            recipient_id: None,
+            timestamp: i as u64,
+            attachment: None,
+            edited_at: None,
+            deleted: false,
+            reactions: Default::default(),
+            parent_id: None,
+            mentions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_chat_history_paging_and_retention() {
+        let mut history = Vec::new();
+        for i in 0..10 {
+            push_history(&mut history, message(i), 8);
+        }
+        // Retention dropped m0 and m1
+        assert_eq!(history.len(), 8);
+        assert_eq!(history[0].id, "m2");
+
+        let (latest, has_more) = history_page(&history, None, 3);
+        assert_eq!(latest.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m7", "m8", "m9"]);
+        assert!(has_more);
+
+        let (older, has_more) = history_page(&history, Some("m7"), 3);
+        assert_eq!(older.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m4", "m5", "m6"]);
+        assert!(has_more);
+
+        let (oldest, has_more) = history_page(&history, Some("m4"), 3);
+        assert_eq!(oldest.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m2", "m3"]);
+        assert!(!has_more);
+
+        assert_eq!(history_page(&history, Some("m0"), 3), (Vec::new(), false));
+    }
+
+    #[test]
+    fn test_pages_carry_thread_parents() {
+        let mut history: Vec<ChatMessage> = (0..6).map(message).collect();
+        // m5 replies to m3, which replies to m1; m4 replies to the lost m0
+        history[3].parent_id = Some("m1".to_string());
+        history[4].parent_id = Some("m0".to_string());
+        history[5].parent_id = Some("m3".to_string());
+        history.remove(0);
+
+        let (page, _) = history_page(&history, None, 1);
+        let parents: Vec<String> = missing_parents(&history, &page).into_iter().map(|m| m.id).collect();
+        assert_eq!(parents, vec!["m1".to_string(), "m3".to_string()]);
+
+        let (page, _) = history_page(&history, None, 3);
+        let parents: Vec<String> = missing_parents(&history, &page).into_iter().map(|m| m.id).collect();
+        assert_eq!(parents, vec!["m1".to_string()]);
+    }
+
+    #[test]
+    fn test_histories_are_kept_per_scope() {
+        let mut histories = ChatHistories::default();
+        let breakout = Some("room-1".to_string());
+        let mut private = message(1);
+        private.user_id = "bob".to_string();
+        private.recipient_id = Some("alice".to_string());
+
+        histories.push(HistoryKey::of_message(&message(0), &None), message(0), 10);
+        histories.push(HistoryKey::of_message(&private, &breakout), private, 10);
+        histories.push(HistoryKey::of_message(&message(2), &breakout), message(2), 10);
+
+        // Private conversations don't depend on who sent or where from
+        let conversation = HistoryKey::from_scope(&ChatScope::Private("bob".to_string()), "alice");
+        assert_eq!(conversation, HistoryKey::private("bob", "alice"));
+        assert_eq!(histories.get(&conversation)[0].id, "m1");
+        assert_eq!(conversation.scope_for("bob"), ChatScope::Private("alice".to_string()));
+        assert_eq!(histories.get(&HistoryKey::Main).len(), 1);
+        assert_eq!(histories.get(&HistoryKey::Breakout("room-1".to_string()))[0].id, "m2");
+
+        assert_eq!(histories.private_conversations("alice"), vec![conversation.clone()]);
+        assert!(histories.private_conversations("carol").is_empty());
+
+        // Messages are only found in histories the participant can read
+        assert_eq!(histories.locate("m2", "carol", &breakout), Some(HistoryKey::Breakout("room-1".to_string())));
+        assert_eq!(histories.locate("m2", "carol", &None), None);
+        assert_eq!(histories.locate("m1", "carol", &breakout), None);
+        assert_eq!(histories.locate("m1", "alice", &None), Some(conversation));
+    }
+
+    #[test]
+    fn test_files_are_shared_through_readable_messages() {
+        let mut histories = ChatHistories::default();
+        let breakout = Some("room-1".to_string());
+        let mut with_file = message(0);
+        with_file.attachment = Some(shared::FileAttachment {
+            id: "f1".to_string(),
+            filename: "notes.pdf".to_string(),
+            mime_type: "application/pdf".to_string(),
+            size: 42,
+            sha256: String::new(),
+            thumbnail: None,
+        });
+        histories.push(HistoryKey::room(&breakout), with_file, 10);
+
+        assert!(histories.shares_file("f1", "carol", &breakout));
+        assert!(!histories.shares_file("f1", "carol", &None));
+        assert!(!histories.shares_file("f2", "carol", &breakout));
+    }
+
+    #[test]
+    fn test_edit_and_delete_permissions() {
+        let mut history = vec![message(1)];
+        assert_eq!(edit_message(&mut history, "m1", "user2", false, "x".to_string(), &[], 5), Err(ChatEditError::Forbidden));
+        assert_eq!(edit_message(&mut history, "m9", "user1", false, "x".to_string(), &[], 5), Err(ChatEditError::NotFound));
+
+        let edited = edit_message(&mut history, "m1", "user1", false, "Fixed".to_string(), &[], 5).unwrap();
+        assert_eq!(edited.content, "Fixed");
+        assert_eq!(edited.edited_at, Some(5));
+        // Moderators may edit and delete anyone's message
+        assert!(edit_message(&mut history, "m1", "host", true, "Moderated".to_string(), &[], 6).is_ok());
+
+        toggle_reaction(&mut history, "m1", "user2", "👍").unwrap();
+        assert_eq!(delete_message(&mut history, "m1", "user2", false), Err(ChatEditError::Forbidden));
+        let deleted = delete_message(&mut history, "m1", "host", true).unwrap();
+        assert!(deleted.deleted);
+        assert!(deleted.content.is_empty());
+        assert!(deleted.reactions.is_empty());
+        assert_eq!(history.len(), 1);
+        assert_eq!(edit_message(&mut history, "m1", "user1", false, "Back".to_string(), &[], 7), Err(ChatEditError::Deleted));
+    }
+
+    #[test]
+    fn test_edit_recomputes_mentions() {
+        let person = |id: &str, name: &str| Participant {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_hand_raised: false,
+            is_sharing_screen: false,
+            speaking_time: 0,
+            video_quality: Default::default(),
+        };
+        let audience = vec![person("user1", "Alice"), person("user2", "Bob"), person("user3", "Carol")];
+        let mut history = vec![message(1)];
+        history[0].mentions = vec!["user2".to_string()];
+
+        let edited = edit_message(&mut history, "m1", "user1", false, "@Carol and @Alice".to_string(), &audience, 5).unwrap();
+        // The author can't mention themselves
+        assert_eq!(edited.mentions, vec!["user3".to_string()]);
+        let edited = edit_message(&mut history, "m1", "user1", false, "no one".to_string(), &audience, 6).unwrap();
+        assert!(edited.mentions.is_empty());
+        assert!(history[0].mentions.is_empty());
+    }
+
+    #[test]
+    fn test_toggle_reaction() {
+        let mut history = vec![message(1)];
+        toggle_reaction(&mut history, "m1", "user1", "👍").unwrap();
+        let updated = toggle_reaction(&mut history, "m1", "user2", "👍").unwrap();
+        assert_eq!(updated.reactions["👍"], vec!["user1".to_string(), "user2".to_string()]);
+
+        toggle_reaction(&mut history, "m1", "user1", "👍").unwrap();
+        let updated = toggle_reaction(&mut history, "m1", "user2", "👍").unwrap();
+        assert!(updated.reactions.is_empty());
+
+        assert_eq!(toggle_reaction(&mut history, "m1", "user1", " "), Err(ChatEditError::InvalidReaction));
+        assert_eq!(toggle_reaction(&mut history, "m1", "user1", "this is not an emoji"), Err(ChatEditError::InvalidReaction));
+    }
+
+    #[test]
+    fn test_chat_export() {
+        let mut histories = ChatHistories::default();
+        let mut hello = message(0);
+        hello.user_id = "alice".to_string();
+        hello.content = "Hello <everyone>\nsecond line".to_string();
+        hello.timestamp = 1_700_000_000_000;
+        let mut secret = message(1);
+        secret.user_id = "bob".to_string();
+        secret.recipient_id = Some("alice".to_string());
+        secret.timestamp = 1_700_000_060_000;
+        secret.edited_at = Some(1_700_000_070_000);
+        secret.attachment = Some(shared::FileAttachment {
+            id: "f1".to_string(),
+            filename: "notes.pdf".to_string(),
+            mime_type: "application/pdf".to_string(),
+            size: 42,
+            sha256: "ab12".to_string(),
+            thumbnail: None,
+        });
+        let mut other = message(2);
+        other.user_id = "bob".to_string();
+        other.recipient_id = Some("carol".to_string());
+        other.timestamp = 1_700_000_120_000;
+        histories.push(HistoryKey::of_message(&hello, &None), hello, 10);
+        histories.push(HistoryKey::of_message(&secret, &None), secret, 10);
+        histories.push(HistoryKey::of_message(&other, &None), other, 10);
+        histories.push(HistoryKey::Breakout("room-1".to_string()), message(3), 10);
+        // Bob has left; Alice renamed herself since
+        histories.remember_name("alice", "Alice");
+        histories.remember_name("bob", "Bob");
+        let current = HashMap::from([("alice".to_string(), "Alice B.".to_string())]);
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+
+        let exported = export_messages(&histories, "alice", &None, &current, offset);
+        assert_eq!(exported.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m0", "m1"]);
+        assert_eq!(exported[0].sender_name, "Alice B.");
+        assert_eq!(exported[1].recipient_name.as_deref(), Some("Alice B."));
+        assert_eq!(exported[1].attachment.as_ref().unwrap().sha256, "ab12");
+
+        assert_eq!(
+            to_text(&exported, offset),
+            "Chat export, times in UTC+02:00\n\
+             Attachments are listed by name, size and SHA-256 checksum. The files are not part of the export.\n\n\
+             [2023-11-15 00:13:20] Alice B.: Hello <everyone>\n    second line\n\
+             [2023-11-15 00:14:20] Bob to Alice B. (private): 1 (edited)\n    Attachment: notes.pdf (42 bytes, SHA-256 ab12)\n"
+        );
+        let html = to_html(&exported, offset);
+        assert!(html.contains("<time datetime=\"2023-11-15T00:13:20+02:00\">2023-11-15 00:13:20</time> <strong>Alice B.</strong>: Hello &lt;everyone&gt;<br>second line</li>"));
+        assert!(html.contains("<br>Attachment: notes.pdf (42 bytes, SHA-256 ab12)</li>"));
+        assert!(!html.contains("<a "));
+
+        let json = serde_json::to_value(&exported).unwrap();
+        assert_eq!(json[0]["time"], "2023-11-15T00:13:20+02:00");
+
+        // Names of people we know nothing about are not guessed
+        let exported = export_messages(&histories, "carol", &None, &HashMap::new(), offset);
+        assert_eq!(exported.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m0", "m2"]);
+        assert_eq!(exported[1].sender_name, "Bob");
+        assert_eq!(exported[1].recipient_name.as_deref(), Some(UNKNOWN_NAME));
+        assert!(!to_text(&exported, offset).contains(ATTACHMENT_NOTE));
+    }
+
+    #[test]
+    fn test_check_can_send() {
+        let mut config = RoomConfig {
+            host_id: Some("host".to_string()),
+            chat_slow_mode_secs: 5,
+            chat_muted: vec!["bob".to_string()],
+            ..Default::default()
+        };
+        assert!(check_can_send(&config, "alice", None, 1_000).is_ok());
+        assert!(check_can_send(&config, "alice", Some(1_000), 3_000).unwrap_err().contains("3 s"));
+        assert!(check_can_send(&config, "alice", Some(1_000), 6_000).is_ok());
+        assert_eq!(check_can_send(&config, "bob", None, 1_000), Err("You have been muted in the chat".to_string()));
+        assert!(check_can_send(&config, "host", Some(1_000), 1_001).is_ok());
+
+        config.chat_mode = shared::ChatMode::Disabled;
+        assert_eq!(check_can_send(&config, "alice", None, 1_000), Err("Chat is disabled".to_string()));
+        assert!(check_can_send(&config, "host", None, 1_000).is_ok());
+    }
+
+    #[test]
+    fn test_check_content() {
+        assert!(check_content(&"é".repeat(MAX_CHAT_CHARS)).is_ok());
+        assert_eq!(check_content(&"a".repeat(MAX_CHAT_CHARS + 1)), Err(ChatEditError::TooLong));
+    }
+}
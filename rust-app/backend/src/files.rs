@@ -0,0 +1,540 @@
+use axum::{
+    body::Body,
+    extract::{Json, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use shared::{FileAttachment, MAX_FILE_BYTES, MAX_THUMBNAIL_CHARS};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use crate::AppState;
+use crate::api::require_session;
+use crate::recordings::{is_valid_id, room_dir_name};
+
+pub const DEFAULT_QUOTA_BYTES: u64 = 500 * 1024 * 1024;
+const MAX_FILENAME_CHARS: usize = 255;
+// Enough for every signature in `sniff_mime`
+const SNIFF_BYTES: usize = 512;
+const FALLBACK_MIME_TYPE: &str = "application/octet-stream";
+
+#[derive(Debug)]
+pub enum FileError {
+    InvalidId,
+    NotFound,
+    TooLarge,
+    QuotaExceeded,
+    Io(io::Error),
+}
+
+impl From<io::Error> for FileError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::NotFound {
+            FileError::NotFound
+        } else {
+            FileError::Io(e)
+        }
+    }
+}
+
+impl IntoResponse for FileError {
+    fn into_response(self) -> Response {
+        match self {
+            FileError::InvalidId => (StatusCode::BAD_REQUEST, "Invalid id").into_response(),
+            FileError::NotFound => (StatusCode::NOT_FOUND, "File not found").into_response(),
+            FileError::TooLarge => (StatusCode::PAYLOAD_TOO_LARGE, format!("Files are limited to {} MB", MAX_FILE_BYTES / (1024 * 1024))).into_response(),
+            FileError::QuotaExceeded => (StatusCode::INSUFFICIENT_STORAGE, "The meeting's file quota is used up").into_response(),
+            FileError::Io(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+}
+
+/// Detects the type of an upload from its first bytes. The client's claimed type is
+/// never used, so a file can't be served as something it is not.
+pub fn sniff_mime(head: &[u8]) -> &'static str {
+    const SIGNATURES: [(usize, &[u8], &str); 12] = [
+        (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+        (0, b"\xff\xd8\xff", "image/jpeg"),
+        (0, b"GIF87a", "image/gif"),
+        (0, b"GIF89a", "image/gif"),
+        (0, b"%PDF-", "application/pdf"),
+        (0, b"PK\x03\x04", "application/zip"),
+        (0, b"\x1f\x8b", "application/gzip"),
+        (0, b"\x1a\x45\xdf\xa3", "video/webm"),
+        (0, b"OggS", "audio/ogg"),
+        (0, b"ID3", "audio/mpeg"),
+        (0, b"fLaC", "audio/flac"),
+        (4, b"ftyp", "video/mp4"),
+    ];
+    for (offset, magic, mime_type) in SIGNATURES {
+        if head.get(offset..offset + magic.len()) == Some(magic) {
+            return mime_type;
+        }
+    }
+    if head.starts_with(b"RIFF") {
+        match head.get(8..12) {
+            Some(b"WEBP") => return "image/webp",
+            Some(b"WAVE") => return "audio/wav",
+            _ => {},
+        }
+    }
+    // Plain text: valid UTF-8 apart from a character cut off at the end, and no NUL bytes.
+    // Markup is deliberately served as text so it never runs in our origin.
+    let is_text = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if !head.is_empty() && is_text && !head.contains(&0) {
+        "text/plain"
+    } else {
+        FALLBACK_MIME_TYPE
+    }
+}
+
+// Types the browser may show in place; everything else is forced to download
+fn is_inline_type(mime_type: &str) -> bool {
+    ["image/", "audio/", "video/"].iter().any(|p| mime_type.starts_with(p)) || mime_type == "application/pdf"
+}
+
+/// Strips directories, quotes and control characters from a client-supplied file name.
+pub fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(MAX_FILENAME_CHARS)
+        .collect();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        "file".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// A thumbnail must be a small base64 data URL of a raster image type.
+pub fn is_valid_thumbnail(data_url: &str) -> bool {
+    let Some(data) = ["data:image/jpeg;base64,", "data:image/png;base64,", "data:image/webp;base64,"]
+        .iter()
+        .find_map(|prefix| data_url.strip_prefix(prefix))
+    else {
+        return false;
+    };
+    data_url.len() <= MAX_THUMBNAIL_CHARS
+        && !data.is_empty()
+        && data.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=')
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub room_id: String,
+    pub uploader_id: String,
+    pub uploaded_at: u64,
+    pub attachment: FileAttachment,
+}
+
+/// Uploaded files on disk under `<root>/<room_id>/<file_id>`, indexed in memory.
+/// Identical content uploaded twice to the same room is stored once. The quota
+/// covers all rooms together: the server hosts one meeting at a time and empties
+/// the store when a room is created, while the room ID in an upload URL is
+/// whatever the client put there and would hand out fresh quota per made-up ID.
+pub struct FileStore {
+    root: PathBuf,
+    quota_bytes: u64,
+    files: HashMap<String, StoredFile>,
+}
+
+impl FileStore {
+    pub fn new(root: impl Into<PathBuf>, quota_bytes: u64) -> Self {
+        FileStore { root: root.into(), quota_bytes, files: HashMap::new() }
+    }
+
+    fn room_dir(&self, room_id: &str) -> Result<PathBuf, FileError> {
+        if room_id.is_empty() || room_id.len() > 256 {
+            return Err(FileError::InvalidId);
+        }
+        Ok(self.root.join(room_dir_name(room_id)))
+    }
+
+    pub fn usage(&self) -> u64 {
+        self.files.values().map(|f| f.attachment.size).sum()
+    }
+
+    /// The directory an upload to `room_id` goes to and the bytes left in the quota.
+    pub fn begin_upload(&self, room_id: &str) -> Result<(PathBuf, u64), FileError> {
+        let remaining = self.quota_bytes.saturating_sub(self.usage());
+        if remaining == 0 {
+            return Err(FileError::QuotaExceeded);
+        }
+        Ok((self.room_dir(room_id)?, remaining))
+    }
+
+    /// Indexes an upload already moved to `<room dir>/<file_id>`. If the room has the
+    /// same content, the existing file is returned instead and the new one is unused.
+    pub fn commit(&mut self, room_id: &str, file_id: &str, filename: String, upload: ReceivedUpload, uploader_id: &str, now: u64) -> Result<FileAttachment, FileError> {
+        if let Some(existing) = self.files.values().find(|f| f.room_id == room_id && f.attachment.sha256 == upload.sha256) {
+            return Ok(FileAttachment { filename, ..existing.attachment.clone() });
+        }
+        // Parallel uploads each saw the quota before the others finished
+        if self.usage() + upload.size > self.quota_bytes {
+            return Err(FileError::QuotaExceeded);
+        }
+
+        let attachment = FileAttachment {
+            id: file_id.to_string(),
+            filename,
+            mime_type: upload.mime_type.to_string(),
+            size: upload.size,
+            sha256: upload.sha256,
+            thumbnail: None,
+        };
+        self.files.insert(attachment.id.clone(), StoredFile {
+            room_id: room_id.to_string(),
+            uploader_id: uploader_id.to_string(),
+            uploaded_at: now,
+            attachment: attachment.clone(),
+        });
+        Ok(attachment)
+    }
+
+    pub fn get(&self, file_id: &str) -> Option<&StoredFile> {
+        self.files.get(file_id)
+    }
+
+    /// Forgets every file and returns the room directories holding them.
+    pub fn clear(&mut self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self.files.values().filter_map(|f| self.room_dir(&f.room_id).ok()).collect();
+        dirs.sort();
+        dirs.dedup();
+        self.files.clear();
+        dirs
+    }
+
+    /// The file's metadata and location, if it was uploaded to `room_id`.
+    pub fn locate(&self, room_id: &str, file_id: &str) -> Result<(FileAttachment, PathBuf), FileError> {
+        if !is_valid_id(file_id) {
+            return Err(FileError::InvalidId);
+        }
+        match self.files.get(file_id) {
+            Some(file) if file.room_id == room_id => Ok((file.attachment.clone(), self.room_dir(room_id)?.join(file_id))),
+            _ => Err(FileError::NotFound),
+        }
+    }
+}
+
+/// Deletes a file on drop unless it was kept, so an upload that fails or whose
+/// request is dropped mid-stream leaves nothing behind.
+struct PendingFile {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl PendingFile {
+    fn new(path: PathBuf) -> Self {
+        PendingFile { path, keep: false }
+    }
+
+    fn keep(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug)]
+pub struct ReceivedUpload {
+    pub size: u64,
+    pub sha256: String,
+    pub mime_type: &'static str,
+}
+
+/// Streams a request body to `path`, hashing it and sniffing its type on the way.
+/// Stops with `TooLarge` as soon as more than `max_bytes` arrived.
+pub async fn receive_upload(body: Body, path: &FsPath, max_bytes: u64) -> Result<ReceivedUpload, FileError> {
+    let mut stream = body.into_data_stream();
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    let mut size = 0u64;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(io::Error::other)?;
+        size += chunk.len() as u64;
+        if size > max_bytes {
+            return Err(FileError::TooLarge);
+        }
+        if head.len() < SNIFF_BYTES {
+            let take = (SNIFF_BYTES - head.len()).min(chunk.len());
+            head.extend_from_slice(&chunk[..take]);
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(ReceivedUpload { size, sha256: hex(&hasher.finalize()), mime_type: sniff_mime(&head) })
+}
+
+/// Streams an upload into its room's directory and indexes it. The store is only
+/// locked for bookkeeping; the disk is never touched while holding it.
+pub async fn store_upload(
+    files: &Mutex<FileStore>,
+    room_id: &str,
+    filename: String,
+    body: Body,
+    declared_len: Option<u64>,
+    uploader_id: &str,
+    now: u64,
+) -> Result<FileAttachment, FileError> {
+    let (dir, remaining) = files.lock().unwrap().begin_upload(room_id)?;
+    if declared_len.is_some_and(|len| len > remaining) {
+        return Err(FileError::QuotaExceeded);
+    }
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let mut pending = PendingFile::new(dir.join(format!(".upload-{}", uuid::Uuid::new_v4())));
+    let received = receive_upload(body, &pending.path, MAX_FILE_BYTES.min(remaining)).await.map_err(|e| match e {
+        FileError::TooLarge if remaining < MAX_FILE_BYTES => FileError::QuotaExceeded,
+        e => e,
+    })?;
+
+    let file_id = uuid::Uuid::new_v4().to_string();
+    let path = dir.join(&file_id);
+    tokio::fs::rename(&pending.path, &path).await?;
+    pending.path = path;
+
+    let attachment = files.lock().unwrap().commit(room_id, &file_id, filename, received, uploader_id, now)?;
+    if attachment.id == file_id {
+        pending.keep();
+    }
+    Ok(attachment)
+}
+
+/// Empties the store and deletes its files, for a new meeting. Like uploads, the
+/// disk is only touched after the lock is released.
+pub async fn purge(files: &Mutex<FileStore>) {
+    let dirs = files.lock().unwrap().clear();
+    for dir in dirs {
+        if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                eprintln!("Could not delete {}: {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    #[serde(default)]
+    pub filename: String,
+}
+
+pub async fn upload_file(
+    State(state): State<Arc<AppState>>,
+    Path(room_id): Path<String>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let uploader_id = match require_session(&state, &headers) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > MAX_FILE_BYTES) {
+        return FileError::TooLarge.into_response();
+    }
+
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    match store_upload(&state.files, &room_id, sanitize_filename(&query.filename), body, declared, &uploader_id, now).await {
+        Ok(attachment) => (StatusCode::CREATED, Json(attachment)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn download_file(
+    State(state): State<Arc<AppState>>,
+    Path((room_id, file_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let participant_id = match require_session(&state, &headers) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let located = state.files.lock().unwrap().locate(&room_id, &file_id);
+    let (attachment, path) = match located {
+        Ok(found) => found,
+        Err(e) => return e.into_response(),
+    };
+
+    // Only the uploader and those who can read a message carrying the file may fetch it
+    let location = state.participant_locations.lock().unwrap().get(&participant_id).cloned().flatten();
+    let shared = state.chat_history.lock().unwrap().shares_file(&file_id, &participant_id, &location);
+    let uploaded = state.files.lock().unwrap().get(&file_id).is_some_and(|f| f.uploader_id == participant_id);
+    if !shared && !uploaded {
+        return FileError::NotFound.into_response();
+    }
+
+    let disposition = if is_inline_type(&attachment.mime_type) { "inline" } else { "attachment" };
+    match tokio::fs::File::open(path).await {
+        Ok(file) => (
+            [
+                (header::CONTENT_TYPE, attachment.mime_type),
+                (header::CONTENT_LENGTH, attachment.size.to_string()),
+                (header::CONTENT_DISPOSITION, format!("{}; filename=\"{}\"", disposition, attachment.filename)),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            ],
+            Body::from_stream(ReaderStream::new(file)),
+        ).into_response(),
+        Err(e) => FileError::from(e).into_response(),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(quota: u64) -> (Mutex<FileStore>, PathBuf) {
+        let root = std::env::temp_dir().join(format!("juncto-files-{}", uuid::Uuid::new_v4()));
+        (Mutex::new(FileStore::new(root.clone(), quota)), root)
+    }
+
+    async fn upload(store: &Mutex<FileStore>, room_id: &str, name: &str, data: &'static [u8]) -> Result<FileAttachment, FileError> {
+        store_upload(store, room_id, name.to_string(), Body::from(data), None, "user1", 1000).await
+    }
+
+    fn files_on_disk(root: &FsPath, room_id: &str) -> usize {
+        fs::read_dir(root.join(room_dir_name(room_id))).map_or(0, |dir| dir.count())
+    }
+
+    #[test]
+    fn test_sniff_mime() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_mime(b"\0\0\0\x20ftypisom"), "video/mp4");
+        assert_eq!(sniff_mime("Grüße".as_bytes()), "text/plain");
+        // A multi-byte character cut off by the sniff window is still text
+        assert_eq!(sniff_mime(&"é".as_bytes()[..1]), "text/plain");
+        assert_eq!(sniff_mime(b"<svg onload=alert(1)>"), "text/plain");
+        assert_eq!(sniff_mime(b"\0\x01\x02"), FALLBACK_MIME_TYPE);
+        assert_eq!(sniff_mime(b""), FALLBACK_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_is_valid_thumbnail() {
+        assert!(is_valid_thumbnail("data:image/jpeg;base64,/9j/4AAQSkZJRg=="));
+        assert!(!is_valid_thumbnail("data:image/svg+xml;base64,PHN2Zz4="));
+        assert!(!is_valid_thumbnail("data:image/png;base64,\"><script>"));
+        assert!(!is_valid_thumbnail("data:image/png;base64,"));
+        assert!(!is_valid_thumbnail(&format!("data:image/png;base64,{}", "A".repeat(MAX_THUMBNAIL_CHARS))));
+        assert!(!is_valid_thumbnail("https://example.com/cat.png"));
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("C:\\Users\\me\\report \"final\".pdf"), "report final.pdf");
+        assert_eq!(sanitize_filename("a\nb.txt"), "ab.txt");
+        assert_eq!(sanitize_filename(".."), "file");
+        assert_eq!(sanitize_filename(""), "file");
+    }
+
+    #[tokio::test]
+    async fn test_upload_hashes_and_deduplicates() {
+        let (store, root) = temp_store(DEFAULT_QUOTA_BYTES);
+        let first = upload(&store, "room-1", "hello.txt", b"hello world").await.unwrap();
+        assert_eq!(first.size, 11);
+        assert_eq!(first.mime_type, "text/plain");
+        assert_eq!(first.sha256, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+
+        let (attachment, path) = store.lock().unwrap().locate("room-1", &first.id).unwrap();
+        assert_eq!(attachment, first);
+        assert_eq!(fs::read(path).unwrap(), b"hello world");
+
+        // Same content under another name shares the stored file and doesn't count twice
+        let second = upload(&store, "room-1", "copy.txt", b"hello world").await.unwrap();
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.filename, "copy.txt");
+        assert_eq!(store.lock().unwrap().usage(), 11);
+        assert_eq!(files_on_disk(&root, "room-1"), 1);
+
+        // Files are only reachable through the room they were uploaded to
+        let store = store.into_inner().unwrap();
+        assert!(matches!(store.locate("room-2", &first.id), Err(FileError::NotFound)));
+        assert!(matches!(store.locate("room-1", "../secret"), Err(FileError::InvalidId)));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_quota_covers_all_rooms() {
+        let (store, root) = temp_store(16);
+        upload(&store, "room-1", "a.txt", b"0123456789").await.unwrap();
+        assert!(matches!(upload(&store, "room-1", "b.txt", b"abcdefghij").await, Err(FileError::QuotaExceeded)));
+        // Made-up room IDs don't get a quota of their own
+        assert!(matches!(upload(&store, "room-2", "b.txt", b"abcdefghij").await, Err(FileError::QuotaExceeded)));
+        // Rejected uploads aren't counted and leave no temporary files behind
+        assert_eq!(store.lock().unwrap().usage(), 10);
+        assert_eq!(files_on_disk(&root, "room-1"), 1);
+        assert_eq!(files_on_disk(&root, "room-2"), 0);
+
+        upload(&store, "room-2", "c.txt", b"abcdef").await.unwrap();
+        assert!(matches!(store.lock().unwrap().begin_upload("room-1"), Err(FileError::QuotaExceeded)));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_purge_deletes_every_room() {
+        let (store, root) = temp_store(16);
+        upload(&store, "room-1", "a.txt", b"0123456789").await.unwrap();
+        upload(&store, "room-2", "b.txt", b"abcdef").await.unwrap();
+
+        purge(&store).await;
+        assert_eq!(store.lock().unwrap().usage(), 0);
+        assert_eq!(files_on_disk(&root, "room-1"), 0);
+        assert_eq!(files_on_disk(&root, "room-2"), 0);
+        // The next meeting has the whole quota again
+        upload(&store, "room-3", "c.txt", b"0123456789abcdef").await.unwrap();
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_upload_removes_temp_file() {
+        let (store, root) = temp_store(DEFAULT_QUOTA_BYTES);
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<&'static [u8], io::Error>>();
+        tx.unbounded_send(Ok(b"partial")).unwrap();
+        let body = Body::from_stream(rx);
+
+        // The client goes away while the body is still streaming
+        let upload = store_upload(&store, "room-1", "big.bin".to_string(), body, None, "user1", 1000);
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(50), upload).await.is_err());
+        assert_eq!(files_on_disk(&root, "room-1"), 0);
+        assert_eq!(store.lock().unwrap().usage(), 0);
+
+        drop(tx);
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,197 @@
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use shared::ContentKind;
+use std::fmt;
+use std::fs;
+
+// Rules are loaded from this JSON file when set, e.g.
+// {"rules": [{"words": ["spam"], "action": "mask"}, {"patterns": ["(?i)free\\s+money"], "action": "flag", "applies_to": ["chat"]}]}
+pub const CONFIG_ENV: &str = "CONTENT_FILTER_CONFIG";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    Mask,   // Replace the match with asterisks
+    Reject, // Refuse the whole text
+    Flag,   // Let it through and tell the moderators
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RuleConfig {
+    #[serde(default)]
+    pub words: Vec<String>, // Whole words, case-insensitive
+    #[serde(default)]
+    pub patterns: Vec<String>, // Regular expressions, used as written
+    pub action: FilterAction,
+    #[serde(default)]
+    pub applies_to: Vec<ContentKind>, // Empty = everything
+}
+
+#[derive(Debug)]
+pub enum FilterConfigError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Regex(regex::Error),
+}
+
+impl fmt::Display for FilterConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterConfigError::Io(e) => write!(f, "cannot read content filter config: {}", e),
+            FilterConfigError::Json(e) => write!(f, "invalid content filter config: {}", e),
+            FilterConfigError::Regex(e) => write!(f, "invalid content filter pattern: {}", e),
+        }
+    }
+}
+
+/// What the filter made of a text.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FilterOutcome {
+    pub text: String, // With masked parts replaced
+    pub rejected: bool,
+    pub flagged: Vec<String>, // Matches of "flag" rules
+}
+
+/// One stage of the filter pipeline. Stages see the text as left by the previous ones.
+pub trait ContentFilter: Send + Sync {
+    fn apply(&self, kind: ContentKind, outcome: &mut FilterOutcome);
+}
+
+/// Runs texts through each stage in turn until one rejects it.
+#[derive(Default)]
+pub struct FilterPipeline {
+    stages: Vec<Box<dyn ContentFilter>>,
+}
+
+impl FilterPipeline {
+    pub fn with_stage(mut self, stage: impl ContentFilter + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// The pipeline configured by the file named in `CONTENT_FILTER_CONFIG`; empty when unset.
+    pub fn from_env() -> Result<Self, FilterConfigError> {
+        match std::env::var(CONFIG_ENV) {
+            Ok(path) => {
+                let json = fs::read_to_string(path).map_err(FilterConfigError::Io)?;
+                let config: FilterConfig = serde_json::from_str(&json).map_err(FilterConfigError::Json)?;
+                Ok(Self::default().with_stage(RuleFilter::new(config)?))
+            },
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn run(&self, kind: ContentKind, text: &str) -> FilterOutcome {
+        let mut outcome = FilterOutcome { text: text.to_string(), ..Default::default() };
+        for stage in &self.stages {
+            stage.apply(kind, &mut outcome);
+            if outcome.rejected {
+                break;
+            }
+        }
+        outcome
+    }
+}
+
+struct Rule {
+    regexes: Vec<Regex>,
+    action: FilterAction,
+    applies_to: Vec<ContentKind>,
+}
+
+/// Word list and regex rules from the config file.
+pub struct RuleFilter {
+    rules: Vec<Rule>,
+}
+
+fn word_regex(word: &str) -> Result<Regex, regex::Error> {
+    // `\b` only anchors next to word characters, so symbols at either end go without it
+    let boundary = |c: Option<char>| if c.is_some_and(|c| c.is_alphanumeric() || c == '_') { r"\b" } else { "" };
+    let word = word.trim();
+    Regex::new(&format!("(?i){}{}{}", boundary(word.chars().next()), regex::escape(word), boundary(word.chars().next_back())))
+}
+
+impl RuleFilter {
+    pub fn new(config: FilterConfig) -> Result<Self, FilterConfigError> {
+        let rules = config.rules.into_iter().map(|rule| {
+            let words = rule.words.iter().filter(|w| !w.trim().is_empty()).map(|w| word_regex(w));
+            let patterns = rule.patterns.iter().map(|p| Regex::new(p));
+            let regexes = words.chain(patterns).collect::<Result<Vec<_>, _>>().map_err(FilterConfigError::Regex)?;
+            Ok(Rule { regexes, action: rule.action, applies_to: rule.applies_to })
+        }).collect::<Result<Vec<_>, FilterConfigError>>()?;
+        Ok(Self { rules })
+    }
+}
+
+impl ContentFilter for RuleFilter {
+    fn apply(&self, kind: ContentKind, outcome: &mut FilterOutcome) {
+        let rules = self.rules.iter().filter(|r| r.applies_to.is_empty() || r.applies_to.contains(&kind));
+        for rule in rules {
+            for regex in &rule.regexes {
+                match rule.action {
+                    FilterAction::Mask => {
+                        let masked = regex.replace_all(&outcome.text, |caps: &Captures| "*".repeat(caps[0].chars().count()));
+                        outcome.text = masked.into_owned();
+                    },
+                    FilterAction::Reject => {
+                        if regex.is_match(&outcome.text) {
+                            outcome.rejected = true;
+                            return;
+                        }
+                    },
+                    FilterAction::Flag => {
+                        for m in regex.find_iter(&outcome.text) {
+                            if !outcome.flagged.iter().any(|f| f == m.as_str()) {
+                                outcome.flagged.push(m.as_str().to_string());
+                            }
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(json: &str) -> FilterPipeline {
+        let config: FilterConfig = serde_json::from_str(json).unwrap();
+        FilterPipeline::default().with_stage(RuleFilter::new(config).unwrap())
+    }
+
+    #[test]
+    fn test_rule_actions() {
+        let filter = pipeline(r#"{"rules": [
+            {"words": ["darn", "f*ck"], "action": "mask"},
+            {"words": ["banned"], "action": "reject", "applies_to": ["display_name"]},
+            {"patterns": ["(?i)buy\\s+now"], "action": "flag"}
+        ]}"#);
+
+        let outcome = filter.run(ContentKind::Chat, "Darn it, f*ck. darned BUY  now");
+        assert_eq!(outcome.text, "**** it, ****. darned BUY  now");
+        assert!(!outcome.rejected);
+        assert_eq!(outcome.flagged, vec!["BUY  now"]);
+
+        // Rules only apply to the kinds they name
+        assert!(!filter.run(ContentKind::Chat, "banned").rejected);
+        assert!(filter.run(ContentKind::DisplayName, "Banned user").rejected);
+        assert!(filter.run(ContentKind::PollQuestion, "all clean").flagged.is_empty());
+    }
+
+    #[test]
+    fn test_empty_pipeline_and_bad_config() {
+        let outcome = FilterPipeline::default().run(ContentKind::Chat, "anything");
+        assert_eq!(outcome, FilterOutcome { text: "anything".to_string(), ..Default::default() });
+
+        let config: FilterConfig = serde_json::from_str(r#"{"rules": [{"patterns": ["("], "action": "flag"}]}"#).unwrap();
+        assert!(matches!(RuleFilter::new(config), Err(FilterConfigError::Regex(_))));
+    }
+}
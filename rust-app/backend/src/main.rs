@@ -1,7 +1,15 @@
 mod api;
+mod captions;
+mod chat;
+mod files;
+mod filter;
+mod polls;
+mod questions;
+mod recordings;
 
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{get, post, put},
     Router,
 };
 use tower_http::services::{ServeDir, ServeFile};
@@ -9,7 +17,10 @@ use std::net::SocketAddr;
 use tokio::sync::{broadcast, oneshot};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
-use shared::{ServerMessage, Participant, RoomConfig, Poll, DrawAction, BreakoutRoom};
+use shared::whiteboard::WhiteboardElement;
+use shared::{ServerMessage, Participant, RoomConfig, Poll, Question, BreakoutRoom, RecordingConsent, AuditEntry, CaptionSegment};
+
+const DEFAULT_CHAT_HISTORY_LIMIT: usize = 1000;
 
 type KnockingMap = HashMap<String, (Participant, Option<oneshot::Sender<bool>>)>;
 
@@ -21,13 +32,35 @@ pub struct AppState {
     pub knocking_participants: Arc<Mutex<KnockingMap>>,
     pub room_config: Arc<Mutex<RoomConfig>>,
     pub polls: Arc<Mutex<HashMap<String, Poll>>>,
-    pub whiteboard: Arc<Mutex<Vec<DrawAction>>>,
-    pub chat_history: Arc<Mutex<Vec<shared::ChatMessage>>>,
+    // Poll ID -> answer times of a quiz question, for scoring
+    pub quiz_clocks: Arc<Mutex<HashMap<String, polls::QuizClock>>>,
+    // Q&A panel questions by ID
+    pub questions: Arc<Mutex<HashMap<String, Question>>>,
+    pub whiteboard: Arc<Mutex<Vec<WhiteboardElement>>>,
+    // Per room and per private conversation
+    pub chat_history: Arc<Mutex<chat::ChatHistories>>,
     pub breakout_rooms: Arc<Mutex<HashMap<String, BreakoutRoom>>>,
     // Track participants' current room: participant_id -> room_id (None = Main)
     pub participant_locations: Arc<Mutex<HashMap<String, Option<String>>>>,
     pub shared_video_url: Arc<Mutex<Option<String>>>,
     pub speaking_start_times: Arc<Mutex<HashMap<String, u64>>>,
+    // Session token -> participant ID, used to authorize REST calls
+    pub session_tokens: Arc<Mutex<HashMap<String, String>>>,
+    // Tokens of closed connections -> (participant ID, epoch ms they left), so a reconnect keeps its ID
+    pub resumable_sessions: Arc<Mutex<HashMap<String, (String, u64)>>>,
+    // Async lock: recording calls hold it across their disk I/O
+    pub recordings: Arc<tokio::sync::Mutex<recordings::RecordingStore>>,
+    // Participant ID -> answer for the current recording
+    pub recording_consents: Arc<Mutex<HashMap<String, RecordingConsent>>>,
+    pub audit_log: Arc<Mutex<Vec<AuditEntry>>>,
+    // Final caption segments of the meeting, kept after it ends for export
+    pub captions: Arc<Mutex<Vec<CaptionSegment>>>,
+    // Chat messages kept per room and private conversation; older ones are dropped
+    pub chat_history_limit: usize,
+    // Chat attachments, uploaded over HTTP and referenced by ID in messages
+    pub files: Arc<Mutex<files::FileStore>>,
+    // Checks chat, display names, polls and breakout room names
+    pub content_filter: Arc<filter::FilterPipeline>,
 }
 
 #[tokio::main]
@@ -42,15 +75,38 @@ async fn main() {
     let room_config = Arc::new(Mutex::new(RoomConfig::default()));
     // Initialize polls
     let polls = Arc::new(Mutex::new(HashMap::new()));
+    let quiz_clocks = Arc::new(Mutex::new(HashMap::new()));
+    // Initialize Q&A
+    let questions = Arc::new(Mutex::new(HashMap::new()));
     // Initialize whiteboard
     let whiteboard = Arc::new(Mutex::new(Vec::new()));
     // Initialize chat history
-    let chat_history = Arc::new(Mutex::new(Vec::new()));
+    let chat_history = Arc::new(Mutex::new(chat::ChatHistories::default()));
     // Initialize breakout rooms
     let breakout_rooms = Arc::new(Mutex::new(HashMap::new()));
     let participant_locations = Arc::new(Mutex::new(HashMap::new()));
     let shared_video_url = Arc::new(Mutex::new(None));
     let speaking_start_times = Arc::new(Mutex::new(HashMap::new()));
+    let session_tokens = Arc::new(Mutex::new(HashMap::new()));
+    let resumable_sessions = Arc::new(Mutex::new(HashMap::new()));
+    let recordings_dir = std::env::var("RECORDINGS_DIR").unwrap_or_else(|_| "recordings".to_string());
+    let recordings = Arc::new(tokio::sync::Mutex::new(recordings::RecordingStore::new(recordings_dir)));
+    let recording_consents = Arc::new(Mutex::new(HashMap::new()));
+    let audit_log = Arc::new(Mutex::new(Vec::new()));
+    let captions = Arc::new(Mutex::new(Vec::new()));
+    let chat_history_limit = std::env::var("CHAT_HISTORY_LIMIT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CHAT_HISTORY_LIMIT);
+    let files_dir = std::env::var("FILES_DIR").unwrap_or_else(|_| "uploads".to_string());
+    let files = Arc::new(Mutex::new(files::FileStore::new(files_dir, files::DEFAULT_QUOTA_BYTES)));
+    let content_filter = match filter::FilterPipeline::from_env() {
+        Ok(pipeline) => Arc::new(pipeline),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        },
+    };
 
     let app_state = Arc::new(AppState {
         tx,
@@ -58,12 +114,23 @@ async fn main() {
         knocking_participants,
         room_config,
         polls,
+        quiz_clocks,
+        questions,
         whiteboard,
         chat_history,
         breakout_rooms,
         participant_locations,
         shared_video_url,
         speaking_start_times,
+        session_tokens,
+        resumable_sessions,
+        recordings,
+        recording_consents,
+        audit_log,
+        captions,
+        chat_history_limit,
+        files,
+        content_filter,
     });
 
     // Define the router
@@ -72,6 +139,20 @@ async fn main() {
 
     let app = Router::new()
         .route("/api/rooms", post(api::create_room))
+        .route("/api/rooms/:id/recordings", post(recordings::start_recording).get(recordings::list_recordings))
+        .route("/api/rooms/:id/recordings/:recording_id", get(recordings::download_recording).delete(recordings::delete_recording))
+        .route(
+            "/api/rooms/:id/recordings/:recording_id/chunks/:seq",
+            put(recordings::upload_chunk).layer(DefaultBodyLimit::max(recordings::MAX_CHUNK_BYTES)),
+        )
+        .route("/api/rooms/:id/recordings/:recording_id/stop", post(recordings::stop_recording))
+        .route("/api/rooms/:id/audit", get(api::audit_log))
+        .route("/api/rooms/:id/captions/export", get(captions::export_captions))
+        .route("/api/rooms/:id/chat/export", get(chat::export_chat))
+        .route("/api/rooms/:id/polls/export", get(polls::export_poll_results))
+        .route("/api/rooms/:id/quiz/export", get(polls::export_quiz))
+        .route("/api/rooms/:id/files", post(files::upload_file))
+        .route("/api/rooms/:id/files/:file_id", get(files::download_file))
         .route("/health", get(api::health_check))
         .route("/ws/chat", get(api::chat_handler))
         .fallback_service(serve_dir)
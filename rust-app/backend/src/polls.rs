@@ -0,0 +1,625 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use shared::{Participant, Poll, PollOption, QuizScore, ServerMessage, MAX_POLL_OPTIONS};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use crate::AppState;
+use crate::api::require_moderator;
+
+// Longest time limit a moderator can give a poll
+pub const MAX_POLL_TIME_LIMIT_SECS: u32 = 24 * 3600;
+// A correct quiz answer is worth between half and all of this, the faster the more
+pub const QUIZ_MAX_POINTS: u32 = 1000;
+// How long the speed bonus lasts for questions without a time limit
+const QUIZ_SPEED_WINDOW_SECS: u32 = 60;
+
+#[derive(Debug, PartialEq)]
+pub enum PollError {
+    NotFound,
+    InvalidOptions,
+    AlreadyVoted,
+    InvalidVote,
+    Closed,
+    NoCorrectAnswer,
+}
+
+impl PollError {
+    pub fn message(&self) -> String {
+        match self {
+            PollError::NotFound => "Poll not found".to_string(),
+            PollError::InvalidOptions => format!("A poll needs a question and 2 to {} distinct options", MAX_POLL_OPTIONS),
+            PollError::InvalidVote => "Invalid choice for this poll".to_string(),
+            PollError::AlreadyVoted => "You have already voted in this poll".to_string(),
+            PollError::Closed => "This poll is closed".to_string(),
+            PollError::NoCorrectAnswer => "Mark at least one option of a quiz question as correct".to_string(),
+        }
+    }
+}
+
+/// A poll as sent by a moderator, with a fresh ID, blank and repeated options dropped,
+/// option IDs assigned in order and any votes it came with discarded. A time limit
+/// becomes a deadline counted from `now_ms`.
+pub fn new_poll(poll: Poll, now_ms: u64) -> Result<Poll, PollError> {
+    let question = poll.question.trim().to_string();
+    let mut options: Vec<PollOption> = Vec::new();
+    for option in poll.options {
+        let text = option.text.trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        match options.iter_mut().find(|o| o.text == text) {
+            // Only quiz questions have correct answers
+            Some(existing) => existing.correct |= poll.quiz && option.correct,
+            None => options.push(PollOption { id: options.len() as u32, text, votes: 0, correct: poll.quiz && option.correct }),
+        }
+    }
+    if question.is_empty() || options.len() < 2 || options.len() > MAX_POLL_OPTIONS {
+        return Err(PollError::InvalidOptions);
+    }
+    if poll.quiz && !options.iter().any(|o| o.correct) {
+        return Err(PollError::NoCorrectAnswer);
+    }
+    let time_limit_secs = poll.time_limit_secs.filter(|secs| *secs > 0).map(|secs| secs.min(MAX_POLL_TIME_LIMIT_SECS));
+    Ok(Poll {
+        id: uuid::Uuid::new_v4().to_string(),
+        question,
+        options,
+        quiz: poll.quiz,
+        multiple_choice: poll.multiple_choice,
+        anonymous: poll.anonymous,
+        hide_results: poll.hide_results,
+        time_limit_secs,
+        closes_at: time_limit_secs.map(|secs| now_ms + u64::from(secs) * 1000),
+        created_at: now_ms,
+        ..Default::default()
+    })
+}
+
+/// Records `voter_id`'s choices. Single choice polls take exactly one option. With
+/// `allow_change`, a new vote replaces the earlier one instead of being refused.
+pub fn cast_vote(poll: &mut Poll, voter_id: &str, option_ids: &[u32], allow_change: bool, now_ms: u64) -> Result<Vec<u32>, PollError> {
+    if !poll.is_open(now_ms) {
+        return Err(PollError::Closed);
+    }
+    if poll.voters.contains(voter_id) && !allow_change {
+        return Err(PollError::AlreadyVoted);
+    }
+    let chosen: Vec<u32> = option_ids.iter().copied().collect::<BTreeSet<_>>().into_iter().collect();
+    let valid = !chosen.is_empty()
+        && (poll.multiple_choice || chosen.len() == 1)
+        && chosen.iter().all(|id| poll.options.iter().any(|o| o.id == *id));
+    if !valid {
+        return Err(PollError::InvalidVote);
+    }
+    if let Some(previous) = poll.ballots.remove(voter_id) {
+        for option in &mut poll.options {
+            if previous.contains(&option.id) {
+                option.votes = option.votes.saturating_sub(1);
+            }
+        }
+        poll.voter_count = poll.voter_count.saturating_sub(1);
+    }
+    for option in &mut poll.options {
+        if chosen.contains(&option.id) {
+            option.votes += 1;
+        }
+    }
+    poll.voters.insert(voter_id.to_string());
+    poll.voter_count += 1;
+    poll.ballots.insert(voter_id.to_string(), chosen.clone());
+    Ok(chosen)
+}
+
+/// The name `voter_id` voted under, or their current one.
+fn voter_name(poll: &Poll, voter_id: &str, participants: &HashMap<String, Participant>) -> String {
+    poll.voter_names
+        .get(voter_id)
+        .or_else(|| participants.get(voter_id).map(|p| &p.name))
+        .cloned()
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+/// When a quiz question was posted and when each voter last answered it. Reopening
+/// the question doesn't restart it, so earlier answers keep their score.
+#[derive(Debug, Clone, Default)]
+pub struct QuizClock {
+    pub started_at: u64,
+    pub answered_at: HashMap<String, u64>,
+}
+
+impl QuizClock {
+    pub fn new(started_at: u64) -> Self {
+        QuizClock { started_at, answered_at: HashMap::new() }
+    }
+
+    pub fn answered(&mut self, voter_id: &str, now_ms: u64) {
+        self.answered_at.insert(voter_id.to_string(), now_ms);
+    }
+}
+
+/// Points for `voter_id`'s answer to a quiz question: nothing when wrong, otherwise half
+/// of `QUIZ_MAX_POINTS` plus a speed bonus shrinking to zero over the time limit.
+pub fn quiz_points(poll: &Poll, clock: &QuizClock, voter_id: &str) -> Option<u32> {
+    let chosen = poll.ballots.get(voter_id)?;
+    if !poll.is_correct(chosen) {
+        return Some(0);
+    }
+    let window_ms = u64::from(poll.time_limit_secs.unwrap_or(QUIZ_SPEED_WINDOW_SECS)) * 1000;
+    let elapsed = clock.answered_at.get(voter_id).map_or(window_ms, |at| at.saturating_sub(clock.started_at)).min(window_ms);
+    let half = u64::from(QUIZ_MAX_POINTS / 2);
+    Some((half + half * (window_ms - elapsed) / window_ms.max(1)) as u32)
+}
+
+/// Totals over the revealed quiz questions, best first. Anonymous questions are left out
+/// so nobody's answers can be worked out from the scores.
+pub fn quiz_leaderboard<'a>(
+    polls: impl IntoIterator<Item = &'a Poll>,
+    clocks: &HashMap<String, QuizClock>,
+    participants: &HashMap<String, Participant>,
+) -> Vec<QuizScore> {
+    let mut scores: HashMap<&str, QuizScore> = HashMap::new();
+    for poll in polls.into_iter().filter(|p| p.quiz && p.revealed && !p.anonymous) {
+        let untimed = QuizClock::new(poll.created_at);
+        let clock = clocks.get(&poll.id).unwrap_or(&untimed);
+        for voter_id in poll.ballots.keys() {
+            let points = quiz_points(poll, clock, voter_id).unwrap_or(0);
+            let score = scores.entry(voter_id).or_insert_with(|| QuizScore {
+                participant_id: voter_id.clone(),
+                name: voter_name(poll, voter_id, participants),
+                points: 0,
+                correct: 0,
+                answered: 0,
+            });
+            score.points += points;
+            score.correct += u32::from(points > 0);
+            score.answered += 1;
+        }
+    }
+    let mut scores: Vec<QuizScore> = scores.into_values().collect();
+    scores.sort_by(|a, b| b.points.cmp(&a.points).then(b.correct.cmp(&a.correct)).then_with(|| a.name.cmp(&b.name)));
+    scores
+}
+
+pub fn leaderboard_message(state: &AppState) -> ServerMessage {
+    let participants = state.participants.lock().unwrap().clone();
+    let polls = state.polls.lock().unwrap();
+    let clocks = state.quiz_clocks.lock().unwrap();
+    ServerMessage::QuizLeaderboard(quiz_leaderboard(polls.values(), &clocks, &participants))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+}
+
+// Quotes a CSV field when it holds a separator, quote or line break
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuizQuestionResult {
+    pub question: String,
+    pub correct_options: Vec<String>,
+    pub answered: u32,
+    pub answered_correctly: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuizResults {
+    pub leaderboard: Vec<QuizScore>,
+    pub questions: Vec<QuizQuestionResult>, // Revealed questions, oldest first
+}
+
+pub fn quiz_results<'a>(
+    polls: impl IntoIterator<Item = &'a Poll>,
+    clocks: &HashMap<String, QuizClock>,
+    participants: &HashMap<String, Participant>,
+) -> QuizResults {
+    let mut revealed: Vec<&Poll> = polls.into_iter().filter(|p| p.quiz && p.revealed).collect();
+    revealed.sort_by_key(|p| p.created_at);
+    let questions = revealed.iter().map(|poll| QuizQuestionResult {
+        question: poll.question.clone(),
+        correct_options: poll.options.iter().filter(|o| o.correct).map(|o| o.text.clone()).collect(),
+        answered: poll.voter_count,
+        answered_correctly: poll.ballots.values().filter(|chosen| poll.is_correct(chosen)).count() as u32,
+    }).collect();
+    QuizResults { leaderboard: quiz_leaderboard(revealed, clocks, participants), questions }
+}
+
+/// The leaderboard as CSV, one row per participant with their rank.
+pub fn leaderboard_csv(scores: &[QuizScore]) -> String {
+    let mut csv = String::from("rank,name,points,correct,answered\n");
+    for (i, score) in scores.iter().enumerate() {
+        csv.push_str(&format!("{},{},{},{},{}\n", i + 1, csv_field(&score.name), score.points, score.correct, score.answered));
+    }
+    csv
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedOption {
+    pub text: String,
+    pub votes: u32,
+    pub correct: bool,
+    pub voters: Vec<String>, // Names; empty for anonymous polls
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedBallot {
+    pub participant_id: String,
+    pub name: String,
+    pub choices: Vec<String>, // Option texts
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedPoll {
+    pub question: String,
+    pub created_at: String, // RFC 3339, UTC
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    pub quiz: bool,
+    pub closed: bool,
+    pub voter_count: u32,
+    pub options: Vec<ExportedOption>,
+    pub ballots: Vec<ExportedBallot>, // Empty for anonymous polls
+}
+
+/// Every poll with its counts, oldest first. Who voted for what is only included for named polls.
+pub fn export_polls<'a>(polls: impl IntoIterator<Item = &'a Poll>, participants: &HashMap<String, Participant>) -> Vec<ExportedPoll> {
+    let mut polls: Vec<Poll> = polls.into_iter().map(Poll::moderator_view).collect();
+    polls.sort_by_key(|p| p.created_at);
+    polls.iter().map(|poll| {
+        let option_text = |id: &u32| poll.options.iter().find(|o| o.id == *id).map(|o| o.text.clone()).unwrap_or_default();
+        let ballots: Vec<ExportedBallot> = poll.ballots.iter().map(|(voter_id, chosen)| ExportedBallot {
+            participant_id: voter_id.clone(),
+            name: voter_name(poll, voter_id, participants),
+            choices: chosen.iter().map(option_text).collect(),
+        }).collect();
+        ExportedPoll {
+            question: poll.question.clone(),
+            created_at: DateTime::from_timestamp_millis(poll.created_at as i64).unwrap_or_default().to_rfc3339(),
+            multiple_choice: poll.multiple_choice,
+            anonymous: poll.anonymous,
+            quiz: poll.quiz,
+            closed: poll.closed,
+            voter_count: poll.voter_count,
+            options: poll.options.iter().map(|option| ExportedOption {
+                text: option.text.clone(),
+                votes: option.votes,
+                correct: option.correct,
+                voters: ballots
+                    .iter()
+                    .filter(|b| poll.ballots[&b.participant_id].contains(&option.id))
+                    .map(|b| b.name.clone())
+                    .collect(),
+            }).collect(),
+            ballots,
+        }
+    }).collect()
+}
+
+/// One row per poll option; voters are listed by name, separated by semicolons.
+pub fn polls_csv(polls: &[ExportedPoll]) -> String {
+    let mut csv = String::from("question,option,votes,correct,voters\n");
+    for poll in polls {
+        for option in &poll.options {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                csv_field(&poll.question),
+                csv_field(&option.text),
+                option.votes,
+                if poll.quiz { if option.correct { "yes" } else { "no" } } else { "" },
+                csv_field(&option.voters.join("; ")),
+            ));
+        }
+    }
+    csv
+}
+
+pub async fn export_poll_results(
+    State(state): State<Arc<AppState>>,
+    Path(_room_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+
+    let polls = {
+        let participants = state.participants.lock().unwrap().clone();
+        let polls = state.polls.lock().unwrap();
+        export_polls(polls.values(), &participants)
+    };
+    if polls.is_empty() && query.format != ExportFormat::Json {
+        return (StatusCode::NOT_FOUND, "No polls in this meeting").into_response();
+    }
+
+    let body = match query.format {
+        ExportFormat::Csv => polls_csv(&polls),
+        ExportFormat::Json => match serde_json::to_string_pretty(&polls) {
+            Ok(json) => json,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"poll-results.{}\"", query.format.extension())),
+        ],
+        body,
+    ).into_response()
+}
+
+pub async fn export_quiz(
+    State(state): State<Arc<AppState>>,
+    Path(_room_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+
+    let results = {
+        let participants = state.participants.lock().unwrap().clone();
+        let polls = state.polls.lock().unwrap();
+        let clocks = state.quiz_clocks.lock().unwrap();
+        quiz_results(polls.values(), &clocks, &participants)
+    };
+    if results.questions.is_empty() && query.format != ExportFormat::Json {
+        return (StatusCode::NOT_FOUND, "No quiz answers revealed yet").into_response();
+    }
+
+    let body = match query.format {
+        ExportFormat::Csv => leaderboard_csv(&results.leaderboard),
+        ExportFormat::Json => match serde_json::to_string_pretty(&results) {
+            Ok(json) => json,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"quiz-results.{}\"", query.format.extension())),
+        ],
+        body,
+    ).into_response()
+}
+
+/// Closes the poll when its deadline passes, unless it was closed, reopened or deleted meanwhile.
+pub fn schedule_close(state: Arc<AppState>, poll_id: String, closes_at: u64) {
+    tokio::spawn(async move {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        tokio::time::sleep(Duration::from_millis(closes_at.saturating_sub(now))).await;
+        let closed = {
+            let mut polls = state.polls.lock().unwrap();
+            match polls.get_mut(&poll_id) {
+                Some(poll) if !poll.closed && poll.closes_at == Some(closes_at) => {
+                    poll.closed = true;
+                    Some(poll.clone())
+                },
+                _ => None,
+            }
+        };
+        if let Some(poll) = closed {
+            broadcast_poll(&state.tx, &poll, false);
+        }
+    });
+}
+
+/// Everything a participant joining now should know about the polls, oldest first.
+pub fn poll_snapshot<'a>(polls: impl IntoIterator<Item = &'a Poll>, participant_id: &str, is_moderator: bool) -> Vec<ServerMessage> {
+    let mut polls: Vec<&Poll> = polls.into_iter().collect();
+    polls.sort_by_key(|p| p.created_at);
+    let mut messages = Vec::new();
+    for poll in polls {
+        messages.push(ServerMessage::PollCreated(if is_moderator { poll.moderator_view() } else { poll.public_view() }));
+        // Our own ballot, which hidden or anonymous polls don't carry
+        if let Some(option_ids) = poll.ballots.get(participant_id) {
+            messages.push(ServerMessage::PollVoteRecorded { poll_id: poll.id.clone(), option_ids: option_ids.clone() });
+        }
+    }
+    messages
+}
+
+/// Sends a new or changed poll to everyone, redacted as its settings require. Moderators
+/// also get the counts and quiz answers while they are hidden from participants.
+pub fn broadcast_poll(tx: &broadcast::Sender<ServerMessage>, poll: &Poll, created: bool) {
+    let public = poll.public_view();
+    let _ = tx.send(if created { ServerMessage::PollCreated(public) } else { ServerMessage::PollUpdated(public) });
+    if poll.redacted() {
+        let _ = tx.send(ServerMessage::PollResults(poll.moderator_view()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(options: &[&str], multiple_choice: bool) -> Poll {
+        Poll {
+            question: "Lunch?".to_string(),
+            options: options.iter().map(|text| PollOption { id: 7, text: text.to_string(), votes: 3, correct: false }).collect(),
+            multiple_choice,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_new_poll_normalizes_options() {
+        let created = new_poll(poll(&["Pizza", " ", "Sushi ", "Pizza", "Tacos"], false), 0).unwrap();
+        assert!(!created.id.is_empty());
+        let options: Vec<(u32, &str, u32)> = created.options.iter().map(|o| (o.id, o.text.as_str(), o.votes)).collect();
+        assert_eq!(options, vec![(0, "Pizza", 0), (1, "Sushi", 0), (2, "Tacos", 0)]);
+
+        assert_eq!(new_poll(poll(&["Only", "Only"], false), 0), Err(PollError::InvalidOptions));
+        let too_many: Vec<String> = (0..=MAX_POLL_OPTIONS).map(|i| i.to_string()).collect();
+        assert_eq!(new_poll(poll(&too_many.iter().map(String::as_str).collect::<Vec<_>>(), false), 0), Err(PollError::InvalidOptions));
+
+        // A repeated option marked correct only counts in a quiz
+        let mut repeated = poll(&["Yes", "No", "Yes"], false);
+        repeated.options[2].correct = true;
+        assert!(new_poll(repeated.clone(), 0).unwrap().options.iter().all(|o| !o.correct));
+        repeated.quiz = true;
+        let quiz = new_poll(repeated, 0).unwrap();
+        assert_eq!(quiz.options.iter().map(|o| o.correct).collect::<Vec<_>>(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_cast_vote() {
+        let mut single = new_poll(poll(&["A", "B", "C"], false), 0).unwrap();
+        assert_eq!(cast_vote(&mut single, "alice", &[0, 1], false, 0), Err(PollError::InvalidVote));
+        assert_eq!(cast_vote(&mut single, "alice", &[9], false, 0), Err(PollError::InvalidVote));
+        assert_eq!(cast_vote(&mut single, "alice", &[1], false, 0), Ok(vec![1]));
+        assert_eq!(cast_vote(&mut single, "alice", &[2], false, 0), Err(PollError::AlreadyVoted));
+        assert_eq!(single.options[1].votes, 1);
+
+        let mut multiple = new_poll(poll(&["A", "B", "C"], true), 0).unwrap();
+        assert_eq!(cast_vote(&mut multiple, "alice", &[2, 0, 2], false, 0), Ok(vec![0, 2]));
+        assert_eq!(cast_vote(&mut multiple, "bob", &[], false, 0), Err(PollError::InvalidVote));
+        cast_vote(&mut multiple, "bob", &[0], false, 0).unwrap();
+        assert_eq!(multiple.options.iter().map(|o| o.votes).collect::<Vec<_>>(), vec![2, 0, 1]);
+        assert_eq!(multiple.voter_count, 2);
+        assert_eq!(multiple.ballots["alice"], vec![0, 2]);
+    }
+
+    #[test]
+    fn test_vote_changes_and_deadline() {
+        let mut timed = new_poll(Poll { time_limit_secs: Some(60), ..poll(&["A", "B"], false) }, 1_000).unwrap();
+        assert_eq!(timed.closes_at, Some(61_000));
+        cast_vote(&mut timed, "alice", &[0], false, 2_000).unwrap();
+        assert_eq!(cast_vote(&mut timed, "alice", &[1], false, 3_000), Err(PollError::AlreadyVoted));
+        assert_eq!(cast_vote(&mut timed, "alice", &[1], true, 3_000), Ok(vec![1]));
+        assert_eq!(timed.options.iter().map(|o| o.votes).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(timed.voter_count, 1);
+        assert_eq!(cast_vote(&mut timed, "bob", &[0], true, 61_000), Err(PollError::Closed));
+
+        timed.closes_at = None;
+        timed.closed = true;
+        assert_eq!(cast_vote(&mut timed, "bob", &[0], true, 4_000), Err(PollError::Closed));
+    }
+
+    #[test]
+    fn test_poll_snapshot() {
+        let mut first = new_poll(Poll { anonymous: true, ..poll(&["A", "B"], false) }, 5).unwrap();
+        let second = new_poll(poll(&["C", "D"], false), 9).unwrap();
+        cast_vote(&mut first, "alice", &[1], false, 6).unwrap();
+
+        let messages = poll_snapshot([&second, &first], "alice", false);
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(&messages[0], ServerMessage::PollCreated(p) if p.id == first.id && p.ballots.is_empty()));
+        assert_eq!(messages[1], ServerMessage::PollVoteRecorded { poll_id: first.id.clone(), option_ids: vec![1] });
+        assert!(matches!(&messages[2], ServerMessage::PollCreated(p) if p.id == second.id));
+        assert_eq!(poll_snapshot([&first], "bob", true).len(), 1);
+    }
+
+    #[test]
+    fn test_quiz_scoring() {
+        let mut question = poll(&["3", "4", "5"], false);
+        question.quiz = true;
+        assert_eq!(new_poll(question.clone(), 0), Err(PollError::NoCorrectAnswer));
+        question.options[1].correct = true;
+        question.time_limit_secs = Some(10);
+        let mut quiz = new_poll(question, 1_000).unwrap();
+        assert!(quiz.options[1].correct);
+
+        let mut clock = QuizClock::new(1_000);
+        for (voter, option, at) in [("alice", 1, 1_000), ("bob", 1, 6_000), ("carol", 0, 2_000)] {
+            cast_vote(&mut quiz, voter, &[option], false, at).unwrap();
+            clock.answered(voter, at);
+        }
+        assert_eq!(quiz_points(&quiz, &clock, "alice"), Some(QUIZ_MAX_POINTS));
+        assert_eq!(quiz_points(&quiz, &clock, "bob"), Some(QUIZ_MAX_POINTS * 3 / 4));
+        assert_eq!(quiz_points(&quiz, &clock, "carol"), Some(0));
+        assert_eq!(quiz_points(&quiz, &clock, "dave"), None);
+
+        // Reopening moves the deadline but scores stay measured from the first start
+        quiz.closes_at = Some(100_000);
+        assert_eq!(quiz_points(&quiz, &clock, "bob"), Some(QUIZ_MAX_POINTS * 3 / 4));
+        cast_vote(&mut quiz, "dave", &[1], false, 95_000).unwrap();
+        clock.answered("dave", 95_000);
+        assert_eq!(quiz_points(&quiz, &clock, "dave"), Some(QUIZ_MAX_POINTS / 2));
+
+        // Nothing counts until the answers are out
+        let participants = HashMap::new();
+        let clocks = HashMap::from([(quiz.id.clone(), clock)]);
+        assert!(quiz_leaderboard([&quiz], &clocks, &participants).is_empty());
+        quiz.revealed = true;
+        let board = quiz_leaderboard([&quiz], &clocks, &participants);
+        let summary: Vec<(&str, u32, u32)> = board.iter().map(|s| (s.participant_id.as_str(), s.points, s.correct)).collect();
+        assert_eq!(summary, vec![("alice", 1000, 1), ("bob", 750, 1), ("dave", 500, 1), ("carol", 0, 0)]);
+
+        let results = quiz_results([&quiz], &clocks, &participants);
+        assert_eq!(results.questions[0].correct_options, vec!["4"]);
+        assert_eq!((results.questions[0].answered, results.questions[0].answered_correctly), (4, 3));
+        let csv = leaderboard_csv(&board);
+        assert_eq!(csv.lines().nth(1), Some("1,Unknown,1000,1,1"));
+        assert_eq!(csv_field("Smith, \"Jo\""), "\"Smith, \"\"Jo\"\"\"");
+    }
+
+    #[test]
+    fn test_export_polls() {
+        let mut named = new_poll(poll(&["Pizza", "Sushi"], true), 2_000).unwrap();
+        let mut anonymous = new_poll(Poll { anonymous: true, ..poll(&["Yes", "No"], false) }, 1_000).unwrap();
+        cast_vote(&mut named, "alice", &[0, 1], false, 2_500).unwrap();
+        cast_vote(&mut named, "bob", &[1], false, 2_600).unwrap();
+        named.voter_names.insert("alice".to_string(), "Alice".to_string());
+        cast_vote(&mut anonymous, "alice", &[0], false, 1_500).unwrap();
+
+        let bob = Participant {
+            id: "bob".to_string(),
+            name: "Bob, Jr".to_string(),
+            is_hand_raised: false,
+            is_sharing_screen: false,
+            speaking_time: 0,
+            video_quality: Default::default(),
+        };
+        let participants = HashMap::from([("bob".to_string(), bob)]);
+        let exported = export_polls([&named, &anonymous], &participants);
+        assert_eq!(exported[0].question, anonymous.question);
+        assert!(exported[0].ballots.is_empty() && exported[0].options[0].voters.is_empty());
+        assert_eq!(exported[0].options[0].votes, 1);
+        assert_eq!(exported[1].ballots[0].choices, vec!["Pizza", "Sushi"]);
+        assert_eq!(exported[1].options[1].voters, vec!["Alice", "Bob, Jr"]);
+
+        let csv = polls_csv(&exported);
+        assert_eq!(csv.lines().last(), Some("Lunch?,Sushi,2,,\"Alice; Bob, Jr\""));
+    }
+}
@@ -0,0 +1,85 @@
+use shared::{Participant, Question, MAX_QUESTION_CHARS};
+
+#[derive(Debug, PartialEq)]
+pub enum QuestionError {
+    NotFound,
+    Empty,
+    TooLong,
+}
+
+impl QuestionError {
+    pub fn message(&self) -> String {
+        match self {
+            QuestionError::NotFound => "Question not found".to_string(),
+            QuestionError::Empty => "Type a question first".to_string(),
+            QuestionError::TooLong => format!("Questions can be at most {} characters", MAX_QUESTION_CHARS),
+        }
+    }
+}
+
+/// A question asked by `author`, who is left off it when asking anonymously.
+pub fn new_question(text: &str, author: &Participant, anonymous: bool, now_ms: u64) -> Result<Question, QuestionError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(QuestionError::Empty);
+    }
+    if text.chars().count() > MAX_QUESTION_CHARS {
+        return Err(QuestionError::TooLong);
+    }
+    Ok(Question {
+        id: uuid::Uuid::new_v4().to_string(),
+        text: text.to_string(),
+        author_id: (!anonymous).then(|| author.id.clone()),
+        author_name: (!anonymous).then(|| author.name.clone()),
+        created_at: now_ms,
+        ..Default::default()
+    })
+}
+
+/// Adds `voter_id`'s upvote, or takes it back if they had already given one.
+pub fn toggle_upvote(question: &mut Question, voter_id: &str) {
+    if !question.upvoters.remove(voter_id) {
+        question.upvoters.insert(voter_id.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participant() -> Participant {
+        Participant {
+            id: "alice".to_string(),
+            name: "Alice".to_string(),
+            is_hand_raised: false,
+            is_sharing_screen: false,
+            speaking_time: 0,
+            video_quality: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_new_question() {
+        let named = new_question("  Will slides be shared? ", &participant(), false, 5).unwrap();
+        assert_eq!(named.text, "Will slides be shared?");
+        assert_eq!(named.author_name.as_deref(), Some("Alice"));
+        assert_eq!(named.created_at, 5);
+
+        let anonymous = new_question("Why?", &participant(), true, 5).unwrap();
+        assert!(anonymous.author_id.is_none() && anonymous.author_name.is_none());
+
+        assert_eq!(new_question("   ", &participant(), false, 5), Err(QuestionError::Empty));
+        assert_eq!(new_question(&"?".repeat(MAX_QUESTION_CHARS + 1), &participant(), false, 5), Err(QuestionError::TooLong));
+        assert!(QuestionError::TooLong.message().contains(&MAX_QUESTION_CHARS.to_string()));
+    }
+
+    #[test]
+    fn test_toggle_upvote() {
+        let mut question = new_question("Why?", &participant(), false, 0).unwrap();
+        toggle_upvote(&mut question, "bob");
+        toggle_upvote(&mut question, "carol");
+        assert_eq!(question.upvotes(), 2);
+        toggle_upvote(&mut question, "bob");
+        assert_eq!(question.upvoters.iter().collect::<Vec<_>>(), vec!["carol"]);
+    }
+}
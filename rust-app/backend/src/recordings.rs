@@ -0,0 +1,444 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Json, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use shared::RecordingManifest;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use crate::AppState;
+use crate::api::require_moderator;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const MEDIA_FILE: &str = "recording.webm";
+const DEFAULT_MIME_TYPE: &str = "video/webm";
+// What MediaRecorder produces; the type is served back as the recording's Content-Type
+const RECORDING_TYPES: [&str; 5] = ["video/webm", "video/mp4", "audio/webm", "audio/ogg", "audio/mp4"];
+// Generous for one MediaRecorder timeslice; axum's default limit is 2MB
+pub const MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum RecordingError {
+    InvalidId,
+    NotFound,
+    AlreadyStopped,
+    OutOfOrder { expected: u32 },
+    UnsupportedType,
+    Io(io::Error),
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::NotFound {
+            RecordingError::NotFound
+        } else {
+            RecordingError::Io(e)
+        }
+    }
+}
+
+impl IntoResponse for RecordingError {
+    fn into_response(self) -> Response {
+        match self {
+            RecordingError::InvalidId => (StatusCode::BAD_REQUEST, "Invalid id").into_response(),
+            RecordingError::NotFound => (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+            RecordingError::AlreadyStopped => (StatusCode::CONFLICT, "Recording already stopped").into_response(),
+            RecordingError::OutOfOrder { expected } => {
+                (StatusCode::CONFLICT, Json(json!({ "error": "Chunk out of order", "expected": expected }))).into_response()
+            },
+            RecordingError::UnsupportedType => (StatusCode::BAD_REQUEST, "Unsupported recording type").into_response(),
+            RecordingError::Io(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+}
+
+/// Whether `mime_type` is an audio or video type from `RECORDING_TYPES`, optionally
+/// with a `codecs` parameter such as `video/webm;codecs=vp9,opus`.
+fn is_recording_type(mime_type: &str) -> bool {
+    let mut parts = mime_type.split(';');
+    let base = parts.next().unwrap_or_default().trim();
+    let params_ok = parts.all(|param| match param.trim().split_once('=') {
+        Some((name, value)) if name.trim().eq_ignore_ascii_case("codecs") => {
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+            !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | ',' | '-' | ' '))
+        },
+        _ => false,
+    });
+    RECORDING_TYPES.iter().any(|t| base.eq_ignore_ascii_case(t)) && params_ok
+}
+
+// Ids end up in file paths, so only allow a conservative character set
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Room IDs are user-chosen meeting names, so escape anything outside the safe set
+pub(crate) fn room_dir_name(room_id: &str) -> String {
+    room_id.bytes().map(|b| {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+            (b as char).to_string()
+        } else {
+            format!("%{:02X}", b)
+        }
+    }).collect()
+}
+
+fn merge_participants(manifest: &mut RecordingManifest, names: &[String]) {
+    for name in names {
+        if !manifest.participants.contains(name) {
+            manifest.participants.push(name.clone());
+        }
+    }
+}
+
+/// Recordings on disk, one directory per recording under `<root>/<room_id>/`.
+/// Chunks are appended to a single media file as they arrive, in sequence order.
+/// Callers serialize access through an async lock, since each call reads and rewrites the manifest.
+pub struct RecordingStore {
+    root: PathBuf,
+}
+
+impl RecordingStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        RecordingStore { root: root.into() }
+    }
+
+    fn room_dir(&self, room_id: &str) -> Result<PathBuf, RecordingError> {
+        if room_id.is_empty() || room_id.len() > 256 {
+            return Err(RecordingError::InvalidId);
+        }
+        Ok(self.root.join(room_dir_name(room_id)))
+    }
+
+    fn recording_dir(&self, room_id: &str, recording_id: &str) -> Result<PathBuf, RecordingError> {
+        if !is_valid_id(recording_id) {
+            return Err(RecordingError::InvalidId);
+        }
+        Ok(self.room_dir(room_id)?.join(recording_id))
+    }
+
+    async fn write_manifest(&self, manifest: &RecordingManifest) -> Result<(), RecordingError> {
+        let path = self.recording_dir(&manifest.room_id, &manifest.id)?.join(MANIFEST_FILE);
+        let json = serde_json::to_vec_pretty(manifest).map_err(io::Error::from)?;
+        fs::write(path, json).await?;
+        Ok(())
+    }
+
+    pub async fn manifest(&self, room_id: &str, recording_id: &str) -> Result<RecordingManifest, RecordingError> {
+        let path = self.recording_dir(room_id, recording_id)?.join(MANIFEST_FILE);
+        let bytes = fs::read(path).await?;
+        Ok(serde_json::from_slice(&bytes).map_err(io::Error::from)?)
+    }
+
+    pub fn media_path(&self, room_id: &str, recording_id: &str) -> Result<PathBuf, RecordingError> {
+        Ok(self.recording_dir(room_id, recording_id)?.join(MEDIA_FILE))
+    }
+
+    pub async fn start(&self, room_id: &str, mime_type: &str, participants: &[String], now: u64) -> Result<RecordingManifest, RecordingError> {
+        let mut manifest = RecordingManifest {
+            id: uuid::Uuid::new_v4().to_string(),
+            room_id: room_id.to_string(),
+            mime_type: mime_type.to_string(),
+            started_at: now,
+            stopped_at: None,
+            participants: Vec::new(),
+            chunk_count: 0,
+            size_bytes: 0,
+        };
+        merge_participants(&mut manifest, participants);
+
+        let dir = self.recording_dir(room_id, &manifest.id)?;
+        fs::create_dir_all(&dir).await?;
+        fs::File::create(dir.join(MEDIA_FILE)).await?;
+        self.write_manifest(&manifest).await?;
+        Ok(manifest)
+    }
+
+    /// Appends chunk `seq`. Chunks must arrive in order; re-sending one that was already
+    /// stored is accepted and ignored so clients can retry after a dropped response.
+    pub async fn append_chunk(&self, room_id: &str, recording_id: &str, seq: u32, data: &[u8], participants: &[String]) -> Result<RecordingManifest, RecordingError> {
+        let mut manifest = self.manifest(room_id, recording_id).await?;
+        if seq < manifest.chunk_count {
+            return Ok(manifest);
+        }
+        if manifest.stopped_at.is_some() {
+            return Err(RecordingError::AlreadyStopped);
+        }
+        if seq != manifest.chunk_count {
+            return Err(RecordingError::OutOfOrder { expected: manifest.chunk_count });
+        }
+
+        let mut file = OpenOptions::new().append(true).open(self.media_path(room_id, recording_id)?).await?;
+        file.write_all(data).await?;
+        file.flush().await?;
+
+        manifest.chunk_count += 1;
+        manifest.size_bytes += data.len() as u64;
+        merge_participants(&mut manifest, participants);
+        self.write_manifest(&manifest).await?;
+        Ok(manifest)
+    }
+
+    pub async fn stop(&self, room_id: &str, recording_id: &str, participants: &[String], now: u64) -> Result<RecordingManifest, RecordingError> {
+        let mut manifest = self.manifest(room_id, recording_id).await?;
+        if manifest.stopped_at.is_none() {
+            manifest.stopped_at = Some(now);
+            merge_participants(&mut manifest, participants);
+            self.write_manifest(&manifest).await?;
+        }
+        Ok(manifest)
+    }
+
+    pub async fn list(&self, room_id: &str) -> Result<Vec<RecordingManifest>, RecordingError> {
+        let dir = self.room_dir(room_id)?;
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut manifests = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            if let Some(recording_id) = name.to_str() {
+                // Skip directories that never got a manifest (e.g. a crash mid-start)
+                if let Ok(manifest) = self.manifest(room_id, recording_id).await {
+                    manifests.push(manifest);
+                }
+            }
+        }
+        manifests.sort_by_key(|m| m.started_at);
+        Ok(manifests)
+    }
+
+    pub async fn delete(&self, room_id: &str, recording_id: &str) -> Result<(), RecordingError> {
+        let dir = self.recording_dir(room_id, recording_id)?;
+        fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StartRecordingRequest {
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+fn participant_names(state: &AppState) -> Vec<String> {
+    let participants = state.participants.lock().unwrap();
+    participants.values().map(|p| p.name.clone()).collect()
+}
+
+pub async fn start_recording(
+    State(state): State<Arc<AppState>>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+    payload: Option<Json<StartRecordingRequest>>,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+    let request = payload.map(|Json(r)| r).unwrap_or_default();
+    let mime_type = request.mime_type.unwrap_or_else(|| DEFAULT_MIME_TYPE.to_string());
+    if !is_recording_type(&mime_type) {
+        return RecordingError::UnsupportedType.into_response();
+    }
+    let names = participant_names(&state);
+
+    let store = state.recordings.lock().await;
+    match store.start(&room_id, &mime_type, &names, now_ms()).await {
+        Ok(manifest) => (StatusCode::CREATED, Json(manifest)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn upload_chunk(
+    State(state): State<Arc<AppState>>,
+    Path((room_id, recording_id, seq)): Path<(String, String, u32)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+    let names = participant_names(&state);
+
+    let store = state.recordings.lock().await;
+    match store.append_chunk(&room_id, &recording_id, seq, &body, &names).await {
+        Ok(manifest) => Json(manifest).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn stop_recording(
+    State(state): State<Arc<AppState>>,
+    Path((room_id, recording_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+    let names = participant_names(&state);
+
+    let store = state.recordings.lock().await;
+    match store.stop(&room_id, &recording_id, &names, now_ms()).await {
+        Ok(manifest) => Json(manifest).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_recordings(
+    State(state): State<Arc<AppState>>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+
+    let store = state.recordings.lock().await;
+    match store.list(&room_id).await {
+        Ok(manifests) => Json(manifests).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn download_recording(
+    State(state): State<Arc<AppState>>,
+    Path((room_id, recording_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+
+    let located = {
+        let store = state.recordings.lock().await;
+        match store.manifest(&room_id, &recording_id).await {
+            Ok(manifest) => store.media_path(&room_id, &recording_id).map(|path| (manifest, path)),
+            Err(e) => Err(e),
+        }
+    };
+    let (manifest, path) = match located {
+        Ok(found) => found,
+        Err(e) => return e.into_response(),
+    };
+
+    // A recording still in progress keeps growing, so no Content-Length
+    match fs::File::open(path).await {
+        Ok(file) => (
+            [
+                (header::CONTENT_TYPE, manifest.mime_type),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"recording-{}.webm\"", manifest.id)),
+            ],
+            Body::from_stream(ReaderStream::new(file)),
+        ).into_response(),
+        Err(e) => RecordingError::from(e).into_response(),
+    }
+}
+
+pub async fn delete_recording(
+    State(state): State<Arc<AppState>>,
+    Path((room_id, recording_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = require_moderator(&state, &headers) {
+        return status.into_response();
+    }
+
+    let store = state.recordings.lock().await;
+    match store.delete(&room_id, &recording_id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (RecordingStore, PathBuf) {
+        let root = std::env::temp_dir().join(format!("juncto-recordings-{}", uuid::Uuid::new_v4()));
+        (RecordingStore::new(root.clone()), root)
+    }
+
+    #[tokio::test]
+    async fn test_chunks_are_assembled_in_order() {
+        let (store, root) = temp_store();
+        let names = vec!["Alice".to_string()];
+        let manifest = store.start("room-1", "video/webm", &names, 1000).await.unwrap();
+
+        store.append_chunk("room-1", &manifest.id, 0, b"abc", &names).await.unwrap();
+        // Skipping ahead is rejected with the expected sequence number
+        match store.append_chunk("room-1", &manifest.id, 2, b"xyz", &names).await {
+            Err(RecordingError::OutOfOrder { expected }) => assert_eq!(expected, 1),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        // A retried chunk is ignored
+        store.append_chunk("room-1", &manifest.id, 0, b"abc", &names).await.unwrap();
+        let bob = vec!["Bob".to_string()];
+        let updated = store.append_chunk("room-1", &manifest.id, 1, b"def", &bob).await.unwrap();
+        assert_eq!(updated.chunk_count, 2);
+        assert_eq!(updated.size_bytes, 6);
+        assert_eq!(updated.participants, vec!["Alice".to_string(), "Bob".to_string()]);
+
+        let stopped = store.stop("room-1", &manifest.id, &[], 5000).await.unwrap();
+        assert_eq!(stopped.stopped_at, Some(5000));
+        assert!(matches!(store.append_chunk("room-1", &manifest.id, 2, b"ghi", &[]).await, Err(RecordingError::AlreadyStopped)));
+
+        let media = std::fs::read(store.media_path("room-1", &manifest.id).unwrap()).unwrap();
+        assert_eq!(media, b"abcdef");
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_delete() {
+        let (store, root) = temp_store();
+        assert!(store.list("room-1").await.unwrap().is_empty());
+
+        let first = store.start("room-1", "video/webm", &[], 1000).await.unwrap();
+        let second = store.start("room-1", "video/webm", &[], 2000).await.unwrap();
+        store.start("room-2", "video/webm", &[], 3000).await.unwrap();
+
+        let listed: Vec<String> = store.list("room-1").await.unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(listed, vec![first.id.clone(), second.id.clone()]);
+
+        store.delete("room-1", &first.id).await.unwrap();
+        assert_eq!(store.list("room-1").await.unwrap().len(), 1);
+        assert!(matches!(store.manifest("room-1", &first.id).await, Err(RecordingError::NotFound)));
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn test_ids_cannot_escape_the_store() {
+        let (store, _root) = temp_store();
+        assert_eq!(room_dir_name("My Meeting/../x"), "My%20Meeting%2F%2E%2E%2Fx");
+        assert!(matches!(store.list("").await, Err(RecordingError::InvalidId)));
+        assert!(matches!(store.manifest("room-1", "../../secret").await, Err(RecordingError::InvalidId)));
+        assert!(matches!(store.delete("room-1", "").await, Err(RecordingError::InvalidId)));
+    }
+
+    #[test]
+    fn test_is_recording_type() {
+        for mime_type in ["video/webm", "video/webm;codecs=vp9,opus", "video/mp4; codecs=\"avc1.42E01E, mp4a.40.2\"", "Audio/Ogg"] {
+            assert!(is_recording_type(mime_type), "{}", mime_type);
+        }
+        for mime_type in ["", "text/html", "video/webm;charset=utf-8", "video/webm;codecs=", "video/webm;codecs=vp8<script>", "video/webm\r\nX-Injected: 1"] {
+            assert!(!is_recording_type(mime_type), "{}", mime_type);
+        }
+    }
+}
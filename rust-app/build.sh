@@ -18,5 +18,8 @@ wasm-bindgen --target web --out-dir frontend/pkg --out-name frontend target/wasm
 # 3. Copy HTML
 echo "Copying Assets..."
 cp frontend/index.html frontend/pkg/index.html
+# Notification sounds played by the frontend
+mkdir -p frontend/pkg/sounds
+cp ../sounds/joined.mp3 ../sounds/left.mp3 ../sounds/knock.mp3 ../sounds/incomingMessage.mp3 ../sounds/reactions-raised-hand.mp3 frontend/pkg/sounds/
 
 echo "Build Complete."
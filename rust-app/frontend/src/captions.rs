@@ -0,0 +1,157 @@
+use leptos::*;
+use gloo_timers::callback::Interval;
+use shared::CaptionSegment;
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, BlobPropertyBag};
+use crate::recording::trigger_download;
+use crate::utils::room_id_from_path;
+
+// Number of lines kept in the overlay
+const VISIBLE_CAPTIONS: usize = 3;
+// How long a finished line stays on screen
+const CAPTION_LINGER_MS: u64 = 8000;
+
+pub const TRANSCRIPT_FORMATS: [(&str, &str); 3] = [("vtt", "WebVTT"), ("srt", "SRT"), ("json", "JSON")];
+
+/// Adds a caption to the live lines. Interim text replaces the speaker's pending line
+/// so a sentence updates in place until it is final.
+pub fn apply_caption(lines: &mut Vec<CaptionSegment>, segment: CaptionSegment) {
+    if let Some(pending) = lines.iter_mut().find(|l| l.user_id == segment.user_id && !l.is_final) {
+        *pending = segment;
+    } else {
+        lines.push(segment);
+    }
+    if lines.len() > VISIBLE_CAPTIONS {
+        let excess = lines.len() - VISIBLE_CAPTIONS;
+        lines.drain(..excess);
+    }
+}
+
+fn is_visible(line: &CaptionSegment, now_ms: u64) -> bool {
+    !line.is_final || now_ms.saturating_sub(line.end_ms) < CAPTION_LINGER_MS
+}
+
+#[component]
+pub fn CaptionsOverlay(captions: ReadSignal<Vec<CaptionSegment>>) -> impl IntoView {
+    let (now, set_now) = create_signal(js_sys::Date::now() as u64);
+    let handle = Interval::new(1000, move || set_now.set(js_sys::Date::now() as u64));
+    on_cleanup(move || drop(handle));
+
+    let visible = move || {
+        let now = now.get();
+        captions.get().into_iter().filter(|l| is_visible(l, now)).collect::<Vec<_>>()
+    };
+
+    view! {
+        <div class="captions-overlay" style="position: absolute; bottom: 20px; left: 50%; transform: translateX(-50%); max-width: 80%; z-index: 15; pointer-events: none; text-align: center;">
+            <For
+                each=visible
+                key=|line| line.id.clone()
+                children=move |line| view! {
+                    <div style="background: rgba(0,0,0,0.75); color: white; padding: 4px 10px; margin-top: 4px; border-radius: 4px; font-size: 1.1em;">
+                        <strong>{line.speaker_name}": "</strong>
+                        <span style:opacity=if line.is_final { "1" } else { "0.7" }>{line.text}</span>
+                    </div>
+                }
+            />
+        </div>
+    }
+}
+
+async fn download_transcript(token: String, format: &str) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
+    let origin = window.location().origin()?;
+    let room_id = room_id_from_path(&window.location().pathname()?).unwrap_or_default();
+    let response = reqwest::Client::new()
+        .get(format!("{}/api/rooms/{}/captions/export?format={}", origin, urlencoding::encode(&room_id), format))
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/plain")
+        .to_string();
+    let text = response.text().await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let options = BlobPropertyBag::new();
+    options.set_type(&content_type);
+    let blob = Blob::new_with_str_sequence_and_options(&js_sys::Array::of1(&JsValue::from_str(&text)), &options)?;
+    trigger_download(&blob, &format!("transcript.{}", format))
+}
+
+// Offered once the meeting is over; our session token stays valid until we leave the page
+#[component]
+pub fn TranscriptDownloads(session_token: ReadSignal<Option<String>>) -> impl IntoView {
+    let (error, set_error) = create_signal(None::<String>);
+
+    let download = move |format: &'static str| {
+        if let Some(token) = session_token.get_untracked() {
+            set_error.set(None);
+            spawn_local(async move {
+                if let Err(e) = download_transcript(token, format).await {
+                    web_sys::console::error_1(&e);
+                    set_error.set(Some("No transcript available".to_string()));
+                }
+            });
+        }
+    };
+
+    view! {
+        <div class="transcript-downloads" style="display: flex; justify-content: center; align-items: center; gap: 10px; padding: 10px; background: #f8f9fa; color: black;">
+            <span>"Meeting transcript:"</span>
+            {TRANSCRIPT_FORMATS.into_iter().map(|(format, label)| view! {
+                <button
+                    on:click=move |_| download(format)
+                    style="padding: 6px 12px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer;"
+                >
+                    {label}
+                </button>
+            }).collect_view()}
+            {move || error.get().map(|e| view! { <span style="color: #dc3545;">{e}</span> })}
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(user_id: &str, text: &str, is_final: bool) -> CaptionSegment {
+        CaptionSegment {
+            id: format!("{}-{}", user_id, text),
+            user_id: user_id.to_string(),
+            speaker_name: user_id.to_string(),
+            text: text.to_string(),
+            is_final,
+            lang: "en".to_string(),
+            start_ms: 0,
+            end_ms: 1000,
+            room_id: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_caption_replaces_interim_lines() {
+        let mut lines = Vec::new();
+        apply_caption(&mut lines, line("alice", "Hel", false));
+        apply_caption(&mut lines, line("bob", "Hi", true));
+        apply_caption(&mut lines, line("alice", "Hello there", true));
+        assert_eq!(lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(), vec!["Hello there", "Hi"]);
+
+        apply_caption(&mut lines, line("alice", "Next", true));
+        apply_caption(&mut lines, line("bob", "Last", false));
+        assert_eq!(lines.len(), VISIBLE_CAPTIONS);
+        assert_eq!(lines[0].text, "Hi");
+    }
+
+    #[test]
+    fn test_finished_lines_expire() {
+        assert!(is_visible(&line("alice", "Hello", true), 5000));
+        assert!(!is_visible(&line("alice", "Hello", true), 1000 + CAPTION_LINGER_MS));
+        assert!(is_visible(&line("alice", "Hel", false), 1_000_000));
+    }
+}
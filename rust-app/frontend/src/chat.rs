@@ -1,19 +1,167 @@
 use leptos::*;
-use shared::{ChatMessage, Participant, FileAttachment};
-use gloo_timers::callback::Timeout;
-use std::collections::HashSet;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
-
-const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024; // 2MB
-
-// Helper for testing
-fn extract_base64_from_data_url(data_url: &str) -> Option<String> {
-    let parts: Vec<&str> = data_url.split(',').collect();
-    if parts.len() == 2 {
-        Some(parts[1].to_string())
+use shared::{ChatMessage, ChatMode, ChatScope, ClientMessage, Participant, FileAttachment, MAX_CHAT_CHARS, MAX_FILE_BYTES};
+use shared::commands::{self, CommandContext, CommandOutcome};
+use shared::markdown::{self, Block, Inline};
+use shared::mentions::{complete_mention, mention_query, suggestions};
+use gloo_timers::callback::{Interval, Timeout};
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, BlobPropertyBag};
+use crate::files::{upload_file, AttachmentView};
+use crate::recording::trigger_download;
+use crate::utils::room_id_from_path;
+
+pub const CHAT_PAGE_SIZE: u32 = 50;
+// Fetch older messages when scrolled this close to the top
+const LOAD_OLDER_THRESHOLD_PX: i32 = 20;
+const QUICK_REACTIONS: [&str; 5] = ["👍", "❤️", "😂", "🎉", "😮"];
+const QUOTE_CHARS: usize = 80;
+// Offered to moderators; 0 turns slow mode off
+const SLOW_MODE_OPTIONS: [u32; 6] = [0, 5, 10, 30, 60, 300];
+pub const CHAT_EXPORT_FORMATS: [(&str, &str); 3] = [("txt", "Text"), ("html", "HTML"), ("json", "JSON")];
+
+// A message to send: content, recipient_id, attachment, parent_id
+pub type OutgoingMessage = (String, Option<String>, Option<FileAttachment>, Option<String>);
+
+// Changes whenever the message is edited, deleted or reacted to, so the row re-renders
+fn message_key(msg: &ChatMessage) -> String {
+    let reactions: Vec<String> = msg.reactions.iter().map(|(emoji, users)| format!("{}{}", emoji, users.join(","))).collect();
+    format!("{}|{:?}|{}|{}", msg.id, msg.edited_at, msg.deleted, reactions.join(";"))
+}
+
+/// Emoji, count and whether we are among the reactors, for each reaction on a message.
+fn reaction_chips(msg: &ChatMessage, my_id: &Option<String>) -> Vec<(String, usize, bool)> {
+    msg.reactions
+        .iter()
+        .map(|(emoji, users)| {
+            let mine = my_id.as_ref().is_some_and(|me| users.contains(me));
+            (emoji.clone(), users.len(), mine)
+        })
+        .collect()
+}
+
+/// Puts an older page of history in front of the loaded messages, skipping ones we already have.
+/// Thread parents sent ahead of their page are put back in time order.
+pub fn prepend_history(messages: &mut Vec<ChatMessage>, page: Vec<ChatMessage>) {
+    let mut merged: Vec<ChatMessage> = page
+        .into_iter()
+        .filter(|m| !messages.iter().any(|x| x.id == m.id))
+        .collect();
+    merged.append(messages);
+    merged.sort_by_key(|m| m.timestamp);
+    *messages = merged;
+}
+
+/// Whether a message we hold belongs to the history `scope`. We only ever hold the
+/// room we are in, so every public message counts as part of the room scopes.
+pub fn in_scope(msg: &ChatMessage, scope: &ChatScope, my_id: &str) -> bool {
+    match (scope, &msg.recipient_id) {
+        (ChatScope::Private(other), Some(recipient)) => {
+            (msg.user_id == my_id && recipient == other) || (&msg.user_id == other && recipient == my_id)
+        },
+        (ChatScope::Private(_), None) => false,
+        (_, recipient) => recipient.is_none(),
+    }
+}
+
+/// Who a reply to `parent` goes to: replies stay in the parent's conversation,
+/// whatever the recipient selector says.
+pub fn reply_recipient(parent: &ChatMessage, my_id: &str) -> Option<String> {
+    parent.recipient_id.as_ref().map(|recipient| {
+        if recipient == my_id { parent.user_id.clone() } else { recipient.clone() }
+    })
+}
+
+/// Swaps the messages of one scope for its latest page and keeps the other scopes.
+pub fn replace_scope(messages: &mut Vec<ChatMessage>, scope: &ChatScope, my_id: &str, page: Vec<ChatMessage>) {
+    messages.retain(|m| !in_scope(m, scope, my_id));
+    prepend_history(messages, page);
+}
+
+fn display_name(participants: &[Participant], my_id: &Option<String>, user_id: &str) -> String {
+    if my_id.as_deref() == Some(user_id) {
+        "Me".to_string()
     } else {
-        None
+        participants.iter().find(|p| p.id == user_id).map(|p| p.name.clone()).unwrap_or(user_id.to_string())
+    }
+}
+
+/// A top-level message and every loaded reply below it, oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thread {
+    pub root: ChatMessage,
+    pub replies: Vec<ChatMessage>,
+}
+
+impl Thread {
+    fn key(&self) -> String {
+        std::iter::once(&self.root).chain(&self.replies).map(message_key).collect::<Vec<_>>().join("/")
+    }
+}
+
+// Follows parent links as far as the loaded messages go
+fn root_of<'a>(by_id: &HashMap<&str, &'a ChatMessage>, msg: &'a ChatMessage) -> &'a str {
+    let mut current = msg;
+    // Bounded so a malformed cycle can't hang the page
+    for _ in 0..by_id.len() {
+        match current.parent_id.as_deref().and_then(|pid| by_id.get(pid)) {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    &current.id
+}
+
+/// Groups messages into threads. A reply whose parent isn't loaded starts a thread of its own.
+pub fn build_threads(messages: &[ChatMessage]) -> Vec<Thread> {
+    let by_id: HashMap<&str, &ChatMessage> = messages.iter().map(|m| (m.id.as_str(), m)).collect();
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for msg in messages {
+        if root_of(&by_id, msg) == msg.id {
+            positions.insert(&msg.id, threads.len());
+            threads.push(Thread { root: msg.clone(), replies: Vec::new() });
+        }
+    }
+    for msg in messages {
+        let root = root_of(&by_id, msg);
+        if root != msg.id {
+            if let Some(&pos) = positions.get(root) {
+                threads[pos].replies.push(msg.clone());
+            }
+        }
+    }
+    threads
+}
+
+/// Whether `user_id` wrote any message in the thread that `message_id` belongs to.
+pub fn is_thread_participant(messages: &[ChatMessage], message_id: &str, user_id: &str) -> bool {
+    build_threads(messages)
+        .into_iter()
+        .find(|t| t.root.id == message_id || t.replies.iter().any(|r| r.id == message_id))
+        .is_some_and(|t| t.root.user_id == user_id || t.replies.iter().any(|r| r.user_id == user_id))
+}
+
+fn quote_snippet(msg: &ChatMessage) -> String {
+    if msg.deleted {
+        return "This message was deleted".to_string();
+    }
+    if msg.content.is_empty() {
+        if let Some(att) = &msg.attachment {
+            return format!("📎 {}", att.filename);
+        }
+    }
+    if msg.content.chars().count() > QUOTE_CHARS {
+        format!("{}…", msg.content.chars().take(QUOTE_CHARS).collect::<String>())
+    } else {
+        msg.content.clone()
+    }
+}
+
+fn quote_for(messages: &[ChatMessage], parent_id: &str, participants: &[Participant], my_id: &Option<String>) -> String {
+    match messages.iter().find(|m| m.id == parent_id) {
+        Some(parent) => format!("{}: {}", display_name(participants, my_id, &parent.user_id), quote_snippet(parent)),
+        None => "In reply to an earlier message".to_string(),
     }
 }
 
@@ -39,20 +187,133 @@ fn format_typing_indicator(users: &HashSet<String>, participants: &[Participant]
     }
 }
 
+// The server renders times in our timezone and leaves out other people's private messages
+async fn download_chat(token: String, format: &str) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
+    let origin = window.location().origin()?;
+    let room_id = room_id_from_path(&window.location().pathname()?).unwrap_or_default();
+    // getTimezoneOffset counts minutes west of UTC
+    let tz_offset = -(js_sys::Date::new_0().get_timezone_offset() as i32);
+    let response = reqwest::Client::new()
+        .get(format!("{}/api/rooms/{}/chat/export?format={}&tz_offset={}", origin, urlencoding::encode(&room_id), format, tz_offset))
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/plain")
+        .to_string();
+    let text = response.text().await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let options = BlobPropertyBag::new();
+    options.set_type(&content_type);
+    let blob = Blob::new_with_str_sequence_and_options(&js_sys::Array::of1(&JsValue::from_str(&text)), &options)?;
+    trigger_download(&blob, &format!("chat.{}", format))
+}
+
 #[component]
 pub fn Chat(
     messages: ReadSignal<Vec<ChatMessage>>,
+    has_more: ReadSignal<bool>,
+    on_load_older: Callback<()>,
     typing_users: ReadSignal<HashSet<String>>,
     participants: ReadSignal<Vec<Participant>>,
-    on_send: Callback<(String, Option<String>, Option<FileAttachment>)>,
+    on_send: Callback<OutgoingMessage>,
     on_typing: Callback<bool>,
     is_connected: ReadSignal<bool>,
     my_id: ReadSignal<Option<String>>,
+    is_host: Signal<bool>,
+    on_edit: Callback<(String, String)>, // message_id, content
+    on_delete: Callback<String>,
+    on_react: Callback<(String, String)>, // message_id, emoji
+    session_token: ReadSignal<Option<String>>,
+    restriction: Signal<Option<String>>, // Why we can't send, if we can't
+    slow_mode_secs: Signal<u32>, // 0 = off
+    chat_mode: Signal<ChatMode>,
+    on_chat_mode: Callback<ChatMode>,
+    on_slow_mode: Callback<u32>,
+    on_command: Callback<ClientMessage>, // Slash commands other than /me
 ) -> impl IntoView {
     let (input_value, set_input_value) = create_signal("".to_string());
+    // Output of /help, or why a command failed: (is_error, text)
+    let (command_feedback, set_command_feedback) = create_signal(None::<(bool, String)>);
     let (recipient, set_recipient) = create_signal(None::<String>); // None = Everyone
     let (selected_file, set_selected_file) = create_signal(None::<FileAttachment>);
+    let (uploading, set_uploading) = create_signal(false);
     let file_input_ref = create_node_ref::<html::Input>();
+    let text_input_ref = create_node_ref::<html::Input>();
+    let messages_ref = create_node_ref::<html::Div>();
+    let (replying_to, set_replying_to) = create_signal(None::<ChatMessage>);
+    // Root IDs of threads whose replies are shown
+    let expanded = create_rw_signal(HashSet::<String>::new());
+    let on_reply = Callback::new(move |msg: ChatMessage| set_replying_to.set(Some(msg)));
+    // List height when older messages were requested, so the view stays put when they arrive
+    let scroll_anchor = store_value(None::<i32>);
+    // Highlighted suggestion, and the `@` whose suggestions were dismissed with Escape
+    let (mention_index, set_mention_index) = create_signal(0usize);
+    let (dismissed_at, set_dismissed_at) = create_signal(None::<usize>);
+    let mention_matches = create_memo(move |_| {
+        let input = input_value.get();
+        let (at, query) = mention_query(&input).filter(|(at, _)| dismissed_at.get() != Some(*at))?;
+        let found: Vec<Participant> = participants.with(|list| {
+            suggestions(query, list, my_id.get().as_deref()).into_iter().cloned().collect()
+        });
+        (!found.is_empty()).then_some((at, found))
+    });
+    let pick_mention = move |name: String| {
+        if let Some((at, _)) = mention_matches.get_untracked() {
+            set_input_value.set(complete_mention(&input_value.get_untracked(), at, &name));
+            set_mention_index.set(0);
+            if let Some(input) = text_input_ref.get_untracked() {
+                let _ = input.focus();
+            }
+        }
+    };
+    let handle_mention_keys = move |ev: web_sys::KeyboardEvent| {
+        let Some((at, found)) = mention_matches.get_untracked() else { return };
+        match ev.key().as_str() {
+            "ArrowDown" => {
+                ev.prevent_default();
+                set_mention_index.update(|i| *i = (*i + 1) % found.len());
+            },
+            "ArrowUp" => {
+                ev.prevent_default();
+                set_mention_index.update(|i| *i = (*i + found.len() - 1) % found.len());
+            },
+            "Enter" | "Tab" => {
+                ev.prevent_default();
+                let i = mention_index.get_untracked().min(found.len() - 1);
+                pick_mention(found[i].name.clone());
+            },
+            "Escape" => set_dismissed_at.set(Some(at)),
+            _ => {},
+        }
+    };
+
+    let load_older = move || {
+        if has_more.get_untracked() && scroll_anchor.get_value().is_none() {
+            if let Some(el) = messages_ref.get_untracked() {
+                scroll_anchor.set_value(Some(el.scroll_height()));
+            }
+            on_load_older.call(());
+        }
+    };
+
+    create_effect(move |_| {
+        messages.track();
+        if let Some(previous_height) = scroll_anchor.get_value() {
+            request_animation_frame(move || {
+                if let Some(el) = messages_ref.get_untracked() {
+                    el.set_scroll_top(el.scroll_top() + el.scroll_height() - previous_height);
+                }
+                scroll_anchor.set_value(None);
+            });
+        }
+    });
 
     // Store timer handle in a ref to clear it if needed, or just let it fire.
     // In Leptos, we can't easily store non-Clone types in signals.
@@ -66,6 +327,8 @@ pub fn Chat(
 
     let handle_input = move |ev: web_sys::Event| {
         set_input_value.set(event_target_value(&ev));
+        set_mention_index.set(0);
+        set_command_feedback.set(None);
 
         let now = js_sys::Date::now();
         if now - last_typing_sent.get() > 2000.0 {
@@ -80,67 +343,100 @@ pub fn Chat(
         }
     };
 
-    // Store closure to avoid leak.
-    // We use a StoredValue to keep ownership of the closure until we are done or component drops.
-    // Since we only handle one file load at a time, we can overwrite the previous one.
-    // Using Rc<RefCell<Option<Closure>>> pattern inside StoredValue or similar.
-    // Actually, just storing it in a RefCell in scope is enough if we weren't in a callback.
-    // We can use a StoredValue<Option<Closure<dyn FnMut(web_sys::Event)>>> to hold the active listener.
-    let file_reader_closure = store_value(None::<Closure<dyn FnMut(web_sys::Event)>>);
-
+    // Files are uploaded as soon as they are picked; the message only carries the reference
     let handle_file_change = move |ev: web_sys::Event| {
         let input: web_sys::HtmlInputElement = event_target(&ev);
-        if let Some(files) = input.files() {
-            if let Some(file) = files.get(0) {
-                let filename = file.name();
-                let mime_type = file.type_();
-                let size = file.size() as u64;
-
-                if size > MAX_FILE_SIZE {
-                    let _ = web_sys::window().unwrap().alert_with_message("File too large. Max size is 2MB.");
-                    input.set_value("");
-                    return;
-                }
+        let Some(file) = input.files().and_then(|files| files.get(0)) else { return };
+        let Some(token) = session_token.get_untracked() else { return };
 
-                let reader = web_sys::FileReader::new().unwrap();
-                let reader_clone = reader.clone();
-                // Need to move necessary data into closure
-                let on_load = Closure::wrap(Box::new(move |_e: web_sys::Event| {
-                    if let Ok(res) = reader_clone.result() {
-                        if let Some(data_url) = res.as_string() {
-                            if let Some(content_base64) = extract_base64_from_data_url(&data_url) {
-                                set_selected_file.set(Some(FileAttachment {
-                                    filename: filename.clone(),
-                                    mime_type: mime_type.clone(),
-                                    size,
-                                    content_base64,
-                                }));
-                            }
-                        }
-                    }
-                    // We can't easily drop ourselves from inside ourselves without Interior Mutability gymnastics.
-                    // But overwriting it on next change is "good enough" to prevent infinite accumulation.
-                    // Or we could clear it here if we had access to the StoredValue.
-                }) as Box<dyn FnMut(_)>);
-
-                reader.set_onload(Some(on_load.as_ref().unchecked_ref()));
-
-                // Store the closure to keep it alive and drop previous one
-                file_reader_closure.set_value(Some(on_load));
+        // Refused before uploading, like the server would
+        if file.size() as u64 > MAX_FILE_BYTES {
+            let _ = web_sys::window().unwrap().alert_with_message(&format!("File too large. Max size is {}MB.", MAX_FILE_BYTES / (1024 * 1024)));
+            input.set_value("");
+            return;
+        }
 
-                let _ = reader.read_as_data_url(&file);
+        set_selected_file.set(None);
+        set_uploading.set(true);
+        spawn_local(async move {
+            match upload_file(token, file).await {
+                Ok(attachment) => set_selected_file.set(Some(attachment)),
+                Err(e) => {
+                    let _ = web_sys::window().unwrap().alert_with_message(&format!("Upload failed: {}", e));
+                    input.set_value("");
+                }
             }
-        }
+            set_uploading.set(false);
+        });
     };
 
+    // Seconds until slow mode lets us send again; the server enforces the same interval
+    let (cooldown, set_cooldown) = create_signal(0u32);
+    let cooldown_timer = Interval::new(1000, move || {
+        if cooldown.get_untracked() > 0 {
+            set_cooldown.update(|c| *c -= 1);
+        }
+    });
+    on_cleanup(move || drop(cooldown_timer));
+    let can_send = move || is_connected.get() && !uploading.get() && restriction.get().is_none() && cooldown.get() == 0;
+
     let send = move |_| {
+        if !can_send() {
+            return;
+        }
         let content = input_value.get();
-        let target = recipient.get();
+        let parent = replying_to.get();
+        let target = match &parent {
+            Some(parent) => reply_recipient(parent, my_id.get_untracked().as_deref().unwrap_or_default()),
+            None => recipient.get(),
+        };
         let attachment = selected_file.get();
+        let parent_id = parent.map(|m| m.id);
+
+        let parsed = participants.with_untracked(|list| {
+            let me = my_id.get_untracked();
+            let ctx = CommandContext { participants: list, my_id: me.as_deref(), is_moderator: is_host.get_untracked() };
+            commands::parse(&content, &ctx)
+        });
+        let content = match parsed {
+            None => content,
+            // `/me` and `//` still post a message, to whoever the input is addressed to
+            Some(Ok(CommandOutcome::Send(ClientMessage::Chat { content, .. }))) => content,
+            Some(Ok(CommandOutcome::Send(msg))) => {
+                on_command.call(msg);
+                set_input_value.set("".to_string());
+                return;
+            },
+            Some(Ok(CommandOutcome::Help(text))) => {
+                set_command_feedback.set(Some((false, text)));
+                set_input_value.set("".to_string());
+                return;
+            },
+            // The input is kept so the command can be fixed
+            Some(Err(e)) => {
+                set_command_feedback.set(Some((true, e.message())));
+                return;
+            },
+        };
 
         if !content.is_empty() || attachment.is_some() {
-            on_send.call((content, target, attachment));
+            if let Some(pid) = &parent_id {
+                // Keep the thread open so the reply shows up where it was written
+                let by_root = messages.with_untracked(|msgs| {
+                    let by_id: HashMap<&str, &ChatMessage> = msgs.iter().map(|m| (m.id.as_str(), m)).collect();
+                    by_id.get(pid.as_str()).map(|m| root_of(&by_id, m).to_string())
+                });
+                if let Some(root) = by_root {
+                    expanded.update(|e| { e.insert(root); });
+                }
+            }
+            on_send.call((content, target, attachment, parent_id));
+            // Moderators are exempt from slow mode
+            if !is_host.get_untracked() {
+                set_cooldown.set(slow_mode_secs.get_untracked());
+            }
             on_typing.call(false);
+            set_replying_to.set(None);
             set_input_value.set("".to_string());
             set_selected_file.set(None);
             if let Some(input) = file_input_ref.get() {
@@ -149,9 +445,74 @@ pub fn Chat(
         }
     };
 
+    let (export_format, set_export_format) = create_signal(CHAT_EXPORT_FORMATS[0].0);
+    let download = move |_| {
+        let Some(token) = session_token.get_untracked() else { return };
+        let format = export_format.get_untracked();
+        spawn_local(async move {
+            if let Err(e) = download_chat(token, format).await {
+                web_sys::console::error_1(&e);
+                let _ = web_sys::window().unwrap().alert_with_message("No chat messages to download");
+            }
+        });
+    };
+
     view! {
         <div class="chat-container" style="border-left: 1px solid #ccc; width: 300px; padding: 10px; display: flex; flex-direction: column; background: white;">
-            <h3>"Chat"</h3>
+            <div class="chat-header" style="display: flex; align-items: center; gap: 5px;">
+                <h3 style="flex: 1;">"Chat"</h3>
+                <select
+                    title="Download format"
+                    on:change=move |ev| {
+                        let val = event_target_value(&ev);
+                        if let Some((format, _)) = CHAT_EXPORT_FORMATS.into_iter().find(|(f, _)| *f == val) {
+                            set_export_format.set(format);
+                        }
+                    }
+                    style="font-size: 0.85em;"
+                >
+                    {CHAT_EXPORT_FORMATS.into_iter().map(|(format, label)| view! { <option value=format>{label}</option> }).collect_view()}
+                </select>
+                <button
+                    on:click=download
+                    title="Download chat"
+                    style="padding: 4px 8px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer; font-size: 0.85em;"
+                >
+                    "Download chat"
+                </button>
+            </div>
+            <Show when=move || is_host.get()>
+                <div class="chat-moderation" style="display: flex; gap: 5px; margin-bottom: 10px; font-size: 0.85em;">
+                    <select
+                        title="Who can send messages"
+                        on:change=move |ev| {
+                            let val = event_target_value(&ev);
+                            if let Some(mode) = ChatMode::ALL.into_iter().find(|m| format!("{:?}", m) == val) {
+                                on_chat_mode.call(mode);
+                            }
+                        }
+                        style="flex: 1;"
+                    >
+                        {ChatMode::ALL.into_iter().map(|m| view! {
+                            <option value=format!("{:?}", m) selected=move || chat_mode.get() == m>{m.label()}</option>
+                        }).collect_view()}
+                    </select>
+                    <select
+                        title="Slow mode"
+                        on:change=move |ev| {
+                            if let Ok(secs) = event_target_value(&ev).parse::<u32>() {
+                                on_slow_mode.call(secs);
+                            }
+                        }
+                    >
+                        {SLOW_MODE_OPTIONS.into_iter().map(|secs| view! {
+                            <option value=secs.to_string() selected=move || slow_mode_secs.get() == secs>
+                                {if secs == 0 { "Slow mode off".to_string() } else { format!("Slow: {} s", secs) }}
+                            </option>
+                        }).collect_view()}
+                    </select>
+                </div>
+            </Show>
             <div class="recipient-selector" style="margin-bottom: 10px;">
                 <label>"To: "</label>
                 <select
@@ -183,64 +544,99 @@ pub fn Chat(
                     />
                 </select>
             </div>
-            <div class="messages" style="flex: 1; overflow-y: auto; height: 300px; border: 1px solid #eee; margin-bottom: 10px; padding: 5px;">
+            <div
+                class="messages"
+                node_ref=messages_ref
+                on:scroll=move |_| {
+                    if messages_ref.get_untracked().is_some_and(|el| el.scroll_top() < LOAD_OLDER_THRESHOLD_PX) {
+                        load_older();
+                    }
+                }
+                style="flex: 1; overflow-y: auto; height: 300px; border: 1px solid #eee; margin-bottom: 10px; padding: 5px;"
+            >
+                <Show when=move || has_more.get()>
+                    <button
+                        on:click=move |_| load_older()
+                        style="width: 100%; padding: 4px; background: none; border: none; color: #007bff; cursor: pointer; font-size: 0.85em;"
+                    >
+                        "Load older messages"
+                    </button>
+                </Show>
                 <ul>
                     <For
-                        each=move || messages.get()
-                        key=|msg| format!("{}_{}", msg.timestamp, msg.user_id)
-                        children=move |msg| {
-                            let parts = participants.get();
-                            let my = my_id.get();
-                            let sender_name = if Some(msg.user_id.clone()) == my {
-                                "Me".to_string()
-                            } else {
-                                parts.iter().find(|p| p.id == msg.user_id).map(|p| p.name.clone()).unwrap_or(msg.user_id.clone())
+                        each=move || build_threads(&messages.get())
+                        key=|thread| thread.key()
+                        children=move |thread| {
+                            let root_id = thread.root.id.clone();
+                            let reply_count = thread.replies.len();
+                            let is_expanded = {
+                                let root_id = root_id.clone();
+                                Signal::derive(move || expanded.with(|e| e.contains(&root_id)))
                             };
-
-                            let mut style = if Some(msg.user_id.clone()) == my { "color: blue;" } else { "color: black;" };
-                            let private_indicator = if msg.recipient_id.is_some() {
-                                style = "color: purple;"; // Private msg style
-                                "(Private) "
-                            } else {
-                                ""
+                            let toggle = {
+                                let root_id = root_id.clone();
+                                move |_| expanded.update(|e| {
+                                    if !e.remove(&root_id) {
+                                        e.insert(root_id.clone());
+                                    }
+                                })
                             };
-
-                            // Format timestamp HH:MM
-                            let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(msg.timestamp as f64));
-                            let hours = date.get_hours();
-                            let minutes = date.get_minutes();
-                            let time_str = format!("{:02}:{:02}", hours, minutes);
-
+                            let all = messages.get_untracked();
+                            let replies: Vec<(ChatMessage, Option<String>)> = thread.replies.into_iter().map(|reply| {
+                                // Quote the direct parent when it isn't the message the thread starts with
+                                let quoted = reply.parent_id.as_ref()
+                                    .filter(|pid| **pid != root_id)
+                                    .map(|pid| quote_for(&all, pid, &participants.get_untracked(), &my_id.get_untracked()));
+                                (reply, quoted)
+                            }).collect();
+                            let root_quote = thread.root.parent_id.as_ref()
+                                .map(|pid| quote_for(&all, pid, &participants.get_untracked(), &my_id.get_untracked()));
+                            let replies = store_value(replies);
                             view! {
-                                <li style=style>
-                                    <small style="color: #999; margin-right: 5px;">"[" {time_str} "] "</small>
-                                    <small>{private_indicator}</small>
-                                    <strong>{sender_name}": "</strong>
-                                    <span>{msg.content.clone()}</span>
-                                    {move || {
-                                        if let Some(att) = &msg.attachment {
-                                            if att.mime_type.starts_with("image/") {
-                                                let src = format!("data:{};base64,{}", att.mime_type, att.content_base64);
-                                                view! {
-                                                    <div>
-                                                        <img src=src style="max-width: 200px; max-height: 200px; display: block; margin-top: 5px;" />
-                                                    </div>
-                                                }.into_view()
+                                <ChatMessageRow
+                                    msg=thread.root
+                                    quoted=root_quote
+                                    participants=participants
+                                    my_id=my_id
+                                    is_host=is_host
+                                    on_edit=on_edit
+                                    on_delete=on_delete
+                                    on_react=on_react
+                                    on_reply=on_reply
+                                    session_token=session_token
+                                />
+                                <Show when=move || { reply_count > 0 }>
+                                    <li style="list-style: none; margin-left: 15px;">
+                                        <button
+                                            on:click=toggle.clone()
+                                            style="background: none; border: none; padding: 0; color: #007bff; cursor: pointer; font-size: 0.8em;"
+                                        >
+                                            {move || if is_expanded.get() {
+                                                "▾ Hide replies".to_string()
                                             } else {
-                                                let href = format!("data:{};base64,{}", att.mime_type, att.content_base64);
-                                                view! {
-                                                    <div>
-                                                        <a href=href download=att.filename.clone() style="display: block; margin-top: 5px;">
-                                                            "📎 " {att.filename.clone()}
-                                                        </a>
-                                                    </div>
-                                                }.into_view()
-                                            }
-                                        } else {
-                                            view! { <span></span> }.into_view()
-                                        }
-                                    }}
-                                </li>
+                                                format!("▸ {} {}", reply_count, if reply_count == 1 { "reply" } else { "replies" })
+                                            }}
+                                        </button>
+                                    </li>
+                                </Show>
+                                <Show when=move || is_expanded.get()>
+                                    <ul class="thread-replies" style="margin-left: 15px; padding-left: 8px; border-left: 2px solid #e0e0e0;">
+                                        {replies.get_value().into_iter().map(|(reply, quoted)| view! {
+                                            <ChatMessageRow
+                                                msg=reply
+                                                quoted=quoted
+                                                participants=participants
+                                                my_id=my_id
+                                                is_host=is_host
+                                                on_edit=on_edit
+                                                on_delete=on_delete
+                                                on_react=on_react
+                                                on_reply=on_reply
+                                                session_token=session_token
+                                            />
+                                        }).collect_view()}
+                                    </ul>
+                                </Show>
                             }
                         }
                     />
@@ -255,19 +651,78 @@ pub fn Chat(
                 }}
             </div>
             <div class="input-area" style="display: flex; flex-direction: column; gap: 5px;">
+                {move || replying_to.get().map(|parent| {
+                    let quote = quote_for(std::slice::from_ref(&parent), &parent.id, &participants.get(), &my_id.get());
+                    view! {
+                        <div class="reply-preview" style="display: flex; justify-content: space-between; align-items: center; border-left: 3px solid #007bff; padding: 2px 6px; background: #f1f5ff; font-size: 0.8em;">
+                            <span>"Replying to " {quote}</span>
+                            <button
+                                on:click=move |_| set_replying_to.set(None)
+                                style="background: none; border: none; cursor: pointer;"
+                                title="Cancel reply"
+                            >
+                                "✕"
+                            </button>
+                        </div>
+                    }
+                })}
+                {move || mention_matches.get().map(|(_, found)| view! {
+                    <ul class="mention-suggestions" style="list-style: none; margin: 0; padding: 2px; border: 1px solid #ccc; background: white; font-size: 0.85em;">
+                        {found.into_iter().enumerate().map(|(i, p)| {
+                            let name = p.name.clone();
+                            view! {
+                                <li
+                                    // mousedown so the input keeps its focus
+                                    on:mousedown=move |ev| {
+                                        ev.prevent_default();
+                                        pick_mention(name.clone());
+                                    }
+                                    style=move || format!(
+                                        "padding: 2px 6px; cursor: pointer; {}",
+                                        if mention_index.get() == i { "background: #e7f1ff;" } else { "" }
+                                    )
+                                >
+                                    "@" {p.name}
+                                </li>
+                            }
+                        }).collect_view()}
+                    </ul>
+                })}
+                {move || command_feedback.get().map(|(is_error, text)| view! {
+                    <div
+                        class="command-feedback"
+                        style=format!(
+                            "white-space: pre-line; font-size: 0.8em; padding: 2px 6px; background: #f8f9fa; color: {};",
+                            if is_error { "#dc3545" } else { "#333" }
+                        )
+                    >
+                        {text}
+                    </div>
+                })}
                 <div style="display: flex; gap: 5px;">
                     <input
                         type="text"
+                        node_ref=text_input_ref
                         prop:value=input_value
+                        maxlength=MAX_CHAT_CHARS.to_string()
                         on:input=handle_input
-                        placeholder="Type a message..."
+                        on:keydown=handle_mention_keys
+                        disabled=move || restriction.get().is_some()
+                        placeholder=move || restriction.get().unwrap_or_else(|| "Type a message, or /help".to_string())
                         style="flex: 1;"
                     />
                     <button
                         on:click=send
-                        disabled=move || !is_connected.get()
+                        disabled=move || !can_send()
+                        title=move || { if cooldown.get() > 0 { "Slow mode is on" } else { "" } }
                         style="width: 60px;">
-                        {move || if is_connected.get() { "Send" } else { "..." }}
+                        {move || if !is_connected.get() {
+                            "...".to_string()
+                        } else if cooldown.get() > 0 {
+                            format!("{} s", cooldown.get())
+                        } else {
+                            "Send".to_string()
+                        }}
                     </button>
                 </div>
                 <div>
@@ -275,9 +730,12 @@ pub fn Chat(
                         type="file"
                         node_ref=file_input_ref
                         on:change=handle_file_change
+                        disabled=move || restriction.get().is_some()
                         style="width: 100%; font-size: 0.8em;"
                      />
-                     {move || if let Some(f) = selected_file.get() {
+                     {move || if uploading.get() {
+                         view! { <small style="color: #666;">" Uploading…"</small> }.into_view()
+                     } else if let Some(f) = selected_file.get() {
                          view! { <small style="color: green;">" Selected: " {f.filename}</small> }.into_view()
                      } else {
                          view! { <span/> }.into_view()
@@ -288,6 +746,197 @@ pub fn Chat(
     }
 }
 
+// Built from text nodes and elements only, so message content can never inject HTML
+fn inline_view(inlines: Vec<Inline>) -> View {
+    inlines.into_iter().map(|inline| match inline {
+        Inline::Text(text) => text.into_view(),
+        Inline::Bold(children) => view! { <strong>{inline_view(children)}</strong> }.into_view(),
+        Inline::Italic(children) => view! { <em>{inline_view(children)}</em> }.into_view(),
+        Inline::Code(code) => view! {
+            <code style="background: #f1f1f1; padding: 0 3px; border-radius: 3px;">{code}</code>
+        }.into_view(),
+        Inline::Link { url, text } => view! {
+            <a href=url target="_blank" rel="noopener noreferrer">{text}</a>
+        }.into_view(),
+        Inline::LineBreak => view! { <br/> }.into_view(),
+    }).collect_view()
+}
+
+fn markdown_view(content: &str) -> View {
+    markdown::parse(content).into_iter().map(|block| match block {
+        Block::Paragraph(inlines) => view! { <p style="margin: 0;">{inline_view(inlines)}</p> }.into_view(),
+        Block::CodeBlock { language, code } => view! {
+            <pre style="background: #f1f1f1; padding: 4px 6px; margin: 2px 0; overflow-x: auto; border-radius: 3px;">
+                <code class=language.map(|l| format!("language-{}", l))>{code}</code>
+            </pre>
+        }.into_view(),
+        Block::List { ordered: true, items } => view! {
+            <ol style="margin: 2px 0; padding-left: 20px;">
+                {items.into_iter().map(|item| view! { <li>{inline_view(item)}</li> }).collect_view()}
+            </ol>
+        }.into_view(),
+        Block::List { ordered: false, items } => view! {
+            <ul style="margin: 2px 0; padding-left: 20px;">
+                {items.into_iter().map(|item| view! { <li>{inline_view(item)}</li> }).collect_view()}
+            </ul>
+        }.into_view(),
+    }).collect_view()
+}
+
+#[component]
+fn ChatMessageRow(
+    msg: ChatMessage,
+    quoted: Option<String>, // "Name: text" of the message this one replies to
+    participants: ReadSignal<Vec<Participant>>,
+    my_id: ReadSignal<Option<String>>,
+    is_host: Signal<bool>,
+    on_edit: Callback<(String, String)>,
+    on_delete: Callback<String>,
+    on_react: Callback<(String, String)>,
+    on_reply: Callback<ChatMessage>,
+    session_token: ReadSignal<Option<String>>,
+) -> impl IntoView {
+    let parts = participants.get();
+    let my = my_id.get();
+    let sender_name = display_name(&parts, &my, &msg.user_id);
+
+    let mut style = if Some(msg.user_id.clone()) == my { "color: blue;" } else { "color: black;" };
+    let private_indicator = if msg.recipient_id.is_some() {
+        style = "color: purple;"; // Private msg style
+        "(Private) "
+    } else {
+        ""
+    };
+    let mentions_me = my.as_ref().is_some_and(|me| msg.mentions.contains(me));
+    let style = if mentions_me {
+        format!("{} background: #fff3cd; border-left: 3px solid #ffc107; padding-left: 4px;", style)
+    } else {
+        style.to_string()
+    };
+
+    // Format timestamp HH:MM
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(msg.timestamp as f64));
+    let hours = date.get_hours();
+    let minutes = date.get_minutes();
+    let time_str = format!("{:02}:{:02}", hours, minutes);
+
+    let msg_id = store_value(msg.id.clone());
+    let is_mine = my.as_deref() == Some(msg.user_id.as_str());
+    // Room and private messages alike are kept server-side, so both can change
+    let can_change = !msg.deleted;
+    let chips = reaction_chips(&msg, &my);
+    let (show_picker, set_show_picker) = create_signal(false);
+
+    if msg.deleted {
+        return view! {
+            <li style="color: #999;">
+                <small style="margin-right: 5px;">"[" {time_str} "] "</small>
+                <strong>{sender_name}": "</strong>
+                <em>"This message was deleted"</em>
+            </li>
+        };
+    }
+
+    let current_content = store_value(msg.content.clone());
+    let reply_target = store_value(msg.clone());
+    view! {
+        <li style=style>
+            {quoted.map(|q| view! {
+                <div class="chat-quote" style="border-left: 3px solid #ccc; padding-left: 6px; margin-bottom: 2px; color: #666; font-size: 0.85em;">
+                    {q}
+                </div>
+            })}
+            <small style="color: #999; margin-right: 5px;">"[" {time_str} "] "</small>
+            <small>{private_indicator}</small>
+            {match commands::me_action(&msg.content) {
+                Some(action) => view! { <em class="chat-action">"* " {sender_name} " " {action.to_string()}</em> }.into_view(),
+                None => view! {
+                    <strong>{sender_name}": "</strong>
+                    <div class="chat-content">{markdown_view(&msg.content)}</div>
+                }.into_view(),
+            }}
+            {msg.edited_at.map(|_| view! { <small style="color: #999; margin-left: 4px;">"(edited)"</small> })}
+            <div class="message-reactions" style="display: flex; flex-wrap: wrap; gap: 4px; margin-top: 2px;">
+                {chips.into_iter().map(|(emoji, count, mine)| {
+                    let chip_emoji = emoji.clone();
+                    view! {
+                        <button
+                            on:click=move |_| on_react.call((msg_id.get_value(), chip_emoji.clone()))
+                            style=format!(
+                                "padding: 0 6px; border-radius: 10px; cursor: pointer; font-size: 0.8em; border: 1px solid {}; background: {};",
+                                if mine { "#007bff" } else { "#ddd" },
+                                if mine { "#e7f1ff" } else { "white" }
+                            )
+                        >
+                            {emoji} " " {count}
+                        </button>
+                    }
+                }).collect_view()}
+            </div>
+            <Show when=move || can_change>
+                <div class="message-actions" style="display: flex; gap: 4px; font-size: 0.75em;">
+                    <button
+                        on:click=move |_| set_show_picker.update(|v| *v = !*v)
+                        title="React"
+                        style="background: none; border: none; cursor: pointer; color: #666;"
+                    >
+                        "☺+"
+                    </button>
+                    <button
+                        on:click=move |_| on_reply.call(reply_target.get_value())
+                        style="background: none; border: none; cursor: pointer; color: #666;"
+                    >
+                        "Reply"
+                    </button>
+                    <Show when=move || is_mine || is_host.get()>
+                        <button
+                            on:click=move |_| {
+                                let window = web_sys::window().unwrap();
+                                let current = current_content.get_value();
+                                if let Ok(Some(content)) = window.prompt_with_message_and_default("Edit message:", &current) {
+                                    if !content.trim().is_empty() && content != current {
+                                        on_edit.call((msg_id.get_value(), content));
+                                    }
+                                }
+                            }
+                            style="background: none; border: none; cursor: pointer; color: #666;"
+                        >
+                            "Edit"
+                        </button>
+                        <button
+                            on:click=move |_| {
+                                let window = web_sys::window().unwrap();
+                                if window.confirm_with_message("Delete this message?").unwrap_or(false) {
+                                    on_delete.call(msg_id.get_value());
+                                }
+                            }
+                            style="background: none; border: none; cursor: pointer; color: #dc3545;"
+                        >
+                            "Delete"
+                        </button>
+                    </Show>
+                </div>
+                <Show when=move || show_picker.get()>
+                    <div class="reaction-picker" style="display: flex; gap: 2px;">
+                        {QUICK_REACTIONS.into_iter().map(|emoji| view! {
+                            <button
+                                on:click=move |_| {
+                                    on_react.call((msg_id.get_value(), emoji.to_string()));
+                                    set_show_picker.set(false);
+                                }
+                                style="background: none; border: none; cursor: pointer;"
+                            >
+                                {emoji}
+                            </button>
+                        }).collect_view()}
+                    </div>
+                </Show>
+            </Show>
+            {msg.attachment.clone().map(|attachment| view! { <AttachmentView attachment=attachment session_token=session_token /> })}
+        </li>
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -303,6 +952,7 @@ mod tests {
                 is_hand_raised: false,
                 is_sharing_screen: false,
                 speaking_time: 0,
+                video_quality: shared::VideoQuality::default(),
             },
             Participant {
                 id: "u2".to_string(),
@@ -310,6 +960,7 @@ mod tests {
                 is_hand_raised: false,
                 is_sharing_screen: false,
                 speaking_time: 0,
+                video_quality: shared::VideoQuality::default(),
             }
         ];
 
@@ -327,15 +978,136 @@ mod tests {
 
         typing.insert("u3".to_string()); // Unknown user
         let res = format_typing_indicator(&typing, &participants, &my_id);
-        assert!(res == "2 users are typing..." || res == "2 users are typing...");
+        assert_eq!(res, "2 users are typing...");
+    }
+
+    #[test]
+    fn test_prepend_history() {
+        let message = |id: &str| ChatMessage {
+            id: id.to_string(),
+            user_id: "u1".to_string(),
+            content: id.to_string(),
+            recipient_id: None,
+            timestamp: 0,
+            attachment: None,
+            edited_at: None,
+            deleted: false,
+            reactions: Default::default(),
+            parent_id: None,
+            mentions: Vec::new(),
+        };
+        let mut messages = vec![message("m3"), message("m4")];
+        prepend_history(&mut messages, vec![message("m1"), message("m2"), message("m3")]);
+        let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m2", "m3", "m4"]);
+
+        // A thread parent that arrived ahead of its page moves back into place
+        let mut messages = vec![reply("p", "u1", None, 1), reply("a", "u1", Some("p"), 5)];
+        prepend_history(&mut messages, vec![reply("x", "u1", None, 0), reply("p", "u1", None, 1), reply("y", "u1", None, 2)]);
+        let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["x", "p", "y", "a"]);
+    }
+
+    #[test]
+    fn test_reaction_chips_and_message_key() {
+        let mut msg = ChatMessage {
+            id: "m1".to_string(),
+            user_id: "u1".to_string(),
+            content: "Hi".to_string(),
+            recipient_id: None,
+            timestamp: 0,
+            attachment: None,
+            edited_at: None,
+            deleted: false,
+            reactions: Default::default(),
+            parent_id: None,
+            mentions: Vec::new(),
+        };
+        let before = message_key(&msg);
+        msg.reactions.insert("👍".to_string(), vec!["u1".to_string(), "u2".to_string()]);
+        msg.reactions.insert("🎉".to_string(), vec!["u2".to_string()]);
+        assert_ne!(message_key(&msg), before);
+
+        let chips = reaction_chips(&msg, &Some("u1".to_string()));
+        assert_eq!(chips, vec![("🎉".to_string(), 1, false), ("👍".to_string(), 2, true)]);
+    }
+
+    fn reply(id: &str, user_id: &str, parent_id: Option<&str>, timestamp: u64) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            user_id: user_id.to_string(),
+            content: id.to_string(),
+            recipient_id: None,
+            timestamp,
+            attachment: None,
+            edited_at: None,
+            deleted: false,
+            reactions: Default::default(),
+            parent_id: parent_id.map(str::to_string),
+            mentions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_replace_scope() {
+        let private = |id: &str, from: &str, to: &str, timestamp: u64| ChatMessage {
+            recipient_id: Some(to.to_string()),
+            ..reply(id, from, None, timestamp)
+        };
+        let mut messages = vec![
+            reply("old", "u2", None, 1),
+            private("p1", "me", "u2", 2),
+            private("p2", "u3", "me", 3),
+        ];
+        assert!(in_scope(&messages[1], &ChatScope::Private("u2".to_string()), "me"));
+        assert!(!in_scope(&messages[2], &ChatScope::Private("u2".to_string()), "me"));
+
+        // Entering a breakout room swaps the room messages, private chats stay
+        replace_scope(&mut messages, &ChatScope::Breakout("r1".to_string()), "me", vec![reply("b1", "u4", None, 4)]);
+        let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["p1", "p2", "b1"]);
+
+        replace_scope(&mut messages, &ChatScope::Private("u3".to_string()), "me", vec![private("p0", "me", "u3", 0), private("p2", "u3", "me", 3)]);
+        let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["p0", "p1", "p2", "b1"]);
+
+        // Replies go back to the other side of the conversation
+        assert_eq!(reply_recipient(&messages[1], "me"), Some("u2".to_string()));
+        assert_eq!(reply_recipient(&messages[2], "me"), Some("u3".to_string()));
+        assert_eq!(reply_recipient(&messages[3], "me"), None);
+    }
+
+    #[test]
+    fn test_build_threads() {
+        let messages = vec![
+            reply("m1", "u1", None, 1),
+            reply("m2", "u2", None, 2),
+            reply("m3", "u2", Some("m1"), 3),
+            reply("m4", "u3", Some("m3"), 4),
+            reply("m5", "u3", Some("gone"), 5),
+        ];
+        let threads = build_threads(&messages);
+        let shape: Vec<(&str, Vec<&str>)> = threads
+            .iter()
+            .map(|t| (t.root.id.as_str(), t.replies.iter().map(|r| r.id.as_str()).collect()))
+            .collect();
+        assert_eq!(shape, vec![("m1", vec!["m3", "m4"]), ("m2", vec![]), ("m5", vec![])]);
+
+        assert!(is_thread_participant(&messages, "m4", "u1"));
+        assert!(is_thread_participant(&messages, "m1", "u3"));
+        assert!(!is_thread_participant(&messages, "m2", "u1"));
+        assert!(!is_thread_participant(&messages, "unknown", "u1"));
     }
 
     #[test]
-    fn test_extract_base64() {
-        let data_url = "data:text/plain;base64,SGVsbG8=";
-        assert_eq!(extract_base64_from_data_url(data_url), Some("SGVsbG8=".to_string()));
+    fn test_quotes() {
+        let mut parent = reply("m1", "u1", None, 1);
+        parent.content = "x".repeat(100);
+        let quote = quote_for(&[parent.clone()], "m1", &[], &Some("u1".to_string()));
+        assert_eq!(quote, format!("Me: {}…", "x".repeat(QUOTE_CHARS)));
 
-        let invalid = "invalid_data";
-        assert_eq!(extract_base64_from_data_url(invalid), None);
+        parent.deleted = true;
+        assert_eq!(quote_snippet(&parent), "This message was deleted");
+        assert_eq!(quote_for(&[], "m1", &[], &None), "In reply to an earlier message");
     }
 }
@@ -3,3 +3,4 @@ pub mod prejoin;
 pub mod breakout;
 pub mod video_grid;
 pub mod toast;
+pub mod recording_consent;
@@ -1,8 +1,13 @@
 use leptos::*;
+use crate::components_ui::recording_consent::RecordingConsentDialog;
 
 #[component]
 pub fn PrejoinScreen(
     on_join: Callback<String>,
+    is_recording: ReadSignal<bool>,
+    show_consent: Signal<bool>,
+    auto_mute_decliners: Signal<bool>,
+    on_consent: Callback<bool>,
 ) -> impl IntoView {
     let (display_name, set_display_name) = create_signal("Guest".to_string());
 
@@ -10,6 +15,11 @@ pub fn PrejoinScreen(
         <div class="prejoin-container" style="display: flex; flex-direction: column; align-items: center; justify-content: center; height: 100vh; background: #f0f0f0;">
             <div class="card" style="background: white; padding: 40px; border-radius: 8px; box-shadow: 0 4px 6px rgba(0,0,0,0.1); text-align: center;">
                 <h2>"Ready to join?"</h2>
+                <Show when=move || is_recording.get()>
+                    <div style="background: red; color: white; padding: 5px 10px; border-radius: 4px; display: inline-block;">
+                        "REC · This meeting is being recorded"
+                    </div>
+                </Show>
                 <div style="margin: 20px 0;">
                     <label style="display: block; margin-bottom: 8px;">"Display Name"</label>
                     <input
@@ -27,6 +37,11 @@ pub fn PrejoinScreen(
                     "Join Meeting"
                 </button>
             </div>
+            <RecordingConsentDialog
+                show=show_consent
+                auto_mute=auto_mute_decliners
+                on_decide=on_consent
+            />
         </div>
     }
 }
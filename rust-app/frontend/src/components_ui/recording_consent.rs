@@ -0,0 +1,42 @@
+use leptos::*;
+
+// Blocks the page until the participant accepts or declines being recorded
+#[component]
+pub fn RecordingConsentDialog(
+    show: Signal<bool>,
+    auto_mute: Signal<bool>,
+    on_decide: Callback<bool>,
+) -> impl IntoView {
+    view! {
+        <Show when=move || show.get()>
+            <div class="modal-overlay recording-consent" style="position: fixed; top: 0; left: 0; width: 100%; height: 100%; background: rgba(0,0,0,0.7); display: flex; justify-content: center; align-items: center; z-index: 2000;">
+                <div class="modal-content" style="background: white; color: black; padding: 30px; border-radius: 8px; width: 420px; max-width: 90%; text-align: center;">
+                    <div style="background: red; color: white; padding: 5px; border-radius: 4px; display: inline-block; margin-bottom: 15px;">
+                        "REC"
+                    </div>
+                    <h3>"This meeting is being recorded"</h3>
+                    <p>"Your audio, video and shared content may be captured. Do you consent to being recorded?"</p>
+                    <Show when=move || auto_mute.get()>
+                        <p style="color: #856404; background: #fff3cd; padding: 8px; border-radius: 4px; font-size: 0.9em;">
+                            "If you decline, your microphone will be muted."
+                        </p>
+                    </Show>
+                    <div style="display: flex; justify-content: center; gap: 10px; margin-top: 20px;">
+                        <button
+                            on:click=move |_| on_decide.call(false)
+                            style="padding: 10px 20px; background-color: #6c757d; color: white; border: none; border-radius: 4px; cursor: pointer;"
+                        >
+                            "Decline"
+                        </button>
+                        <button
+                            on:click=move |_| on_decide.call(true)
+                            style="padding: 10px 20px; background-color: #28a745; color: white; border: none; border-radius: 4px; cursor: pointer;"
+                        >
+                            "I Consent"
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
@@ -1,7 +1,8 @@
 use leptos::*;
-use shared::Participant;
+use shared::{MediaStats, Participant, VideoQuality};
 use web_sys::MediaStream;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use crate::connection_stats::QualityIndicator;
 
 #[derive(Clone, PartialEq)]
 enum GridItem {
@@ -46,6 +47,9 @@ pub fn VideoGrid(
     my_id: ReadSignal<Option<String>>,
     shared_video_url: ReadSignal<Option<String>>,
     speaking_peers: ReadSignal<HashSet<String>>,
+    media_stats: ReadSignal<HashMap<String, MediaStats>>,
+    video_quality: ReadSignal<VideoQuality>,
+    remote_streams: ReadSignal<HashMap<String, MediaStream>>,
 ) -> impl IntoView {
     let video_ref = create_node_ref::<html::Video>();
     let screen_ref = create_node_ref::<html::Video>();
@@ -134,9 +138,14 @@ pub fn VideoGrid(
             } else {
                  "width: 320px; height: 240px; background: black; border-radius: 8px; position: relative; overflow: hidden; border: 2px solid #007bff;"
             }>
-                <Show when=move || local_stream.get().is_some() fallback=move || view! {
-                    <div style="width: 100%; height: 100%; display: flex; align-items: center; justify-content: center; color: white;">
-                        "Camera Off"
+                <Show when=move || local_stream.get().is_some() && video_quality.get() != VideoQuality::AudioOnly fallback=move || view! {
+                    <div style="width: 100%; height: 100%; display: flex; flex-direction: column; align-items: center; justify-content: center; gap: 10px; color: white;">
+                        <Show when=move || video_quality.get() == VideoQuality::AudioOnly fallback=|| "Camera Off">
+                            <div class="avatar" style="width: 80px; height: 80px; background: #555; border-radius: 50%; display: flex; align-items: center; justify-content: center; font-size: 32px; color: white;">
+                                "🎧"
+                            </div>
+                            "Audio Only"
+                        </Show>
                     </div>
                 }>
                     <video
@@ -207,12 +216,39 @@ pub fn VideoGrid(
                             let initial_char = p.name.chars().next().unwrap_or('?').to_uppercase().to_string();
                             let is_hand_raised = p.is_hand_raised;
                             let id_clone = p.id.clone();
-                            let is_speaking = move || speaking_peers.get().contains(&id_clone);
+                            let is_speaking = Signal::derive(move || speaking_peers.get().contains(&id_clone));
+                            let id_stats = p.id.clone();
+                            let tile_stats = Signal::derive(move || {
+                                if is_screen { None } else { media_stats.with(|s| s.get(&id_stats).cloned()) }
+                            });
+                            let id_quality = p.id.clone();
+                            let is_audio_only = Signal::derive(move || !is_screen && participants.with(|list| {
+                                list.iter().any(|x| x.id == id_quality && x.video_quality == VideoQuality::AudioOnly)
+                            }));
+                            let id_stream = p.id.clone();
+                            let stream = Signal::derive(move || {
+                                if is_screen { None } else { remote_streams.with(|s| s.get(&id_stream).cloned()) }
+                            });
+                            let remote_ref = create_node_ref::<html::Video>();
+                            create_effect(move |_| {
+                                if let Some(video_el) = remote_ref.get() {
+                                    video_el.set_src_object(stream.get().as_ref());
+                                    let _ = video_el.play();
+                                }
+                            });
+                            // Audio keeps playing through the hidden video element
+                            let shows_video = Signal::derive(move || stream.with(Option::is_some) && !is_audio_only.get());
 
                             view! {
-                                <div class="video-card" style=move || format!("width: 320px; height: 240px; background: #222; border-radius: 8px; position: relative; display: flex; align-items: center; justify-content: center; border: {} solid {};", if is_speaking() { "3px" } else { "1px" }, if is_speaking() { "#28a745" } else { "#444" })>
+                                <div class=move || if is_speaking.get() { "video-card speaking" } else { "video-card" } style=move || format!("width: 320px; height: 240px; background: #222; border-radius: 8px; position: relative; display: flex; align-items: center; justify-content: center; border: {} solid {};", if is_speaking.get() { "3px" } else { "1px" }, if is_speaking.get() { "#28a745" } else { "#444" })>
+                                    <video
+                                        _ref=remote_ref
+                                        autoplay
+                                        playsinline
+                                        style=move || format!("width: 100%; height: 100%; object-fit: cover; border-radius: 8px; display: {};", if shows_video.get() { "block" } else { "none" })
+                                    />
                                     <Show when=move || is_screen fallback=move || view!{
-                                        <div class="avatar" style="width: 80px; height: 80px; background: #555; border-radius: 50%; display: flex; align-items: center; justify-content: center; font-size: 32px; color: white;">
+                                        <div class="avatar" style=move || format!("width: 80px; height: 80px; background: #555; border-radius: 50%; display: {}; align-items: center; justify-content: center; font-size: 32px; color: white;", if shows_video.get() { "none" } else { "flex" })>
                                             {initial_char.clone()}
                                         </div>
                                     }>
@@ -226,6 +262,10 @@ pub fn VideoGrid(
                                     </div>
 
                                     <div class="status-icons" style="position: absolute; top: 10px; right: 10px; display: flex; gap: 5px;">
+                                        <QualityIndicator stats=tile_stats />
+                                        <Show when=move || is_audio_only.get()>
+                                            <span style="font-size: 20px;" title="Audio Only">"🎧"</span>
+                                        </Show>
                                         <Show when=move || is_hand_raised && !is_screen>
                                             <span style="font-size: 20px;" title="Hand Raised">"✋"</span>
                                         </Show>
@@ -254,6 +294,7 @@ mod tests {
             is_hand_raised: false,
             is_sharing_screen: false,
             speaking_time: 0,
+            video_quality: shared::VideoQuality::default(),
         };
 
         let item_user = GridItem::User(p.clone());
@@ -1,18 +1,175 @@
 use leptos::*;
 use gloo_timers::callback::Interval;
+use shared::{ConnectionQuality, MediaStats, QualityLevel, VideoQuality};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{RtcPeerConnection, RtcStatsReport};
+
+const POLL_INTERVAL_MS: u32 = 2000;
+// Loss above this for this many consecutive polls (6 seconds) counts as sustained
+const SUSTAINED_LOSS_PERCENT: f64 = 5.0;
+const SUSTAINED_LOSS_SAMPLES: u32 = 3;
+
+#[derive(Clone, Debug, Default)]
+pub struct LossMonitor {
+    consecutive: u32,
+}
+
+impl LossMonitor {
+    /// Records one poll's packet loss and returns true once loss has been sustained
+    /// long enough to act on. The streak restarts after each positive answer.
+    pub fn record(&mut self, packet_loss: f64) -> bool {
+        if packet_loss > SUSTAINED_LOSS_PERCENT {
+            self.consecutive += 1;
+        } else {
+            self.consecutive = 0;
+        }
+        if self.consecutive >= SUSTAINED_LOSS_SAMPLES {
+            self.consecutive = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Records one poll of all peers and returns the quality to switch our camera to,
+    /// one step down from `current`, once loss has been sustained.
+    pub fn downgrade(&mut self, summary: &ConnectionQuality, current: VideoQuality) -> Option<VideoQuality> {
+        let sustained = self.record(summary.packet_loss);
+        (sustained && current != VideoQuality::AudioOnly).then(|| current.downgrade())
+    }
+}
+
+// Cumulative counters read from the inbound-rtp and candidate-pair entries of a stats report
+#[derive(Clone, Debug, Default, PartialEq)]
+struct RtpSample {
+    timestamp: f64, // ms
+    bytes_received: f64,
+    packets_received: f64,
+    packets_lost: f64,
+    jitter_ms: f64,
+    rtt_ms: f64,
+    frame_width: u32,
+    frame_height: u32,
+    frames_per_second: f64,
+}
+
+// Bitrate and loss are rates, so they need the previous sample of the same peer.
+fn compute_media_stats(prev: Option<&RtpSample>, cur: &RtpSample) -> MediaStats {
+    let (bitrate_kbps, packet_loss) = match prev {
+        Some(prev) if cur.timestamp > prev.timestamp => {
+            let elapsed_s = (cur.timestamp - prev.timestamp) / 1000.0;
+            let bytes = (cur.bytes_received - prev.bytes_received).max(0.0);
+            let received = (cur.packets_received - prev.packets_received).max(0.0);
+            let lost = (cur.packets_lost - prev.packets_lost).max(0.0);
+            let loss = if received + lost > 0.0 { lost / (received + lost) * 100.0 } else { 0.0 };
+            ((bytes * 8.0 / 1000.0 / elapsed_s) as u32, loss)
+        },
+        _ => (0, 0.0),
+    };
+
+    MediaStats {
+        bitrate_kbps,
+        packet_loss,
+        jitter_ms: cur.jitter_ms,
+        rtt_ms: cur.rtt_ms,
+        frame_width: cur.frame_width,
+        frame_height: cur.frame_height,
+        frames_per_second: cur.frames_per_second,
+    }
+}
+
+pub fn quality_color(level: QualityLevel) -> &'static str {
+    match level {
+        QualityLevel::Good => "#28a745",
+        QualityLevel::Fair => "#ffc107",
+        QualityLevel::Poor => "#dc3545",
+    }
+}
+
+pub fn format_media_stats(stats: &MediaStats) -> String {
+    format!(
+        "{} kbps · {:.1}% loss · {:.0} ms jitter · {}x{} @ {:.0} fps",
+        stats.bitrate_kbps, stats.packet_loss, stats.jitter_ms, stats.frame_width, stats.frame_height, stats.frames_per_second
+    )
+}
+
+fn number_field(entry: &JsValue, key: &str) -> f64 {
+    js_sys::Reflect::get(entry, &key.into()).ok().and_then(|v| v.as_f64()).unwrap_or(0.0)
+}
+
+fn string_field(entry: &JsValue, key: &str) -> String {
+    js_sys::Reflect::get(entry, &key.into()).ok().and_then(|v| v.as_string()).unwrap_or_default()
+}
+
+async fn collect_sample(pc: &RtcPeerConnection) -> Result<RtpSample, JsValue> {
+    let report: RtcStatsReport = JsFuture::from(pc.get_stats()).await?.dyn_into()?;
+    let mut sample = RtpSample { timestamp: js_sys::Date::now(), ..Default::default() };
+
+    let values = js_sys::try_iter(&report.values())?.ok_or_else(|| JsValue::from_str("Stats report is not iterable"))?;
+    for entry in values {
+        let entry = entry?;
+        match string_field(&entry, "type").as_str() {
+            "inbound-rtp" => {
+                sample.bytes_received += number_field(&entry, "bytesReceived");
+                sample.packets_received += number_field(&entry, "packetsReceived");
+                sample.packets_lost += number_field(&entry, "packetsLost");
+                // Jitter is reported in seconds
+                sample.jitter_ms = sample.jitter_ms.max(number_field(&entry, "jitter") * 1000.0);
+                if string_field(&entry, "kind") == "video" {
+                    sample.frame_width = number_field(&entry, "frameWidth") as u32;
+                    sample.frame_height = number_field(&entry, "frameHeight") as u32;
+                    sample.frames_per_second = number_field(&entry, "framesPerSecond");
+                }
+            },
+            "candidate-pair" if string_field(&entry, "state") == "succeeded" => {
+                sample.rtt_ms = number_field(&entry, "currentRoundTripTime") * 1000.0;
+            },
+            _ => {}
+        }
+    }
+
+    Ok(sample)
+}
 
 #[component]
 pub fn ConnectionStats(
-    on_ping: Callback<()>,
-    rtt: ReadSignal<u64>,
+    peer_connections: StoredValue<HashMap<String, RtcPeerConnection>>,
+    media_stats: ReadSignal<HashMap<String, MediaStats>>,
+    on_stats: Callback<HashMap<String, MediaStats>>,
 ) -> impl IntoView {
+    let previous_samples = store_value(HashMap::<String, RtpSample>::new());
+
     create_effect(move |_| {
-        let handle = Interval::new(2000, move || {
-            on_ping.call(());
+        let handle = Interval::new(POLL_INTERVAL_MS, move || {
+            let peers: Vec<(String, RtcPeerConnection)> = peer_connections.with_value(|p| {
+                p.iter().map(|(id, pc)| (id.clone(), pc.clone())).collect()
+            });
+
+            spawn_local(async move {
+                let mut stats = HashMap::new();
+                for (peer_id, pc) in peers {
+                    match collect_sample(&pc).await {
+                        Ok(sample) => {
+                            let prev = previous_samples.with_value(|s| s.get(&peer_id).cloned());
+                            stats.insert(peer_id.clone(), compute_media_stats(prev.as_ref(), &sample));
+                            previous_samples.update_value(|s| { s.insert(peer_id, sample); });
+                        },
+                        Err(e) => web_sys::console::error_1(&e),
+                    }
+                }
+                // Forget samples of peers that have left
+                previous_samples.update_value(|s| s.retain(|id, _| stats.contains_key(id)));
+                on_stats.call(stats);
+            });
         });
         on_cleanup(move || drop(handle));
     });
 
+    let summary = move || media_stats.with(|s| ConnectionQuality::summarize(s.values()));
+
     view! {
         <div class="connection-stats" style="
             position: absolute;
@@ -26,20 +183,113 @@ pub fn ConnectionStats(
             pointer-events: none;
             z-index: 100;
         ">
-            <span style=move || format!("color: {}", if rtt.get() < 100 { "#28a745" } else if rtt.get() < 300 { "#ffc107" } else { "#dc3545" })>
+            <span style=move || format!("color: {}", quality_color(summary().level))>
                 "● "
             </span>
-            {move || format!("{} ms", rtt.get())}
+            {move || {
+                let s = summary();
+                if s.peer_count == 0 {
+                    "No one else connected".to_string()
+                } else {
+                    format!("{:.0} ms · {} kbps · {:.1}% loss · {:.0} ms jitter", s.rtt_ms, s.bitrate_kbps, s.packet_loss, s.jitter_ms)
+                }
+            }}
         </div>
     }
 }
 
+#[component]
+pub fn QualityIndicator(
+    stats: Signal<Option<MediaStats>>,
+) -> impl IntoView {
+    view! {
+        <Show when=move || stats.get().is_some()>
+            <span
+                class="quality-indicator"
+                style=move || format!("font-size: 14px; color: {};", stats.get().map(|s| quality_color(s.quality())).unwrap_or("#ccc"))
+                title=move || stats.get().map(|s| format_media_stats(&s)).unwrap_or_default()
+            >
+                "▮▮▮"
+            </span>
+        </Show>
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_stats_render() {
-        assert_eq!(1, 1);
+    fn test_compute_media_stats() {
+        let prev = RtpSample {
+            timestamp: 1000.0,
+            bytes_received: 0.0,
+            packets_received: 100.0,
+            packets_lost: 0.0,
+            ..Default::default()
+        };
+        let cur = RtpSample {
+            timestamp: 3000.0,
+            bytes_received: 250_000.0,
+            packets_received: 195.0,
+            packets_lost: 5.0,
+            jitter_ms: 12.0,
+            rtt_ms: 40.0,
+            frame_width: 640,
+            frame_height: 480,
+            frames_per_second: 30.0,
+        };
+
+        let stats = compute_media_stats(Some(&prev), &cur);
+        assert_eq!(stats.bitrate_kbps, 1000);
+        assert_eq!(stats.packet_loss, 5.0);
+        assert_eq!(stats.frame_width, 640);
+        assert_eq!(stats.quality(), QualityLevel::Fair);
+
+        // First sample has no rate information yet
+        let first = compute_media_stats(None, &cur);
+        assert_eq!(first.bitrate_kbps, 0);
+        assert_eq!(first.packet_loss, 0.0);
+    }
+
+    #[test]
+    fn test_loss_monitor_requires_sustained_loss() {
+        let mut monitor = LossMonitor::default();
+        assert!(!monitor.record(8.0));
+        assert!(!monitor.record(8.0));
+        assert!(!monitor.record(1.0)); // Recovered, streak resets
+        assert!(!monitor.record(8.0));
+        assert!(!monitor.record(8.0));
+        assert!(monitor.record(8.0));
+        assert!(!monitor.record(8.0)); // Starts counting again after firing
+    }
+
+    #[test]
+    fn test_loss_monitor_downgrades_after_sustained_loss() {
+        let lossy = |loss: f64| ConnectionQuality::summarize(&[
+            MediaStats { packet_loss: 1.0, ..Default::default() },
+            MediaStats { packet_loss: loss, ..Default::default() },
+        ]);
+        let mut monitor = LossMonitor::default();
+        let mut quality = VideoQuality::High;
+        for _ in 0..SUSTAINED_LOSS_SAMPLES - 1 {
+            assert_eq!(monitor.downgrade(&lossy(8.0), quality), None);
+        }
+        quality = monitor.downgrade(&lossy(8.0), quality).unwrap();
+        assert_eq!(quality, VideoQuality::Standard);
+
+        // A good poll in between starts the count over
+        assert_eq!(monitor.downgrade(&lossy(8.0), quality), None);
+        assert_eq!(monitor.downgrade(&lossy(0.0), quality), None);
+        for _ in 0..SUSTAINED_LOSS_SAMPLES - 1 {
+            assert_eq!(monitor.downgrade(&lossy(8.0), quality), None);
+        }
+        assert_eq!(monitor.downgrade(&lossy(8.0), quality), Some(VideoQuality::Low));
+
+        // Nothing below audio only
+        let mut monitor = LossMonitor::default();
+        for _ in 0..SUSTAINED_LOSS_SAMPLES {
+            assert_eq!(monitor.downgrade(&lossy(8.0), VideoQuality::AudioOnly), None);
+        }
     }
 }
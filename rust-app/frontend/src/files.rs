@@ -0,0 +1,331 @@
+use leptos::*;
+use shared::{FileAttachment, MAX_THUMBNAIL_CHARS};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement, Url};
+use crate::recording::trigger_download;
+use crate::utils::room_id_from_path;
+
+// Longest side of a thumbnail in pixels
+const THUMBNAIL_MAX_PX: u32 = 240;
+const THUMBNAIL_QUALITY: f64 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewKind {
+    Image,
+    Video,
+    Audio,
+    Pdf,
+    Other,
+}
+
+pub fn preview_kind(mime_type: &str) -> PreviewKind {
+    if mime_type.starts_with("image/") {
+        PreviewKind::Image
+    } else if mime_type.starts_with("video/") {
+        PreviewKind::Video
+    } else if mime_type.starts_with("audio/") {
+        PreviewKind::Audio
+    } else if mime_type == "application/pdf" {
+        PreviewKind::Pdf
+    } else {
+        PreviewKind::Other
+    }
+}
+
+/// Scales `width` x `height` down to fit in `max` x `max`, keeping the aspect ratio.
+/// Images that already fit keep their size.
+pub fn thumbnail_size(width: u32, height: u32, max: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max {
+        return (width, height);
+    }
+    let scale = |side: u32| ((side as u64 * max as u64 + longest as u64 / 2) / longest as u64).max(1) as u32;
+    (scale(width), scale(height))
+}
+
+pub fn format_file_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{} KB", bytes.div_ceil(KB))
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+async fn load_image(src: &str) -> Result<HtmlImageElement, JsValue> {
+    let image = HtmlImageElement::new()?;
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        image.set_onload(Some(&resolve));
+        image.set_onerror(Some(&reject));
+    });
+    image.set_src(src);
+    JsFuture::from(promise).await?;
+    Ok(image)
+}
+
+/// Draws a picked image onto a small canvas and returns it as a JPEG data URL,
+/// so receivers can show a preview without downloading the original.
+pub async fn make_thumbnail(file: &web_sys::File) -> Result<String, JsValue> {
+    let document = web_sys::window().and_then(|w| w.document()).ok_or(JsValue::from_str("No document"))?;
+    let url = Url::create_object_url_with_blob(file)?;
+    let image = load_image(&url).await;
+    Url::revoke_object_url(&url)?;
+    let image = image?;
+
+    let (width, height) = thumbnail_size(image.natural_width(), image.natural_height(), THUMBNAIL_MAX_PX);
+    let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let ctx = canvas
+        .get_context("2d")?
+        .ok_or(JsValue::from_str("No 2d context"))?
+        .dyn_into::<CanvasRenderingContext2d>()?;
+    ctx.draw_image_with_html_image_element_and_dw_and_dh(&image, 0.0, 0.0, width as f64, height as f64)?;
+
+    let data_url = canvas.to_data_url_with_type_and_encoder_options("image/jpeg", &JsValue::from_f64(THUMBNAIL_QUALITY))?;
+    if data_url.len() > MAX_THUMBNAIL_CHARS {
+        return Err(JsValue::from_str("Thumbnail too large"));
+    }
+    Ok(data_url)
+}
+
+fn files_url() -> Result<String, JsValue> {
+    let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
+    let origin = window.location().origin()?;
+    let room_id = room_id_from_path(&window.location().pathname()?).unwrap_or_default();
+    Ok(format!("{}/api/rooms/{}/files", origin, urlencoding::encode(&room_id)))
+}
+
+/// Uploads a picked file to the room's file store. The returned reference is what
+/// goes into the chat message. Errors are meant to be shown to the user.
+pub async fn upload_file(token: String, file: web_sys::File) -> Result<FileAttachment, String> {
+    let url = files_url().map_err(|_| "Upload failed".to_string())?;
+    let buffer = JsFuture::from(file.array_buffer()).await.map_err(|_| "Could not read the file".to_string())?;
+    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}?filename={}", url, urlencoding::encode(&file.name())))
+        .bearer_auth(token)
+        .body(bytes)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        // The backend explains size and quota rejections in the body
+        return Err(response.text().await.unwrap_or_else(|_| "Upload failed".to_string()));
+    }
+    let mut attachment: FileAttachment = response.json().await.map_err(|e| e.to_string())?;
+
+    if preview_kind(&attachment.mime_type) == PreviewKind::Image {
+        // Without a thumbnail receivers just load the full image
+        match make_thumbnail(&file).await {
+            Ok(thumbnail) => attachment.thumbnail = Some(thumbnail),
+            Err(e) => web_sys::console::warn_1(&e),
+        }
+    }
+    Ok(attachment)
+}
+
+/// Fetches an uploaded file; downloads need our session token, so plain links won't do.
+pub async fn fetch_file(token: String, attachment: &FileAttachment) -> Result<Blob, JsValue> {
+    let bytes = reqwest::Client::new()
+        .get(format!("{}/{}", files_url()?, attachment.id))
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .bytes()
+        .await
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let options = BlobPropertyBag::new();
+    options.set_type(&attachment.mime_type);
+    let array = js_sys::Uint8Array::from(bytes.as_ref());
+    Blob::new_with_u8_array_sequence_and_options(&js_sys::Array::of1(&array), &options)
+}
+
+#[component]
+pub fn AttachmentView(attachment: FileAttachment, session_token: ReadSignal<Option<String>>) -> impl IntoView {
+    let kind = preview_kind(&attachment.mime_type);
+    let thumbnail = attachment.thumbnail.clone();
+    let label = format!("📎 {} ({})", attachment.filename, format_file_size(attachment.size));
+    let attachment = store_value(attachment);
+    // Object URL of the full file, fetched on demand and shared by all previews
+    let (media_url, set_media_url) = create_signal(None::<String>);
+    let (loading, set_loading) = create_signal(false);
+    let (show_lightbox, set_show_lightbox) = create_signal(false);
+
+    let load_media = move || {
+        if media_url.get_untracked().is_some() || loading.get_untracked() {
+            return;
+        }
+        if let Some(token) = session_token.get_untracked() {
+            set_loading.set(true);
+            spawn_local(async move {
+                match fetch_file(token, &attachment.get_value()).await.and_then(|blob| Url::create_object_url_with_blob(&blob)) {
+                    Ok(url) => { let _ = set_media_url.try_set(Some(url)); },
+                    Err(e) => web_sys::console::error_1(&e),
+                }
+                let _ = set_loading.try_set(false);
+            });
+        }
+    };
+
+    on_cleanup(move || {
+        if let Some(url) = media_url.try_get_untracked().flatten() {
+            let _ = Url::revoke_object_url(&url);
+        }
+    });
+
+    // Without a thumbnail the image itself is the preview
+    if kind == PreviewKind::Image && thumbnail.is_none() {
+        load_media();
+    }
+
+    let download = move |_| {
+        if let Some(token) = session_token.get_untracked() {
+            spawn_local(async move {
+                let attachment = attachment.get_value();
+                let result = fetch_file(token, &attachment).await.and_then(|blob| trigger_download(&blob, &attachment.filename));
+                if let Err(e) = result {
+                    web_sys::console::error_1(&e);
+                }
+            });
+        }
+    };
+
+    // The thumbnail stands in until the full image has loaded
+    let lightbox_src = {
+        let thumbnail = thumbnail.clone();
+        Signal::derive(move || media_url.get().or_else(|| thumbnail.clone()).unwrap_or_default())
+    };
+
+    let open_style = "padding: 4px 8px; border: 1px solid #ccc; border-radius: 4px; background: #f8f9fa; cursor: pointer; font-size: 0.85em;";
+    let preview = match kind {
+        PreviewKind::Image => {
+            let thumbnail = thumbnail.clone();
+            (move || thumbnail.clone().or_else(|| media_url.get()).map(|src| view! {
+                <img
+                    src=src
+                    on:click=move |_| {
+                        load_media();
+                        set_show_lightbox.set(true);
+                    }
+                    style="max-width: 200px; max-height: 200px; display: block; cursor: zoom-in; border-radius: 4px;"
+                />
+            })).into_view()
+        },
+        PreviewKind::Video => (move || match media_url.get() {
+            Some(src) => view! { <video src=src controls=true autoplay=true style="max-width: 100%; max-height: 200px; display: block;"></video> }.into_view(),
+            None => view! {
+                <button on:click=move |_| load_media() disabled=loading style=open_style>
+                    {move || if loading.get() { "Loading…" } else { "▶ Play video" }}
+                </button>
+            }.into_view(),
+        }).into_view(),
+        PreviewKind::Audio => (move || match media_url.get() {
+            Some(src) => view! { <audio src=src controls=true autoplay=true style="width: 100%; display: block;"></audio> }.into_view(),
+            None => view! {
+                <button on:click=move |_| load_media() disabled=loading style=open_style>
+                    {move || if loading.get() { "Loading…" } else { "▶ Play audio" }}
+                </button>
+            }.into_view(),
+        }).into_view(),
+        // The browser's own PDF viewer, opened on the first page without its toolbars
+        PreviewKind::Pdf => (move || match media_url.get() {
+            Some(src) => view! {
+                <object
+                    data=format!("{}#page=1&toolbar=0&navpanes=0&view=FitH", src)
+                    type="application/pdf"
+                    style="width: 200px; height: 260px; display: block; border: 1px solid #ddd;"
+                ></object>
+            }.into_view(),
+            None => view! {
+                <button on:click=move |_| load_media() disabled=loading style=open_style>
+                    {move || if loading.get() { "Loading…" } else { "📄 Preview first page" }}
+                </button>
+            }.into_view(),
+        }).into_view(),
+        PreviewKind::Other => ().into_view(),
+    };
+
+    view! {
+        <div class="attachment" style="margin-top: 5px; display: flex; flex-direction: column; gap: 4px; align-items: flex-start;">
+            {preview}
+            <button
+                on:click=download
+                style="background: none; border: none; padding: 0; color: #007bff; cursor: pointer; text-decoration: underline;"
+            >
+                {label}
+            </button>
+            <Show when=move || show_lightbox.get()>
+                <Lightbox src=lightbox_src on_close=Callback::new(move |_| set_show_lightbox.set(false)) />
+            </Show>
+        </div>
+    }
+}
+
+/// An image shown over the whole page, closed by clicking anywhere or pressing Escape.
+/// The key listener only lives as long as the lightbox is open.
+#[component]
+fn Lightbox(src: Signal<String>, on_close: Callback<()>) -> impl IntoView {
+    let escape_handle = window_event_listener(ev::keydown, move |ev| {
+        if ev.key() == "Escape" {
+            on_close.call(());
+        }
+    });
+    on_cleanup(move || escape_handle.remove());
+
+    view! {
+        <div
+            class="lightbox"
+            on:click=move |_| on_close.call(())
+            style="position: fixed; inset: 0; background: rgba(0,0,0,0.85); z-index: 1000; display: flex; align-items: center; justify-content: center; cursor: zoom-out;"
+        >
+            <img src=src style="max-width: 90vw; max-height: 90vh; box-shadow: 0 0 20px black;" />
+            <button
+                on:click=move |_| on_close.call(())
+                title="Close"
+                style="position: absolute; top: 15px; right: 20px; background: none; border: none; color: white; font-size: 2em; cursor: pointer;"
+            >
+                "✕"
+            </button>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_kind() {
+        assert_eq!(preview_kind("image/png"), PreviewKind::Image);
+        assert_eq!(preview_kind("video/webm"), PreviewKind::Video);
+        assert_eq!(preview_kind("audio/ogg"), PreviewKind::Audio);
+        assert_eq!(preview_kind("application/pdf"), PreviewKind::Pdf);
+        assert_eq!(preview_kind("application/zip"), PreviewKind::Other);
+    }
+
+    #[test]
+    fn test_thumbnail_size() {
+        assert_eq!(thumbnail_size(4000, 3000, 240), (240, 180));
+        assert_eq!(thumbnail_size(1000, 4000, 240), (60, 240));
+        assert_eq!(thumbnail_size(100, 50, 240), (100, 50));
+        // Extreme panoramas keep at least one pixel
+        assert_eq!(thumbnail_size(100_000, 10, 240), (240, 1));
+    }
+
+    #[test]
+    fn test_format_file_size() {
+        assert_eq!(format_file_size(512), "512 B");
+        assert_eq!(format_file_size(1536), "2 KB");
+        assert_eq!(format_file_size(5 * 1024 * 1024 + 512 * 1024), "5.5 MB");
+    }
+}
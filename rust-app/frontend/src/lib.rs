@@ -8,12 +8,18 @@ mod toolbox;
 mod settings;
 mod reactions;
 mod polls;
+mod qna;
 mod whiteboard;
 mod media;
 mod shortcuts;
 mod speaker_stats;
 mod virtual_background;
 mod connection_stats;
+mod peers;
+mod recording;
+mod captions;
+mod files;
+mod notifications;
 
 use leptos::*;
 use leptos_router::*;
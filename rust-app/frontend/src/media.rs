@@ -1,7 +1,8 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{MediaDeviceInfo, MediaStream, MediaStreamConstraints, AudioContext, AnalyserNode};
+use web_sys::{MediaDeviceInfo, MediaStream, MediaStreamConstraints, MediaStreamTrack, AudioContext, AnalyserNode, RtcPeerConnection, RtcRtpSender};
+use shared::VideoQuality;
 
 pub async fn enumerate_devices() -> Result<Vec<MediaDeviceInfo>, JsValue> {
     let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
@@ -24,20 +25,32 @@ pub async fn enumerate_devices() -> Result<Vec<MediaDeviceInfo>, JsValue> {
     Ok(devices)
 }
 
-pub async fn get_user_media(video_device_id: Option<String>, audio_device_id: Option<String>) -> Result<MediaStream, JsValue> {
+fn video_constraints(width: u32, height: u32, frame_rate: u32) -> js_sys::Object {
+    // Plain numbers are treated as "ideal" values, so lower-end cameras still work
+    let video_obj = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&video_obj, &"width".into(), &width.into());
+    let _ = js_sys::Reflect::set(&video_obj, &"height".into(), &height.into());
+    let _ = js_sys::Reflect::set(&video_obj, &"frameRate".into(), &frame_rate.into());
+    video_obj
+}
+
+pub async fn get_user_media(video_device_id: Option<String>, audio_device_id: Option<String>, quality: VideoQuality) -> Result<MediaStream, JsValue> {
     let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
     let navigator = window.navigator();
     let media_devices = navigator.media_devices()?;
 
     let constraints = MediaStreamConstraints::new();
 
-    // Video constraints
-    let video_val = if let Some(id) = video_device_id {
-         let video_obj = js_sys::Object::new();
-         let _ = js_sys::Reflect::set(&video_obj, &"deviceId".into(), &id.into());
-         wasm_bindgen::JsValue::from(video_obj)
-    } else {
-        wasm_bindgen::JsValue::TRUE
+    // Video constraints (audio-only mode doesn't capture the camera at all)
+    let video_val = match quality.video_constraints() {
+        Some((width, height, frame_rate)) => {
+            let video_obj = video_constraints(width, height, frame_rate);
+            if let Some(id) = video_device_id {
+                let _ = js_sys::Reflect::set(&video_obj, &"deviceId".into(), &id.into());
+            }
+            wasm_bindgen::JsValue::from(video_obj)
+        },
+        None => wasm_bindgen::JsValue::FALSE,
     };
     constraints.set_video(&video_val);
 
@@ -74,6 +87,55 @@ pub async fn get_display_media() -> Result<MediaStream, JsValue> {
     result.dyn_into::<MediaStream>().map_err(|_| JsValue::from_str("Not a MediaStream"))
 }
 
+/// Applies a quality level to the camera tracks of an existing stream.
+/// Audio-only stops and removes the video tracks; other levels re-constrain them in place.
+pub fn apply_video_quality(stream: &MediaStream, quality: VideoQuality) -> Result<(), JsValue> {
+    let video_tracks = stream.get_video_tracks();
+    for i in 0..video_tracks.length() {
+        if let Ok(track) = video_tracks.get(i).dyn_into::<MediaStreamTrack>() {
+            match quality.video_constraints() {
+                Some((width, height, frame_rate)) => {
+                    let constraints = video_constraints(width, height, frame_rate);
+                    let func = js_sys::Reflect::get(&track, &"applyConstraints".into())?.dyn_into::<js_sys::Function>()?;
+                    func.call1(&track, &constraints)?;
+                },
+                None => {
+                    track.stop();
+                    stream.remove_track(&track);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Caps the video we send over a peer connection to what the receiving participant asked for.
+pub fn limit_video_bitrate(pc: &RtcPeerConnection, quality: VideoQuality) -> Result<(), JsValue> {
+    let senders = pc.get_senders();
+    for i in 0..senders.length() {
+        let sender = senders.get(i).dyn_into::<RtcRtpSender>()?;
+        let is_video = sender.track().map(|t| t.kind() == "video").unwrap_or(false);
+        if !is_video {
+            continue;
+        }
+
+        let params = sender.get_parameters();
+        let encodings = js_sys::Reflect::get(&params, &"encodings".into())?;
+        if let Ok(encodings) = encodings.dyn_into::<js_sys::Array>() {
+            for j in 0..encodings.length() {
+                let encoding = encodings.get(j);
+                let max_bitrate = quality.max_bitrate_kbps();
+                js_sys::Reflect::set(&encoding, &"active".into(), &(max_bitrate > 0).into())?;
+                if max_bitrate > 0 {
+                    js_sys::Reflect::set(&encoding, &"maxBitrate".into(), &(max_bitrate * 1000).into())?;
+                }
+            }
+        }
+        let _ = sender.set_parameters_with_parameters(&params);
+    }
+    Ok(())
+}
+
 pub struct AudioMonitor {
     context: AudioContext,
     #[allow(dead_code)]
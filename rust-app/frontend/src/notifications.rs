@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use web_sys::{HtmlAudioElement, Notification, NotificationOptions, NotificationPermission};
+
+const SETTINGS_KEY: &str = "notification_settings";
+// Copied from the repository's `sounds/` directory by build.sh
+const SOUNDS_PATH: &str = "/sounds";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundCue {
+    Join,
+    Leave,
+    Knock,
+    Chat,
+    HandRaised,
+}
+
+impl SoundCue {
+    pub const ALL: [SoundCue; 5] = [SoundCue::Join, SoundCue::Leave, SoundCue::Knock, SoundCue::Chat, SoundCue::HandRaised];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoundCue::Join => "Participant joined",
+            SoundCue::Leave => "Participant left",
+            SoundCue::Knock => "Someone knocking",
+            SoundCue::Chat => "Chat message",
+            SoundCue::HandRaised => "Hand raised",
+        }
+    }
+
+    fn file(&self) -> &'static str {
+        match self {
+            SoundCue::Join => "joined.mp3",
+            SoundCue::Leave => "left.mp3",
+            SoundCue::Knock => "knock.mp3",
+            SoundCue::Chat => "incomingMessage.mp3",
+            SoundCue::HandRaised => "reactions-raised-hand.mp3",
+        }
+    }
+}
+
+// Kept per browser in localStorage; missing fields fall back to the defaults
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub join_sound: bool,
+    pub leave_sound: bool,
+    pub knock_sound: bool,
+    pub chat_sound: bool,
+    pub hand_raised_sound: bool,
+    pub desktop: bool, // Only shown while the tab is in the background
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            join_sound: true,
+            leave_sound: true,
+            knock_sound: true,
+            chat_sound: true,
+            hand_raised_sound: true,
+            desktop: false,
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn plays(&self, cue: SoundCue) -> bool {
+        match cue {
+            SoundCue::Join => self.join_sound,
+            SoundCue::Leave => self.leave_sound,
+            SoundCue::Knock => self.knock_sound,
+            SoundCue::Chat => self.chat_sound,
+            SoundCue::HandRaised => self.hand_raised_sound,
+        }
+    }
+
+    pub fn set_sound(&mut self, cue: SoundCue, on: bool) {
+        let flag = match cue {
+            SoundCue::Join => &mut self.join_sound,
+            SoundCue::Leave => &mut self.leave_sound,
+            SoundCue::Knock => &mut self.knock_sound,
+            SoundCue::Chat => &mut self.chat_sound,
+            SoundCue::HandRaised => &mut self.hand_raised_sound,
+        };
+        *flag = on;
+    }
+
+    fn from_json(json: &str) -> Self {
+        serde_json::from_str(json).unwrap_or_default()
+    }
+}
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+pub fn load_settings() -> NotificationSettings {
+    local_storage()
+        .and_then(|s| s.get_item(SETTINGS_KEY).ok().flatten())
+        .map(|json| NotificationSettings::from_json(&json))
+        .unwrap_or_default()
+}
+
+pub fn save_settings(settings: &NotificationSettings) {
+    if let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(settings)) {
+        let _ = storage.set_item(SETTINGS_KEY, &json);
+    }
+}
+
+pub fn play_sound(settings: &NotificationSettings, cue: SoundCue) {
+    if !settings.plays(cue) {
+        return;
+    }
+    if let Ok(audio) = HtmlAudioElement::new_with_src(&format!("{}/{}", SOUNDS_PATH, cue.file())) {
+        // Autoplay may be refused before the first user gesture; a missed cue is harmless
+        let _ = audio.play();
+    }
+}
+
+fn tab_hidden() -> bool {
+    web_sys::window().and_then(|w| w.document()).is_some_and(|d| d.hidden())
+}
+
+/// Shows a desktop notification when enabled, permitted and the tab is in the background.
+pub fn notify_desktop(settings: &NotificationSettings, title: &str, body: &str) {
+    if !settings.desktop || !tab_hidden() || Notification::permission() != NotificationPermission::Granted {
+        return;
+    }
+    let options = NotificationOptions::new();
+    options.set_body(body);
+    let _ = Notification::new_with_options(title, &options);
+}
+
+/// Asks for permission to show notifications; must be called from a user gesture.
+pub fn request_permission() {
+    if Notification::permission() == NotificationPermission::Default {
+        let _ = Notification::request_permission();
+    }
+}
+
+pub fn permission_denied() -> bool {
+    Notification::permission() == NotificationPermission::Denied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_toggle_each_cue() {
+        let mut settings = NotificationSettings::default();
+        for cue in SoundCue::ALL {
+            assert!(settings.plays(cue));
+            settings.set_sound(cue, false);
+            assert!(!settings.plays(cue));
+        }
+        assert!(!settings.desktop);
+    }
+
+    #[test]
+    fn test_settings_from_json_fills_missing_fields() {
+        let settings = NotificationSettings::from_json(r#"{"chat_sound":false,"desktop":true}"#);
+        assert!(!settings.chat_sound);
+        assert!(settings.desktop);
+        assert!(settings.knock_sound);
+        assert_eq!(NotificationSettings::from_json("not json"), NotificationSettings::default());
+    }
+}
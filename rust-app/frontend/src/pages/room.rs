@@ -8,15 +8,19 @@ use crate::components_ui::lobby::LobbyScreen;
 use crate::components_ui::breakout::BreakoutRooms;
 use crate::components_ui::video_grid::VideoGrid;
 use crate::components_ui::toast::ToastContainer;
+use crate::components_ui::recording_consent::RecordingConsentDialog;
 use crate::settings::SettingsDialog;
 use crate::reactions::ReactionDisplay;
 use crate::polls::PollsDialog;
+use crate::qna::QnaPanel;
 use crate::whiteboard::Whiteboard;
 use crate::shortcuts::{KeyboardShortcuts, ShortcutsDialog};
 use crate::state::{use_room_state, RoomConnectionState};
 use crate::speaker_stats::SpeakerStatsDialog;
 use crate::virtual_background::VirtualBackgroundDialog;
 use crate::connection_stats::ConnectionStats;
+use crate::recording::{RecordingLayout, RecordingRecovery};
+use crate::captions::{CaptionsOverlay, TranscriptDownloads};
 use gloo_timers::callback::Interval;
 
 #[component]
@@ -48,6 +52,21 @@ pub fn Room() -> impl IntoView {
         format!("{:02}:{:02}:{:02}", h, m, s)
     };
 
+    // Countdown started by a moderator's /timer; refreshed by the meeting timer's tick
+    let countdown = move || {
+        elapsed_time.track();
+        state.timer_ends_at.get().map(|ends_at| {
+            let left = ends_at.saturating_sub(js_sys::Date::now() as u64).div_ceil(1000);
+            if left == 0 {
+                "⏱ Time's up".to_string()
+            } else if left >= 3600 {
+                format!("⏱ {}:{:02}:{:02}", left / 3600, (left % 3600) / 60, left % 60)
+            } else {
+                format!("⏱ {:02}:{:02}", left / 60, left % 60)
+            }
+        })
+    };
+
     view! {
         <div style="height: 100vh;">
             <ToastContainer
@@ -56,7 +75,16 @@ pub fn Room() -> impl IntoView {
             />
             {move || match state.connection_state.get() {
                 RoomConnectionState::Prejoin => view! {
-                    <PrejoinScreen on_join=state.join_meeting />
+                    <Show when=move || state.meeting_ended.get() && state.session_token.get().is_some()>
+                        <TranscriptDownloads session_token=state.session_token />
+                    </Show>
+                    <PrejoinScreen
+                        on_join=state.join_meeting
+                        is_recording=state.is_recording
+                        show_consent=state.needs_recording_consent
+                        auto_mute_decliners=state.auto_mute_decliners
+                        on_consent=state.give_recording_consent
+                    />
                 }.into_view(),
                 RoomConnectionState::Lobby => view! {
                     <LobbyScreen />
@@ -70,8 +98,9 @@ pub fn Room() -> impl IntoView {
                             on_screen_share=state.toggle_screen_share
                         />
                         <ConnectionStats
-                            on_ping=state.send_ping
-                            rtt=state.rtt
+                            peer_connections=state.peer_connections
+                            media_stats=state.media_stats
+                            on_stats=state.on_media_stats
                         />
                         <ParticipantsList
                             participants=state.participants
@@ -82,6 +111,10 @@ pub fn Room() -> impl IntoView {
                             on_allow=state.grant_access
                             on_deny=state.deny_access
                             on_kick=state.kick_participant
+                            quality_reports=state.quality_reports
+                            recording_consents=state.recording_consents
+                            chat_muted=state.chat_muted
+                            on_chat_mute=state.set_chat_muted
                         />
                         <div class="main-content" style="flex: 1; display: flex; flex-direction: column; background: #333; color: white;">
                             <BreakoutRooms
@@ -99,14 +132,40 @@ pub fn Room() -> impl IntoView {
                                             <span class="meeting-timer" style="font-family: monospace; font-size: 1.2em; color: #aaa;">
                                                 {format_time}
                                             </span>
+                                            {move || countdown().map(|text| view! {
+                                                <span class="countdown-timer" style="font-family: monospace; font-size: 1.2em; color: #ffc107;">
+                                                    {text}
+                                                </span>
+                                            })}
                                         </div>
                                         <Show when=move || state.current_room_id.get().is_some()>
                                             <h4 style="color: #17a2b8;">" (In Breakout Room)"</h4>
                                         </Show>
+                                        <Show when=move || state.is_host.get()>
+                                            <RecordingRecovery />
+                                        </Show>
                                         <Show when=move || state.is_recording.get()>
                                             <div style="background: red; color: white; padding: 5px; border-radius: 4px; display: inline-block; margin-bottom: 10px;">
                                                 "REC"
                                             </div>
+                                            <Show when=move || state.is_host.get()>
+                                                <select
+                                                    style="margin-left: 10px; padding: 4px; border-radius: 4px;"
+                                                    title="Recording layout"
+                                                    on:change=move |ev| {
+                                                        let val = event_target_value(&ev);
+                                                        if let Some(layout) = RecordingLayout::ALL.into_iter().find(|l| format!("{:?}", l) == val) {
+                                                            state.set_recording_layout.call(layout);
+                                                        }
+                                                    }
+                                                >
+                                                    {RecordingLayout::ALL.into_iter().map(|l| view! {
+                                                        <option value=format!("{:?}", l) selected=move || state.recording_layout.get() == l>
+                                                            {l.label()}
+                                                        </option>
+                                                    }).collect_view()}
+                                                </select>
+                                            </Show>
                                         </Show>
                                         <VideoGrid
                                             participants=state.participants
@@ -115,14 +174,22 @@ pub fn Room() -> impl IntoView {
                                             my_id=state.my_id
                                             shared_video_url=state.shared_video_url
                                             speaking_peers=state.speaking_peers
+                                            media_stats=state.media_stats
+                                            video_quality=state.video_quality
+                                            remote_streams=state.remote_streams
                                         />
                                     </div>
                                 </div>
                                 <ReactionDisplay last_reaction=state.last_reaction />
+                                <Show when=move || state.show_captions.get()>
+                                    <CaptionsOverlay captions=state.captions />
+                                </Show>
                                 <Show when=move || state.show_whiteboard.get()>
                                     <Whiteboard
-                                        on_draw=state.send_draw
-                                        history=state.whiteboard_history
+                                        elements=state.whiteboard_elements
+                                        on_add=state.add_whiteboard_element
+                                        on_update=state.update_whiteboard_element
+                                        on_delete=state.delete_whiteboard_element
                                         my_id=state.my_id
                                     />
                                 </Show>
@@ -137,8 +204,11 @@ pub fn Room() -> impl IntoView {
                                 on_toggle_lock=state.toggle_lock
                                 on_toggle_lobby=state.toggle_lobby
                                 on_toggle_recording=state.toggle_recording
+                                auto_mute_decliners=state.auto_mute_decliners
+                                on_toggle_auto_mute_decliners=state.toggle_auto_mute_decliners
                                 on_settings=Callback::new(move |_| state.set_show_settings.set(true))
                                 on_polls=Callback::new(move |_| state.set_show_polls.set(true))
+                                on_qna=Callback::new(move |_| state.set_show_qna.set(true))
                                 on_shortcuts=Callback::new(move |_| state.set_show_shortcuts.set(true))
                                 on_speaker_stats=Callback::new(move |_| state.set_show_speaker_stats.set(true))
                                 on_virtual_background=Callback::new(move |_| state.set_show_virtual_background.set(true))
@@ -154,6 +224,13 @@ pub fn Room() -> impl IntoView {
                                 on_stop_share_video=state.stop_share_video
                                 is_sharing_video=Signal::derive(move || state.shared_video_url.get().is_some())
                                 on_whiteboard=Callback::new(move |_| state.set_show_whiteboard.update(|v| *v = !*v))
+                                show_captions=state.show_captions
+                                on_captions=Callback::new(move |_| state.set_show_captions.update(|v| *v = !*v))
+                                show_chat=state.show_chat
+                                mention_count=state.mention_count
+                                unread_count=state.unread_count
+                                knocking_count=Signal::derive(move || state.knocking_participants.with(Vec::len))
+                                on_chat=Callback::new(move |_| state.set_show_chat.update(|v| *v = !*v))
                                 on_reaction=state.send_reaction
                                 on_toggle_camera=state.toggle_camera
                                 on_toggle_mic=state.toggle_mic
@@ -162,19 +239,44 @@ pub fn Room() -> impl IntoView {
                                 on_end_meeting=state.end_meeting
                             />
                         </div>
-                        <Chat
-                            messages=state.messages
-                            typing_users=state.typing_users
-                            participants=state.participants
-                            on_send=state.send_message
-                            on_typing=state.set_is_typing
-                            is_connected=state.is_connected
-                            my_id=state.my_id
+                        // Hidden rather than unmounted so drafts and open threads survive
+                        <div class="chat-panel" style=move || if state.show_chat.get() { "display: flex;" } else { "display: none;" }>
+                            <Chat
+                                messages=state.messages
+                                has_more=state.chat_has_more
+                                on_load_older=state.load_older_messages
+                                typing_users=state.typing_users
+                                participants=state.participants
+                                on_send=state.send_message
+                                on_typing=state.set_is_typing
+                                is_connected=state.is_connected
+                                my_id=state.my_id
+                                is_host=state.is_host
+                                on_edit=state.edit_message
+                                on_delete=state.delete_message
+                                on_react=state.react_to_message
+                                session_token=state.session_token
+                                restriction=state.chat_restriction
+                                slow_mode_secs=state.chat_slow_mode_secs
+                                chat_mode=state.chat_mode
+                                on_chat_mode=state.set_chat_mode
+                                on_slow_mode=state.set_chat_slow_mode
+                                on_command=state.send_command
+                            />
+                        </div>
+                        <RecordingConsentDialog
+                            show=state.needs_recording_consent
+                            auto_mute=state.auto_mute_decliners
+                            on_decide=state.give_recording_consent
                         />
                         <SettingsDialog
                             show=state.show_settings
                             on_close=Callback::new(move |_| state.set_show_settings.set(false))
                             on_save_profile=state.save_profile
+                            video_quality=state.video_quality
+                            on_video_quality=state.set_video_quality
+                            notification_settings=state.notification_settings
+                            on_notification_settings=state.set_notification_settings
                         />
                         <PollsDialog
                             show=state.show_polls
@@ -182,6 +284,29 @@ pub fn Room() -> impl IntoView {
                             on_close=Callback::new(move |_| state.set_show_polls.set(false))
                             on_create_poll=state.create_poll
                             on_vote=state.vote_poll
+                            my_votes=state.my_poll_votes
+                            is_host=state.is_host
+                            participants=state.participants
+                            on_close_poll=state.close_poll
+                            on_reopen_poll=state.reopen_poll
+                            on_delete_poll=state.delete_poll
+                            vote_changes=state.poll_vote_changes
+                            on_toggle_vote_changes=state.toggle_poll_vote_changes
+                            on_reveal_answers=state.reveal_poll_answers
+                            leaderboard=state.quiz_leaderboard
+                            session_token=state.session_token
+                        />
+                        <QnaPanel
+                            show=state.show_qna
+                            questions=state.questions
+                            my_id=state.my_id
+                            is_host=state.is_host
+                            on_close=Callback::new(move |_| state.set_show_qna.set(false))
+                            on_ask=state.ask_question
+                            on_upvote=state.upvote_question
+                            on_set_answered=state.set_question_answered
+                            on_set_pinned=state.set_question_pinned
+                            on_dismiss=state.dismiss_question
                         />
                         <ShortcutsDialog
                             show=state.show_shortcuts
@@ -1,5 +1,7 @@
 use leptos::*;
-use shared::Participant;
+use shared::{ConnectionQuality, Participant};
+use std::collections::HashMap;
+use crate::connection_stats::quality_color;
 
 fn sort_participants(mut participants: Vec<Participant>) -> Vec<Participant> {
     participants.sort_by(|a, b| {
@@ -23,6 +25,10 @@ pub fn ParticipantsList(
     on_allow: Callback<String>,
     on_deny: Callback<String>,
     on_kick: Callback<String>,
+    quality_reports: ReadSignal<HashMap<String, ConnectionQuality>>,
+    recording_consents: ReadSignal<HashMap<String, bool>>, // Host only
+    chat_muted: Signal<Vec<String>>,
+    on_chat_mute: Callback<(String, bool)>, // participant_id, muted
 ) -> impl IntoView {
     let format_time = |ms: u64| {
         let seconds = ms / 1000;
@@ -87,6 +93,10 @@ pub fn ParticipantsList(
                     key=|p| (p.id.clone(), p.name.clone(), p.is_hand_raised, p.is_sharing_screen)
                     children=move |p| {
                         let id_kick = p.id.clone();
+                        let id_quality = p.id.clone();
+                        let report = move || quality_reports.with(|r| r.get(&id_quality).cloned());
+                        let id_consent = p.id.clone();
+                        let consent = move || recording_consents.with(|c| c.get(&id_consent).copied());
                         // Use reactive check for host status
                         view! {
                             <li style="display: flex; justify-content: space-between; align-items: center; margin-bottom: 5px;">
@@ -100,6 +110,16 @@ pub fn ParticipantsList(
                                     </Show>
                                 </div>
                                 <div style="display: flex; align-items: center;">
+                                    {move || consent().map(|accepted| if accepted {
+                                        view! { <span class="recording-consent" style="margin-right: 5px; color: #28a745;" title="Consented to recording">"REC ✓"</span> }
+                                    } else {
+                                        view! { <span class="recording-consent" style="margin-right: 5px; color: #dc3545;" title="Declined recording">"REC ✗"</span> }
+                                    })}
+                                    {move || report().map(|r| {
+                                        let color = quality_color(r.level);
+                                        let title = format!("{} kbps · {:.1}% loss · {:.0} ms jitter · {:.0} ms RTT", r.bitrate_kbps, r.packet_loss, r.jitter_ms, r.rtt_ms);
+                                        view! { <span class="quality-report" style=format!("margin-right: 5px; color: {};", color) title=title>"●"</span> }
+                                    })}
                                     {if p.is_sharing_screen {
                                         view! { <span style="margin-right: 5px;">"🖥️"</span> }.into_view()
                                     } else {
@@ -114,6 +134,23 @@ pub fn ParticipantsList(
                                         let id_check = id_kick.clone();
                                         move || is_host.get() && my_id.get() != Some(id_check.clone())
                                     }>
+                                        {
+                                            let id_mute = id_kick.clone();
+                                            let is_muted = {
+                                                let id_mute = id_mute.clone();
+                                                move || chat_muted.with(|muted| muted.contains(&id_mute))
+                                            };
+                                            let is_muted_label = is_muted.clone();
+                                            view! {
+                                                <button
+                                                    on:click=move |_| on_chat_mute.call((id_mute.clone(), !is_muted()))
+                                                    style="background: none; border: 1px solid #ccc; color: #6c757d; padding: 2px 5px; cursor: pointer; border-radius: 3px; font-size: 0.8em; margin-right: 3px;"
+                                                    title="Stop or allow this participant's chat messages"
+                                                >
+                                                    {move || if is_muted_label() { "Unmute chat" } else { "Mute chat" }}
+                                                </button>
+                                            }
+                                        }
                                         {
                                             let id_action = id_kick.clone();
                                             view! {
@@ -150,6 +187,7 @@ mod tests {
             is_hand_raised: false,
             is_sharing_screen: false,
             speaking_time: 0,
+            video_quality: shared::VideoQuality::default(),
         };
         let p2 = Participant {
             id: "2".to_string(),
@@ -157,6 +195,7 @@ mod tests {
             is_hand_raised: true, // Hand raised should be first
             is_sharing_screen: false,
             speaking_time: 0,
+            video_quality: shared::VideoQuality::default(),
         };
         let p3 = Participant {
             id: "3".to_string(),
@@ -164,6 +203,7 @@ mod tests {
             is_hand_raised: false,
             is_sharing_screen: false,
             speaking_time: 0,
+            video_quality: shared::VideoQuality::default(),
         };
 
         let unsorted = vec![p1.clone(), p2.clone(), p3.clone()];
@@ -0,0 +1,291 @@
+// Mesh of WebRTC peer connections, one per remote participant, negotiated over the
+// meeting WebSocket. Each pair negotiates once: tracks are swapped with replaceTrack
+// afterwards, so camera and quality changes never need a new offer.
+use leptos::*;
+use shared::{ClientMessage, RtcSignal};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    MediaStream, MediaStreamTrack, RtcConfiguration, RtcIceCandidateInit, RtcIceServer, RtcPeerConnection,
+    RtcPeerConnectionIceEvent, RtcRtpTransceiver, RtcRtpTransceiverDirection, RtcRtpTransceiverInit, RtcSdpType,
+    RtcSessionDescriptionInit, RtcTrackEvent, WebSocket,
+};
+
+const STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+const TRACK_KINDS: [&str; 2] = ["audio", "video"];
+
+/// Whether we make the offer to `peer_id`; the participant with the smaller ID does,
+/// so each pair negotiates exactly once.
+pub fn is_offerer(my_id: &str, peer_id: &str) -> bool {
+    my_id < peer_id
+}
+
+// Connections to open (those we offer to) and to close, given who is in the meeting
+fn plan_sync(my_id: &str, connected: &[String], present: &[String]) -> (Vec<String>, Vec<String>) {
+    let to_open = present.iter().filter(|id| *id != my_id && is_offerer(my_id, id) && !connected.contains(id)).cloned().collect();
+    let to_close = connected.iter().filter(|id| !present.contains(id)).cloned().collect();
+    (to_open, to_close)
+}
+
+#[derive(Clone, Copy)]
+pub struct PeerMesh {
+    // Remote participant id -> peer connection, also polled by the stats collector and the recorder
+    pub connections: StoredValue<HashMap<String, RtcPeerConnection>>,
+    // Fires whenever a connection finishes negotiating, so sender limits can be applied
+    pub negotiated: Trigger,
+    // Candidates that arrived before the description they belong to
+    pending_candidates: StoredValue<HashMap<String, Vec<RtcIceCandidateInit>>>,
+    set_remote_streams: WriteSignal<HashMap<String, MediaStream>>,
+    local_stream: ReadSignal<Option<MediaStream>>,
+    ws: ReadSignal<Option<WebSocket>>,
+}
+
+impl PeerMesh {
+    pub fn new(
+        ws: ReadSignal<Option<WebSocket>>,
+        local_stream: ReadSignal<Option<MediaStream>>,
+        set_remote_streams: WriteSignal<HashMap<String, MediaStream>>,
+    ) -> Self {
+        Self {
+            connections: store_value(HashMap::new()),
+            negotiated: create_trigger(),
+            pending_candidates: store_value(HashMap::new()),
+            set_remote_streams,
+            local_stream,
+            ws,
+        }
+    }
+
+    fn send(&self, to: &str, signal: RtcSignal) {
+        if let Some(socket) = self.ws.get_untracked() {
+            let msg = ClientMessage::RtcSignal { to: to.to_string(), signal };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    }
+
+    fn create(&self, peer_id: &str) -> Result<RtcPeerConnection, JsValue> {
+        let server = RtcIceServer::new();
+        server.set_urls(&JsValue::from_str(STUN_SERVER));
+        let config = RtcConfiguration::new();
+        config.set_ice_servers(&js_sys::Array::of1(&server));
+        let pc = RtcPeerConnection::new_with_configuration(&config)?;
+
+        let mesh = *self;
+        let to = peer_id.to_string();
+        let on_ice = Closure::<dyn FnMut(_)>::new(move |ev: RtcPeerConnectionIceEvent| {
+            if let Some(candidate) = ev.candidate() {
+                mesh.send(&to, RtcSignal::IceCandidate {
+                    candidate: candidate.candidate(),
+                    sdp_mid: candidate.sdp_mid(),
+                    sdp_m_line_index: candidate.sdp_m_line_index(),
+                });
+            }
+        });
+        pc.set_onicecandidate(Some(on_ice.as_ref().unchecked_ref()));
+        on_ice.forget();
+
+        let from = peer_id.to_string();
+        let set_remote_streams = self.set_remote_streams;
+        let on_track = Closure::<dyn FnMut(_)>::new(move |ev: RtcTrackEvent| {
+            let stream = ev.streams().get(0).dyn_into::<MediaStream>().ok().or_else(|| {
+                let stream = MediaStream::new().ok()?;
+                stream.add_track(&ev.track());
+                Some(stream)
+            });
+            if let Some(stream) = stream {
+                set_remote_streams.update(|streams| { streams.insert(from.clone(), stream); });
+            }
+        });
+        pc.set_ontrack(Some(on_track.as_ref().unchecked_ref()));
+        on_track.forget();
+
+        self.connections.update_value(|c| {
+            if let Some(old) = c.insert(peer_id.to_string(), pc.clone()) {
+                old.close();
+            }
+        });
+        Ok(pc)
+    }
+
+    /// Opens connections to newly present participants and closes those to participants who left.
+    pub fn sync(&self, my_id: &str, present: &[String]) {
+        let connected: Vec<String> = self.connections.with_value(|c| c.keys().cloned().collect());
+        let (to_open, to_close) = plan_sync(my_id, &connected, present);
+        for peer_id in to_close {
+            self.remove(&peer_id);
+        }
+        for peer_id in to_open {
+            // Registered right away, so a second sync before the offer is out doesn't open another
+            let pc = match self.create(&peer_id) {
+                Ok(pc) => pc,
+                Err(e) => {
+                    web_sys::console::error_1(&e);
+                    continue;
+                },
+            };
+            let mesh = *self;
+            spawn_local(async move {
+                if let Err(e) = mesh.offer(&peer_id, &pc).await {
+                    web_sys::console::error_1(&e);
+                }
+            });
+        }
+    }
+
+    async fn offer(&self, peer_id: &str, pc: &RtcPeerConnection) -> Result<(), JsValue> {
+        for kind in TRACK_KINDS {
+            let init = RtcRtpTransceiverInit::new();
+            init.set_direction(RtcRtpTransceiverDirection::Sendrecv);
+            pc.add_transceiver_with_str_and_init(kind, &init);
+        }
+        attach_tracks(pc, self.local_stream.get_untracked().as_ref());
+        let sdp = describe(pc, JsFuture::from(pc.create_offer()).await?, RtcSdpType::Offer).await?;
+        self.send(peer_id, RtcSignal::Offer { sdp });
+        Ok(())
+    }
+
+    pub fn handle_signal(&self, from: String, signal: RtcSignal) {
+        let mesh = *self;
+        spawn_local(async move {
+            if let Err(e) = mesh.apply_signal(&from, signal).await {
+                web_sys::console::error_1(&e);
+            }
+        });
+    }
+
+    async fn apply_signal(&self, from: &str, signal: RtcSignal) -> Result<(), JsValue> {
+        match signal {
+            RtcSignal::Offer { sdp } => {
+                // We never renegotiate, so an offer always starts a fresh session
+                let pc = self.create(from)?;
+                set_remote(&pc, RtcSdpType::Offer, &sdp).await?;
+                let transceivers = pc.get_transceivers();
+                for i in 0..transceivers.length() {
+                    transceivers.get(i).unchecked_into::<RtcRtpTransceiver>().set_direction(RtcRtpTransceiverDirection::Sendrecv);
+                }
+                attach_tracks(&pc, self.local_stream.get_untracked().as_ref());
+                self.add_pending_candidates(from, &pc).await;
+                let sdp = describe(&pc, JsFuture::from(pc.create_answer()).await?, RtcSdpType::Answer).await?;
+                self.send(from, RtcSignal::Answer { sdp });
+                self.negotiated.notify();
+            },
+            RtcSignal::Answer { sdp } => {
+                let Some(pc) = self.connections.with_value(|c| c.get(from).cloned()) else { return Ok(()) };
+                set_remote(&pc, RtcSdpType::Answer, &sdp).await?;
+                self.add_pending_candidates(from, &pc).await;
+                self.negotiated.notify();
+            },
+            RtcSignal::IceCandidate { candidate, sdp_mid, sdp_m_line_index } => {
+                let init = RtcIceCandidateInit::new(&candidate);
+                init.set_sdp_mid(sdp_mid.as_deref());
+                init.set_sdp_m_line_index(sdp_m_line_index);
+                let pc = self.connections.with_value(|c| c.get(from).cloned());
+                match pc.filter(|pc| pc.remote_description().is_some()) {
+                    Some(pc) => { JsFuture::from(pc.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init))).await?; },
+                    None => self.pending_candidates.update_value(|p| p.entry(from.to_string()).or_default().push(init)),
+                }
+            },
+        }
+        Ok(())
+    }
+
+    async fn add_pending_candidates(&self, peer_id: &str, pc: &RtcPeerConnection) {
+        let pending = self.pending_candidates.try_update_value(|p| p.remove(peer_id)).flatten().unwrap_or_default();
+        for init in pending {
+            if let Err(e) = JsFuture::from(pc.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init))).await {
+                web_sys::console::error_1(&e);
+            }
+        }
+    }
+
+    /// Sends the tracks of our current camera stream, or nothing, on every connection.
+    pub fn set_local_stream(&self, stream: Option<&MediaStream>) {
+        self.connections.with_value(|c| {
+            for pc in c.values() {
+                attach_tracks(pc, stream);
+            }
+        });
+    }
+
+    pub fn remove(&self, peer_id: &str) {
+        self.connections.update_value(|c| {
+            if let Some(pc) = c.remove(peer_id) {
+                pc.close();
+            }
+        });
+        self.pending_candidates.update_value(|p| { p.remove(peer_id); });
+        self.set_remote_streams.update(|streams| { streams.remove(peer_id); });
+    }
+
+    pub fn close_all(&self) {
+        self.connections.update_value(|c| {
+            for (_, pc) in c.drain() {
+                pc.close();
+            }
+        });
+        self.pending_candidates.update_value(HashMap::clear);
+        self.set_remote_streams.set(HashMap::new());
+    }
+}
+
+// Sets our side of the negotiation and returns the SDP to send to the other side
+async fn describe(pc: &RtcPeerConnection, description: JsValue, kind: RtcSdpType) -> Result<String, JsValue> {
+    let sdp = js_sys::Reflect::get(&description, &"sdp".into())?.as_string().unwrap_or_default();
+    let init = RtcSessionDescriptionInit::new(kind);
+    init.set_sdp(&sdp);
+    JsFuture::from(pc.set_local_description(&init)).await?;
+    Ok(sdp)
+}
+
+async fn set_remote(pc: &RtcPeerConnection, kind: RtcSdpType, sdp: &str) -> Result<(), JsValue> {
+    let init = RtcSessionDescriptionInit::new(kind);
+    init.set_sdp(sdp);
+    JsFuture::from(pc.set_remote_description(&init)).await?;
+    Ok(())
+}
+
+// Puts the first track of each kind from `stream` on the matching transceiver
+fn attach_tracks(pc: &RtcPeerConnection, stream: Option<&MediaStream>) {
+    let transceivers = pc.get_transceivers();
+    for i in 0..transceivers.length() {
+        let transceiver: RtcRtpTransceiver = transceivers.get(i).unchecked_into();
+        let kind = transceiver.receiver().track().kind();
+        let track = stream.and_then(|s| {
+            let tracks = if kind == "audio" { s.get_audio_tracks() } else { s.get_video_tracks() };
+            tracks.get(0).dyn_into::<MediaStreamTrack>().ok()
+        });
+        // Rejected only when the connection is closing, which needs no handling
+        let _ = transceiver.sender().replace_track(track.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exactly_one_side_offers() {
+        assert!(is_offerer("alice", "bob"));
+        assert!(!is_offerer("bob", "alice"));
+    }
+
+    #[test]
+    fn test_plan_sync() {
+        // bob offers to carol; alice will offer to bob, so bob waits; dave has left
+        let (to_open, to_close) = plan_sync("bob", &ids(&["dave"]), &ids(&["alice", "bob", "carol"]));
+        assert_eq!(to_open, ids(&["carol"]));
+        assert_eq!(to_close, ids(&["dave"]));
+
+        // Connections that already exist are kept, whichever side opened them
+        let (to_open, to_close) = plan_sync("bob", &ids(&["alice", "carol"]), &ids(&["alice", "bob", "carol"]));
+        assert!(to_open.is_empty() && to_close.is_empty());
+    }
+}
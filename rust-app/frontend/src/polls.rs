@@ -1,5 +1,67 @@
 use leptos::*;
-use shared::{Poll, PollOption};
+use shared::{Participant, Poll, PollOption, QuizScore, MAX_POLL_OPTIONS};
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::JsValue;
+use web_sys::{Blob, BlobPropertyBag};
+use crate::recording::trigger_download;
+use crate::utils::room_id_from_path;
+
+// (format, label) pairs offered for the poll and quiz results downloads
+const RESULTS_EXPORT_FORMATS: [(&str, &str); 2] = [("csv", "CSV"), ("json", "JSON")];
+
+// Time limits offered when creating a poll, in seconds; 0 = until closed by hand
+const POLL_TIME_LIMITS: [(u32, &str); 6] = [
+    (0, "No time limit"),
+    (30, "30 seconds"),
+    (60, "1 minute"),
+    (120, "2 minutes"),
+    (300, "5 minutes"),
+    (900, "15 minutes"),
+];
+
+// Share of voters who picked an option; with multiple choice the shares add up to more than 100
+fn option_percent(option: &PollOption, voter_count: u32) -> f64 {
+    if voter_count > 0 {
+        (option.votes as f64 / voter_count as f64) * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Names of the voters who picked `option_id`, for named polls.
+fn option_voters(poll: &Poll, option_id: u32, participants: &[Participant]) -> Vec<String> {
+    poll.ballots
+        .iter()
+        .filter(|(_, chosen)| chosen.contains(&option_id))
+        .map(|(voter, _)| participants.iter().find(|p| &p.id == voter).map(|p| p.name.clone()).unwrap_or_else(|| "Unknown".to_string()))
+        .collect()
+}
+
+/// Fetches `/api/rooms/<room>/<kind>/export` and saves it as `<file_stem>.<format>`.
+async fn download_results(token: String, kind: &str, file_stem: &str, format: &str) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
+    let origin = window.location().origin()?;
+    let room_id = room_id_from_path(&window.location().pathname()?).unwrap_or_default();
+    let response = reqwest::Client::new()
+        .get(format!("{}/api/rooms/{}/{}/export?format={}", origin, urlencoding::encode(&room_id), kind, format))
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("text/plain")
+        .to_string();
+    let text = response.text().await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let options = BlobPropertyBag::new();
+    options.set_type(&content_type);
+    let blob = Blob::new_with_str_sequence_and_options(&js_sys::Array::of1(&JsValue::from_str(&text)), &options)?;
+    trigger_download(&blob, &format!("{}.{}", file_stem, format))
+}
 
 #[component]
 pub fn PollsDialog(
@@ -7,38 +69,21 @@ pub fn PollsDialog(
     polls: ReadSignal<Vec<Poll>>,
     on_close: Callback<()>,
     on_create_poll: Callback<Poll>,
-    on_vote: Callback<(String, u32)>, // poll_id, option_id
+    on_vote: Callback<(String, Vec<u32>)>, // poll_id, option_ids
+    my_votes: ReadSignal<HashMap<String, Vec<u32>>>, // poll_id -> option_ids
+    is_host: Signal<bool>,
+    participants: ReadSignal<Vec<Participant>>,
+    on_close_poll: Callback<String>,
+    on_reopen_poll: Callback<String>,
+    on_delete_poll: Callback<String>,
+    vote_changes: Signal<bool>, // Voters may change their vote while a poll is open
+    on_toggle_vote_changes: Callback<()>,
+    on_reveal_answers: Callback<String>,
+    leaderboard: ReadSignal<Vec<QuizScore>>,
+    session_token: ReadSignal<Option<String>>,
 ) -> impl IntoView {
     let (active_tab, set_active_tab) = create_signal("list");
-
-    // Create Poll State
-    let (question, set_question) = create_signal("".to_string());
-    let (option1, set_option1) = create_signal("".to_string());
-    let (option2, set_option2) = create_signal("".to_string());
-
-    let create = move |_| {
-        let q = question.get();
-        let o1 = option1.get();
-        let o2 = option2.get();
-
-        if !q.is_empty() && !o1.is_empty() && !o2.is_empty() {
-            let poll = Poll {
-                id: "".to_string(), // Backend assigns ID
-                question: q,
-                options: vec![
-                    PollOption { id: 0, text: o1, votes: 0 },
-                    PollOption { id: 1, text: o2, votes: 0 },
-                ],
-                voters: std::collections::HashSet::new(),
-            };
-            on_create_poll.call(poll);
-            // Reset and switch to list
-            set_question.set("".to_string());
-            set_option1.set("".to_string());
-            set_option2.set("".to_string());
-            set_active_tab.set("list");
-        }
-    };
+    let has_quiz = move || polls.with(|list| list.iter().any(|p| p.quiz)) || leaderboard.with(|l| !l.is_empty());
 
     view! {
         <Show when=move || show.get()>
@@ -56,111 +101,486 @@ pub fn PollsDialog(
                         >
                             "Active Polls"
                         </button>
-                        <button
-                            on:click=move |_| set_active_tab.set("create")
-                            style=move || format!("padding: 10px; border: none; background: none; cursor: pointer; border-bottom: 2px solid {}", if active_tab.get() == "create" { "#007bff" } else { "transparent" })
-                        >
-                            "Create Poll"
-                        </button>
+                        <Show when=move || is_host.get()>
+                            <button
+                                on:click=move |_| set_active_tab.set("create")
+                                style=move || format!("padding: 10px; border: none; background: none; cursor: pointer; border-bottom: 2px solid {}", if active_tab.get() == "create" { "#007bff" } else { "transparent" })
+                            >
+                                "Create Poll"
+                            </button>
+                        </Show>
+                        <Show when=has_quiz>
+                            <button
+                                on:click=move |_| set_active_tab.set("leaderboard")
+                                style=move || format!("padding: 10px; border: none; background: none; cursor: pointer; border-bottom: 2px solid {}", if active_tab.get() == "leaderboard" { "#007bff" } else { "transparent" })
+                            >
+                                "Leaderboard"
+                            </button>
+                        </Show>
                     </div>
 
                     <div class="tab-content">
                         <Show when=move || active_tab.get() == "list">
+                            <Show when=move || is_host.get()>
+                                <label style="display: block; font-size: 0.85em; margin-bottom: 10px;">
+                                    <input type="checkbox" prop:checked=vote_changes on:change=move |_| on_toggle_vote_changes.call(()) />
+                                    " Allow changing votes"
+                                </label>
+                            </Show>
                             <div class="polls-list" style="max-height: 300px; overflow-y: auto;">
                                 <For
                                     each=move || polls.get()
-                                    key=|p| (p.id.clone(), p.options.iter().map(|o| o.votes).sum::<u32>())
-                                    children=move |p| {
-                                        let pid = p.id.clone();
-                                        view! {
-                                            <div class="poll-item" style="border: 1px solid #eee; padding: 10px; margin-bottom: 10px; border-radius: 4px;">
-                                                <h4>{p.question}</h4>
-                                                <ul style="list-style: none; padding: 0;">
-                                                    <For
-                                                        each=move || {
-                                                            let opts = p.options.clone();
-                                                            let total_votes: u32 = opts.iter().map(|o| o.votes).sum();
-                                                            opts.into_iter().map(move |o| (o, total_votes)).collect::<Vec<_>>()
-                                                        }
-                                                        key=|tuple| tuple.0.id
-                                                        children=move |(opt, total_votes)| {
-                                                            let pid_clone = pid.clone();
-                                                            let percent = if total_votes > 0 {
-                                                                (opt.votes as f64 / total_votes as f64) * 100.0
-                                                            } else {
-                                                                0.0
-                                                            };
-
-                                                            view! {
-                                                                <li style="margin-bottom: 10px; position: relative;">
-                                                                    <div style="display: flex; justify-content: space-between; align-items: center; position: relative; z-index: 2;">
-                                                                        <span>{opt.text}</span>
-                                                                        <div>
-                                                                            <span style="margin-right: 10px; font-weight: bold;">{opt.votes} " votes (" {format!("{:.0}", percent)} "%)"</span>
-                                                                            <button
-                                                                                on:click=move |_| on_vote.call((pid_clone.clone(), opt.id))
-                                                                                style="padding: 4px 8px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer;"
-                                                                            >
-                                                                                "Vote"
-                                                                            </button>
-                                                                        </div>
-                                                                    </div>
-                                                                    <div
-                                                                        class="poll-bar"
-                                                                        style=format!("position: absolute; top: 0; left: 0; height: 100%; width: {}%; background-color: rgba(0, 123, 255, 0.2); border-radius: 4px; z-index: 1; transition: width 0.3s ease;", percent)
-                                                                    ></div>
-                                                                </li>
-                                                            }
-                                                        }
-                                                    />
-                                                </ul>
-                                            </div>
-                                        }
+                                    // Changes with every vote and whenever moderators receive hidden counts
+                                    key=|p| (p.id.clone(), p.voter_count, p.closed, p.closes_at, p.options.iter().map(|o| o.votes).sum::<u32>())
+                                    children=move |p| view! {
+                                        <PollCard
+                                            poll=p
+                                            on_vote=on_vote
+                                            my_votes=my_votes
+                                            is_host=is_host
+                                            participants=participants
+                                            on_close_poll=on_close_poll
+                                            on_reopen_poll=on_reopen_poll
+                                            on_delete_poll=on_delete_poll
+                                            vote_changes=vote_changes
+                                            on_reveal_answers=on_reveal_answers
+                                        />
                                     }
                                 />
                                 <Show when=move || polls.get().is_empty()>
                                     <p style="text-align: center; color: #666;">"No active polls."</p>
                                 </Show>
                             </div>
+                            // Results are gone once the room is reset, so moderators can keep a copy
+                            <Show when=move || is_host.get() && !polls.with(Vec::is_empty)>
+                                <ResultsDownload kind="polls" file_stem="poll-results" empty_message="No polls to download" session_token=session_token />
+                            </Show>
                         </Show>
                         <Show when=move || active_tab.get() == "create">
-                            <div class="form-group" style="margin-bottom: 15px;">
-                                <label style="display: block; margin-bottom: 5px;">"Question"</label>
-                                <input
-                                    type="text"
-                                    prop:value=question
-                                    on:input=move |ev| set_question.set(event_target_value(&ev))
-                                    style="width: 100%; padding: 8px; border: 1px solid #ccc; border-radius: 4px;"
-                                />
-                            </div>
-                            <div class="form-group" style="margin-bottom: 15px;">
-                                <label style="display: block; margin-bottom: 5px;">"Option 1"</label>
-                                <input
-                                    type="text"
-                                    prop:value=option1
-                                    on:input=move |ev| set_option1.set(event_target_value(&ev))
-                                    style="width: 100%; padding: 8px; border: 1px solid #ccc; border-radius: 4px;"
-                                />
-                            </div>
-                            <div class="form-group" style="margin-bottom: 15px;">
-                                <label style="display: block; margin-bottom: 5px;">"Option 2"</label>
-                                <input
-                                    type="text"
-                                    prop:value=option2
-                                    on:input=move |ev| set_option2.set(event_target_value(&ev))
-                                    style="width: 100%; padding: 8px; border: 1px solid #ccc; border-radius: 4px;"
-                                />
+                            <CreatePollForm
+                                on_create_poll=Callback::new(move |poll: Poll| {
+                                    on_create_poll.call(poll);
+                                    set_active_tab.set("list");
+                                })
+                            />
+                        </Show>
+                        <Show when=move || active_tab.get() == "leaderboard">
+                            <Leaderboard leaderboard=leaderboard is_host=is_host session_token=session_token />
+                        </Show>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
+
+#[component]
+fn PollCard(
+    poll: Poll,
+    on_vote: Callback<(String, Vec<u32>)>,
+    my_votes: ReadSignal<HashMap<String, Vec<u32>>>,
+    is_host: Signal<bool>,
+    participants: ReadSignal<Vec<Participant>>,
+    on_close_poll: Callback<String>,
+    on_reopen_poll: Callback<String>,
+    on_delete_poll: Callback<String>,
+    vote_changes: Signal<bool>,
+    on_reveal_answers: Callback<String>,
+) -> impl IntoView {
+    let poll = store_value(poll);
+    let poll_id = poll.with_value(|p| p.id.clone());
+    let my_choice = {
+        let poll_id = poll_id.clone();
+        Signal::derive(move || my_votes.with(|votes| votes.get(&poll_id).cloned()))
+    };
+    let (closed, quiz, revealed) = poll.with_value(|p| (p.closed, p.quiz, p.revealed));
+    // Whether we can vote now: open, and either no vote yet or changes are allowed
+    let can_vote = move || !closed && (my_choice.with(Option::is_none) || vote_changes.get());
+    // Moderators get the counts even while they are hidden from everyone else
+    let shows_results = move || !poll.with_value(Poll::results_hidden) || is_host.get();
+    // Options ticked but not yet submitted, for multiple choice polls
+    let (selected, set_selected) = create_signal(HashSet::<u32>::new());
+
+    let submit = {
+        let poll_id = poll_id.clone();
+        move |_| {
+            let chosen: Vec<u32> = selected.get().into_iter().collect();
+            if !chosen.is_empty() {
+                on_vote.call((poll_id.clone(), chosen));
+            }
+        }
+    };
+
+    let (multiple_choice, anonymous, hide_results, voter_count) =
+        poll.with_value(|p| (p.multiple_choice, p.anonymous, p.hide_results, p.voter_count));
+    // Deadline as HH:MM local time; the backend closes the poll when it passes
+    let closes_at = poll.with_value(|p| p.closes_at).filter(|_| !closed).map(|ms| {
+        let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(ms as f64));
+        format!("{:02}:{:02}", date.get_hours(), date.get_minutes())
+    });
+
+    view! {
+        <div class="poll-item" style="border: 1px solid #eee; padding: 10px; margin-bottom: 10px; border-radius: 4px;">
+            <h4>
+                {poll.with_value(|p| p.question.clone())}
+                {quiz.then(|| view! {
+                    <span style="margin-left: 6px; padding: 1px 6px; font-size: 0.7em; background: #6f42c1; color: white; border-radius: 8px;">"Quiz"</span>
+                })}
+                {closed.then(|| view! {
+                    <span style="margin-left: 6px; padding: 1px 6px; font-size: 0.7em; background: #6c757d; color: white; border-radius: 8px;">"Closed"</span>
+                })}
+            </h4>
+            <div class="poll-settings" style="display: flex; gap: 6px; flex-wrap: wrap; font-size: 0.75em; color: #666; margin-bottom: 6px;">
+                <span>{if multiple_choice { "Multiple choice" } else { "Single choice" }}</span>
+                <span>{if anonymous { "· Anonymous" } else { "· Named" }}</span>
+                {hide_results.then(|| view! { <span>"· Results shown when closed"</span> })}
+                <span>{format!("· {} {}", voter_count, if voter_count == 1 { "voter" } else { "voters" })}</span>
+                {closes_at.map(|time| view! { <span>{format!("· Closes at {}", time)}</span> })}
+            </div>
+            <ul style="list-style: none; padding: 0;">
+                {poll.with_value(|p| p.options.clone()).into_iter().map(|opt| {
+                    let option_id = opt.id;
+                    let percent = option_percent(&opt, voter_count);
+                    let voters = poll.with_value(|p| if p.anonymous { Vec::new() } else { option_voters(p, option_id, &participants.get_untracked()) });
+                    let vote_id = poll_id.clone();
+                    let picked = move || my_choice.with(|c| c.as_ref().is_some_and(|c| c.contains(&option_id)));
+                    let show_vote_button = move || !multiple_choice && can_vote() && !picked();
+                    view! {
+                        <li style="margin-bottom: 10px; position: relative;">
+                            <div style="display: flex; justify-content: space-between; align-items: center; position: relative; z-index: 2;">
+                                <span>
+                                    {multiple_choice.then(|| {
+                                        view! {
+                                            <input
+                                                type="checkbox"
+                                                prop:checked=move || selected.with(|s| s.contains(&option_id))
+                                                disabled=move || !can_vote()
+                                                on:change=move |ev| {
+                                                    let checked = event_target_checked(&ev);
+                                                    set_selected.update(|s| {
+                                                        if checked { s.insert(option_id); } else { s.remove(&option_id); }
+                                                    });
+                                                }
+                                                style="margin-right: 6px;"
+                                            />
+                                        }
+                                    })}
+                                    {opt.text}
+                                    {move || picked().then(|| view! { <strong style="color: #28a745;">" ✓"</strong> })}
+                                    // Moderators see the answers all along, everyone else once revealed
+                                    {opt.correct.then(|| view! { <small style="margin-left: 6px; color: #28a745;">"(correct answer)"</small> })}
+                                </span>
+                                <div>
+                                    <Show when=shows_results>
+                                        <span style="margin-right: 10px; font-weight: bold;">{opt.votes} " votes (" {format!("{:.0}", percent)} "%)"</span>
+                                    </Show>
+                                    <Show when=show_vote_button>
+                                        <button
+                                            on:click={let vote_id = vote_id.clone(); move |_| on_vote.call((vote_id.clone(), vec![option_id]))}
+                                            style="padding: 4px 8px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer;"
+                                        >
+                                            "Vote"
+                                        </button>
+                                    </Show>
+                                </div>
                             </div>
+                            <Show when=shows_results>
+                                <div
+                                    class="poll-bar"
+                                    style=format!("position: absolute; top: 0; left: 0; height: 100%; width: {}%; background-color: rgba(0, 123, 255, 0.2); border-radius: 4px; z-index: 1; transition: width 0.3s ease;", percent.min(100.0))
+                                ></div>
+                            </Show>
+                            {(!voters.is_empty()).then(|| view! {
+                                <small style="display: block; color: #666; position: relative; z-index: 2;">{voters.join(", ")}</small>
+                            })}
+                        </li>
+                    }
+                }).collect_view()}
+            </ul>
+            <Show when=move || multiple_choice && can_vote()>
+                <button
+                    on:click=submit.clone()
+                    disabled=move || selected.with(HashSet::is_empty)
+                    style="padding: 4px 8px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer;"
+                >
+                    {move || if my_choice.with(Option::is_some) { "Change vote" } else { "Submit vote" }}
+                </button>
+            </Show>
+            <Show when=move || !shows_results()>
+                <p style="color: #666; font-size: 0.85em;">"Results will be shown when the poll closes."</p>
+            </Show>
+            {revealed.then_some(move || my_choice.get().map(|chosen| if poll.with_value(|p| p.is_correct(&chosen)) {
+                view! { <p style="color: #28a745; font-weight: bold;">"Correct!"</p> }
+            } else {
+                view! { <p style="color: #dc3545; font-weight: bold;">"Not quite."</p> }
+            }))}
+            <Show when=move || is_host.get()>
+                <div class="poll-actions" style="display: flex; gap: 6px; margin-top: 6px;">
+                    {
+                        let id = poll_id.clone();
+                        if !closed {
+                            Some(view! { <button on:click=move |_| on_close_poll.call(id.clone()) style="padding: 2px 8px; cursor: pointer;">"Close"</button> })
+                        } else if !revealed {
+                            Some(view! { <button on:click=move |_| on_reopen_poll.call(id.clone()) style="padding: 2px 8px; cursor: pointer;">"Reopen"</button> })
+                        } else {
+                            None
+                        }
+                    }
+                    {(quiz && !revealed).then(|| {
+                        let id = poll_id.clone();
+                        view! {
                             <button
-                                on:click=create
-                                style="padding: 10px 20px; background-color: #28a745; color: white; border: none; border-radius: 4px; cursor: pointer; width: 100%;"
+                                on:click=move |_| on_reveal_answers.call(id.clone())
+                                title="Closes the question and shows everyone the answer"
+                                style="padding: 2px 8px; cursor: pointer;"
                             >
-                                "Create Poll"
+                                "Reveal answer"
                             </button>
-                        </Show>
-                    </div>
+                        }
+                    })}
+                    <button
+                        on:click={let id = poll_id.clone(); move |_| on_delete_poll.call(id.clone())}
+                        style="padding: 2px 8px; cursor: pointer; color: #dc3545;"
+                    >
+                        "Delete"
+                    </button>
                 </div>
-            </div>
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+fn Leaderboard(
+    leaderboard: ReadSignal<Vec<QuizScore>>,
+    is_host: Signal<bool>,
+    session_token: ReadSignal<Option<String>>,
+) -> impl IntoView {
+    view! {
+        <div class="quiz-leaderboard" style="max-height: 300px; overflow-y: auto;">
+            <Show
+                when=move || !leaderboard.with(Vec::is_empty)
+                fallback=|| view! { <p style="text-align: center; color: #666;">"Scores appear once answers are revealed."</p> }
+            >
+                <table style="width: 100%; border-collapse: collapse;">
+                    <thead>
+                        <tr style="border-bottom: 1px solid #ccc;">
+                            <th style="text-align: left; padding: 4px;">"#"</th>
+                            <th style="text-align: left; padding: 4px;">"Name"</th>
+                            <th style="text-align: right; padding: 4px;">"Correct"</th>
+                            <th style="text-align: right; padding: 4px;">"Points"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || leaderboard.get().into_iter().enumerate().map(|(i, score)| view! {
+                            <tr style="border-bottom: 1px solid #eee;">
+                                <td style="padding: 4px;">{i + 1}</td>
+                                <td style="padding: 4px;">{score.name}</td>
+                                <td style="padding: 4px; text-align: right;">{format!("{}/{}", score.correct, score.answered)}</td>
+                                <td style="padding: 4px; text-align: right; font-weight: bold;">{score.points}</td>
+                            </tr>
+                        }).collect_view()}
+                    </tbody>
+                </table>
+            </Show>
+        </div>
+        <Show when=move || is_host.get()>
+            <ResultsDownload kind="quiz" file_stem="quiz-results" empty_message="No quiz answers revealed yet" session_token=session_token />
         </Show>
     }
 }
+
+// Format picker and download button for a moderator-only results export
+#[component]
+fn ResultsDownload(
+    kind: &'static str,
+    file_stem: &'static str,
+    empty_message: &'static str, // Shown when the server has nothing to export
+    session_token: ReadSignal<Option<String>>,
+) -> impl IntoView {
+    let (export_format, set_export_format) = create_signal(RESULTS_EXPORT_FORMATS[0].0);
+    let download = move |_| {
+        let Some(token) = session_token.get_untracked() else { return };
+        let format = export_format.get_untracked();
+        spawn_local(async move {
+            if let Err(e) = download_results(token, kind, file_stem, format).await {
+                web_sys::console::error_1(&e);
+                let _ = web_sys::window().unwrap().alert_with_message(empty_message);
+            }
+        });
+    };
+
+    view! {
+        <div style="display: flex; gap: 5px; margin-top: 10px;">
+            <select
+                title="Download format"
+                on:change=move |ev| {
+                    let val = event_target_value(&ev);
+                    if let Some((format, _)) = RESULTS_EXPORT_FORMATS.into_iter().find(|(f, _)| *f == val) {
+                        set_export_format.set(format);
+                    }
+                }
+            >
+                {RESULTS_EXPORT_FORMATS.into_iter().map(|(format, label)| view! { <option value=format>{label}</option> }).collect_view()}
+            </select>
+            <button
+                on:click=download
+                style="flex: 1; padding: 6px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer;"
+            >
+                "Download results"
+            </button>
+        </div>
+    }
+}
+
+#[component]
+fn CreatePollForm(on_create_poll: Callback<Poll>) -> impl IntoView {
+    let (question, set_question) = create_signal("".to_string());
+    // Each option input keeps its own signal, so typing doesn't re-render the list
+    let next_key = store_value(2u32);
+    // (key, text, marked correct)
+    let new_option = |key: u32| (key, create_rw_signal(String::new()), create_rw_signal(false));
+    let options = create_rw_signal(vec![new_option(0), new_option(1)]);
+    let (quiz, set_quiz) = create_signal(false);
+    let (multiple_choice, set_multiple_choice) = create_signal(false);
+    let (anonymous, set_anonymous) = create_signal(false);
+    let (hide_results, set_hide_results) = create_signal(false);
+    let (time_limit, set_time_limit) = create_signal(0u32);
+    let (error, set_error) = create_signal(None::<&'static str>);
+
+    let add_option = move |_| {
+        if options.with(Vec::len) < MAX_POLL_OPTIONS {
+            let key = next_key.get_value();
+            next_key.set_value(key + 1);
+            options.update(|list| list.push(new_option(key)));
+        }
+    };
+
+    let create = move |_| {
+        let q = question.get().trim().to_string();
+        let texts: Vec<(String, bool)> = options
+            .get()
+            .into_iter()
+            .map(|(_, text, correct)| (text.get().trim().to_string(), correct.get()))
+            .filter(|(t, _)| !t.is_empty())
+            .collect();
+
+        if q.is_empty() || texts.len() < 2 {
+            set_error.set(Some("Enter a question and at least two options."));
+            return;
+        }
+        if quiz.get() && !texts.iter().any(|(_, correct)| *correct) {
+            set_error.set(Some("Mark the correct answer."));
+            return;
+        }
+        let poll = Poll {
+            id: "".to_string(), // Backend assigns ID
+            question: q,
+            options: texts.into_iter().enumerate().map(|(i, (text, correct))| PollOption { id: i as u32, text, votes: 0, correct }).collect(),
+            quiz: quiz.get(),
+            multiple_choice: multiple_choice.get(),
+            anonymous: anonymous.get(),
+            hide_results: hide_results.get(),
+            time_limit_secs: Some(time_limit.get()).filter(|secs| *secs > 0),
+            ..Default::default()
+        };
+        on_create_poll.call(poll);
+        // Reset for the next poll
+        set_error.set(None);
+        set_question.set("".to_string());
+        options.update(|list| {
+            list.truncate(2);
+            for (_, text, correct) in list.iter() {
+                text.set(String::new());
+                correct.set(false);
+            }
+        });
+    };
+
+    view! {
+        <div class="form-group" style="margin-bottom: 15px;">
+            <label style="display: block; margin-bottom: 5px;">"Question"</label>
+            <input
+                type="text"
+                prop:value=question
+                on:input=move |ev| set_question.set(event_target_value(&ev))
+                style="width: 100%; padding: 8px; border: 1px solid #ccc; border-radius: 4px;"
+            />
+        </div>
+        <div class="form-group" style="margin-bottom: 15px;">
+            <label style="display: block; margin-bottom: 5px;">"Options"</label>
+            <For
+                each=move || { options.get().into_iter().enumerate().collect::<Vec<_>>() }
+                key=|(i, (key, _, _))| (*i, *key)
+                children=move |(i, (key, text, correct))| view! {
+                    <div style="display: flex; gap: 5px; margin-bottom: 5px;">
+                        <Show when=move || quiz.get()>
+                            <input
+                                type="checkbox"
+                                title="Correct answer"
+                                prop:checked=correct
+                                on:change=move |ev| correct.set(event_target_checked(&ev))
+                            />
+                        </Show>
+                        <input
+                            type="text"
+                            placeholder=format!("Option {}", i + 1)
+                            prop:value=text
+                            on:input=move |ev| text.set(event_target_value(&ev))
+                            style="flex: 1; padding: 8px; border: 1px solid #ccc; border-radius: 4px;"
+                        />
+                        <Show when=move || { options.with(Vec::len) > 2 }>
+                            <button
+                                on:click=move |_| options.update(|list| list.retain(|(k, _, _)| *k != key))
+                                title="Remove option"
+                                style="background: none; border: none; cursor: pointer; color: #dc3545;"
+                            >
+                                "✕"
+                            </button>
+                        </Show>
+                    </div>
+                }
+            />
+            <button
+                on:click=add_option
+                disabled=move || options.with(Vec::len) >= MAX_POLL_OPTIONS
+                style="background: none; border: none; color: #007bff; cursor: pointer; padding: 0;"
+            >
+                "+ Add option"
+            </button>
+        </div>
+        <div class="form-group" style="display: flex; flex-direction: column; gap: 5px; margin-bottom: 15px;">
+            <label>
+                <input type="checkbox" prop:checked=quiz on:change=move |ev| set_quiz.set(event_target_checked(&ev)) />
+                " Quiz (tick the correct answers)"
+            </label>
+            <label>
+                <input type="checkbox" prop:checked=multiple_choice on:change=move |ev| set_multiple_choice.set(event_target_checked(&ev)) />
+                " Allow multiple choices"
+            </label>
+            <label>
+                <input type="checkbox" prop:checked=anonymous on:change=move |ev| set_anonymous.set(event_target_checked(&ev)) />
+                " Anonymous (nobody sees who voted)"
+            </label>
+            <label>
+                <input type="checkbox" prop:checked=hide_results on:change=move |ev| set_hide_results.set(event_target_checked(&ev)) />
+                " Hide results until the poll closes"
+            </label>
+            <label>
+                "Time limit "
+                <select on:change=move |ev| set_time_limit.set(event_target_value(&ev).parse().unwrap_or(0))>
+                    {POLL_TIME_LIMITS.iter().map(|(secs, label)| view! {
+                        <option value=secs.to_string() selected=move || time_limit.get() == *secs>{*label}</option>
+                    }).collect_view()}
+                </select>
+            </label>
+        </div>
+        {move || error.get().map(|e| view! { <p style="color: #dc3545; font-size: 0.85em;">{e}</p> })}
+        <button
+            on:click=create
+            style="padding: 10px 20px; background-color: #28a745; color: white; border: none; border-radius: 4px; cursor: pointer; width: 100%;"
+        >
+            "Create Poll"
+        </button>
+    }
+}
@@ -0,0 +1,159 @@
+use leptos::*;
+use shared::{sort_questions, Question, MAX_QUESTION_CHARS};
+
+// Changes whenever anything shown for the question does, including who upvoted it
+fn item_key(question: &Question) -> (String, bool, bool, Vec<String>) {
+    let mut upvoters: Vec<String> = question.upvoters.iter().cloned().collect();
+    upvoters.sort();
+    (question.id.clone(), question.answered, question.pinned, upvoters)
+}
+
+#[component]
+pub fn QnaPanel(
+    show: ReadSignal<bool>,
+    questions: ReadSignal<Vec<Question>>,
+    my_id: ReadSignal<Option<String>>,
+    is_host: Signal<bool>,
+    on_close: Callback<()>,
+    on_ask: Callback<(String, bool)>, // text, anonymous
+    on_upvote: Callback<String>,
+    on_set_answered: Callback<(String, bool)>,
+    on_set_pinned: Callback<(String, bool)>,
+    on_dismiss: Callback<String>,
+) -> impl IntoView {
+    let (text, set_text) = create_signal(String::new());
+    let (anonymous, set_anonymous) = create_signal(false);
+    let sorted = move || {
+        let mut list = questions.get();
+        sort_questions(&mut list);
+        list
+    };
+
+    let ask = move |_| {
+        let question = text.get().trim().to_string();
+        if !question.is_empty() {
+            on_ask.call((question, anonymous.get()));
+            set_text.set(String::new());
+        }
+    };
+
+    view! {
+        <Show when=move || show.get()>
+            <div class="modal-overlay" style="position: fixed; top: 0; left: 0; width: 100%; height: 100%; background: rgba(0,0,0,0.5); display: flex; justify-content: center; align-items: center; z-index: 1000;">
+                <div class="modal-content" style="background: white; padding: 20px; border-radius: 8px; width: 450px; max-width: 90%;">
+                    <div class="modal-header" style="display: flex; justify-content: space-between; margin-bottom: 20px;">
+                        <h3>"Q&A"</h3>
+                        <button on:click=move |_| on_close.call(()) style="background: none; border: none; font-size: 20px; cursor: pointer;">"×"</button>
+                    </div>
+
+                    <div class="qna-ask" style="margin-bottom: 15px;">
+                        <textarea
+                            placeholder="Ask a question"
+                            maxlength=MAX_QUESTION_CHARS.to_string()
+                            prop:value=text
+                            on:input=move |ev| set_text.set(event_target_value(&ev))
+                            style="width: 100%; padding: 8px; border: 1px solid #ccc; border-radius: 4px; resize: vertical;"
+                        ></textarea>
+                        <div style="display: flex; justify-content: space-between; align-items: center; margin-top: 5px;">
+                            <label style="font-size: 0.85em;">
+                                <input type="checkbox" prop:checked=anonymous on:change=move |ev| set_anonymous.set(event_target_checked(&ev)) />
+                                " Ask anonymously"
+                            </label>
+                            <button
+                                on:click=ask
+                                disabled=move || text.with(|t| t.trim().is_empty())
+                                style="padding: 6px 14px; background-color: #007bff; color: white; border: none; border-radius: 4px; cursor: pointer;"
+                            >
+                                "Ask"
+                            </button>
+                        </div>
+                    </div>
+
+                    <div class="qna-list" style="max-height: 350px; overflow-y: auto;">
+                        <For
+                            each=sorted
+                            key=item_key
+                            children=move |question| view! {
+                                <QuestionItem
+                                    question=question
+                                    my_id=my_id
+                                    is_host=is_host
+                                    on_upvote=on_upvote
+                                    on_set_answered=on_set_answered
+                                    on_set_pinned=on_set_pinned
+                                    on_dismiss=on_dismiss
+                                />
+                            }
+                        />
+                        <Show when=move || questions.with(Vec::is_empty)>
+                            <p style="text-align: center; color: #666;">"No questions yet."</p>
+                        </Show>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
+
+#[component]
+fn QuestionItem(
+    question: Question,
+    my_id: ReadSignal<Option<String>>,
+    is_host: Signal<bool>,
+    on_upvote: Callback<String>,
+    on_set_answered: Callback<(String, bool)>,
+    on_set_pinned: Callback<(String, bool)>,
+    on_dismiss: Callback<String>,
+) -> impl IntoView {
+    let id = store_value(question.id.clone());
+    let (answered, pinned, upvotes) = (question.answered, question.pinned, question.upvotes());
+    let upvoted = {
+        let upvoters = question.upvoters.clone();
+        Signal::derive(move || my_id.with(|my| my.as_ref().is_some_and(|my| upvoters.contains(my))))
+    };
+    let author = question.author_name.clone().unwrap_or_else(|| "Anonymous".to_string());
+
+    view! {
+        <div
+            class="qna-item"
+            style=format!(
+                "display: flex; gap: 10px; border: 1px solid {}; padding: 10px; margin-bottom: 8px; border-radius: 4px; opacity: {};",
+                if pinned { "#ffc107" } else { "#eee" },
+                if answered { "0.6" } else { "1" },
+            )
+        >
+            <button
+                on:click=move |_| on_upvote.call(id.get_value())
+                title=move || if upvoted.get() { "Take back your upvote" } else { "Upvote" }
+                style=move || format!(
+                    "display: flex; flex-direction: column; align-items: center; min-width: 40px; border: 1px solid #ccc; border-radius: 4px; cursor: pointer; background: {};",
+                    if upvoted.get() { "#e7f1ff" } else { "white" },
+                )
+            >
+                <span>"▲"</span>
+                <strong>{upvotes}</strong>
+            </button>
+            <div style="flex: 1;">
+                <div style="display: flex; gap: 6px; font-size: 0.75em; color: #666; margin-bottom: 4px;">
+                    <span>{author}</span>
+                    {pinned.then(|| view! { <span style="color: #b8860b;">"· Pinned"</span> })}
+                    {answered.then(|| view! { <span style="color: #28a745;">"· Answered"</span> })}
+                </div>
+                <p style="margin: 0; white-space: pre-wrap; word-break: break-word;">{question.text}</p>
+                <Show when=move || is_host.get()>
+                    <div class="qna-actions" style="display: flex; gap: 6px; margin-top: 6px; font-size: 0.85em;">
+                        <button on:click=move |_| on_set_pinned.call((id.get_value(), !pinned)) style="padding: 2px 8px; cursor: pointer;">
+                            {if pinned { "Unpin" } else { "Pin" }}
+                        </button>
+                        <button on:click=move |_| on_set_answered.call((id.get_value(), !answered)) style="padding: 2px 8px; cursor: pointer;">
+                            {if answered { "Mark unanswered" } else { "Mark answered" }}
+                        </button>
+                        <button on:click=move |_| on_dismiss.call(id.get_value()) style="padding: 2px 8px; cursor: pointer; color: #dc3545;">
+                            "Dismiss"
+                        </button>
+                    </div>
+                </Show>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,623 @@
+use leptos::*;
+use gloo_timers::callback::Interval;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use shared::RecordingManifest;
+use web_sys::{
+    AudioContext, Blob, BlobEvent, BlobPropertyBag, CanvasRenderingContext2d, HtmlAnchorElement, HtmlCanvasElement,
+    HtmlVideoElement, IdbDatabase, IdbKeyRange, IdbObjectStore, IdbOpenDbRequest, IdbRequest, IdbTransactionMode,
+    MediaRecorder, MediaRecorderOptions, MediaStream, MediaStreamAudioDestinationNode, MediaStreamAudioSourceNode,
+    MediaStreamTrack, RtcPeerConnection, RtcRtpReceiver, Url,
+};
+
+const DB_NAME: &str = "juncto-recordings";
+const DB_VERSION: u32 = 1;
+const CHUNK_STORE: &str = "chunks";
+
+const CANVAS_WIDTH: u32 = 1280;
+const CANVAS_HEIGHT: u32 = 720;
+const FRAME_RATE: u32 = 15;
+const CHUNK_INTERVAL_MS: i32 = 1000;
+// Peers that connect after recording started are picked up on this interval
+const AUDIO_RESCAN_MS: u32 = 2000;
+const UPLOAD_ATTEMPTS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RecordingLayout {
+    #[default]
+    Grid,
+    ActiveSpeaker,
+}
+
+impl RecordingLayout {
+    pub const ALL: [RecordingLayout; 2] = [RecordingLayout::Grid, RecordingLayout::ActiveSpeaker];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecordingLayout::Grid => "Grid",
+            RecordingLayout::ActiveSpeaker => "Active Speaker",
+        }
+    }
+}
+
+pub fn recording_file_name(recording_id: &str) -> String {
+    format!("meeting-recording-{}.webm", recording_id)
+}
+
+/// Splits the canvas into a near-square grid and returns one (x, y, width, height) cell per tile.
+fn grid_cells(count: usize, width: f64, height: f64) -> Vec<(f64, f64, f64, f64)> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let cols = (count as f64).sqrt().ceil() as usize;
+    let rows = count.div_ceil(cols);
+    let cell_w = width / cols as f64;
+    let cell_h = height / rows as f64;
+    (0..count)
+        .map(|i| ((i % cols) as f64 * cell_w, (i / cols) as f64 * cell_h, cell_w, cell_h))
+        .collect()
+}
+
+// Chunks are keyed by [recording id, sequence number] so a range over one id returns them in order
+fn chunk_key(recording_id: &str, seq: u32) -> JsValue {
+    js_sys::Array::of2(&recording_id.into(), &seq.into()).into()
+}
+
+fn recording_range(recording_id: &str) -> Result<JsValue, JsValue> {
+    IdbKeyRange::bound(&chunk_key(recording_id, 0), &chunk_key(recording_id, u32::MAX)).map(JsValue::from)
+}
+
+/// Resolves with the request's result once IndexedDB fires success, or rejects on error.
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let req = request.clone();
+        let on_success = Closure::once_into_js(move || {
+            let _ = resolve.call1(&JsValue::NULL, &req.result().unwrap_or(JsValue::UNDEFINED));
+        });
+        let req = request.clone();
+        let on_error = Closure::once_into_js(move || {
+            let err = req.error().ok().flatten().map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+            let _ = reject.call1(&JsValue::NULL, &err);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
+
+async fn open_db() -> Result<IdbDatabase, JsValue> {
+    let window = web_sys::window().ok_or(JsValue::from_str("No global window"))?;
+    let factory = window.indexed_db()?.ok_or(JsValue::from_str("IndexedDB is not available"))?;
+    let request: IdbOpenDbRequest = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+    let upgrade_req = request.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        if let Ok(db) = upgrade_req.result().and_then(|r| r.dyn_into::<IdbDatabase>()) {
+            if !db.object_store_names().contains(CHUNK_STORE) {
+                let _ = db.create_object_store(CHUNK_STORE);
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    request_result(&request).await?.dyn_into::<IdbDatabase>()
+}
+
+fn chunk_store(db: &IdbDatabase, mode: IdbTransactionMode) -> Result<IdbObjectStore, JsValue> {
+    db.transaction_with_str_and_mode(CHUNK_STORE, mode)?.object_store(CHUNK_STORE)
+}
+
+// The transaction is created synchronously, so a read started after this call sees the chunk
+fn store_chunk(db: &IdbDatabase, recording_id: &str, seq: u32, chunk: &Blob) -> Result<(), JsValue> {
+    let request = chunk_store(db, IdbTransactionMode::Readwrite)?.put_with_key(chunk, &chunk_key(recording_id, seq))?;
+    let on_error = Closure::once_into_js(move || {
+        web_sys::console::error_1(&"Failed to store recording chunk".into());
+    });
+    request.set_onerror(Some(on_error.unchecked_ref()));
+    Ok(())
+}
+
+async fn load_chunks(db: &IdbDatabase, recording_id: &str) -> Result<js_sys::Array, JsValue> {
+    let request = chunk_store(db, IdbTransactionMode::Readonly)?.get_all_with_key(&recording_range(recording_id)?)?;
+    request_result(&request).await?.dyn_into::<js_sys::Array>()
+}
+
+async fn delete_chunks(db: &IdbDatabase, recording_id: &str) -> Result<(), JsValue> {
+    let request = chunk_store(db, IdbTransactionMode::Readwrite)?.delete(&recording_range(recording_id)?)?;
+    request_result(&request).await.map(|_| ())
+}
+
+/// Ids of recordings whose chunks are still in IndexedDB, i.e. ones that never finished downloading.
+pub async fn pending_recordings() -> Result<Vec<String>, JsValue> {
+    let db = open_db().await?;
+    let request = chunk_store(&db, IdbTransactionMode::Readonly)?.get_all_keys()?;
+    let keys = request_result(&request).await?.dyn_into::<js_sys::Array>()?;
+
+    let mut ids = Vec::new();
+    for key in keys.iter() {
+        if let Some(id) = key.dyn_into::<js_sys::Array>().ok().and_then(|k| k.get(0).as_string()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    db.close();
+    Ok(ids)
+}
+
+pub(crate) fn trigger_download(blob: &Blob, file_name: &str) -> Result<(), JsValue> {
+    let document = web_sys::window().and_then(|w| w.document()).ok_or(JsValue::from_str("No document"))?;
+    let url = Url::create_object_url_with_blob(blob)?;
+    let anchor = document.create_element("a")?.dyn_into::<HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    Url::revoke_object_url(&url)
+}
+
+/// Assembles the stored chunks into a WebM file, downloads it and then drops the chunks.
+pub async fn download_recording(recording_id: &str) -> Result<(), JsValue> {
+    let db = open_db().await?;
+    let chunks = load_chunks(&db, recording_id).await?;
+    if chunks.length() > 0 {
+        let options = BlobPropertyBag::new();
+        options.set_type("video/webm");
+        let blob = Blob::new_with_blob_sequence_and_options(&chunks, &options)?;
+        trigger_download(&blob, &recording_file_name(recording_id))?;
+    }
+    delete_chunks(&db, recording_id).await?;
+    db.close();
+    Ok(())
+}
+
+pub async fn discard_recording(recording_id: &str) -> Result<(), JsValue> {
+    let db = open_db().await?;
+    delete_chunks(&db, recording_id).await?;
+    db.close();
+    Ok(())
+}
+
+/// Where the server-side copy of a recording goes. The token is this client's session token.
+#[derive(Clone, Debug)]
+pub struct RecordingUpload {
+    pub room_id: String,
+    pub token: String,
+}
+
+fn api_error(e: reqwest::Error) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+async fn blob_bytes(blob: &Blob) -> Result<Vec<u8>, JsValue> {
+    let buffer = JsFuture::from(blob.array_buffer()).await?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+// Streams chunks to the backend one at a time, in order, alongside the IndexedDB copy
+struct ChunkUploader {
+    client: reqwest::Client,
+    recording_url: String,
+    token: String,
+    queue: RefCell<VecDeque<Blob>>,
+    next_seq: Cell<u32>,
+    uploading: Cell<bool>,
+    stopping: Cell<bool>,
+    failed: Cell<bool>,
+    on_failed: Callback<()>,
+}
+
+impl ChunkUploader {
+    async fn start(upload: RecordingUpload, mime_type: &str, on_failed: Callback<()>) -> Result<Rc<Self>, JsValue> {
+        let origin = web_sys::window().ok_or(JsValue::from_str("No global window"))?.location().origin()?;
+        let client = reqwest::Client::new();
+        let manifest: RecordingManifest = client
+            .post(format!("{}/api/rooms/{}/recordings", origin, urlencoding::encode(&upload.room_id)))
+            .bearer_auth(&upload.token)
+            .json(&serde_json::json!({ "mime_type": mime_type }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(api_error)?
+            .json()
+            .await
+            .map_err(api_error)?;
+
+        Ok(Rc::new(ChunkUploader {
+            client,
+            recording_url: format!("{}/api/rooms/{}/recordings/{}", origin, urlencoding::encode(&manifest.room_id), manifest.id),
+            token: upload.token,
+            queue: RefCell::new(VecDeque::new()),
+            next_seq: Cell::new(0),
+            uploading: Cell::new(false),
+            stopping: Cell::new(false),
+            failed: Cell::new(false),
+            on_failed,
+        }))
+    }
+
+    fn push(self: &Rc<Self>, chunk: Blob) {
+        if self.failed.get() {
+            return;
+        }
+        self.queue.borrow_mut().push_back(chunk);
+        self.drain();
+    }
+
+    /// Marks the recording finished once every queued chunk has been sent.
+    fn finish(self: &Rc<Self>) {
+        self.stopping.set(true);
+        self.drain();
+    }
+
+    fn drain(self: &Rc<Self>) {
+        if self.uploading.replace(true) {
+            return;
+        }
+        let uploader = self.clone();
+        spawn_local(async move {
+            loop {
+                let next = uploader.queue.borrow_mut().pop_front();
+                let Some(chunk) = next else { break };
+                if let Err(e) = uploader.upload(&chunk).await {
+                    web_sys::console::error_1(&e);
+                    // Later chunks would leave a hole, so the server copy ends before this one
+                    uploader.failed.set(true);
+                    uploader.queue.borrow_mut().clear();
+                    uploader.on_failed.call(());
+                }
+            }
+            if uploader.stopping.get() {
+                let stopped = uploader.client
+                    .post(format!("{}/stop", uploader.recording_url))
+                    .bearer_auth(&uploader.token)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status());
+                if let Err(e) = stopped {
+                    web_sys::console::error_1(&api_error(e));
+                }
+            }
+            uploader.uploading.set(false);
+        });
+    }
+
+    async fn upload(&self, chunk: &Blob) -> Result<(), JsValue> {
+        let bytes = blob_bytes(chunk).await?;
+        let seq = self.next_seq.get();
+        let mut last_error = JsValue::UNDEFINED;
+        for _ in 0..UPLOAD_ATTEMPTS {
+            let result = self.client
+                .put(format!("{}/chunks/{}", self.recording_url, seq))
+                .bearer_auth(&self.token)
+                .body(bytes.clone())
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            match result {
+                Ok(_) => {
+                    self.next_seq.set(seq + 1);
+                    return Ok(());
+                },
+                Err(e) => last_error = api_error(e),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+fn draw_tile(ctx: &CanvasRenderingContext2d, card: &web_sys::Element, (x, y, w, h): (f64, f64, f64, f64)) {
+    let video = card.query_selector("video").ok().flatten().and_then(|v| v.dyn_into::<HtmlVideoElement>().ok());
+    match video {
+        // HAVE_CURRENT_DATA or better, otherwise there is no frame to copy yet
+        Some(video) if video.ready_state() >= 2 => {
+            let _ = ctx.draw_image_with_html_video_element_and_dw_and_dh(&video, x, y, w, h);
+        },
+        _ => {
+            ctx.set_fill_style_str("#222");
+            ctx.fill_rect(x, y, w, h);
+        }
+    }
+
+    let name = card.query_selector(".name-tag").ok().flatten().and_then(|n| n.text_content()).unwrap_or_default();
+    if !name.is_empty() {
+        ctx.set_fill_style_str("rgba(0,0,0,0.5)");
+        ctx.fill_rect(x + 10.0, y + h - 34.0, name.len() as f64 * 9.0 + 16.0, 24.0);
+        ctx.set_fill_style_str("white");
+        let _ = ctx.fill_text(&name, x + 18.0, y + h - 16.0);
+    }
+}
+
+// Copies the tiles currently shown in the video grid onto the recording canvas
+fn draw_layout(ctx: &CanvasRenderingContext2d, layout: RecordingLayout) {
+    ctx.set_fill_style_str("black");
+    ctx.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+
+    let Some(document) = web_sys::window().and_then(|w| w.document()) else { return };
+    let Ok(nodes) = document.query_selector_all(".video-grid .video-card") else { return };
+    let cards: Vec<web_sys::Element> = (0..nodes.length())
+        .filter_map(|i| nodes.item(i).and_then(|n| n.dyn_into::<web_sys::Element>().ok()))
+        .collect();
+
+    let cards = match layout {
+        RecordingLayout::Grid => cards,
+        RecordingLayout::ActiveSpeaker => {
+            // Fall back to the first tile while nobody is talking
+            let speaker = cards.iter().find(|c| c.class_list().contains("speaking")).or(cards.first()).cloned();
+            speaker.into_iter().collect()
+        }
+    };
+
+    ctx.set_font("16px sans-serif");
+    for (card, cell) in cards.iter().zip(grid_cells(cards.len(), CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64)) {
+        draw_tile(ctx, card, cell);
+    }
+}
+
+fn audio_tracks(stream: &MediaStream) -> Vec<MediaStreamTrack> {
+    let tracks = stream.get_audio_tracks();
+    (0..tracks.length()).filter_map(|i| tracks.get(i).dyn_into::<MediaStreamTrack>().ok()).collect()
+}
+
+// Routes every audio track we haven't seen yet into the recording mix
+struct AudioMixer {
+    context: AudioContext,
+    destination: MediaStreamAudioDestinationNode,
+    sources: Vec<MediaStreamAudioSourceNode>,
+    connected: HashSet<String>,
+}
+
+impl AudioMixer {
+    fn new() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let destination = context.create_media_stream_destination()?;
+        Ok(AudioMixer { context, destination, sources: Vec::new(), connected: HashSet::new() })
+    }
+
+    fn connect(&mut self, track: MediaStreamTrack) -> Result<(), JsValue> {
+        if !self.connected.insert(track.id()) {
+            return Ok(());
+        }
+        let stream = MediaStream::new_with_tracks(&js_sys::Array::of1(&track))?;
+        let source = self.context.create_media_stream_source(&stream)?;
+        source.connect_with_audio_node(&self.destination)?;
+        self.sources.push(source);
+        Ok(())
+    }
+
+    fn connect_peers(&mut self, peers: &HashMap<String, RtcPeerConnection>) {
+        for pc in peers.values() {
+            let receivers = pc.get_receivers();
+            for i in 0..receivers.length() {
+                if let Ok(receiver) = receivers.get(i).dyn_into::<RtcRtpReceiver>() {
+                    let track = receiver.track();
+                    if track.kind() == "audio" {
+                        if let Err(e) = self.connect(track) {
+                            web_sys::console::error_1(&e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Records the meeting in this browser: the grid (or active speaker) drawn onto a canvas,
+/// plus the local microphone and all remote audio, written to IndexedDB one chunk per second.
+pub struct MeetingRecorder {
+    recording_id: String,
+    recorder: MediaRecorder,
+    mixer: Rc<RefCell<AudioMixer>>,
+    layout: Rc<Cell<RecordingLayout>>,
+    on_data: Closure<dyn FnMut(BlobEvent)>,
+    uploader: Option<Rc<ChunkUploader>>,
+    _draw_interval: Interval,
+    _audio_interval: Interval,
+}
+
+impl MeetingRecorder {
+    pub async fn start(
+        peer_connections: StoredValue<HashMap<String, RtcPeerConnection>>,
+        local_stream: Option<MediaStream>,
+        layout: RecordingLayout,
+        upload: Option<RecordingUpload>,
+        on_upload_failed: Callback<()>,
+    ) -> Result<Self, JsValue> {
+        let db = open_db().await?;
+        let recording_id = (js_sys::Date::now() as u64).to_string();
+
+        let document = web_sys::window().and_then(|w| w.document()).ok_or(JsValue::from_str("No document"))?;
+        let canvas = document.create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_width(CANVAS_WIDTH);
+        canvas.set_height(CANVAS_HEIGHT);
+        let ctx = canvas
+            .get_context("2d")?
+            .ok_or(JsValue::from_str("No 2d context"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        let layout = Rc::new(Cell::new(layout));
+        let draw_layout_cell = layout.clone();
+        let draw_interval = Interval::new(1000 / FRAME_RATE, move || {
+            draw_layout(&ctx, draw_layout_cell.get());
+        });
+
+        let mixer = Rc::new(RefCell::new(AudioMixer::new()?));
+        if let Some(stream) = &local_stream {
+            for track in audio_tracks(stream) {
+                mixer.borrow_mut().connect(track)?;
+            }
+        }
+        peer_connections.with_value(|peers| mixer.borrow_mut().connect_peers(peers));
+        let rescan_mixer = mixer.clone();
+        let audio_interval = Interval::new(AUDIO_RESCAN_MS, move || {
+            peer_connections.with_value(|peers| rescan_mixer.borrow_mut().connect_peers(peers));
+        });
+
+        let stream = canvas.capture_stream_with_frame_request_rate(FRAME_RATE as f64)?;
+        for track in audio_tracks(&mixer.borrow().destination.stream()) {
+            stream.add_track(&track);
+        }
+
+        let options = MediaRecorderOptions::new();
+        let mime_type = if MediaRecorder::is_type_supported("video/webm;codecs=vp8,opus") {
+            "video/webm;codecs=vp8,opus"
+        } else {
+            "video/webm"
+        };
+        options.set_mime_type(mime_type);
+        let recorder = MediaRecorder::new_with_media_stream_and_media_recorder_options(&stream, &options)?;
+
+        // Failing to reach the server still leaves the local recording
+        let uploader = match upload {
+            Some(upload) => match ChunkUploader::start(upload, mime_type, on_upload_failed).await {
+                Ok(uploader) => Some(uploader),
+                Err(e) => {
+                    web_sys::console::error_1(&e);
+                    on_upload_failed.call(());
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let chunk_id = recording_id.clone();
+        let chunk_uploader = uploader.clone();
+        let mut seq = 0u32;
+        let on_data = Closure::<dyn FnMut(BlobEvent)>::new(move |ev: BlobEvent| {
+            if let Some(chunk) = ev.data() {
+                if chunk.size() > 0.0 {
+                    if let Err(e) = store_chunk(&db, &chunk_id, seq, &chunk) {
+                        web_sys::console::error_1(&e);
+                    }
+                    if let Some(uploader) = &chunk_uploader {
+                        uploader.push(chunk);
+                    }
+                    seq += 1;
+                }
+            }
+        });
+        recorder.set_ondataavailable(Some(on_data.as_ref().unchecked_ref()));
+        recorder.start_with_time_slice(CHUNK_INTERVAL_MS)?;
+
+        Ok(MeetingRecorder {
+            recording_id,
+            recorder,
+            mixer,
+            layout,
+            on_data,
+            uploader,
+            _draw_interval: draw_interval,
+            _audio_interval: audio_interval,
+        })
+    }
+
+    pub fn set_layout(&self, layout: RecordingLayout) {
+        self.layout.set(layout);
+    }
+
+    /// Stops recording and downloads the file once the final chunk has been stored.
+    pub fn stop(self) {
+        let MeetingRecorder { recording_id, recorder, mixer, on_data, uploader, .. } = self;
+
+        // The recorder still emits the last chunk after stop(), so the data handler
+        // has to stay alive until the stop event
+        let on_stop = Closure::once_into_js(move || {
+            drop(on_data);
+            let _ = mixer.borrow().context.close();
+            if let Some(uploader) = uploader {
+                uploader.finish();
+            }
+            spawn_local(async move {
+                if let Err(e) = download_recording(&recording_id).await {
+                    web_sys::console::error_1(&e);
+                }
+            });
+        });
+        recorder.set_onstop(Some(on_stop.unchecked_ref()));
+        if let Err(e) = recorder.stop() {
+            web_sys::console::error_1(&e);
+        }
+    }
+}
+
+/// Offers recordings left behind by a crashed or closed tab.
+#[component]
+pub fn RecordingRecovery() -> impl IntoView {
+    let (pending, set_pending) = create_signal(Vec::<String>::new());
+
+    spawn_local(async move {
+        match pending_recordings().await {
+            Ok(ids) => set_pending.set(ids),
+            Err(e) => web_sys::console::error_1(&e),
+        }
+    });
+
+    let resolve = move |id: String, keep: bool| {
+        set_pending.update(|list| list.retain(|x| *x != id));
+        spawn_local(async move {
+            let result = if keep { download_recording(&id).await } else { discard_recording(&id).await };
+            if let Err(e) = result {
+                web_sys::console::error_1(&e);
+            }
+        });
+    };
+
+    view! {
+        <Show when=move || !pending.get().is_empty()>
+            <div class="recording-recovery" style="background: #fff3cd; color: #856404; padding: 10px; border-radius: 4px; margin-bottom: 10px;">
+                <strong>"Unsaved recordings found"</strong>
+                <For
+                    each=move || pending.get()
+                    key=|id| id.clone()
+                    children=move |id| {
+                        let download_id = id.clone();
+                        let discard_id = id.clone();
+                        view! {
+                            <div style="display: flex; align-items: center; gap: 10px; margin-top: 5px;">
+                                <span>{recording_file_name(&id)}</span>
+                                <button on:click=move |_| resolve(download_id.clone(), true) style="padding: 2px 8px; cursor: pointer;">
+                                    "Download"
+                                </button>
+                                <button on:click=move |_| resolve(discard_id.clone(), false) style="padding: 2px 8px; cursor: pointer;">
+                                    "Discard"
+                                </button>
+                            </div>
+                        }
+                    }
+                />
+            </div>
+        </Show>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_cells_cover_canvas() {
+        assert!(grid_cells(0, 1280.0, 720.0).is_empty());
+
+        let single = grid_cells(1, 1280.0, 720.0);
+        assert_eq!(single, vec![(0.0, 0.0, 1280.0, 720.0)]);
+
+        // 3 tiles -> 2x2 grid with the last cell empty
+        let three = grid_cells(3, 1280.0, 720.0);
+        assert_eq!(three.len(), 3);
+        assert_eq!(three[1], (640.0, 0.0, 640.0, 360.0));
+        assert_eq!(three[2], (0.0, 360.0, 640.0, 360.0));
+
+        // 5 tiles -> 3 columns, 2 rows
+        let five = grid_cells(5, 1200.0, 600.0);
+        assert_eq!(five[4], (400.0, 300.0, 400.0, 300.0));
+    }
+
+    #[test]
+    fn test_recording_file_name() {
+        assert_eq!(recording_file_name("1700000000000"), "meeting-recording-1700000000000.webm");
+        assert_eq!(RecordingLayout::default(), RecordingLayout::Grid);
+    }
+}
@@ -1,12 +1,18 @@
 use leptos::*;
 use web_sys::{MediaDeviceInfo, MediaDeviceKind};
 use crate::media::{enumerate_devices, get_user_media};
+use shared::VideoQuality;
+use crate::notifications::{self, NotificationSettings, SoundCue};
 
 #[component]
 pub fn SettingsDialog(
     show: ReadSignal<bool>,
     on_close: Callback<()>,
     on_save_profile: Callback<String>,
+    video_quality: ReadSignal<VideoQuality>,
+    on_video_quality: Callback<VideoQuality>,
+    notification_settings: ReadSignal<NotificationSettings>,
+    on_notification_settings: Callback<NotificationSettings>,
 ) -> impl IntoView {
     let (active_tab, set_active_tab) = create_signal("profile");
     let (display_name, set_display_name) = create_signal("".to_string());
@@ -45,7 +51,7 @@ pub fn SettingsDialog(
         let v_id = selected_video.get();
         let a_id = selected_audio.get();
 
-        match get_user_media(v_id, a_id).await {
+        match get_user_media(v_id, a_id, video_quality.get_untracked()).await {
             Ok(stream) => {
                 if let Some(video_el) = video_ref.get() {
                     video_el.set_src_object(Some(&stream));
@@ -88,6 +94,12 @@ pub fn SettingsDialog(
                         >
                             "Devices"
                         </button>
+                        <button
+                            on:click=move |_| set_active_tab.set("notifications")
+                            style=move || format!("padding: 10px; border: none; background: none; cursor: pointer; border-bottom: 2px solid {}", if active_tab.get() == "notifications" { "#007bff" } else { "transparent" })
+                        >
+                            "Notifications"
+                        </button>
                     </div>
 
                     <div class="tab-content">
@@ -183,6 +195,27 @@ pub fn SettingsDialog(
                                 </select>
                             </div>
 
+                            <div class="form-group" style="margin-bottom: 15px;">
+                                <label style="display: block; margin-bottom: 5px;">"Video Quality"</label>
+                                <select
+                                    style="width: 100%; padding: 8px; border: 1px solid #ccc; border-radius: 4px;"
+                                    on:change=move |ev| {
+                                        let val = event_target_value(&ev);
+                                        if let Some(quality) = VideoQuality::ALL.into_iter().find(|q| format!("{:?}", q) == val) {
+                                            on_video_quality.call(quality);
+                                            start_preview.dispatch(());
+                                        }
+                                    }
+                                >
+                                    {VideoQuality::ALL.into_iter().map(|q| view! {
+                                        <option value=format!("{:?}", q) selected=move || video_quality.get() == q>
+                                            {q.label()}
+                                        </option>
+                                    }).collect_view()}
+                                </select>
+                                <p style="color: #666; font-size: 0.8em; margin-top: 5px;">"Lower levels use less bandwidth. Audio only turns off video in both directions."</p>
+                            </div>
+
                             <div class="preview" style="margin-top: 20px; border: 1px solid #ccc; height: 200px; background: #000; display: flex; justify-content: center; align-items: center; overflow: hidden;">
                                 <video
                                     _ref=video_ref
@@ -193,6 +226,43 @@ pub fn SettingsDialog(
                             </div>
                             <p style="color: #666; font-size: 0.8em; margin-top: 5px;">"This is a local preview only."</p>
                         </Show>
+                        <Show when=move || active_tab.get() == "notifications">
+                            <div class="form-group" style="margin-bottom: 15px;">
+                                <label style="display: block; margin-bottom: 5px;">"Sounds"</label>
+                                {SoundCue::ALL.into_iter().map(|cue| view! {
+                                    <label style="display: block; margin-bottom: 4px;">
+                                        <input
+                                            type="checkbox"
+                                            prop:checked=move || notification_settings.get().plays(cue)
+                                            on:change=move |ev| {
+                                                let mut settings = notification_settings.get_untracked();
+                                                settings.set_sound(cue, event_target_checked(&ev));
+                                                on_notification_settings.call(settings);
+                                            }
+                                        />
+                                        " " {cue.label()}
+                                    </label>
+                                }).collect_view()}
+                            </div>
+                            <div class="form-group" style="margin-bottom: 15px;">
+                                <label style="display: block;">
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=move || notification_settings.get().desktop
+                                        on:change=move |ev| {
+                                            let mut settings = notification_settings.get_untracked();
+                                            settings.desktop = event_target_checked(&ev);
+                                            on_notification_settings.call(settings);
+                                        }
+                                    />
+                                    " Desktop notifications while this tab is in the background"
+                                </label>
+                                <Show when=move || notification_settings.get().desktop && notifications::permission_denied()>
+                                    <p style="color: #dc3545; font-size: 0.8em; margin-top: 5px;">"Notifications are blocked for this site in the browser settings."</p>
+                                </Show>
+                            </div>
+                            <p style="color: #666; font-size: 0.8em;">"These settings are saved in this browser."</p>
+                        </Show>
                     </div>
                 </div>
             </div>
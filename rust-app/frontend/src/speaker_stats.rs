@@ -9,7 +9,7 @@ pub fn SpeakerStatsDialog(
 ) -> impl IntoView {
     let sorted_participants = move || {
         let mut p = participants.get();
-        p.sort_by(|a, b| b.speaking_time.cmp(&a.speaking_time));
+        p.sort_by_key(|p| std::cmp::Reverse(p.speaking_time));
         p
     };
 
@@ -58,9 +58,6 @@ pub fn SpeakerStatsDialog(
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use shared::Participant;
-
     #[test]
     fn test_format_time_logic() {
         let format_time = |ms: u64| {
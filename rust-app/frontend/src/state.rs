@@ -1,13 +1,28 @@
 use leptos::*;
-use shared::{ChatMessage, Participant, ServerMessage, ClientMessage, Poll, DrawAction, FileAttachment};
-use web_sys::{MessageEvent, WebSocket, MediaStream};
-use std::collections::HashSet;
+use shared::whiteboard::WhiteboardElement;
+use shared::{ChatMessage, ChatMode, ChatScope, Participant, ServerMessage, ClientMessage, Poll, QuizScore, Question, MediaStats, ConnectionQuality, VideoQuality, CaptionSegment};
+use web_sys::{MessageEvent, WebSocket, MediaStream, RtcPeerConnection};
+use std::collections::{HashMap, HashSet};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use crate::media::{get_user_media, get_display_media, AudioMonitor};
+use crate::media::{get_user_media, get_display_media, apply_video_quality, limit_video_bitrate, AudioMonitor};
+use crate::connection_stats::LossMonitor;
+use crate::peers::PeerMesh;
+use crate::recording::{MeetingRecorder, RecordingLayout, RecordingUpload};
+use crate::captions::apply_caption;
+use crate::chat::{is_thread_participant, prepend_history, replace_scope, OutgoingMessage, CHAT_PAGE_SIZE};
+use crate::utils::room_id_from_path;
 use crate::components_ui::toast::{ToastMessage, ToastType};
+use crate::notifications::{self, NotificationSettings, SoundCue};
 use gloo_timers::callback::Timeout;
 
+// sessionStorage key of our last session token, used to resume our ID after a reload
+const SESSION_TOKEN_KEY: &str = "session_token";
+
+fn session_storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.session_storage().ok().flatten())
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum RoomConnectionState {
     Prejoin,
@@ -19,6 +34,7 @@ pub enum RoomConnectionState {
 pub struct RoomState {
     pub connection_state: ReadSignal<RoomConnectionState>,
     pub messages: ReadSignal<Vec<ChatMessage>>,
+    pub chat_has_more: ReadSignal<bool>, // Older messages can be fetched from the server
     pub participants: ReadSignal<Vec<Participant>>,
     pub knocking_participants: ReadSignal<Vec<Participant>>,
     pub is_connected: ReadSignal<bool>,
@@ -27,12 +43,17 @@ pub struct RoomState {
     pub is_recording: ReadSignal<bool>,
     pub show_settings: ReadSignal<bool>,
     pub show_polls: ReadSignal<bool>,
+    pub show_qna: ReadSignal<bool>,
     pub show_shortcuts: ReadSignal<bool>,
     pub polls: ReadSignal<Vec<Poll>>,
+    pub my_poll_votes: ReadSignal<HashMap<String, Vec<u32>>>, // Poll ID -> our choices, as recorded by the server
+    pub quiz_leaderboard: ReadSignal<Vec<QuizScore>>,
+    pub questions: ReadSignal<Vec<Question>>, // Unordered; see `shared::sort_questions`
     pub last_reaction: ReadSignal<Option<(String, String, u64)>>,
     pub show_whiteboard: ReadSignal<bool>,
-    pub whiteboard_history: ReadSignal<Vec<DrawAction>>,
+    pub whiteboard_elements: ReadSignal<Vec<WhiteboardElement>>, // In drawing order
     pub my_id: ReadSignal<Option<String>>,
+    pub session_token: ReadSignal<Option<String>>, // Authorizes REST calls
     pub typing_users: ReadSignal<HashSet<String>>,
     pub is_host: Signal<bool>,
     pub host_id: Signal<Option<String>>,
@@ -46,16 +67,54 @@ pub struct RoomState {
     pub speaking_peers: ReadSignal<HashSet<String>>,
     pub show_speaker_stats: ReadSignal<bool>,
     pub show_virtual_background: ReadSignal<bool>,
-    pub rtt: ReadSignal<u64>,
+    // Remote participant id -> peer connection, polled by the stats collector
+    pub peer_connections: StoredValue<HashMap<String, RtcPeerConnection>>,
+    pub remote_streams: ReadSignal<HashMap<String, MediaStream>>, // Remote participant id -> camera and microphone
+    pub media_stats: ReadSignal<HashMap<String, MediaStats>>,
+    pub quality_reports: ReadSignal<HashMap<String, ConnectionQuality>>, // Host only
+    pub video_quality: ReadSignal<VideoQuality>,
+    pub recording_layout: ReadSignal<RecordingLayout>,
+    pub needs_recording_consent: Signal<bool>,
+    pub auto_mute_decliners: Signal<bool>,
+    pub poll_vote_changes: Signal<bool>,
+    pub chat_mode: Signal<ChatMode>,
+    pub chat_slow_mode_secs: Signal<u32>,
+    pub chat_muted: Signal<Vec<String>>,
+    pub chat_restriction: Signal<Option<String>>, // Why we can't send, if we can't
+    pub timer_ends_at: Signal<Option<u64>>, // Epoch ms; set by a moderator's /timer
+    pub recording_consents: ReadSignal<HashMap<String, bool>>, // Host only
+    pub captions: ReadSignal<Vec<CaptionSegment>>, // Live lines for the overlay
+    pub show_captions: ReadSignal<bool>,
+    pub meeting_ended: ReadSignal<bool>,
+    pub show_chat: ReadSignal<bool>,
+    pub mention_count: ReadSignal<usize>, // Mentions received while the chat was hidden
+    pub unread_count: ReadSignal<usize>, // Messages received while the chat was hidden
+    pub notification_settings: ReadSignal<NotificationSettings>,
     // Setters or Actions
     pub set_show_settings: WriteSignal<bool>,
     pub set_show_polls: WriteSignal<bool>,
+    pub set_show_qna: WriteSignal<bool>,
     pub set_show_shortcuts: WriteSignal<bool>,
     pub set_show_whiteboard: WriteSignal<bool>,
     pub set_show_speaker_stats: WriteSignal<bool>,
     pub set_show_virtual_background: WriteSignal<bool>,
-    pub send_ping: Callback<()>,
-    pub send_message: Callback<(String, Option<String>, Option<FileAttachment>)>, // content, recipient_id, attachment
+    pub set_show_captions: WriteSignal<bool>,
+    pub set_show_chat: WriteSignal<bool>,
+    pub set_notification_settings: Callback<NotificationSettings>,
+    pub on_media_stats: Callback<HashMap<String, MediaStats>>,
+    pub set_video_quality: Callback<VideoQuality>,
+    pub set_recording_layout: Callback<RecordingLayout>,
+    pub give_recording_consent: Callback<bool>,
+    pub toggle_auto_mute_decliners: Callback<()>,
+    pub set_chat_mode: Callback<ChatMode>,
+    pub set_chat_slow_mode: Callback<u32>,
+    pub set_chat_muted: Callback<(String, bool)>, // participant_id, muted
+    pub send_command: Callback<ClientMessage>, // Slash commands typed in the chat
+    pub send_message: Callback<OutgoingMessage>,
+    pub load_older_messages: Callback<()>,
+    pub edit_message: Callback<(String, String)>, // message_id, content
+    pub delete_message: Callback<String>,
+    pub react_to_message: Callback<(String, String)>, // message_id, emoji
     pub start_share_video: Callback<String>,
     pub stop_share_video: Callback<()>,
     pub toggle_lock: Callback<()>,
@@ -70,8 +129,20 @@ pub struct RoomState {
     pub toggle_screen_share: Callback<()>,
     pub kick_participant: Callback<String>,
     pub create_poll: Callback<Poll>,
-    pub vote_poll: Callback<(String, u32)>,
-    pub send_draw: Callback<DrawAction>,
+    pub vote_poll: Callback<(String, Vec<u32>)>, // poll_id, option_ids
+    pub close_poll: Callback<String>,
+    pub reopen_poll: Callback<String>,
+    pub delete_poll: Callback<String>,
+    pub toggle_poll_vote_changes: Callback<()>,
+    pub reveal_poll_answers: Callback<String>,
+    pub ask_question: Callback<(String, bool)>, // text, anonymous
+    pub upvote_question: Callback<String>,
+    pub set_question_answered: Callback<(String, bool)>, // question_id, answered
+    pub set_question_pinned: Callback<(String, bool)>, // question_id, pinned
+    pub dismiss_question: Callback<String>,
+    pub add_whiteboard_element: Callback<WhiteboardElement>,
+    pub update_whiteboard_element: Callback<WhiteboardElement>,
+    pub delete_whiteboard_element: Callback<String>,
     pub set_is_typing: Callback<bool>,
     pub create_breakout_room: Callback<String>,
     pub join_breakout_room: Callback<Option<String>>,
@@ -84,6 +155,9 @@ pub struct RoomState {
 pub fn use_room_state() -> RoomState {
     let (current_state, set_current_state) = create_signal(RoomConnectionState::Prejoin);
     let (messages, set_messages) = create_signal(Vec::<ChatMessage>::new());
+    let (chat_has_more, set_chat_has_more) = create_signal(false);
+    // Oldest message fetched so far, for each scope that has older messages
+    let (chat_cursors, set_chat_cursors) = create_signal(HashMap::<ChatScope, String>::new());
     let (typing_users, set_typing_users) = create_signal(HashSet::<String>::new());
     let (breakout_rooms, set_breakout_rooms) = create_signal(Vec::<shared::BreakoutRoom>::new());
     let (current_room_id, set_current_room_id) = create_signal(None::<String>);
@@ -96,13 +170,17 @@ pub fn use_room_state() -> RoomState {
     let (is_recording, set_is_recording) = create_signal(false);
     let (show_settings, set_show_settings) = create_signal(false);
     let (show_polls, set_show_polls) = create_signal(false);
+    let (show_qna, set_show_qna) = create_signal(false);
     let (show_shortcuts, set_show_shortcuts) = create_signal(false);
     let (polls, set_polls) = create_signal(Vec::<Poll>::new());
+    let (my_poll_votes, set_my_poll_votes) = create_signal(HashMap::<String, Vec<u32>>::new());
+    let (quiz_leaderboard, set_quiz_leaderboard) = create_signal(Vec::<QuizScore>::new());
+    let (questions, set_questions) = create_signal(Vec::<Question>::new());
     let (last_reaction, set_last_reaction) = create_signal(None::<(String, String, u64)>);
     let (show_whiteboard, set_show_whiteboard) = create_signal(false);
-    let (whiteboard_history, set_whiteboard_history) = create_signal(Vec::<DrawAction>::new());
-    let (_last_draw_action, set_last_draw_action) = create_signal(None::<DrawAction>);
+    let (whiteboard_elements, set_whiteboard_elements) = create_signal(Vec::<WhiteboardElement>::new());
     let (my_id, set_my_id) = create_signal(None::<String>);
+    let (session_token, set_session_token) = create_signal(None::<String>);
     let (local_stream, set_local_stream) = create_signal(None::<MediaStream>);
     let (local_screen_stream, set_local_screen_stream) = create_signal(None::<MediaStream>);
     let (toasts, set_toasts) = create_signal(Vec::<ToastMessage>::new());
@@ -112,8 +190,25 @@ pub fn use_room_state() -> RoomState {
     let (_audio_monitor, set_audio_monitor) = create_signal(None::<AudioMonitor>);
     let (show_speaker_stats, set_show_speaker_stats) = create_signal(false);
     let (show_virtual_background, set_show_virtual_background) = create_signal(false);
-    let (rtt, set_rtt) = create_signal(0u64);
-    let (last_ping_time, set_last_ping_time) = create_signal(0f64);
+    let (remote_streams, set_remote_streams) = create_signal(HashMap::<String, MediaStream>::new());
+    let peer_mesh = PeerMesh::new(ws, local_stream, set_remote_streams);
+    let peer_connections = peer_mesh.connections;
+    let (media_stats, set_media_stats) = create_signal(HashMap::<String, MediaStats>::new());
+    let (quality_reports, set_quality_reports) = create_signal(HashMap::<String, ConnectionQuality>::new());
+    let (video_quality, set_video_quality_signal) = create_signal(VideoQuality::default());
+    let loss_monitor = store_value(LossMonitor::default());
+    let (meeting_recorder, set_meeting_recorder) = create_signal(None::<MeetingRecorder>);
+    let (recording_layout, set_recording_layout_signal) = create_signal(RecordingLayout::default());
+    // Our answer for the current recording; None until asked
+    let (recording_consent, set_recording_consent) = create_signal(None::<bool>);
+    let (recording_consents, set_recording_consents) = create_signal(HashMap::<String, bool>::new());
+    let (captions, set_captions) = create_signal(Vec::<CaptionSegment>::new());
+    let (show_captions, set_show_captions) = create_signal(true);
+    let (meeting_ended, set_meeting_ended) = create_signal(false);
+    let (show_chat, set_show_chat) = create_signal(true);
+    let (mention_count, set_mention_count) = create_signal(0usize);
+    let (unread_count, set_unread_count) = create_signal(0usize);
+    let (notification_settings, set_notification_settings_signal) = create_signal(notifications::load_settings());
 
     // We assume the first participant in the list is the host for now,
     // or we'd need to send host_id in RoomConfig.
@@ -139,6 +234,22 @@ pub fn use_room_state() -> RoomState {
         }
     });
 
+    let auto_mute_decliners = Signal::derive(move || room_config.with(|c| c.auto_mute_decliners));
+    let poll_vote_changes = Signal::derive(move || room_config.with(|c| c.poll_vote_changes));
+    let chat_mode = Signal::derive(move || room_config.with(|c| c.chat_mode));
+    let chat_slow_mode_secs = Signal::derive(move || room_config.with(|c| c.chat_slow_mode_secs));
+    let chat_muted = Signal::derive(move || room_config.with(|c| c.chat_muted.clone()));
+    let timer_ends_at = Signal::derive(move || room_config.with(|c| c.timer_ends_at));
+    // Mirrors the server's check so the input can say why it is disabled
+    let chat_restriction = Signal::derive(move || {
+        let me = my_id.get().unwrap_or_default();
+        room_config.with(|c| c.chat_restriction(&me, is_host.get()).map(|r| r.message().to_string()))
+    });
+    // The host consents by starting the recording
+    let needs_recording_consent = Signal::derive(move || {
+        is_recording.get() && recording_consent.get().is_none() && !is_host.get()
+    });
+
     let add_toast = move |msg: String, type_: ToastType| {
         let id = js_sys::Date::now() as u64;
         set_toasts.update(|t| t.push(ToastMessage { id, message: msg, type_ }));
@@ -153,11 +264,31 @@ pub fn use_room_state() -> RoomState {
         set_toasts.update(|t| t.retain(|x| x.id != id));
     });
 
+    // Opening the chat shows the new and highlighted messages, so the badges have done their job
+    create_effect(move |_| {
+        if show_chat.get() {
+            set_mention_count.set(0);
+            set_unread_count.set(0);
+        }
+    });
+
+    let set_notification_settings = Callback::new(move |settings: NotificationSettings| {
+        // Called from the settings toggle, which is the user gesture browsers require
+        if settings.desktop && !notification_settings.get_untracked().desktop {
+            notifications::request_permission();
+        }
+        notifications::save_settings(&settings);
+        set_notification_settings_signal.set(settings);
+    });
+    let play_sound = move |cue: SoundCue| notifications::play_sound(&notification_settings.get_untracked(), cue);
+    let notify_desktop = move |title: &str, body: &str| notifications::notify_desktop(&notification_settings.get_untracked(), title, body);
+
     // Initialize WebSocket
     create_effect(move |_| {
         // Ensure my_id is reset on new connection logic if needed, but here we just connect.
         // Actually, if we reconnect, we might get a new ID.
         set_my_id.set(None);
+        set_session_token.set(None);
         // Default config has host_id = None.
         set_room_config.set(shared::RoomConfig::default());
 
@@ -180,6 +311,12 @@ pub fn use_room_state() -> RoomState {
                                 set_my_id.set(Some(id));
                                 set_current_state.set(RoomConnectionState::Joined);
                             },
+                            ServerMessage::SessionToken(token) => {
+                                if let Some(storage) = session_storage() {
+                                    let _ = storage.set_item(SESSION_TOKEN_KEY, &token);
+                                }
+                                set_session_token.set(Some(token));
+                            },
                             ServerMessage::RoomUpdated(config) => {
                                 set_is_locked.set(config.is_locked);
 
@@ -191,6 +328,9 @@ pub fn use_room_state() -> RoomState {
                                     } else {
                                         add_toast("Recording Stopped".to_string(), ToastType::Info);
                                     }
+                                    // Each recording asks for consent again
+                                    set_recording_consent.set(None);
+                                    set_recording_consents.set(HashMap::new());
                                 }
                                 set_is_recording.set(config.is_recording);
 
@@ -198,12 +338,55 @@ pub fn use_room_state() -> RoomState {
                                 set_room_config.set(config);
                             },
                             ServerMessage::Chat { message, .. } => {
+                                if let Some(me) = my_id.get_untracked().filter(|me| *me != message.user_id) {
+                                    let name = participants.with_untracked(|list| {
+                                        list.iter().find(|p| p.id == message.user_id).map(|p| p.name.clone())
+                                    }).unwrap_or_else(|| "Someone".to_string());
+                                    play_sound(SoundCue::Chat);
+                                    if !show_chat.get_untracked() {
+                                        set_unread_count.update(|n| *n += 1);
+                                    }
+                                    let mentioned = message.mentions.contains(&me);
+                                    let title = if mentioned { format!("{} mentioned you", name) } else { name.clone() };
+                                    notify_desktop(&title, if message.content.is_empty() { "Sent a file" } else { message.content.as_str() });
+                                    if mentioned {
+                                        add_toast(title, ToastType::Info);
+                                        if !show_chat.get_untracked() {
+                                            set_mention_count.update(|n| *n += 1);
+                                        }
+                                    } else if message.parent_id.as_ref().is_some_and(|pid| messages.with_untracked(|msgs| is_thread_participant(msgs, pid, &me))) {
+                                        // Replies in threads we took part in are worth a heads-up
+                                        add_toast(format!("{} replied in a thread", name), ToastType::Info);
+                                    }
+                                }
                                 set_messages.update(|msgs| msgs.push(message));
                             },
-                            ServerMessage::ChatHistory(history) => {
-                                set_messages.set(history);
+                            ServerMessage::ChatHistory { messages: page_messages, parents, before, has_more, scope } => {
+                                set_chat_cursors.update(|cursors| match page_messages.first() {
+                                    Some(first) if has_more => { cursors.insert(scope.clone(), first.id.clone()); },
+                                    _ => { cursors.remove(&scope); },
+                                });
+                                set_chat_has_more.set(!chat_cursors.get_untracked().is_empty());
+                                let mut page = parents;
+                                page.extend(page_messages);
+                                if before.is_none() {
+                                    let me = my_id.get_untracked().unwrap_or_default();
+                                    set_messages.update(|msgs| replace_scope(msgs, &scope, &me, page));
+                                } else {
+                                    set_messages.update(|msgs| prepend_history(msgs, page));
+                                }
+                            },
+                            ServerMessage::ChatMessageUpdated { message: updated, .. } => {
+                                set_messages.update(|msgs| {
+                                    if let Some(existing) = msgs.iter_mut().find(|m| m.id == updated.id) {
+                                        *existing = updated;
+                                    }
+                                });
                             },
                             ServerMessage::ParticipantJoined(p) => {
+                                if my_id.get_untracked().as_ref() != Some(&p.id) {
+                                    play_sound(SoundCue::Join);
+                                }
                                 set_knocking_participants.update(|list| list.retain(|x| x.id != p.id));
                                 set_participants.update(|list| {
                                     if !list.iter().any(|x| x.id == p.id) {
@@ -215,9 +398,13 @@ pub fn use_room_state() -> RoomState {
                                 set_knocking_participants.update(|list| list.retain(|x| x.id != id));
                             },
                             ServerMessage::ParticipantLeft(id) => {
+                                play_sound(SoundCue::Leave);
                                 set_participants.update(|list| list.retain(|p| p.id != id));
                                 // Remove from typing users if present
                                 set_typing_users.update(|users| { users.remove(&id); });
+                                peer_mesh.remove(&id);
+                                set_media_stats.update(|stats| { stats.remove(&id); });
+                                set_quality_reports.update(|reports| { reports.remove(&id); });
                             },
                             ServerMessage::ParticipantList(list) => {
                                 set_participants.set(list);
@@ -257,20 +444,24 @@ pub fn use_room_state() -> RoomState {
                                 set_current_state.set(RoomConnectionState::Prejoin);
                                 set_participants.set(Vec::new());
                                 set_is_connected.set(false);
+                                set_meeting_ended.set(true);
                             },
                             ServerMessage::KnockingParticipant(p) => {
-                                set_knocking_participants.update(|list| {
-                                    if !list.iter().any(|x| x.id == p.id) {
-                                        list.push(p);
-                                    }
-                                });
+                                if !knocking_participants.with_untracked(|list| list.iter().any(|x| x.id == p.id)) {
+                                    play_sound(SoundCue::Knock);
+                                    notify_desktop(&format!("{} is asking to join", p.name), "Open the meeting to let them in");
+                                    set_knocking_participants.update(|list| list.push(p));
+                                }
                             },
                             ServerMessage::ParticipantUpdated(p) => {
                                 set_participants.update(|list| {
                                     if let Some(existing) = list.iter_mut().find(|x| x.id == p.id) {
                                         // Check for hand raise
                                         if p.is_hand_raised && !existing.is_hand_raised {
-                                            add_toast(format!("{} raised their hand", p.name), ToastType::Info);
+                                            let text = format!("{} raised their hand", p.name);
+                                            play_sound(SoundCue::HandRaised);
+                                            notify_desktop(&text, "");
+                                            add_toast(text, ToastType::Info);
                                         }
                                         *existing = p;
                                     }
@@ -299,22 +490,60 @@ pub fn use_room_state() -> RoomState {
                             ServerMessage::BreakoutRoomsList(rooms) => {
                                 set_breakout_rooms.set(rooms);
                             },
+                            // Also sent for every open poll when we join, so a reconnect replaces rather than repeats
                             ServerMessage::PollCreated(poll) => {
-                                set_polls.update(|list| list.push(poll));
+                                set_polls.update(|list| {
+                                    match list.iter_mut().find(|x| x.id == poll.id) {
+                                        Some(existing) => *existing = poll,
+                                        None => list.push(poll),
+                                    }
+                                });
                             },
-                            ServerMessage::PollUpdated(poll) => {
+                            // Moderators also get the counts of polls whose results are hidden
+                            ServerMessage::PollUpdated(poll) | ServerMessage::PollResults(poll) => {
                                 set_polls.update(|list| {
                                     if let Some(existing) = list.iter_mut().find(|x| x.id == poll.id) {
                                         *existing = poll;
                                     }
                                 });
                             },
-                            ServerMessage::Draw(action) => {
-                                set_last_draw_action.set(Some(action.clone()));
-                                set_whiteboard_history.update(|h| h.push(action));
+                            ServerMessage::PollVoteRecorded { poll_id, option_ids } => {
+                                set_my_poll_votes.update(|votes| { votes.insert(poll_id, option_ids); });
+                            },
+                            ServerMessage::PollDeleted(poll_id) => {
+                                set_polls.update(|list| list.retain(|p| p.id != poll_id));
+                                set_my_poll_votes.update(|votes| { votes.remove(&poll_id); });
+                            },
+                            ServerMessage::QuizLeaderboard(scores) => {
+                                set_quiz_leaderboard.set(scores);
+                            },
+                            ServerMessage::QuestionList(list) => {
+                                set_questions.set(list);
+                            },
+                            ServerMessage::QuestionAsked(question) | ServerMessage::QuestionUpdated(question) => {
+                                set_questions.update(|list| {
+                                    match list.iter_mut().find(|q| q.id == question.id) {
+                                        Some(existing) => *existing = question,
+                                        None => list.push(question),
+                                    }
+                                });
+                            },
+                            ServerMessage::QuestionDismissed(question_id) => {
+                                set_questions.update(|list| list.retain(|q| q.id != question_id));
+                            },
+                            ServerMessage::WhiteboardElementAdded(element) | ServerMessage::WhiteboardElementUpdated(element) => {
+                                set_whiteboard_elements.update(|elements| {
+                                    match elements.iter_mut().find(|e| e.id == element.id) {
+                                        Some(existing) => *existing = element,
+                                        None => elements.push(element),
+                                    }
+                                });
+                            },
+                            ServerMessage::WhiteboardElementDeleted(element_id) => {
+                                set_whiteboard_elements.update(|elements| elements.retain(|e| e.id != element_id));
                             },
-                            ServerMessage::WhiteboardHistory(history) => {
-                                set_whiteboard_history.set(history);
+                            ServerMessage::WhiteboardHistory(elements) => {
+                                set_whiteboard_elements.set(elements);
                             },
                             ServerMessage::VideoShared(url) => {
                                 set_shared_video_url.set(Some(url));
@@ -331,14 +560,41 @@ pub fn use_room_state() -> RoomState {
                                     }
                                 });
                             },
-                            ServerMessage::Pong { .. } => {
-                                let now = js_sys::Date::now();
-                                let start = last_ping_time.get_untracked();
-                                if start > 0.0 {
-                                    let latency = (now - start) as u64;
-                                    set_rtt.set(latency);
+                            ServerMessage::Pong { .. } => {},
+                            ServerMessage::RecordingConsents(consents) => {
+                                set_recording_consents.set(consents.into_iter().map(|c| (c.user_id, c.accepted)).collect());
+                            },
+                            ServerMessage::ForceMute(target) => {
+                                if my_id.get_untracked() == Some(target) && !is_muted.get_untracked() {
+                                    set_is_muted.set(true);
+                                    if let Some(stream) = local_stream.get_untracked() {
+                                        let audio_tracks = stream.get_audio_tracks();
+                                        for i in 0..audio_tracks.length() {
+                                            if let Ok(track) = audio_tracks.get(i).dyn_into::<web_sys::MediaStreamTrack>() {
+                                                track.set_enabled(false);
+                                            }
+                                        }
+                                    }
+                                    add_toast("You were muted because you declined to be recorded".to_string(), ToastType::Info);
                                 }
                             },
+                            ServerMessage::Caption(segment) => {
+                                set_captions.update(|lines| apply_caption(lines, segment));
+                            },
+                            ServerMessage::ConnectionQualityReport(report) => {
+                                set_quality_reports.update(|reports| {
+                                    reports.insert(report.user_id.clone(), report);
+                                });
+                            },
+                            ServerMessage::RtcSignal { from, signal, .. } => {
+                                peer_mesh.handle_signal(from, signal);
+                            },
+                            ServerMessage::ContentFlagged(flag) => {
+                                add_toast(
+                                    format!("Flagged {} from {}: {}", flag.kind.label(), flag.user_name, flag.matches.join(", ")),
+                                    ToastType::Error,
+                                );
+                            },
                             ServerMessage::Error(err) => {
                                 add_toast(err, ToastType::Error);
                             }
@@ -378,11 +634,61 @@ pub fn use_room_state() -> RoomState {
         if let Some(socket) = ws.get() {
             let _ = socket.close();
         }
+        peer_mesh.close_all();
     });
 
-    let send_message = Callback::new(move |(content, recipient_id, attachment): (String, Option<String>, Option<FileAttachment>)| {
+    // Keep a peer connection to everyone else in the meeting
+    create_effect(move |_| {
+        let Some(me) = my_id.get() else { return };
+        let present: Vec<String> = participants.with(|list| list.iter().map(|p| p.id.clone()).collect());
+        peer_mesh.sync(&me, &present);
+    });
+
+    create_effect(move |_| {
+        peer_mesh.set_local_stream(local_stream.get().as_ref());
+    });
+
+    let send_message = Callback::new(move |(content, recipient_id, attachment, parent_id): OutgoingMessage| {
         if let Some(socket) = ws.get() {
-            let msg = ClientMessage::Chat { content, recipient_id, attachment };
+            let msg = ClientMessage::Chat { content, recipient_id, attachment, parent_id };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let load_older_messages = Callback::new(move |_: ()| {
+        if let Some(socket) = ws.get_untracked() {
+            for (scope, before) in chat_cursors.get_untracked() {
+                let msg = ClientMessage::FetchChatHistory { before: Some(before), limit: CHAT_PAGE_SIZE, scope };
+                if let Ok(json) = serde_json::to_string(&msg) {
+                    let _ = socket.send_with_str(&json);
+                }
+            }
+        }
+    });
+
+    let edit_message = Callback::new(move |(message_id, content): (String, String)| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::EditMessage { message_id, content };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let delete_message = Callback::new(move |message_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::DeleteMessage(message_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let react_to_message = Callback::new(move |(message_id, emoji): (String, String)| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::ToggleMessageReaction { message_id, emoji };
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
             }
@@ -514,18 +820,126 @@ pub fn use_room_state() -> RoomState {
         }
     });
 
-    let vote_poll = Callback::new(move |(poll_id, option_id): (String, u32)| {
+    let vote_poll = Callback::new(move |(poll_id, option_ids): (String, Vec<u32>)| {
         if let Some(socket) = ws.get() {
-            let msg = ClientMessage::Vote { poll_id, option_id };
+            let msg = ClientMessage::Vote { poll_id, option_ids };
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
             }
         }
     });
 
-    let send_draw = Callback::new(move |action: DrawAction| {
+    let close_poll = Callback::new(move |poll_id: String| {
         if let Some(socket) = ws.get() {
-            let msg = ClientMessage::Draw(action);
+            let msg = ClientMessage::ClosePoll(poll_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let reopen_poll = Callback::new(move |poll_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::ReopenPoll(poll_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let delete_poll = Callback::new(move |poll_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::DeletePoll(poll_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let toggle_poll_vote_changes = Callback::new(move |_: ()| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::TogglePollVoteChanges;
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let reveal_poll_answers = Callback::new(move |poll_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::RevealPollAnswers(poll_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let ask_question = Callback::new(move |(text, anonymous): (String, bool)| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::AskQuestion { text, anonymous };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let upvote_question = Callback::new(move |question_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::UpvoteQuestion(question_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let set_question_answered = Callback::new(move |(question_id, answered): (String, bool)| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::SetQuestionAnswered { question_id, answered };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let set_question_pinned = Callback::new(move |(question_id, pinned): (String, bool)| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::SetQuestionPinned { question_id, pinned };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let dismiss_question = Callback::new(move |question_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::DismissQuestion(question_id);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let add_whiteboard_element = Callback::new(move |element: WhiteboardElement| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::AddWhiteboardElement(element);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let update_whiteboard_element = Callback::new(move |element: WhiteboardElement| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::UpdateWhiteboardElement(element);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let delete_whiteboard_element = Callback::new(move |element_id: String| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::DeleteWhiteboardElement(element_id);
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
             }
@@ -534,6 +948,12 @@ pub fn use_room_state() -> RoomState {
 
     let join_meeting = Callback::new(move |display_name: String| {
         if let Some(socket) = ws.get() {
+            // Pick up where an earlier connection of this tab left off
+            if let Some(token) = session_storage().and_then(|s| s.get_item(SESSION_TOKEN_KEY).ok().flatten()) {
+                if let Ok(json) = serde_json::to_string(&ClientMessage::ResumeSession(token)) {
+                    let _ = socket.send_with_str(&json);
+                }
+            }
             let msg = ClientMessage::Join(display_name);
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
@@ -561,8 +981,10 @@ pub fn use_room_state() -> RoomState {
 
     let join_breakout_room = Callback::new(move |room_id: Option<String>| {
         set_current_room_id.set(room_id.clone());
-        // Clear messages when switching rooms? Maybe.
-        set_messages.set(Vec::new());
+        // The new room's chat arrives from the server; private conversations carry on
+        set_messages.update(|msgs| msgs.retain(|m| m.recipient_id.is_some()));
+        set_chat_cursors.update(|cursors| cursors.retain(|scope, _| matches!(scope, ChatScope::Private(_))));
+        set_chat_has_more.set(!chat_cursors.get_untracked().is_empty());
 
         if let Some(socket) = ws.get() {
             let msg = ClientMessage::JoinBreakoutRoom(room_id);
@@ -590,53 +1012,99 @@ pub fn use_room_state() -> RoomState {
         }
     });
 
-    let toggle_camera = Callback::new(move |_: ()| {
-        if local_stream.get().is_some() {
-            // Turn off
-            // Stop tracks
-            if let Some(stream) = local_stream.get() {
-                let tracks = stream.get_tracks();
-                for i in 0..tracks.length() {
-                    if let Ok(track) = tracks.get(i).dyn_into::<web_sys::MediaStreamTrack>() {
-                        track.stop();
-                    }
+    let stop_camera = move || {
+        if let Some(stream) = local_stream.get_untracked() {
+            let tracks = stream.get_tracks();
+            for i in 0..tracks.length() {
+                if let Ok(track) = tracks.get(i).dyn_into::<web_sys::MediaStreamTrack>() {
+                    track.stop();
                 }
             }
-            set_local_stream.set(None);
-            set_audio_monitor.set(None);
-        } else {
-            // Turn on
-            spawn_local(async move {
-                if let Ok(stream) = get_user_media(None, None).await {
-                    // Apply existing mute state to new stream
-                    if is_muted.get_untracked() {
-                        let audio_tracks = stream.get_audio_tracks();
-                        for i in 0..audio_tracks.length() {
-                            if let Ok(track) = audio_tracks.get(i).dyn_into::<web_sys::MediaStreamTrack>() {
-                                track.set_enabled(false);
-                            }
+        }
+        set_local_stream.set(None);
+        set_audio_monitor.set(None);
+    };
+
+    let start_camera = move || {
+        spawn_local(async move {
+            if let Ok(stream) = get_user_media(None, None, video_quality.get_untracked()).await {
+                // Apply existing mute state to new stream
+                if is_muted.get_untracked() {
+                    let audio_tracks = stream.get_audio_tracks();
+                    for i in 0..audio_tracks.length() {
+                        if let Ok(track) = audio_tracks.get(i).dyn_into::<web_sys::MediaStreamTrack>() {
+                            track.set_enabled(false);
                         }
                     }
+                }
 
-                    set_local_stream.set(Some(stream.clone()));
-
-                    let on_speaking = Box::new(move |is_speaking: bool| {
-                        if let Some(socket) = ws.get_untracked() {
-                             let msg = ClientMessage::Speaking(is_speaking);
-                             if let Ok(json) = serde_json::to_string(&msg) {
-                                 let _ = socket.send_with_str(&json);
-                             }
-                        }
-                    });
+                set_local_stream.set(Some(stream.clone()));
 
-                    if let Ok(monitor) = AudioMonitor::new(&stream, on_speaking) {
-                        set_audio_monitor.set(Some(monitor));
+                let on_speaking = Box::new(move |is_speaking: bool| {
+                    if let Some(socket) = ws.get_untracked() {
+                         let msg = ClientMessage::Speaking(is_speaking);
+                         if let Ok(json) = serde_json::to_string(&msg) {
+                             let _ = socket.send_with_str(&json);
+                         }
                     }
+                });
+
+                if let Ok(monitor) = AudioMonitor::new(&stream, on_speaking) {
+                    set_audio_monitor.set(Some(monitor));
                 }
-            });
+            }
+        });
+    };
+
+    let toggle_camera = Callback::new(move |_: ()| {
+        if local_stream.get().is_some() {
+            stop_camera();
+        } else {
+            start_camera();
+        }
+    });
+
+    let set_video_quality = Callback::new(move |quality: VideoQuality| {
+        let previous = video_quality.get_untracked();
+        if quality == previous {
+            return;
+        }
+        set_video_quality_signal.set(quality);
+
+        if let Some(stream) = local_stream.get_untracked() {
+            if previous == VideoQuality::AudioOnly {
+                // The camera was released, so it has to be captured again
+                stop_camera();
+                start_camera();
+            } else if let Err(e) = apply_video_quality(&stream, quality) {
+                web_sys::console::error_1(&e);
+            }
+        }
+
+        if let Some(socket) = ws.get_untracked() {
+            let msg = ClientMessage::SetVideoQuality(quality);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
         }
     });
 
+    // Cap what we send to each peer at the level that participant asked to receive
+    create_effect(move |_| {
+        peer_mesh.negotiated.track();
+        local_stream.track(); // New tracks start without the cap
+        let list = participants.get();
+        peer_connections.with_value(|peers| {
+            for p in list {
+                if let Some(pc) = peers.get(&p.id) {
+                    if let Err(e) = limit_video_bitrate(pc, p.video_quality) {
+                        web_sys::console::error_1(&e);
+                    }
+                }
+            }
+        });
+    });
+
     let start_share_video = Callback::new(move |url: String| {
         if let Some(socket) = ws.get() {
             let msg = ClientMessage::StartShareVideo(url);
@@ -669,19 +1137,135 @@ pub fn use_room_state() -> RoomState {
         }
     });
 
-    let send_ping = Callback::new(move |_: ()| {
+    let on_media_stats = Callback::new(move |stats: HashMap<String, MediaStats>| {
+        // Only report once there is something to report on
+        if !stats.is_empty() {
+            let summary = ConnectionQuality::summarize(stats.values());
+            let lower = loss_monitor.try_update_value(|m| m.downgrade(&summary, video_quality.get_untracked())).flatten();
+
+            if let Some(socket) = ws.get_untracked() {
+                let msg = ClientMessage::ConnectionQuality(summary);
+                if let Ok(json) = serde_json::to_string(&msg) {
+                    let _ = socket.send_with_str(&json);
+                }
+            }
+
+            if let Some(lower) = lower {
+                add_toast(format!("Poor connection, switching to {} quality", lower.label()), ToastType::Info);
+                set_video_quality.call(lower);
+            }
+        }
+        set_media_stats.set(stats);
+    });
+
+    // The host's browser records the meeting while RoomConfig says so
+    create_effect(move |was_recording: Option<bool>| {
+        let should_record = is_recording.get() && is_host.get();
+        if should_record && was_recording != Some(true) {
+            // Chunks are also streamed to the server so the recording survives this tab
+            let pathname = web_sys::window().and_then(|w| w.location().pathname().ok()).unwrap_or_default();
+            let upload = room_id_from_path(&pathname)
+                .zip(session_token.get_untracked())
+                .map(|(room_id, token)| RecordingUpload { room_id, token });
+            let on_upload_failed = Callback::new(move |_| {
+                add_toast("Uploading the recording failed; it is still saved in this browser".to_string(), ToastType::Error);
+            });
+            spawn_local(async move {
+                match MeetingRecorder::start(peer_connections, local_stream.get_untracked(), recording_layout.get_untracked(), upload, on_upload_failed).await {
+                    // Recording may have been switched off while we were setting up
+                    Ok(recorder) if !is_recording.get_untracked() => recorder.stop(),
+                    Ok(recorder) => set_meeting_recorder.set(Some(recorder)),
+                    Err(e) => {
+                        web_sys::console::error_1(&e);
+                        add_toast("Could not start local recording".to_string(), ToastType::Error);
+                    }
+                }
+            });
+        } else if !should_record && was_recording == Some(true) {
+            set_meeting_recorder.update(|r| {
+                if let Some(recorder) = r.take() {
+                    recorder.stop();
+                }
+            });
+        }
+        should_record
+    });
+
+    // Answers given on the prejoin screen are sent once we have joined
+    create_effect(move |_| {
+        if let (Some(_), Some(accepted)) = (my_id.get(), recording_consent.get()) {
+            if is_recording.get_untracked() {
+                if let Some(socket) = ws.get_untracked() {
+                    let msg = ClientMessage::RecordingConsent(accepted);
+                    if let Ok(json) = serde_json::to_string(&msg) {
+                        let _ = socket.send_with_str(&json);
+                    }
+                }
+            }
+        }
+    });
+
+    let give_recording_consent = Callback::new(move |accepted: bool| {
+        set_recording_consent.set(Some(accepted));
+    });
+
+    let toggle_auto_mute_decliners = Callback::new(move |_: ()| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::ToggleAutoMuteDecliners;
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let set_chat_mode = Callback::new(move |mode: ChatMode| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::SetChatMode(mode);
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let set_chat_slow_mode = Callback::new(move |secs: u32| {
         if let Some(socket) = ws.get() {
-            set_last_ping_time.set(js_sys::Date::now());
-            let msg = ClientMessage::Ping;
+            let msg = ClientMessage::SetChatSlowMode(secs);
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
             }
         }
     });
 
+    let set_chat_muted = Callback::new(move |(participant_id, muted): (String, bool)| {
+        if let Some(socket) = ws.get() {
+            let msg = ClientMessage::SetChatMuted { participant_id, muted };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let send_command = Callback::new(move |msg: ClientMessage| {
+        if let Some(socket) = ws.get() {
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket.send_with_str(&json);
+            }
+        }
+    });
+
+    let set_recording_layout = Callback::new(move |layout: RecordingLayout| {
+        set_recording_layout_signal.set(layout);
+        meeting_recorder.with_untracked(|r| {
+            if let Some(recorder) = r {
+                recorder.set_layout(layout);
+            }
+        });
+    });
+
     RoomState {
         connection_state: current_state,
         messages,
+        chat_has_more,
         participants,
         knocking_participants,
         is_connected,
@@ -690,12 +1274,17 @@ pub fn use_room_state() -> RoomState {
         is_recording,
         show_settings,
         show_polls,
+        show_qna,
         show_shortcuts,
         polls,
+        my_poll_votes,
+        quiz_leaderboard,
+        questions,
         last_reaction,
         show_whiteboard,
-        whiteboard_history,
+        whiteboard_elements,
         my_id,
+        session_token,
         typing_users,
         is_host,
         host_id,
@@ -709,15 +1298,52 @@ pub fn use_room_state() -> RoomState {
         speaking_peers,
         show_speaker_stats,
         show_virtual_background,
-        rtt,
+        peer_connections,
+        remote_streams,
+        media_stats,
+        quality_reports,
+        video_quality,
+        recording_layout,
+        needs_recording_consent,
+        auto_mute_decliners,
+        poll_vote_changes,
+        chat_mode,
+        chat_slow_mode_secs,
+        chat_muted,
+        chat_restriction,
+        timer_ends_at,
+        recording_consents,
+        captions,
+        show_captions,
+        meeting_ended,
+        show_chat,
+        mention_count,
+        unread_count,
+        notification_settings,
         set_show_settings,
         set_show_polls,
+        set_show_qna,
         set_show_shortcuts,
         set_show_whiteboard,
         set_show_speaker_stats,
         set_show_virtual_background,
-        send_ping,
+        set_show_captions,
+        set_show_chat,
+        set_notification_settings,
+        on_media_stats,
+        set_video_quality,
+        set_recording_layout,
+        give_recording_consent,
+        toggle_auto_mute_decliners,
+        set_chat_mode,
+        set_chat_slow_mode,
+        set_chat_muted,
+        send_command,
         send_message,
+        load_older_messages,
+        edit_message,
+        delete_message,
+        react_to_message,
         toggle_lock,
         toggle_lobby,
         toggle_recording,
@@ -731,7 +1357,19 @@ pub fn use_room_state() -> RoomState {
         kick_participant,
         create_poll,
         vote_poll,
-        send_draw,
+        close_poll,
+        reopen_poll,
+        delete_poll,
+        toggle_poll_vote_changes,
+        reveal_poll_answers,
+        ask_question,
+        upvote_question,
+        set_question_answered,
+        set_question_pinned,
+        dismiss_question,
+        add_whiteboard_element,
+        update_whiteboard_element,
+        delete_whiteboard_element,
         set_is_typing,
         create_breakout_room,
         join_breakout_room,
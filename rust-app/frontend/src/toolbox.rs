@@ -9,8 +9,11 @@ pub fn Toolbox(
     on_toggle_lock: Callback<()>,
     on_toggle_lobby: Callback<()>,
     on_toggle_recording: Callback<()>,
+    auto_mute_decliners: Signal<bool>,
+    on_toggle_auto_mute_decliners: Callback<()>,
     on_settings: Callback<()>,
     on_polls: Callback<()>,
+    on_qna: Callback<()>,
     on_shortcuts: Callback<()>,
     on_speaker_stats: Callback<()>,
     on_virtual_background: Callback<()>,
@@ -20,6 +23,13 @@ pub fn Toolbox(
     on_stop_share_video: Callback<()>,
     is_sharing_video: Signal<bool>,
     on_whiteboard: Callback<()>,
+    show_captions: ReadSignal<bool>,
+    on_captions: Callback<()>,
+    show_chat: ReadSignal<bool>,
+    mention_count: ReadSignal<usize>,
+    unread_count: ReadSignal<usize>,
+    knocking_count: Signal<usize>,
+    on_chat: Callback<()>,
     on_reaction: Callback<String>,
     on_toggle_camera: Callback<()>,
     on_toggle_mic: Callback<()>,
@@ -95,6 +105,44 @@ pub fn Toolbox(
             >
                 "Whiteboard"
             </button>
+            <button
+                on:click=move |_| on_captions.call(())
+                style=move || format!(
+                    "padding: 8px 16px; background-color: {}; color: white; border: none; cursor: pointer; border-radius: 4px;",
+                    if show_captions.get() { "#20c997" } else { "#6c757d" }
+                )
+                title="Show or hide closed captions"
+            >
+                "CC"
+            </button>
+            <button
+                on:click=move |_| on_chat.call(())
+                style=move || format!(
+                    "position: relative; padding: 8px 16px; background-color: {}; color: white; border: none; cursor: pointer; border-radius: 4px;",
+                    if show_chat.get() { "#007bff" } else { "#6c757d" }
+                )
+                title="Show or hide the chat"
+            >
+                "Chat"
+                <Show when=move || { unread_count.get() > 0 }>
+                    <span
+                        class="unread-badge"
+                        style="position: absolute; top: -6px; left: -6px; min-width: 18px; padding: 0 4px; border-radius: 9px; background: #17a2b8; color: white; font-size: 0.75em; line-height: 18px;"
+                        title="Unread messages"
+                    >
+                        {move || unread_count.get()}
+                    </span>
+                </Show>
+                <Show when=move || { mention_count.get() > 0 }>
+                    <span
+                        class="mention-badge"
+                        style="position: absolute; top: -6px; right: -6px; min-width: 18px; padding: 0 4px; border-radius: 9px; background: #dc3545; color: white; font-size: 0.75em; line-height: 18px;"
+                        title="Mentions while the chat was hidden"
+                    >
+                        {move || format!("@{}", mention_count.get())}
+                    </span>
+                </Show>
+            </button>
             <button
                 on:click=move |_| on_raise_hand.call(())
                 style="padding: 8px 16px; background-color: #ffc107; color: black; border: none; cursor: pointer; border-radius: 4px;"
@@ -114,9 +162,18 @@ pub fn Toolbox(
                 </button>
                 <button
                     on:click=move |_| on_toggle_lobby.call(())
-                    style="padding: 8px 16px; background-color: #20c997; color: white; border: none; cursor: pointer; border-radius: 4px;"
+                    style="position: relative; padding: 8px 16px; background-color: #20c997; color: white; border: none; cursor: pointer; border-radius: 4px;"
                 >
                     {move || if is_lobby_enabled.get() { "Disable Lobby" } else { "Enable Lobby" }}
+                    <Show when=move || { knocking_count.get() > 0 }>
+                        <span
+                            class="knocking-badge"
+                            style="position: absolute; top: -6px; right: -6px; min-width: 18px; padding: 0 4px; border-radius: 9px; background: #dc3545; color: white; font-size: 0.75em; line-height: 18px;"
+                            title="Waiting in the lobby"
+                        >
+                            {move || knocking_count.get()}
+                        </span>
+                    </Show>
                 </button>
                 <button
                     on:click=move |_| on_toggle_recording.call(())
@@ -124,6 +181,13 @@ pub fn Toolbox(
                 >
                     {move || if is_recording.get() { "Stop Recording" } else { "Start Recording" }}
                 </button>
+                <button
+                    on:click=move |_| on_toggle_auto_mute_decliners.call(())
+                    style="padding: 8px 16px; background-color: #6c757d; color: white; border: none; cursor: pointer; border-radius: 4px;"
+                    title="Mute participants who decline to be recorded"
+                >
+                    {move || if auto_mute_decliners.get() { "Decliners: Muted" } else { "Decliners: Not Muted" }}
+                </button>
             </Show>
             <button
                 on:click=move |_| on_speaker_stats.call(())
@@ -149,6 +213,12 @@ pub fn Toolbox(
             >
                 "Polls"
             </button>
+            <button
+                on:click=move |_| on_qna.call(())
+                style="padding: 8px 16px; background-color: #17a2b8; color: white; border: none; cursor: pointer; border-radius: 4px;"
+            >
+                "Q&A"
+            </button>
             <button
                 on:click=move |_| on_shortcuts.call(())
                 style="padding: 8px 16px; background-color: #666; color: white; border: none; cursor: pointer; border-radius: 4px;"
@@ -3,6 +3,15 @@ pub fn create_room_url(name: &str) -> String {
     format!("/room/{}", encoded_name)
 }
 
+/// Extracts the room ID from a `/room/<id>` path.
+pub fn room_id_from_path(path: &str) -> Option<String> {
+    let encoded = path.strip_prefix("/room/")?.split('/').next()?;
+    if encoded.is_empty() {
+        return None;
+    }
+    urlencoding::decode(encoded).ok().map(|id| id.into_owned())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -12,4 +21,12 @@ mod tests {
         assert_eq!(create_room_url("My Room"), "/room/My%20Room");
         assert_eq!(create_room_url("Test"), "/room/Test");
     }
+
+    #[test]
+    fn test_room_id_from_path() {
+        assert_eq!(room_id_from_path("/room/My%20Room"), Some("My Room".to_string()));
+        assert_eq!(room_id_from_path("/room/Test/"), Some("Test".to_string()));
+        assert_eq!(room_id_from_path("/room/"), None);
+        assert_eq!(room_id_from_path("/"), None);
+    }
 }
@@ -1,102 +1,395 @@
 use leptos::*;
 use leptos::html::Canvas;
-use shared::DrawAction;
-use wasm_bindgen::JsCast;
+use shared::whiteboard::{element_at, wrap_text, Point, Shape, WhiteboardElement, MAX_PATH_POINTS, STICKY_NOTE_FONT_SIZE, STICKY_NOTE_SIZE, STROKE_WIDTHS};
+use std::collections::HashSet;
+use std::f64::consts::{FRAC_PI_6, PI, TAU};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::CanvasRenderingContext2d;
+
+const HIT_TOLERANCE: f64 = 4.0;
+const LINE_HEIGHT: f64 = 1.2; // Relative to the font size
+const STICKY_NOTE_COLOR: &str = "#fff59d";
+const STICKY_NOTE_PADDING: f64 = 10.0;
+const STICKY_NOTE_CHARS_PER_LINE: usize = 16;
+const SELECTION_COLOR: &str = "#007bff";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    Select,
+    Pen,
+    Rect,
+    Ellipse,
+    Arrow,
+    Text,
+    StickyNote,
+    Eraser,
+}
+
+const TOOLS: [(Tool, &str); 8] = [
+    (Tool::Select, "Select"),
+    (Tool::Pen, "Pen"),
+    (Tool::Rect, "Rectangle"),
+    (Tool::Ellipse, "Ellipse"),
+    (Tool::Arrow, "Arrow"),
+    (Tool::Text, "Text"),
+    (Tool::StickyNote, "Sticky note"),
+    (Tool::Eraser, "Eraser"),
+];
+
+impl Tool {
+    // The shape a drag with this tool starts from, for tools that draw by dragging
+    fn start_shape(self, p: Point) -> Option<Shape> {
+        match self {
+            Tool::Pen => Some(Shape::Path { points: vec![p] }),
+            Tool::Rect => Some(Shape::Rect { from: p, to: p }),
+            Tool::Ellipse => Some(Shape::Ellipse { from: p, to: p }),
+            Tool::Arrow => Some(Shape::Arrow { from: p, to: p }),
+            _ => None,
+        }
+    }
+}
+
+// Grows a shape being drawn as the pointer moves to `p`
+fn extend_shape(shape: &mut Shape, p: Point) {
+    match shape {
+        Shape::Path { points } => {
+            if points.len() < MAX_PATH_POINTS {
+                points.push(p);
+            }
+        },
+        Shape::Rect { to, .. } | Shape::Ellipse { to, .. } | Shape::Arrow { to, .. } => *to = p,
+        Shape::Text { .. } | Shape::StickyNote { .. } => {},
+    }
+}
+
+// A click without a drag draws a dot with the pen but nothing with the other tools
+fn is_worth_keeping(shape: &Shape) -> bool {
+    match shape {
+        Shape::Rect { from, to } | Shape::Ellipse { from, to } | Shape::Arrow { from, to } => from != to,
+        _ => true,
+    }
+}
+
+fn replace_text(shape: &mut Shape, new_text: String) {
+    if let Shape::Text { text, .. } | Shape::StickyNote { text, .. } = shape {
+        *text = new_text;
+    }
+}
+
+fn text_font_size(width: f64) -> f64 {
+    12.0 + width * 2.0
+}
+
+/// What to show: the room's elements with our own changes applied before the server confirms them.
+/// Pending elements without an ID are ones we added; the rest are ones we moved or edited.
+fn visible_elements(elements: &[WhiteboardElement], pending: &[WhiteboardElement]) -> Vec<WhiteboardElement> {
+    elements
+        .iter()
+        .map(|e| pending.iter().find(|p| p.id == e.id).unwrap_or(e).clone())
+        .chain(pending.iter().filter(|p| p.id.is_empty()).cloned())
+        .collect()
+}
+
+// Whether the server has caught up with a pending change of ours, or made it moot
+fn is_settled(pending: &WhiteboardElement, elements: &[WhiteboardElement], my_id: &str) -> bool {
+    if pending.id.is_empty() {
+        elements.iter().any(|e| e.sender_id == my_id && e.shape == pending.shape && e.color == pending.color)
+    } else {
+        elements.iter().all(|e| e.id != pending.id) || elements.contains(pending)
+    }
+}
+
+fn ask_text(message: &str, current: &str) -> Option<String> {
+    web_sys::window()?.prompt_with_message_and_default(message, current).ok().flatten()
+}
+
+fn context(canvas: &web_sys::HtmlCanvasElement) -> Option<CanvasRenderingContext2d> {
+    canvas.get_context("2d").ok().flatten()?.dyn_into().ok()
+}
+
+fn draw_lines(ctx: &CanvasRenderingContext2d, lines: &[String], at: Point, font_size: f64) {
+    ctx.set_font(&format!("{font_size}px sans-serif"));
+    ctx.set_text_baseline("top");
+    for (i, line) in lines.iter().enumerate() {
+        let _ = ctx.fill_text(line, at.x, at.y + i as f64 * font_size * LINE_HEIGHT);
+    }
+}
+
+#[allow(deprecated)]
+fn draw_element(ctx: &CanvasRenderingContext2d, element: &WhiteboardElement) {
+    let color = JsValue::from_str(&element.color);
+    ctx.set_stroke_style(&color);
+    ctx.set_fill_style(&color);
+    ctx.set_line_width(element.width);
+    ctx.set_line_cap("round");
+    ctx.set_line_join("round");
+    ctx.begin_path();
+    match &element.shape {
+        Shape::Path { points } => {
+            let Some(first) = points.first() else { return };
+            ctx.move_to(first.x, first.y);
+            // Starting at the first point too, so a lone point still shows as a dot
+            for p in points {
+                ctx.line_to(p.x, p.y);
+            }
+            ctx.stroke();
+        },
+        Shape::Rect { from, to } => ctx.stroke_rect(from.x, from.y, to.x - from.x, to.y - from.y),
+        Shape::Ellipse { from, to } => {
+            let (rx, ry) = ((to.x - from.x).abs() / 2.0, (to.y - from.y).abs() / 2.0);
+            let _ = ctx.ellipse((from.x + to.x) / 2.0, (from.y + to.y) / 2.0, rx, ry, 0.0, 0.0, TAU);
+            ctx.stroke();
+        },
+        Shape::Arrow { from, to } => {
+            ctx.move_to(from.x, from.y);
+            ctx.line_to(to.x, to.y);
+            let angle = (to.y - from.y).atan2(to.x - from.x);
+            let head = 10.0 + element.width * 2.0;
+            for side in [angle + PI - FRAC_PI_6, angle + PI + FRAC_PI_6] {
+                ctx.move_to(to.x, to.y);
+                ctx.line_to(to.x + head * side.cos(), to.y + head * side.sin());
+            }
+            ctx.stroke();
+        },
+        Shape::Text { at, text, font_size } => {
+            let lines: Vec<String> = text.lines().map(str::to_string).collect();
+            draw_lines(ctx, &lines, *at, *font_size);
+        },
+        Shape::StickyNote { at, text } => {
+            ctx.set_fill_style(&JsValue::from_str(STICKY_NOTE_COLOR));
+            ctx.fill_rect(at.x, at.y, STICKY_NOTE_SIZE, STICKY_NOTE_SIZE);
+            ctx.set_fill_style(&color);
+            // Text that doesn't fit on the note is cut off
+            let max_lines = ((STICKY_NOTE_SIZE - 2.0 * STICKY_NOTE_PADDING) / (STICKY_NOTE_FONT_SIZE * LINE_HEIGHT)) as usize;
+            let mut lines = wrap_text(text, STICKY_NOTE_CHARS_PER_LINE);
+            lines.truncate(max_lines);
+            draw_lines(ctx, &lines, Point::new(at.x + STICKY_NOTE_PADDING, at.y + STICKY_NOTE_PADDING), STICKY_NOTE_FONT_SIZE);
+        },
+    }
+}
+
+#[allow(deprecated)]
+fn draw_selection(ctx: &CanvasRenderingContext2d, element: &WhiteboardElement) {
+    let (min, max) = element.shape.bounds();
+    let margin = element.width / 2.0 + HIT_TOLERANCE;
+    ctx.set_stroke_style(&JsValue::from_str(SELECTION_COLOR));
+    ctx.set_line_width(1.0);
+    let _ = ctx.set_line_dash(&js_sys::Array::of2(&4.0.into(), &4.0.into()));
+    ctx.stroke_rect(min.x - margin, min.y - margin, max.x - min.x + 2.0 * margin, max.y - min.y + 2.0 * margin);
+    let _ = ctx.set_line_dash(&js_sys::Array::new());
+}
+
+fn event_point(ev: &web_sys::MouseEvent) -> Point {
+    Point::new(ev.offset_x() as f64, ev.offset_y() as f64)
+}
 
 #[component]
 pub fn Whiteboard(
-    on_draw: Callback<DrawAction>,
-    history: ReadSignal<Vec<DrawAction>>,
+    elements: ReadSignal<Vec<WhiteboardElement>>,
     my_id: ReadSignal<Option<String>>,
+    on_add: Callback<WhiteboardElement>,
+    on_update: Callback<WhiteboardElement>,
+    on_delete: Callback<String>,
 ) -> impl IntoView {
     let canvas_ref = create_node_ref::<Canvas>();
-    let (is_drawing, set_is_drawing) = create_signal(false);
-    let (last_pos, set_last_pos) = create_signal(None::<(f64, f64)>);
+    let (tool, set_tool) = create_signal(Tool::Pen);
     let (color, set_color) = create_signal("#000000".to_string());
+    let (width, set_width) = create_signal(2.0);
+    let draft = create_rw_signal(None::<WhiteboardElement>); // Shape being drawn
+    let pending = create_rw_signal(Vec::<WhiteboardElement>::new()); // Sent, not yet confirmed
+    let selected = create_rw_signal(None::<String>);
+    let dragging = create_rw_signal(None::<(String, Point)>); // Selected element and where the pointer was
+    let erased = create_rw_signal(None::<HashSet<String>>); // Set while erasing, so each element is deleted once
 
-    // Draw an action on the canvas
-    let draw_on_canvas = move |action: &DrawAction| {
-        if let Some(canvas) = canvas_ref.get() {
-            let ctx = canvas
-                .get_context("2d")
-                .unwrap()
-                .unwrap()
-                .dyn_into::<web_sys::CanvasRenderingContext2d>()
-                .unwrap();
-
-            ctx.begin_path();
-            ctx.set_line_width(action.width);
-            #[allow(deprecated)]
-            ctx.set_stroke_style(&wasm_bindgen::JsValue::from_str(&action.color));
-            ctx.move_to(action.start_x, action.start_y);
-            ctx.line_to(action.end_x, action.end_y);
-            ctx.stroke();
+    let visible = move || pending.with_untracked(|p| elements.with_untracked(|e| visible_elements(e, p)));
+
+    // Drop our own changes once the server has echoed them back
+    create_effect(move |_| {
+        let my_id = my_id.get_untracked().unwrap_or_default();
+        elements.with(|elements| {
+            if pending.with_untracked(|p| p.iter().any(|p| is_settled(p, elements, &my_id))) {
+                pending.update(|p| p.retain(|p| !is_settled(p, elements, &my_id)));
+            }
+        });
+    });
+
+    // Redraw everything whenever anything changes
+    create_effect(move |_| {
+        let shown = pending.with(|p| elements.with(|e| visible_elements(e, p)));
+        let draft = draft.get();
+        let selected = selected.get();
+        let Some(canvas) = canvas_ref.get() else { return };
+        let Some(ctx) = context(&canvas) else { return };
+        ctx.clear_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
+        for element in shown.iter().chain(draft.iter()) {
+            draw_element(&ctx, element);
+        }
+        if let Some(element) = selected.and_then(|id| shown.iter().find(|e| e.id == id)) {
+            draw_selection(&ctx, element);
         }
+    });
+
+    let new_element = move |shape: Shape| WhiteboardElement {
+        id: String::new(),
+        shape,
+        color: color.get_untracked(),
+        width: width.get_untracked(),
+        sender_id: my_id.get_untracked().unwrap_or_default(),
     };
 
-    // React to history (both initial load and updates)
-    create_effect(move |last_len: Option<usize>| {
-        let actions = history.get();
-        let len = actions.len();
-        let start = last_len.unwrap_or(0);
-        let is_initial_load = last_len.is_none();
-
-        for i in start..len {
-            if let Some(action) = actions.get(i) {
-                // Filter local echo only for live updates, not initial load
-                if !is_initial_load {
-                    if let Some(id) = my_id.get() {
-                        if action.sender_id == id {
-                            continue;
-                        }
-                    }
+    let submit = move |element: WhiteboardElement| {
+        if element.validate().is_ok() {
+            pending.update(|p| p.push(element.clone()));
+            on_add.call(element);
+        }
+    };
+
+    let submit_change = move |element: WhiteboardElement| {
+        pending.update(|p| {
+            p.retain(|e| e.id != element.id);
+            p.push(element.clone());
+        });
+        on_update.call(element);
+    };
+
+    let erase_at = move |p: Point| {
+        let tolerance = width.get_untracked() / 2.0 + HIT_TOLERANCE;
+        let hit = erased.with_untracked(|erased| {
+            let erased = erased.as_ref()?;
+            elements.with_untracked(|e| e.iter().rev().find(|e| !erased.contains(&e.id) && e.hit(p, tolerance)).map(|e| e.id.clone()))
+        });
+        if let Some(id) = hit {
+            erased.update(|erased| {
+                if let Some(erased) = erased {
+                    erased.insert(id.clone());
                 }
-                draw_on_canvas(action);
-            }
+            });
+            on_delete.call(id);
         }
-        len
-    });
+    };
+
+    let delete_selected = move || {
+        if let Some(id) = selected.get_untracked() {
+            selected.set(None);
+            on_delete.call(id);
+        }
+    };
 
     let on_mousedown = move |ev: web_sys::MouseEvent| {
-        set_is_drawing.set(true);
-        set_last_pos.set(Some((ev.offset_x() as f64, ev.offset_y() as f64)));
+        let p = event_point(&ev);
+        match tool.get_untracked() {
+            Tool::Select => {
+                let hit = element_at(&visible(), p, HIT_TOLERANCE).map(|e| e.id.clone()).filter(|id| !id.is_empty());
+                dragging.set(hit.clone().map(|id| (id, p)));
+                selected.set(hit);
+            },
+            Tool::Eraser => {
+                erased.set(Some(HashSet::new()));
+                erase_at(p);
+            },
+            Tool::Text => {
+                if let Some(text) = ask_text("Text", "").filter(|t| !t.trim().is_empty()) {
+                    let font_size = text_font_size(width.get_untracked());
+                    submit(new_element(Shape::Text { at: p, text, font_size }));
+                }
+            },
+            Tool::StickyNote => {
+                if let Some(text) = ask_text("Sticky note", "") {
+                    submit(new_element(Shape::StickyNote { at: p, text }));
+                }
+            },
+            tool => draft.set(tool.start_shape(p).map(new_element)),
+        }
+    };
+
+    let on_mousemove = move |ev: web_sys::MouseEvent| {
+        let p = event_point(&ev);
+        if let Some((id, last)) = dragging.get_untracked() {
+            let moved = visible().into_iter().find(|e| e.id == id);
+            if let Some(mut moved) = moved {
+                moved.shape.translate(p.x - last.x, p.y - last.y);
+                pending.update(|pending| {
+                    pending.retain(|e| e.id != id);
+                    pending.push(moved);
+                });
+                dragging.set(Some((id, p)));
+            }
+        } else if erased.with_untracked(Option::is_some) {
+            erase_at(p);
+        } else if draft.with_untracked(Option::is_some) {
+            draft.update(|d| {
+                if let Some(d) = d {
+                    extend_shape(&mut d.shape, p);
+                }
+            });
+        }
     };
 
     let on_mouseup = move |_| {
-        set_is_drawing.set(false);
-        set_last_pos.set(None);
+        if let Some((id, _)) = dragging.get_untracked() {
+            dragging.set(None);
+            // Only sent once the move is over, rather than for every step of it
+            if let Some(moved) = pending.with_untracked(|p| p.iter().find(|e| e.id == id).cloned()) {
+                on_update.call(moved);
+            }
+        }
+        erased.set(None);
+        if let Some(element) = draft.get_untracked() {
+            draft.set(None);
+            if is_worth_keeping(&element.shape) {
+                submit(element);
+            }
+        }
     };
 
-    let on_mousemove = move |ev: web_sys::MouseEvent| {
-        if is_drawing.get() {
-            if let Some((start_x, start_y)) = last_pos.get() {
-                let end_x = ev.offset_x() as f64;
-                let end_y = ev.offset_y() as f64;
-
-                let action = DrawAction {
-                    color: color.get(),
-                    width: 2.0,
-                    start_x,
-                    start_y,
-                    end_x,
-                    end_y,
-                    sender_id: my_id.get().unwrap_or_default(),
-                };
-
-                // Draw locally immediately
-                draw_on_canvas(&action);
-
-                // Send to server
-                on_draw.call(action);
-
-                set_last_pos.set(Some((end_x, end_y)));
+    // Double-click text or a sticky note to edit what it says
+    let on_dblclick = move |ev: web_sys::MouseEvent| {
+        if tool.get_untracked() != Tool::Select {
+            return;
+        }
+        let hit = element_at(&visible(), event_point(&ev), HIT_TOLERANCE).filter(|e| !e.id.is_empty() && e.shape.text().is_some()).cloned();
+        if let Some(mut element) = hit {
+            let current = element.shape.text().unwrap_or_default().to_string();
+            if let Some(text) = ask_text("Edit text", &current).filter(|t| *t != current) {
+                replace_text(&mut element.shape, text);
+                if element.validate().is_ok() {
+                    submit_change(element);
+                }
             }
         }
     };
 
+    let on_keydown = move |ev: web_sys::KeyboardEvent| {
+        if matches!(ev.key().as_str(), "Delete" | "Backspace") {
+            ev.prevent_default();
+            delete_selected();
+        }
+    };
+
     view! {
         <div class="whiteboard-container" style="position: absolute; top: 0; left: 0; width: 100%; height: 100%; background: rgba(255, 255, 255, 0.9); z-index: 10; display: flex; flex-direction: column; align-items: center;">
-            <div class="controls" style="margin: 10px; display: flex; gap: 10px; align-items: center;">
+            <div class="controls" style="margin: 10px; display: flex; gap: 10px; align-items: center; flex-wrap: wrap;">
+                <div class="tools" style="display: flex; gap: 4px;">
+                    {TOOLS.iter().map(|(t, label)| {
+                        let t = *t;
+                        view! {
+                            <button
+                                on:click=move |_| {
+                                    set_tool.set(t);
+                                    if t != Tool::Select {
+                                        selected.set(None);
+                                    }
+                                }
+                                style=move || format!(
+                                    "padding: 4px 10px; border: 1px solid #ccc; border-radius: 4px; cursor: pointer; background: {};",
+                                    if tool.get() == t { "#e7f1ff" } else { "white" },
+                                )
+                            >
+                                {*label}
+                            </button>
+                        }
+                    }).collect_view()}
+                </div>
                 <label>"Color: "</label>
                 <input
                     type="color"
@@ -104,17 +397,89 @@ pub fn Whiteboard(
                     on:input=move |ev| set_color.set(event_target_value(&ev))
                     style="cursor: pointer;"
                 />
+                <label>
+                    "Width "
+                    <select on:change=move |ev| set_width.set(event_target_value(&ev).parse().unwrap_or(2.0))>
+                        {STROKE_WIDTHS.iter().map(|w| view! {
+                            <option value=w.to_string() selected=move || width.get() == *w>{format!("{w} px")}</option>
+                        }).collect_view()}
+                    </select>
+                </label>
+                <button
+                    on:click=move |_| delete_selected()
+                    disabled=move || selected.with(Option::is_none)
+                    style="padding: 4px 10px; cursor: pointer; color: #dc3545;"
+                >
+                    "Delete"
+                </button>
             </div>
             <canvas
                 _ref=canvas_ref
                 width="800"
                 height="600"
-                style="border: 1px solid black; cursor: crosshair; background: white;"
+                tabindex="0"
+                style=move || format!(
+                    "border: 1px solid black; cursor: {}; background: white;",
+                    if tool.get() == Tool::Select { "default" } else { "crosshair" },
+                )
                 on:mousedown=on_mousedown
                 on:mouseup=on_mouseup
                 on:mouseleave=on_mouseup
                 on:mousemove=on_mousemove
+                on:dblclick=on_dblclick
+                on:keydown=on_keydown
             />
         </div>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(id: &str, x: f64) -> WhiteboardElement {
+        WhiteboardElement {
+            id: id.to_string(),
+            shape: Shape::Rect { from: Point::new(x, 0.0), to: Point::new(x + 10.0, 10.0) },
+            color: "#000000".to_string(),
+            width: 2.0,
+            sender_id: "alice".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_visible_elements_apply_pending_changes() {
+        let elements = vec![rect("a", 0.0), rect("b", 20.0)];
+        let pending = vec![rect("", 40.0), rect("b", 30.0)];
+        let shown = visible_elements(&elements, &pending);
+        assert_eq!(shown, vec![rect("a", 0.0), rect("b", 30.0), rect("", 40.0)]);
+    }
+
+    #[test]
+    fn test_pending_changes_settle() {
+        let elements = vec![rect("a", 0.0), rect("b", 20.0)];
+        // Added: settled once an element with the same shape comes back from us
+        assert!(is_settled(&rect("", 20.0), &elements, "alice"));
+        assert!(!is_settled(&rect("", 20.0), &elements, "bob"));
+        assert!(!is_settled(&rect("", 50.0), &elements, "alice"));
+        // Moved: settled once the server has the new position, or the element is gone
+        assert!(!is_settled(&rect("b", 30.0), &elements, "alice"));
+        assert!(is_settled(&rect("b", 20.0), &elements, "alice"));
+        assert!(is_settled(&rect("c", 30.0), &elements, "alice"));
+    }
+
+    #[test]
+    fn test_drawing_shapes() {
+        let mut shape = Tool::Arrow.start_shape(Point::new(5.0, 5.0)).unwrap();
+        assert!(!is_worth_keeping(&shape));
+        extend_shape(&mut shape, Point::new(50.0, 5.0));
+        assert_eq!(shape, Shape::Arrow { from: Point::new(5.0, 5.0), to: Point::new(50.0, 5.0) });
+        assert!(is_worth_keeping(&shape));
+        assert!(is_worth_keeping(&Tool::Pen.start_shape(Point::new(5.0, 5.0)).unwrap()));
+        assert!(Tool::Text.start_shape(Point::new(5.0, 5.0)).is_none());
+
+        let mut note = Shape::StickyNote { at: Point::new(0.0, 0.0), text: "Old".to_string() };
+        replace_text(&mut note, "New".to_string());
+        assert_eq!(note.text(), Some("New"));
+    }
+}
@@ -0,0 +1,195 @@
+// Slash commands typed in the chat input. Each registry entry turns its arguments into
+// the `ClientMessage` it stands for, so the web client and server-side bots parse alike.
+// The server still checks permissions; the checks here only give an early, inline error.
+use crate::{ClientMessage, Participant, Poll, PollOption, MAX_POLL_OPTIONS, MAX_TIMER_SECS};
+
+const ME_PREFIX: &str = "/me ";
+
+/// Who is typing the command, and who they can name in it.
+pub struct CommandContext<'a> {
+    pub participants: &'a [Participant],
+    pub my_id: Option<&'a str>,
+    pub is_moderator: bool,
+}
+
+// Handled as soon as it is parsed, so the size of `ClientMessage::CreatePoll` doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutcome {
+    Send(ClientMessage),
+    Help(String), // Shown to the user only
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Unknown(String),
+    Usage(&'static str),
+    ModeratorsOnly(&'static str),
+    NoSuchParticipant(String),
+    AmbiguousParticipant(String),
+    InvalidDuration,
+}
+
+impl CommandError {
+    pub fn message(&self) -> String {
+        match self {
+            CommandError::Unknown(name) => format!("Unknown command /{}, type /help for a list", name),
+            CommandError::Usage(usage) => format!("Usage: {}", usage),
+            CommandError::ModeratorsOnly(name) => format!("Only moderators can use /{}", name),
+            CommandError::NoSuchParticipant(name) => format!("No participant named {}", name),
+            CommandError::AmbiguousParticipant(name) => format!("More than one participant is named {}", name),
+            CommandError::InvalidDuration => format!("Timer must be between 1 s and {} h, e.g. 5m or 1h30m", MAX_TIMER_SECS / 3600),
+        }
+    }
+}
+
+type Handler = fn(&Command, &str, &CommandContext) -> Result<CommandOutcome, CommandError>;
+
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+    pub moderator_only: bool,
+    run: Handler,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command { name: "poll", usage: "/poll Question? | Option | Option", description: "Start a poll", moderator_only: true, run: poll },
+    Command { name: "timer", usage: "/timer 5m (or /timer off)", description: "Start a countdown for everyone", moderator_only: true, run: timer },
+    Command { name: "raise", usage: "/raise", description: "Raise or lower your hand", moderator_only: false, run: raise },
+    Command { name: "mute", usage: "/mute @name", description: "Mute someone in the chat", moderator_only: true, run: mute },
+    Command { name: "unmute", usage: "/unmute @name", description: "Let someone chat again", moderator_only: true, run: mute },
+    Command { name: "kick", usage: "/kick @name", description: "Remove someone from the meeting", moderator_only: true, run: kick },
+    Command { name: "me", usage: "/me waves", description: "Describe what you are doing", moderator_only: false, run: me },
+    Command { name: "help", usage: "/help", description: "List the commands", moderator_only: false, run: help },
+];
+
+pub fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+/// What a chat input stands for. `None` when it is a plain message; `//` at the
+/// start sends the text with one slash instead of running a command.
+pub fn parse(input: &str, ctx: &CommandContext) -> Option<Result<CommandOutcome, CommandError>> {
+    let input = input.trim();
+    if let Some(text) = input.strip_prefix("//") {
+        return Some(Ok(chat(format!("/{}", text))));
+    }
+    let rest = input.strip_prefix('/')?;
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let Some(command) = find_command(name) else {
+        return Some(Err(CommandError::Unknown(name.to_string())));
+    };
+    if command.moderator_only && !ctx.is_moderator {
+        return Some(Err(CommandError::ModeratorsOnly(command.name)));
+    }
+    Some((command.run)(command, args.trim(), ctx))
+}
+
+/// The action of a `/me` message, shown as "* Name waves".
+pub fn me_action(content: &str) -> Option<&str> {
+    content.strip_prefix(ME_PREFIX).map(str::trim).filter(|a| !a.is_empty())
+}
+
+fn chat(content: String) -> CommandOutcome {
+    CommandOutcome::Send(ClientMessage::Chat { content, recipient_id: None, attachment: None, parent_id: None })
+}
+
+fn poll(command: &Command, args: &str, _: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    let mut parts = args.split('|').map(str::trim);
+    let question = parts.next().unwrap_or_default().to_string();
+    let options: Vec<String> = parts.filter(|o| !o.is_empty()).map(str::to_string).collect();
+    if question.is_empty() || options.len() < 2 || options.len() > MAX_POLL_OPTIONS {
+        return Err(CommandError::Usage(command.usage));
+    }
+    Ok(CommandOutcome::Send(ClientMessage::CreatePoll(Poll {
+        id: String::new(), // Backend assigns ID
+        question,
+        options: options.into_iter().enumerate().map(|(i, text)| PollOption { id: i as u32, text, ..Default::default() }).collect(),
+        ..Default::default()
+    })))
+}
+
+/// Seconds in `5m`, `90s`, `1h30m` and the like; a bare number is minutes.
+pub fn parse_duration(text: &str) -> Option<u32> {
+    let text = text.trim().to_lowercase();
+    if let Ok(minutes) = text.parse::<u32>() {
+        return minutes.checked_mul(60);
+    }
+    let mut total: u32 = 0;
+    let mut digits = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let value: u32 = digits.parse().ok()?;
+        total = total.checked_add(value.checked_mul(unit)?)?;
+        digits.clear();
+    }
+    digits.is_empty().then_some(total)
+}
+
+fn timer(command: &Command, args: &str, _: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    if args.is_empty() {
+        return Err(CommandError::Usage(command.usage));
+    }
+    if args.eq_ignore_ascii_case("off") || args.eq_ignore_ascii_case("stop") {
+        return Ok(CommandOutcome::Send(ClientMessage::SetTimer(0)));
+    }
+    match parse_duration(args) {
+        Some(secs) if secs > 0 && secs <= MAX_TIMER_SECS => Ok(CommandOutcome::Send(ClientMessage::SetTimer(secs))),
+        _ => Err(CommandError::InvalidDuration),
+    }
+}
+
+fn raise(_: &Command, _: &str, _: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    Ok(CommandOutcome::Send(ClientMessage::ToggleRaiseHand))
+}
+
+/// The other participant named by `@Name`; names may contain spaces and are matched ignoring case.
+fn participant<'a>(command: &Command, args: &str, ctx: &CommandContext<'a>) -> Result<&'a Participant, CommandError> {
+    let name = args.trim_start_matches('@').trim();
+    if name.is_empty() {
+        return Err(CommandError::Usage(command.usage));
+    }
+    let name_lower = name.to_lowercase();
+    let mut found = ctx.participants.iter().filter(|p| Some(p.id.as_str()) != ctx.my_id && p.name.to_lowercase() == name_lower);
+    match (found.next(), found.next()) {
+        (Some(p), None) => Ok(p),
+        (Some(_), Some(_)) => Err(CommandError::AmbiguousParticipant(name.to_string())),
+        (None, _) => Err(CommandError::NoSuchParticipant(name.to_string())),
+    }
+}
+
+fn mute(command: &Command, args: &str, ctx: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    let target = participant(command, args, ctx)?;
+    Ok(CommandOutcome::Send(ClientMessage::SetChatMuted { participant_id: target.id.clone(), muted: command.name == "mute" }))
+}
+
+fn kick(command: &Command, args: &str, ctx: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    let target = participant(command, args, ctx)?;
+    Ok(CommandOutcome::Send(ClientMessage::KickParticipant(target.id.clone())))
+}
+
+fn me(command: &Command, args: &str, _: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    if args.is_empty() {
+        return Err(CommandError::Usage(command.usage));
+    }
+    Ok(chat(format!("{}{}", ME_PREFIX, args)))
+}
+
+fn help(_: &Command, _: &str, ctx: &CommandContext) -> Result<CommandOutcome, CommandError> {
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .filter(|c| ctx.is_moderator || !c.moderator_only)
+        .map(|c| format!("{} - {}", c.usage, c.description))
+        .collect();
+    Ok(CommandOutcome::Help(lines.join("\n")))
+}
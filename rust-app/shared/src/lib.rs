@@ -1,14 +1,30 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
+pub mod commands;
+pub mod markdown;
+pub mod mentions;
+pub mod whiteboard;
+
+use whiteboard::WhiteboardElement;
+
+// Reference to a file uploaded through `POST /api/rooms/:id/files`
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FileAttachment {
+    pub id: String,
     pub filename: String,
-    pub mime_type: String,
+    pub mime_type: String, // Sniffed by the server from the content
     pub size: u64,
-    pub content_base64: String,
+    pub sha256: String, // Hex digest of the content
+    #[serde(default)]
+    pub thumbnail: Option<String>, // Small image data URL made by the sender's browser
 }
 
+// Largest file a participant can upload
+pub const MAX_FILE_BYTES: u64 = 25 * 1024 * 1024;
+// Thumbnails travel inside chat messages, so they must stay small
+pub const MAX_THUMBNAIL_CHARS: usize = 64 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RoomConfig {
     pub room_name: String,
@@ -17,8 +33,23 @@ pub struct RoomConfig {
     pub is_lobby_enabled: bool,
     pub max_participants: u32,
     pub host_id: Option<String>,
+    #[serde(default)]
+    pub auto_mute_decliners: bool, // Mute participants who decline recording consent
+    #[serde(default)]
+    pub chat_mode: ChatMode,
+    #[serde(default)]
+    pub chat_slow_mode_secs: u32, // Minimum interval between a participant's messages; 0 = off
+    #[serde(default)]
+    pub chat_muted: Vec<String>, // Participant IDs that may not send chat messages
+    #[serde(default)]
+    pub timer_ends_at: Option<u64>, // Epoch ms when the moderator's countdown runs out
+    #[serde(default)]
+    pub poll_vote_changes: bool, // Voters may change their vote while a poll is open
 }
 
+// Longest countdown a moderator can start
+pub const MAX_TIMER_SECS: u32 = 4 * 3600;
+
 impl Default for RoomConfig {
     fn default() -> Self {
         Self {
@@ -28,6 +59,74 @@ impl Default for RoomConfig {
             is_lobby_enabled: false,
             max_participants: 100,
             host_id: None,
+            auto_mute_decliners: false,
+            chat_mode: ChatMode::default(),
+            chat_slow_mode_secs: 0,
+            chat_muted: Vec::new(),
+            timer_ends_at: None,
+            poll_vote_changes: false,
+        }
+    }
+}
+
+impl RoomConfig {
+    /// Why `participant_id` may not send chat messages right now, if anything.
+    /// Moderators are never restricted.
+    pub fn chat_restriction(&self, participant_id: &str, is_moderator: bool) -> Option<ChatRestriction> {
+        if is_moderator {
+            return None;
+        }
+        match self.chat_mode {
+            ChatMode::Disabled => Some(ChatRestriction::Disabled),
+            ChatMode::ModeratorsOnly => Some(ChatRestriction::ModeratorsOnly),
+            ChatMode::Everyone if self.chat_muted.iter().any(|id| id == participant_id) => Some(ChatRestriction::Muted),
+            ChatMode::Everyone => None,
+        }
+    }
+
+    /// Milliseconds left before a participant who last sent at `last_sent_ms` may send again.
+    pub fn slow_mode_wait_ms(&self, last_sent_ms: Option<u64>, now_ms: u64) -> u64 {
+        let interval_ms = u64::from(self.chat_slow_mode_secs) * 1000;
+        match last_sent_ms {
+            Some(last) if interval_ms > 0 => (last + interval_ms).saturating_sub(now_ms),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ChatMode {
+    #[default]
+    Everyone,
+    ModeratorsOnly,
+    Disabled,
+}
+
+impl ChatMode {
+    pub const ALL: [ChatMode; 3] = [ChatMode::Everyone, ChatMode::ModeratorsOnly, ChatMode::Disabled];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChatMode::Everyone => "Everyone can chat",
+            ChatMode::ModeratorsOnly => "Moderators only",
+            ChatMode::Disabled => "Chat disabled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRestriction {
+    Disabled,
+    ModeratorsOnly,
+    Muted,
+}
+
+impl ChatRestriction {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ChatRestriction::Disabled => "Chat is disabled",
+            ChatRestriction::ModeratorsOnly => "Only moderators can send messages",
+            ChatRestriction::Muted => "You have been muted in the chat",
         }
     }
 }
@@ -39,26 +138,81 @@ pub struct UserIdentity {
     pub email: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct DrawAction {
-    pub color: String,
-    pub start_x: f64,
-    pub start_y: f64,
-    pub end_x: f64,
-    pub end_y: f64,
-    pub width: f64,
-    #[serde(default)] // For backward compatibility if needed, though we are migrating fresh
-    pub sender_id: String,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ChatMessage {
+    #[serde(default)]
+    pub id: String, // Assigned by the server
     pub user_id: String,
     pub content: String,
     pub recipient_id: Option<String>,
     pub timestamp: u64,
     #[serde(default)] // Default to None for backward compatibility during migration
     pub attachment: Option<FileAttachment>,
+    #[serde(default)]
+    pub edited_at: Option<u64>,
+    #[serde(default)]
+    pub deleted: bool, // Content and attachment are cleared; the entry keeps its place
+    #[serde(default)]
+    pub reactions: BTreeMap<String, Vec<String>>, // Emoji -> user IDs
+    #[serde(default)]
+    pub parent_id: Option<String>, // Message this one replies to
+    #[serde(default)]
+    pub mentions: Vec<String>, // Participant IDs flagged by the server from `@Name` in the content
+}
+
+// Longest chat message the server accepts, in characters
+pub const MAX_CHAT_CHARS: usize = 4000;
+
+// Per-user media quality; governs both what a participant sends and what they want to receive
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum VideoQuality {
+    High,
+    #[default]
+    Standard,
+    Low,
+    AudioOnly,
+}
+
+impl VideoQuality {
+    pub const ALL: [VideoQuality; 4] = [VideoQuality::High, VideoQuality::Standard, VideoQuality::Low, VideoQuality::AudioOnly];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VideoQuality::High => "High",
+            VideoQuality::Standard => "Standard",
+            VideoQuality::Low => "Low",
+            VideoQuality::AudioOnly => "Audio only",
+        }
+    }
+
+    /// Camera capture constraints as (width, height, frame rate), or None when no video is sent.
+    pub fn video_constraints(&self) -> Option<(u32, u32, u32)> {
+        match self {
+            VideoQuality::High => Some((1280, 720, 30)),
+            VideoQuality::Standard => Some((640, 360, 30)),
+            VideoQuality::Low => Some((320, 180, 15)),
+            VideoQuality::AudioOnly => None,
+        }
+    }
+
+    /// Upper bound for video sent to a participant at this level.
+    pub fn max_bitrate_kbps(&self) -> u32 {
+        match self {
+            VideoQuality::High => 2500,
+            VideoQuality::Standard => 800,
+            VideoQuality::Low => 250,
+            VideoQuality::AudioOnly => 0,
+        }
+    }
+
+    /// The next level down, used when the connection degrades.
+    pub fn downgrade(&self) -> VideoQuality {
+        match self {
+            VideoQuality::High => VideoQuality::Standard,
+            VideoQuality::Standard => VideoQuality::Low,
+            VideoQuality::Low | VideoQuality::AudioOnly => VideoQuality::AudioOnly,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -69,31 +223,367 @@ pub struct Participant {
     pub is_sharing_screen: bool,
     #[serde(default)]
     pub speaking_time: u64, // Total milliseconds spoken
+    #[serde(default)]
+    pub video_quality: VideoQuality,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct PollOption {
     pub id: u32,
     pub text: String,
     pub votes: u32,
+    #[serde(default)]
+    pub correct: bool, // Quiz answer; withheld from participants until revealed
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub const MAX_POLL_OPTIONS: usize = 10;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Poll {
     pub id: String,
     pub question: String,
     pub options: Vec<PollOption>,
     #[serde(default)]
-    pub voters: HashSet<String>,
+    pub voters: HashSet<String>, // Never sent to clients for anonymous polls
+    #[serde(default)]
+    pub voter_count: u32,
+    #[serde(default)]
+    pub multiple_choice: bool,
+    #[serde(default)]
+    pub anonymous: bool,
+    #[serde(default)]
+    pub hide_results: bool, // Counts are withheld from participants until the poll closes
+    #[serde(default)]
+    pub closed: bool,
+    #[serde(default)]
+    pub time_limit_secs: Option<u32>, // Requested by the moderator; closes the poll automatically
+    #[serde(default)]
+    pub closes_at: Option<u64>, // Epoch ms, set by the server from the time limit
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub ballots: BTreeMap<String, Vec<u32>>, // Voter ID -> chosen option IDs; only sent for named polls with visible results
+    #[serde(default)]
+    pub quiz: bool, // Options marked correct, answers scored
+    #[serde(default)]
+    pub revealed: bool, // Quiz answers shown to everyone; the poll is closed for good
+    #[serde(skip)]
+    pub voter_names: BTreeMap<String, String>, // Voter ID -> display name when they voted, for results of people who left
+}
+
+impl Poll {
+    /// Closed polls and those past their deadline take no more votes.
+    pub fn is_open(&self, now_ms: u64) -> bool {
+        !self.closed && self.closes_at.is_none_or(|at| now_ms < at)
+    }
+
+    pub fn results_hidden(&self) -> bool {
+        self.hide_results && !self.closed
+    }
+
+    pub fn answers_hidden(&self) -> bool {
+        self.quiz && !self.revealed
+    }
+
+    /// Whether participants get less than moderators do.
+    pub fn redacted(&self) -> bool {
+        self.results_hidden() || self.answers_hidden()
+    }
+
+    /// Whether a quiz answer is right: the correct option for single choice,
+    /// exactly the correct ones for multiple choice.
+    pub fn is_correct(&self, option_ids: &[u32]) -> bool {
+        let correct = |id: &u32| self.options.iter().any(|o| o.id == *id && o.correct);
+        if self.multiple_choice {
+            let wanted = self.options.iter().filter(|o| o.correct).count();
+            option_ids.len() == wanted && option_ids.iter().all(correct)
+        } else {
+            option_ids.len() == 1 && correct(&option_ids[0])
+        }
+    }
+
+    /// What moderators may see: everything but the voters of anonymous polls.
+    pub fn moderator_view(&self) -> Poll {
+        let mut poll = self.clone();
+        if poll.anonymous {
+            poll.voters.clear();
+            poll.ballots.clear();
+        }
+        poll
+    }
+
+    /// What every participant may see; hidden results have their counts zeroed.
+    pub fn public_view(&self) -> Poll {
+        let mut poll = self.moderator_view();
+        if poll.results_hidden() {
+            poll.ballots.clear();
+            for option in &mut poll.options {
+                option.votes = 0;
+            }
+        }
+        if poll.answers_hidden() {
+            for option in &mut poll.options {
+                option.correct = false;
+            }
+        }
+        poll
+    }
+}
+
+// A participant's standing across the revealed quiz questions
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuizScore {
+    pub participant_id: String,
+    pub name: String,
+    pub points: u32,
+    pub correct: u32, // Questions answered correctly
+    pub answered: u32,
+}
+
+pub const MAX_QUESTION_CHARS: usize = 500;
+
+// A question from the audience in the Q&A panel
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Question {
+    pub id: String,
+    pub text: String,
+    pub author_id: Option<String>, // None when asked anonymously
+    pub author_name: Option<String>,
+    #[serde(default)]
+    pub upvoters: HashSet<String>,
+    #[serde(default)]
+    pub answered: bool,
+    #[serde(default)]
+    pub pinned: bool,
+    pub created_at: u64,
+}
+
+impl Question {
+    pub fn upvotes(&self) -> usize {
+        self.upvoters.len()
+    }
+}
+
+/// Q&A panel order: pinned questions, then open ones before answered ones,
+/// each by upvotes and then oldest first.
+pub fn sort_questions(questions: &mut [Question]) {
+    questions.sort_by(|a, b| {
+        b.pinned
+            .cmp(&a.pinned)
+            .then(a.answered.cmp(&b.answered))
+            .then(b.upvotes().cmp(&a.upvotes()))
+            .then(a.created_at.cmp(&b.created_at))
+    });
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum QualityLevel {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl QualityLevel {
+    /// Classifies a link from packet loss (percent), jitter and round-trip time (ms).
+    pub fn classify(packet_loss: f64, jitter_ms: f64, rtt_ms: f64) -> Self {
+        if packet_loss > 10.0 || jitter_ms > 100.0 || rtt_ms > 500.0 {
+            QualityLevel::Poor
+        } else if packet_loss > 2.0 || jitter_ms > 30.0 || rtt_ms > 250.0 {
+            QualityLevel::Fair
+        } else {
+            QualityLevel::Good
+        }
+    }
+}
+
+// Media statistics for one remote participant, derived from RTCPeerConnection.getStats()
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct MediaStats {
+    pub bitrate_kbps: u32,
+    pub packet_loss: f64, // Percent of packets lost since the previous sample
+    pub jitter_ms: f64,
+    pub rtt_ms: f64,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub frames_per_second: f64,
+}
+
+impl MediaStats {
+    pub fn quality(&self) -> QualityLevel {
+        QualityLevel::classify(self.packet_loss, self.jitter_ms, self.rtt_ms)
+    }
+}
+
+// Compact summary of a participant's receive quality, relayed to moderators
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConnectionQuality {
+    #[serde(default)] // Filled in by the server
+    pub user_id: String,
+    pub level: QualityLevel,
+    pub bitrate_kbps: u32, // Total across all peers
+    pub packet_loss: f64, // Worst peer
+    pub jitter_ms: f64, // Worst peer
+    pub rtt_ms: f64, // Average across peers
+    pub peer_count: u32,
+}
+
+impl ConnectionQuality {
+    pub fn summarize<'a>(stats: impl IntoIterator<Item = &'a MediaStats>) -> Self {
+        let mut summary = ConnectionQuality {
+            user_id: String::new(),
+            level: QualityLevel::Good,
+            bitrate_kbps: 0,
+            packet_loss: 0.0,
+            jitter_ms: 0.0,
+            rtt_ms: 0.0,
+            peer_count: 0,
+        };
+        let mut rtt_total = 0.0;
+        for s in stats {
+            summary.peer_count += 1;
+            summary.bitrate_kbps += s.bitrate_kbps;
+            summary.packet_loss = summary.packet_loss.max(s.packet_loss);
+            summary.jitter_ms = summary.jitter_ms.max(s.jitter_ms);
+            rtt_total += s.rtt_ms;
+        }
+        if summary.peer_count > 0 {
+            summary.rtt_ms = rtt_total / summary.peer_count as f64;
+        }
+        summary.level = QualityLevel::classify(summary.packet_loss, summary.jitter_ms, summary.rtt_ms);
+        summary
+    }
+}
+
+// WebRTC negotiation between two participants, relayed as-is by the server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RtcSignal {
+    Offer { sdp: String },
+    Answer { sdp: String },
+    IceCandidate { candidate: String, sdp_mid: Option<String>, sdp_m_line_index: Option<u16> },
+}
+
+// A participant's answer to the recording consent prompt, kept for the current recording
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordingConsent {
+    pub user_id: String,
+    pub accepted: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AuditAction {
+    RecordingStarted,
+    RecordingStopped,
+    RecordingConsentGiven,
+    RecordingConsentDeclined,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub actor_id: String,
+    pub actor_name: String,
+    pub action: AuditAction,
+}
+
+// Text run through the server's content filter
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    Chat,
+    DisplayName,
+    PollQuestion, // Question and option texts
+    BreakoutRoomName,
+    Question, // Asked in the Q&A panel
+    Whiteboard, // Text and sticky note elements
+}
+
+impl ContentKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentKind::Chat => "chat message",
+            ContentKind::DisplayName => "display name",
+            ContentKind::PollQuestion => "poll",
+            ContentKind::BreakoutRoomName => "breakout room name",
+            ContentKind::Question => "question",
+            ContentKind::Whiteboard => "whiteboard text",
+        }
+    }
+}
+
+// Content that matched a "flag" rule, for moderators to review
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContentFlag {
+    pub kind: ContentKind,
+    pub user_id: String,
+    pub user_name: String,
+    pub text: String, // As sent, before masking
+    pub matches: Vec<String>,
+    pub timestamp: u64,
+}
+
+// One caption line. Interim segments are replaced by later ones from the same speaker
+// until a final segment arrives; only final segments are kept in the transcript.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptionSegment {
+    pub id: String,
+    pub user_id: String,
+    pub speaker_name: String,
+    pub text: String,
+    pub is_final: bool,
+    pub lang: String,
+    pub start_ms: u64, // Server time the utterance started
+    pub end_ms: u64,
+    pub room_id: Option<String>, // None = Main room
+}
+
+// Metadata stored next to a server-side recording
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordingManifest {
+    pub id: String,
+    pub room_id: String,
+    pub mime_type: String,
+    pub started_at: u64,
+    pub stopped_at: Option<u64>,
+    pub participants: Vec<String>, // Display names of everyone present while recording
+    pub chunk_count: u32,
+    pub size_bytes: u64,
+}
+
+// Which chat history a page belongs to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(tag = "kind", content = "id")]
+pub enum ChatScope {
+    #[default]
+    Main,
+    Breakout(String), // Breakout room ID
+    Private(String), // The other participant's ID
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", content = "payload")]
 pub enum ClientMessage {
     CreatePoll(Poll),
-    Vote { poll_id: String, option_id: u32 },
+    Vote { poll_id: String, option_ids: Vec<u32> }, // Exactly one unless the poll is multiple choice
     Join(String), // Display Name
-    Chat { content: String, recipient_id: Option<String>, attachment: Option<FileAttachment> },
+    ResumeSession(String), // Token of an earlier connection, sent before Join to keep its ID
+    Chat {
+        content: String,
+        recipient_id: Option<String>,
+        attachment: Option<FileAttachment>,
+        #[serde(default)]
+        parent_id: Option<String>,
+    },
+    FetchChatHistory {
+        before: Option<String>, // Messages older than this message ID
+        limit: u32,
+        #[serde(default)]
+        scope: ChatScope,
+    },
+    EditMessage { message_id: String, content: String },
+    DeleteMessage(String), // Message ID
+    ToggleMessageReaction { message_id: String, emoji: String },
     ToggleRoomLock,
     ToggleRecording,
     UpdateProfile(String), // New Name
@@ -107,12 +597,34 @@ pub enum ClientMessage {
     EndMeeting,
     CreateBreakoutRoom(String), // Room Name
     JoinBreakoutRoom(Option<String>), // Room ID (None for Main)
-    Draw(DrawAction),
+    AddWhiteboardElement(WhiteboardElement), // The server assigns the ID
+    UpdateWhiteboardElement(WhiteboardElement), // Moved or edited; replaces the element with the same ID
+    DeleteWhiteboardElement(String), // Element ID
     Typing(bool),
     StartShareVideo(String), // URL
     StopShareVideo,
     Speaking(bool),
     Ping,
+    RtcSignal { to: String, signal: RtcSignal }, // To a participant ID
+    ConnectionQuality(ConnectionQuality),
+    SetVideoQuality(VideoQuality),
+    RecordingConsent(bool), // Accepted
+    Caption { text: String, is_final: bool, lang: String },
+    ToggleAutoMuteDecliners,
+    ClosePoll(String), // Poll ID; moderators only
+    ReopenPoll(String), // Poll ID; moderators only
+    DeletePoll(String), // Poll ID; moderators only
+    TogglePollVoteChanges, // Moderators only
+    RevealPollAnswers(String), // Poll ID; moderators only, closes the quiz question
+    AskQuestion { text: String, anonymous: bool },
+    UpvoteQuestion(String), // Question ID; asking again takes the vote back
+    SetQuestionAnswered { question_id: String, answered: bool }, // Moderators only
+    SetQuestionPinned { question_id: String, pinned: bool }, // Moderators only
+    DismissQuestion(String), // Question ID; moderators only
+    SetChatMode(ChatMode), // Moderators only
+    SetChatSlowMode(u32), // Seconds, 0 = off; moderators only
+    SetChatMuted { participant_id: String, muted: bool }, // Moderators only
+    SetTimer(u32), // Countdown in seconds, 0 = stop; moderators only
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -138,10 +650,32 @@ pub enum ServerMessage {
     Reaction { sender_id: String, emoji: String },
     PollCreated(Poll),
     PollUpdated(Poll),
-    Draw(DrawAction),
-    WhiteboardHistory(Vec<DrawAction>),
-    ChatHistory(Vec<ChatMessage>),
+    PollResults(Poll), // Moderators only; counts of a poll whose results are hidden from participants
+    PollVoteRecorded { poll_id: String, option_ids: Vec<u32> }, // Sent only to the voter
+    PollDeleted(String), // Poll ID
+    QuizLeaderboard(Vec<QuizScore>), // Highest score first
+    QuestionList(Vec<Question>), // Sent on join
+    QuestionAsked(Question),
+    QuestionUpdated(Question),
+    QuestionDismissed(String), // Question ID
+    WhiteboardElementAdded(WhiteboardElement),
+    WhiteboardElementUpdated(WhiteboardElement),
+    WhiteboardElementDeleted(String), // Element ID
+    WhiteboardHistory(Vec<WhiteboardElement>), // Bottom to top
+    // Oldest first. `before` echoes the request; None means this is the latest page.
+    // `parents` holds older messages that replies in this page refer to, so threads stay whole.
+    ChatHistory {
+        messages: Vec<ChatMessage>,
+        #[serde(default)]
+        parents: Vec<ChatMessage>,
+        before: Option<String>,
+        has_more: bool,
+        #[serde(default)]
+        scope: ChatScope,
+    },
+    ChatMessageUpdated { message: ChatMessage, room_id: Option<String> }, // Edited, deleted or reacted to
     Welcome { id: String },
+    SessionToken(String), // Sent only to its owner, authorizes REST calls
     Knocking,
     AccessGranted,
     AccessDenied,
@@ -150,6 +684,12 @@ pub enum ServerMessage {
     VideoStopped,
     PeerSpeaking { user_id: String, speaking: bool },
     Pong { timestamp: u64 },
+    RtcSignal { from: String, to: String, signal: RtcSignal }, // Delivered to `to` only
+    ConnectionQualityReport(ConnectionQuality), // Moderators only
+    RecordingConsents(Vec<RecordingConsent>), // Moderators only
+    ForceMute(String), // Target ID
+    Caption(CaptionSegment),
+    ContentFlagged(ContentFlag), // Moderators only
     Error(String),
 }
 
@@ -0,0 +1,224 @@
+// Safe Markdown subset for chat messages. The parser only produces a tree of
+// text nodes; raw HTML in the input stays literal text and is never interpreted.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+    CodeBlock { language: Option<String>, code: String },
+    List { ordered: bool, items: Vec<Vec<Inline>> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Code(String),
+    Link { url: String, text: String }, // url is always http(s)
+    LineBreak,
+}
+
+const FENCE: &str = "```";
+const LINK_PREFIXES: [&str; 3] = ["https://", "http://", "www."];
+// Trailing characters that usually end a sentence rather than a URL
+const URL_TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', '"', '\'', ')', ']'];
+
+pub fn parse(input: &str) -> Vec<Block> {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        if let Some(info) = trimmed.strip_prefix(FENCE) {
+            flush_paragraph(&mut paragraph, &mut blocks);
+            let language = Some(info.trim()).filter(|l| !l.is_empty()).map(str::to_string);
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && lines[i].trim() != FENCE {
+                code.push(lines[i]);
+                i += 1;
+            }
+            // Skip the closing fence; an unclosed block runs to the end of the message
+            i += 1;
+            blocks.push(Block::CodeBlock { language, code: code.join("\n") });
+            continue;
+        }
+
+        if let Some((ordered, _)) = list_item(line) {
+            flush_paragraph(&mut paragraph, &mut blocks);
+            let mut items = Vec::new();
+            while i < lines.len() {
+                match list_item(lines[i]) {
+                    Some((o, text)) if o == ordered => items.push(parse_inline(text)),
+                    _ => break,
+                }
+                i += 1;
+            }
+            blocks.push(Block::List { ordered, items });
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            flush_paragraph(&mut paragraph, &mut blocks);
+        } else {
+            paragraph.push(line);
+        }
+        i += 1;
+    }
+    flush_paragraph(&mut paragraph, &mut blocks);
+    blocks
+}
+
+fn flush_paragraph(lines: &mut Vec<&str>, blocks: &mut Vec<Block>) {
+    if lines.is_empty() {
+        return;
+    }
+    let mut inlines = Vec::new();
+    for (n, line) in lines.drain(..).enumerate() {
+        if n > 0 {
+            inlines.push(Inline::LineBreak);
+        }
+        inlines.extend(parse_inline(line));
+    }
+    blocks.push(Block::Paragraph(inlines));
+}
+
+/// Whether the line is a list item (`- `, `* `, `+ ` or `1. `) and its text.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    let trimmed = line.trim_start();
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = trimmed.strip_prefix(bullet) {
+            return Some((false, rest));
+        }
+    }
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && digits <= 9 {
+        if let Some(rest) = trimmed[digits..].strip_prefix(". ") {
+            return Some((true, rest));
+        }
+    }
+    None
+}
+
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let mut out = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+    // Per delimiter, the earliest start from which no closer was found. A later
+    // search cannot find one either, so repeated openers don't rescan the text.
+    let mut unclosed: Vec<(&str, usize)> = Vec::new();
+
+    while i < text.len() {
+        let rest = &text[i..];
+        let prev = text[..i].chars().next_back();
+
+        if let Some(code) = rest.strip_prefix('`') {
+            if let Some(end) = code.find('`').filter(|&end| end > 0) {
+                push_text(&mut out, &mut plain);
+                out.push(Inline::Code(code[..end].to_string()));
+                i += end + 2;
+                continue;
+            }
+        }
+
+        if let Some((delim, bold)) = emphasis_delimiter(rest) {
+            let start = i + delim.len();
+            let searched_before = unclosed.iter().any(|&(d, from)| d == delim && from <= start);
+            if !searched_before && can_open(text, start, delim, prev) {
+                match closing_delimiter(text, start, delim) {
+                    Some(end) => {
+                        push_text(&mut out, &mut plain);
+                        let inner = parse_inline(&text[start..end]);
+                        out.push(if bold { Inline::Bold(inner) } else { Inline::Italic(inner) });
+                        i = end + delim.len();
+                        continue;
+                    },
+                    None => unclosed.push((delim, start)),
+                }
+            }
+        }
+
+        if !prev.is_some_and(char::is_alphanumeric) {
+            if let Some(len) = link_len(rest) {
+                push_text(&mut out, &mut plain);
+                let text = rest[..len].to_string();
+                let has_scheme = text.get(..4).is_some_and(|head| head.eq_ignore_ascii_case("http"));
+                let url = if has_scheme { text.clone() } else { format!("https://{}", text) };
+                out.push(Inline::Link { url, text });
+                i += len;
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap();
+        plain.push(c);
+        i += c.len_utf8();
+    }
+    push_text(&mut out, &mut plain);
+    out
+}
+
+fn push_text(out: &mut Vec<Inline>, plain: &mut String) {
+    if !plain.is_empty() {
+        out.push(Inline::Text(std::mem::take(plain)));
+    }
+}
+
+fn emphasis_delimiter(rest: &str) -> Option<(&'static str, bool)> {
+    ["**", "__", "*", "_"]
+        .into_iter()
+        .find(|d| rest.starts_with(d))
+        .map(|d| (d, d.len() == 2))
+}
+
+/// Whether a delimiter just before `start` can open emphasis.
+/// Underscores only count at word boundaries so `snake_case_names` stay text.
+fn can_open(text: &str, start: usize, delim: &str, before_open: Option<char>) -> bool {
+    if delim.starts_with('_') && before_open.is_some_and(char::is_alphanumeric) {
+        return false;
+    }
+    let inner = &text[start..];
+    !inner.starts_with(char::is_whitespace) && !inner.starts_with(delim)
+}
+
+/// Byte offset of the delimiter closing one opened just before `start`.
+fn closing_delimiter(text: &str, start: usize, delim: &str) -> Option<usize> {
+    let underscore = delim.starts_with('_');
+    let mut from = start;
+    while let Some(offset) = text[from..].find(delim) {
+        let end = from + offset;
+        let before = text[..end].chars().next_back();
+        let after = text[end + delim.len()..].chars().next();
+        // A single `*` must not match half of a `**`
+        let doubled = delim.len() == 1 && after == Some(delim.as_bytes()[0] as char);
+        let boundary_ok = !underscore || !after.is_some_and(char::is_alphanumeric);
+        if end > start && !before.is_some_and(char::is_whitespace) && !doubled && boundary_ok {
+            return Some(end);
+        }
+        from = end + delim.len() + usize::from(doubled);
+    }
+    None
+}
+
+/// Length of an http(s) or `www.` link at the start of `rest`, trailing punctuation excluded.
+fn link_len(rest: &str) -> Option<usize> {
+    let prefix = LINK_PREFIXES.iter().find(|p| rest.get(..p.len()).is_some_and(|head| head.eq_ignore_ascii_case(p)))?;
+    let end = rest.find(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '`').unwrap_or(rest.len());
+    let mut candidate = &rest[..end];
+    let opening = candidate.matches('(').count();
+    let mut closing = candidate.matches(')').count();
+    while let Some(last) = candidate.chars().next_back() {
+        // Keep a closing parenthesis that balances one inside the URL
+        let balanced = last == ')' && opening >= closing;
+        if !URL_TRAILING.contains(&last) || balanced {
+            break;
+        }
+        closing -= usize::from(last == ')');
+        candidate = &candidate[..candidate.len() - 1];
+    }
+    (candidate.len() > prefix.len()).then_some(candidate.len())
+}
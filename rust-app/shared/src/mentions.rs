@@ -0,0 +1,71 @@
+// `@Name` mentions in chat messages. Display names may contain spaces, so mentions
+// are matched against the known participant names rather than tokenized from the text.
+use crate::Participant;
+
+// Longer than any sensible name, so the popup closes once the user has moved on
+const MAX_QUERY_CHARS: usize = 32;
+pub const MAX_SUGGESTIONS: usize = 5;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// IDs of the participants mentioned in `content`, in order of first mention.
+/// Each `@` takes the longest matching name, so `@Al Smith` does not also mention `Al`;
+/// an `@` inside a word (an email address) is not a mention.
+pub fn find_mentions<'a>(content: &str, participants: impl IntoIterator<Item = &'a Participant>) -> Vec<String> {
+    let participants: Vec<(&Participant, String)> = participants
+        .into_iter()
+        .filter(|p| !p.name.trim().is_empty())
+        .map(|p| (p, p.name.to_lowercase()))
+        .collect();
+    let lowered = content.to_lowercase();
+    let mut found = Vec::new();
+
+    for (at, _) in lowered.match_indices('@') {
+        if lowered[..at].chars().next_back().is_some_and(is_word_char) {
+            continue;
+        }
+        let rest = &lowered[at + 1..];
+        let longest = participants
+            .iter()
+            .map(|(_, name)| name)
+            .filter(|name| rest.starts_with(name.as_str()) && !rest[name.len()..].starts_with(is_word_char))
+            .max_by_key(|name| name.len());
+        let Some(longest) = longest else { continue };
+        for (p, name) in &participants {
+            if name == longest && !found.contains(&p.id) {
+                found.push(p.id.clone());
+            }
+        }
+    }
+    found
+}
+
+/// The partial name typed after the last `@` of `input`, with the byte offset of that `@`.
+pub fn mention_query(input: &str) -> Option<(usize, &str)> {
+    let at = input.rfind('@')?;
+    if input[..at].chars().next_back().is_some_and(is_word_char) {
+        return None;
+    }
+    let query = &input[at + 1..];
+    if query.starts_with(char::is_whitespace) || query.contains('\n') || query.chars().count() > MAX_QUERY_CHARS {
+        return None;
+    }
+    Some((at, query))
+}
+
+/// Participants whose name starts with `query`, ignoring case, other than `my_id`.
+pub fn suggestions<'a>(query: &str, participants: &'a [Participant], my_id: Option<&str>) -> Vec<&'a Participant> {
+    let query = query.to_lowercase();
+    participants
+        .iter()
+        .filter(|p| Some(p.id.as_str()) != my_id && p.name.to_lowercase().starts_with(&query))
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// `input` with the query at `at` replaced by the full mention.
+pub fn complete_mention(input: &str, at: usize, name: &str) -> String {
+    format!("{}@{} ", &input[..at], name)
+}
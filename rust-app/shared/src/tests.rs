@@ -1,12 +1,19 @@
 use super::*;
+use super::whiteboard::{element_at, wrap_text, Point, Shape, WhiteboardError};
 #[test]
 fn test_chat_message_serialization() {
     let msg = ChatMessage {
+        id: "m1".to_string(),
         user_id: "user1".to_string(),
         content: "Hello Rust".to_string(),
         recipient_id: None,
         timestamp: 1627840000,
         attachment: None,
+        edited_at: None,
+        deleted: false,
+        reactions: Default::default(),
+        parent_id: None,
+        mentions: Vec::new(),
     };
     let json = serde_json::to_string(&msg).unwrap();
     let deserialized: ChatMessage = serde_json::from_str(&json).unwrap();
@@ -16,17 +23,25 @@ fn test_chat_message_serialization() {
 #[test]
 fn test_chat_message_with_attachment_serialization() {
     let attachment = FileAttachment {
+        id: "f1".to_string(),
         filename: "test.txt".to_string(),
         mime_type: "text/plain".to_string(),
         size: 12,
-        content_base64: "SGVsbG8gV29ybGQ=".to_string(),
+        sha256: "64ec88ca00b268e5ba1a35678a1b5316d212f4f366b2477232534a8aeca37f3c".to_string(),
+        thumbnail: None,
     };
     let msg = ChatMessage {
+        id: "m2".to_string(),
         user_id: "user1".to_string(),
         content: "Here is a file".to_string(),
         recipient_id: None,
         timestamp: 1627840000,
         attachment: Some(attachment),
+        edited_at: None,
+        deleted: false,
+        reactions: Default::default(),
+        parent_id: None,
+        mentions: Vec::new(),
     };
     let json = serde_json::to_string(&msg).unwrap();
     let deserialized: ChatMessage = serde_json::from_str(&json).unwrap();
@@ -43,6 +58,7 @@ fn test_server_message_serialization() {
         is_hand_raised: false,
         is_sharing_screen: false,
         speaking_time: 0,
+        video_quality: VideoQuality::default(),
     };
     let msg = ServerMessage::ParticipantJoined(p.clone());
     let json = serde_json::to_string(&msg).unwrap();
@@ -113,10 +129,11 @@ fn test_poll_serialization() {
         id: "poll1".to_string(),
         question: "Color?".to_string(),
         options: vec![
-            PollOption { id: 0, text: "Red".to_string(), votes: 0 },
-            PollOption { id: 1, text: "Blue".to_string(), votes: 5 },
+            PollOption { id: 0, text: "Red".to_string(), votes: 0, correct: false },
+            PollOption { id: 1, text: "Blue".to_string(), votes: 5, correct: false },
         ],
-        voters: std::collections::HashSet::new(),
+        multiple_choice: true,
+        ..Default::default()
     };
     let msg = ClientMessage::CreatePoll(poll.clone());
     let json = serde_json::to_string(&msg).unwrap();
@@ -156,18 +173,461 @@ fn test_speaking_message() {
 }
 
 #[test]
-fn test_draw_serialization() {
-    let draw = DrawAction {
+fn test_whiteboard_element_serialization() {
+    let element = WhiteboardElement {
+        id: "e1".to_string(),
+        shape: Shape::Arrow { from: Point::new(10.0, 20.0), to: Point::new(30.0, 40.0) },
         color: "#000000".to_string(),
-        start_x: 10.0,
-        start_y: 20.0,
-        end_x: 30.0,
-        end_y: 40.0,
         width: 2.0,
         sender_id: "user1".to_string(),
     };
-    let msg = ClientMessage::Draw(draw.clone());
+    let msg = ClientMessage::AddWhiteboardElement(element.clone());
     let json = serde_json::to_string(&msg).unwrap();
+    assert!(json.contains(r#""kind":"arrow""#));
     let deserialized: ClientMessage = serde_json::from_str(&json).unwrap();
     assert_eq!(msg, deserialized);
 }
+
+#[test]
+fn test_whiteboard_geometry() {
+    let element = |shape: Shape| WhiteboardElement { id: String::new(), shape, color: "#000".to_string(), width: 2.0, sender_id: String::new() };
+    let path = element(Shape::Path { points: vec![Point::new(0.0, 0.0), Point::new(100.0, 0.0), Point::new(100.0, 100.0)] });
+    assert!(path.hit(Point::new(50.0, 3.0), 2.0));
+    assert!(!path.hit(Point::new(50.0, 50.0), 2.0));
+    assert_eq!(path.shape.bounds(), (Point::new(0.0, 0.0), Point::new(100.0, 100.0)));
+
+    let ellipse = element(Shape::Ellipse { from: Point::new(100.0, 0.0), to: Point::new(0.0, 50.0) });
+    assert!(ellipse.hit(Point::new(50.0, 25.0), 0.0));
+    assert!(!ellipse.hit(Point::new(2.0, 2.0), 0.0));
+
+    let mut note = element(Shape::StickyNote { at: Point::new(10.0, 10.0), text: "Idea".to_string() });
+    note.shape.translate(5.0, -5.0);
+    assert_eq!(note.shape.bounds().0, Point::new(15.0, 5.0));
+    // The topmost element wins
+    let elements = vec![ellipse.clone(), WhiteboardElement { id: "top".to_string(), ..note }];
+    assert_eq!(element_at(&elements, Point::new(20.0, 20.0), 0.0).map(|e| e.id.as_str()), Some("top"));
+    assert!(element_at(&elements, Point::new(500.0, 500.0), 0.0).is_none());
+}
+
+#[test]
+fn test_whiteboard_validation() {
+    let element = |shape: Shape, width: f64| WhiteboardElement { id: String::new(), shape, color: "#ff0000".to_string(), width, sender_id: String::new() };
+    assert!(element(Shape::Rect { from: Point::new(0.0, 0.0), to: Point::new(5.0, 5.0) }, 4.0).validate().is_ok());
+    assert_eq!(element(Shape::Rect { from: Point::new(f64::NAN, 0.0), to: Point::new(5.0, 5.0) }, 4.0).validate(), Err(WhiteboardError::Invalid));
+    assert_eq!(element(Shape::Path { points: vec![] }, 4.0).validate(), Err(WhiteboardError::Invalid));
+    assert_eq!(element(Shape::Arrow { from: Point::new(0.0, 0.0), to: Point::new(1.0, 1.0) }, 0.0).validate(), Err(WhiteboardError::Invalid));
+    assert_eq!(element(Shape::Text { at: Point::new(0.0, 0.0), text: " ".to_string(), font_size: 16.0 }, 2.0).validate(), Err(WhiteboardError::Invalid));
+
+    assert_eq!(wrap_text("a quick brown fox\n\nabcdefghij", 7), vec!["a quick", "brown", "fox", "", "abcdefg", "hij"]);
+}
+
+#[test]
+fn test_quality_classification() {
+    assert_eq!(QualityLevel::classify(0.0, 5.0, 40.0), QualityLevel::Good);
+    assert_eq!(QualityLevel::classify(3.0, 5.0, 40.0), QualityLevel::Fair);
+    assert_eq!(QualityLevel::classify(0.0, 5.0, 300.0), QualityLevel::Fair);
+    assert_eq!(QualityLevel::classify(15.0, 5.0, 40.0), QualityLevel::Poor);
+    assert_eq!(QualityLevel::classify(0.0, 150.0, 40.0), QualityLevel::Poor);
+}
+
+#[test]
+fn test_connection_quality_summary() {
+    let stats = vec![
+        MediaStats { bitrate_kbps: 800, packet_loss: 0.5, jitter_ms: 10.0, rtt_ms: 40.0, ..Default::default() },
+        MediaStats { bitrate_kbps: 400, packet_loss: 4.0, jitter_ms: 20.0, rtt_ms: 60.0, ..Default::default() },
+    ];
+    let summary = ConnectionQuality::summarize(&stats);
+    assert_eq!(summary.peer_count, 2);
+    assert_eq!(summary.bitrate_kbps, 1200);
+    assert_eq!(summary.packet_loss, 4.0);
+    assert_eq!(summary.jitter_ms, 20.0);
+    assert_eq!(summary.rtt_ms, 50.0);
+    assert_eq!(summary.level, QualityLevel::Fair);
+
+    let empty = ConnectionQuality::summarize(&[]);
+    assert_eq!(empty.peer_count, 0);
+    assert_eq!(empty.level, QualityLevel::Good);
+
+    let msg = ClientMessage::ConnectionQuality(summary);
+    let json = serde_json::to_string(&msg).unwrap();
+    let deserialized: ClientMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+}
+
+#[test]
+fn test_rtc_signal_serialization() {
+    let msg = ClientMessage::RtcSignal {
+        to: "bob".to_string(),
+        signal: RtcSignal::IceCandidate { candidate: "candidate:1 1 udp 1 10.0.0.1 5000 typ host".to_string(), sdp_mid: Some("0".to_string()), sdp_m_line_index: Some(0) },
+    };
+    let json = serde_json::to_string(&msg).unwrap();
+    assert!(json.contains(r#""kind":"ice_candidate""#));
+    let deserialized: ClientMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+}
+
+#[test]
+fn test_video_quality_levels() {
+    assert_eq!(VideoQuality::default(), VideoQuality::Standard);
+    assert_eq!(VideoQuality::High.video_constraints(), Some((1280, 720, 30)));
+    assert_eq!(VideoQuality::AudioOnly.video_constraints(), None);
+    assert_eq!(VideoQuality::High.downgrade(), VideoQuality::Standard);
+    assert_eq!(VideoQuality::Low.downgrade(), VideoQuality::AudioOnly);
+    assert_eq!(VideoQuality::AudioOnly.downgrade(), VideoQuality::AudioOnly);
+
+    // Participants from older clients have no quality field
+    let json = r#"{"id":"1","name":"Alice","is_hand_raised":false,"is_sharing_screen":false}"#;
+    let p: Participant = serde_json::from_str(json).unwrap();
+    assert_eq!(p.video_quality, VideoQuality::Standard);
+
+    let msg = ClientMessage::SetVideoQuality(VideoQuality::AudioOnly);
+    let json = serde_json::to_string(&msg).unwrap();
+    let deserialized: ClientMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+}
+
+#[test]
+fn test_recording_consent_messages() {
+    // Configs saved before the option existed still load
+    let json = r#"{"room_name":"R","is_locked":false,"is_recording":true,"is_lobby_enabled":false,"max_participants":10,"host_id":null}"#;
+    let config: RoomConfig = serde_json::from_str(json).unwrap();
+    assert!(!config.auto_mute_decliners);
+
+    let msg = ClientMessage::RecordingConsent(false);
+    let json = serde_json::to_string(&msg).unwrap();
+    assert_eq!(json, r#"{"type":"RecordingConsent","payload":false}"#);
+
+    let consents = ServerMessage::RecordingConsents(vec![RecordingConsent {
+        user_id: "user1".to_string(),
+        accepted: true,
+        timestamp: 1234567890,
+    }]);
+    let json = serde_json::to_string(&consents).unwrap();
+    let deserialized: ServerMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(consents, deserialized);
+}
+
+#[test]
+fn test_caption_messages() {
+    let msg = ClientMessage::Caption {
+        text: "Hello".to_string(),
+        is_final: true,
+        lang: "en".to_string(),
+    };
+    let json = serde_json::to_string(&msg).unwrap();
+    let deserialized: ClientMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+
+    let segment = CaptionSegment {
+        id: "c1".to_string(),
+        user_id: "user1".to_string(),
+        speaker_name: "Alice".to_string(),
+        text: "Hello".to_string(),
+        is_final: false,
+        lang: "en".to_string(),
+        start_ms: 1000,
+        end_ms: 1500,
+        room_id: None,
+    };
+    let msg = ServerMessage::Caption(segment);
+    let json = serde_json::to_string(&msg).unwrap();
+    let deserialized: ServerMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+}
+
+#[test]
+fn test_chat_history_paging_messages() {
+    let msg = ClientMessage::FetchChatHistory { before: Some("m1".to_string()), limit: 50, scope: ChatScope::Private("u2".to_string()) };
+    let json = serde_json::to_string(&msg).unwrap();
+    let deserialized: ClientMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+
+    let msg = ServerMessage::ChatHistory { messages: Vec::new(), parents: Vec::new(), before: None, has_more: true, scope: ChatScope::Breakout("r1".to_string()) };
+    let json = serde_json::to_string(&msg).unwrap();
+    let deserialized: ServerMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(msg, deserialized);
+
+    // Requests without a scope page through the main room
+    let parsed: ClientMessage = serde_json::from_str(r#"{"type":"FetchChatHistory","payload":{"before":null,"limit":10}}"#).unwrap();
+    assert_eq!(parsed, ClientMessage::FetchChatHistory { before: None, limit: 10, scope: ChatScope::Main });
+
+    // Messages stored before ids existed still deserialize
+    let legacy = r#"{"user_id":"u1","content":"Hi","recipient_id":null,"timestamp":1}"#;
+    let parsed: ChatMessage = serde_json::from_str(legacy).unwrap();
+    assert!(parsed.id.is_empty());
+}
+
+#[test]
+fn test_markdown_inline_formatting() {
+    use markdown::{parse_inline, Inline};
+    assert_eq!(
+        parse_inline("a **bold** and *it* and `x*y*`"),
+        vec![
+            Inline::Text("a ".to_string()),
+            Inline::Bold(vec![Inline::Text("bold".to_string())]),
+            Inline::Text(" and ".to_string()),
+            Inline::Italic(vec![Inline::Text("it".to_string())]),
+            Inline::Text(" and ".to_string()),
+            Inline::Code("x*y*".to_string()),
+        ]
+    );
+    // Unmatched or word-internal delimiters stay literal
+    assert_eq!(parse_inline("2 * 3 * 4"), vec![Inline::Text("2 * 3 * 4".to_string())]);
+    assert_eq!(parse_inline("snake_case_name"), vec![Inline::Text("snake_case_name".to_string())]);
+    assert_eq!(parse_inline("**open"), vec![Inline::Text("**open".to_string())]);
+    assert_eq!(
+        parse_inline("__*both*__"),
+        vec![Inline::Bold(vec![Inline::Italic(vec![Inline::Text("both".to_string())])])]
+    );
+}
+
+#[test]
+fn test_markdown_unclosed_delimiters_parse_quickly() {
+    use markdown::{parse, parse_inline, Inline};
+    // Every opener here is unclosed; searching to the end for each one was quadratic
+    for unit in ["*a ", "_a ", "**a ", "__a ", "*a **b _c __d "] {
+        let input = unit.repeat(60_000 / unit.len());
+        let started = std::time::Instant::now();
+        assert_eq!(parse_inline(&input), vec![Inline::Text(input.clone())]);
+        parse(&input);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1), "{:?} took {:?}", unit, started.elapsed());
+    }
+    // As are the trailing parentheses trimmed from a link
+    let input = format!("http://a{}", ")".repeat(60_000));
+    let started = std::time::Instant::now();
+    assert_eq!(parse_inline(&input)[0], Inline::Link { url: "http://a".to_string(), text: "http://a".to_string() });
+    assert!(started.elapsed() < std::time::Duration::from_secs(1), "took {:?}", started.elapsed());
+}
+
+#[test]
+fn test_markdown_links() {
+    use markdown::{parse_inline, Inline};
+    let link = |url: &str, text: &str| Inline::Link { url: url.to_string(), text: text.to_string() };
+    assert_eq!(
+        parse_inline("see https://example.com/a?b=1."),
+        vec![Inline::Text("see ".to_string()), link("https://example.com/a?b=1", "https://example.com/a?b=1"), Inline::Text(".".to_string())]
+    );
+    assert_eq!(
+        parse_inline("(www.example.com)"),
+        vec![Inline::Text("(".to_string()), link("https://www.example.com", "www.example.com"), Inline::Text(")".to_string())]
+    );
+    assert_eq!(
+        parse_inline("https://en.wikipedia.org/wiki/Rust_(language)"),
+        vec![link("https://en.wikipedia.org/wiki/Rust_(language)", "https://en.wikipedia.org/wiki/Rust_(language)")]
+    );
+    // Only http(s) is linked; other schemes and code spans stay text
+    assert_eq!(parse_inline("javascript:alert(1)"), vec![Inline::Text("javascript:alert(1)".to_string())]);
+    assert_eq!(parse_inline("`https://x.io`"), vec![Inline::Code("https://x.io".to_string())]);
+    assert_eq!(parse_inline("xhttps://x.io"), vec![Inline::Text("xhttps://x.io".to_string())]);
+    assert_eq!(parse_inline("héllo wé"), vec![Inline::Text("héllo wé".to_string())]);
+}
+
+#[test]
+fn test_markdown_blocks() {
+    use markdown::{parse, Block, Inline};
+    let text = |s: &str| Inline::Text(s.to_string());
+    let blocks = parse("Hello\nworld\n\n- one\n- **two**\n1. first\n2. second\n```rust\nlet x = 1;\n\n<b>\n```\n<script>");
+    assert_eq!(
+        blocks,
+        vec![
+            Block::Paragraph(vec![text("Hello"), Inline::LineBreak, text("world")]),
+            Block::List { ordered: false, items: vec![vec![text("one")], vec![Inline::Bold(vec![text("two")])]] },
+            Block::List { ordered: true, items: vec![vec![text("first")], vec![text("second")]] },
+            Block::CodeBlock { language: Some("rust".to_string()), code: "let x = 1;\n\n<b>".to_string() },
+            Block::Paragraph(vec![text("<script>")]),
+        ]
+    );
+    // An unclosed fence runs to the end
+    assert_eq!(parse("```\ncode"), vec![Block::CodeBlock { language: None, code: "code".to_string() }]);
+}
+
+fn named(id: &str, name: &str) -> Participant {
+    Participant {
+        id: id.to_string(),
+        name: name.to_string(),
+        is_hand_raised: false,
+        is_sharing_screen: false,
+        speaking_time: 0,
+        video_quality: VideoQuality::default(),
+    }
+}
+
+#[test]
+fn test_find_mentions() {
+    use mentions::find_mentions;
+    let people = vec![named("a", "Al"), named("b", "Al Smith"), named("c", "Bob")];
+    assert_eq!(find_mentions("hi @bob and @Al Smith!", &people), vec!["c", "b"]);
+    assert_eq!(find_mentions("@Al, @al again", &people), vec!["a"]);
+    // Partial words and email addresses are not mentions
+    assert!(find_mentions("@Bobby mail bob@Bob.com", &people).is_empty());
+}
+
+#[test]
+fn test_mention_autocomplete() {
+    use mentions::{complete_mention, mention_query, suggestions};
+    assert_eq!(mention_query("hey @Al"), Some((4, "Al")));
+    assert_eq!(mention_query("@"), Some((0, "")));
+    assert_eq!(mention_query("me@example"), None);
+    assert_eq!(mention_query("@ nobody"), None);
+
+    let people = vec![named("me", "Alex"), named("a", "Al Smith"), named("c", "Bob")];
+    let found: Vec<&str> = suggestions("al", &people, Some("me")).iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(found, vec!["a"]);
+    assert_eq!(complete_mention("hey @Al", 4, "Al Smith"), "hey @Al Smith ");
+}
+
+#[test]
+fn test_chat_restrictions() {
+    let mut config = RoomConfig { chat_muted: vec!["bob".to_string()], ..Default::default() };
+    assert_eq!(config.chat_restriction("alice", false), None);
+    assert_eq!(config.chat_restriction("bob", false), Some(ChatRestriction::Muted));
+    assert_eq!(config.chat_restriction("bob", true), None);
+
+    config.chat_mode = ChatMode::ModeratorsOnly;
+    assert_eq!(config.chat_restriction("alice", false), Some(ChatRestriction::ModeratorsOnly));
+    config.chat_mode = ChatMode::Disabled;
+    assert_eq!(config.chat_restriction("alice", false), Some(ChatRestriction::Disabled));
+    assert_eq!(config.chat_restriction("host", true), None);
+
+    // Older configs without the chat fields still load
+    let old: RoomConfig = serde_json::from_str(r#"{"room_name":"r","is_locked":false,"is_recording":false,"is_lobby_enabled":false,"max_participants":10,"host_id":null}"#).unwrap();
+    assert_eq!(old.chat_mode, ChatMode::Everyone);
+    assert!(old.chat_muted.is_empty());
+}
+
+#[test]
+fn test_slow_mode_wait() {
+    let mut config = RoomConfig::default();
+    assert_eq!(config.slow_mode_wait_ms(Some(1_000), 1_500), 0);
+    config.chat_slow_mode_secs = 10;
+    assert_eq!(config.slow_mode_wait_ms(None, 1_500), 0);
+    assert_eq!(config.slow_mode_wait_ms(Some(1_000), 1_500), 9_500);
+    assert_eq!(config.slow_mode_wait_ms(Some(1_000), 11_000), 0);
+}
+
+#[test]
+fn test_slash_commands() {
+    use commands::{parse, CommandContext, CommandError, CommandOutcome};
+    let people = vec![named("me", "Host"), named("a", "Al Smith"), named("b", "Bob"), named("c", "bob")];
+    let host = CommandContext { participants: &people, my_id: Some("me"), is_moderator: true };
+    let guest = CommandContext { is_moderator: false, ..host };
+
+    assert_eq!(parse("hello /raise", &host), None);
+    assert_eq!(parse("/raise", &guest), Some(Ok(CommandOutcome::Send(ClientMessage::ToggleRaiseHand))));
+    assert_eq!(parse("/kick @al smith", &host), Some(Ok(CommandOutcome::Send(ClientMessage::KickParticipant("a".to_string())))));
+    assert_eq!(parse("/kick @Al Smith", &guest), Some(Err(CommandError::ModeratorsOnly("kick"))));
+    assert_eq!(parse("/mute @Bob", &host), Some(Err(CommandError::AmbiguousParticipant("Bob".to_string()))));
+    assert_eq!(parse("/unmute Carol", &host), Some(Err(CommandError::NoSuchParticipant("Carol".to_string()))));
+    assert_eq!(
+        parse("/mute Al Smith", &host),
+        Some(Ok(CommandOutcome::Send(ClientMessage::SetChatMuted { participant_id: "a".to_string(), muted: true })))
+    );
+    assert_eq!(parse("/dance", &guest), Some(Err(CommandError::Unknown("dance".to_string()))));
+    assert!(matches!(parse("/me", &guest), Some(Err(CommandError::Usage(_)))));
+
+    let Some(Ok(CommandOutcome::Send(ClientMessage::Chat { content, .. }))) = parse("/me waves", &guest) else { panic!("expected a chat message") };
+    assert_eq!(commands::me_action(&content), Some("waves"));
+    let Some(Ok(CommandOutcome::Send(ClientMessage::Chat { content, .. }))) = parse("//me is literal", &guest) else { panic!("expected a chat message") };
+    assert_eq!(content, "/me is literal");
+
+    let Some(Ok(CommandOutcome::Help(text))) = parse("/help", &guest) else { panic!("expected help") };
+    assert!(text.contains("/raise") && !text.contains("/kick"));
+}
+
+#[test]
+fn test_poll_and_timer_commands() {
+    use commands::{parse, parse_duration, CommandContext, CommandError, CommandOutcome};
+    let host = CommandContext { participants: &[], my_id: Some("me"), is_moderator: true };
+
+    let Some(Ok(CommandOutcome::Send(ClientMessage::CreatePoll(poll)))) = parse("/poll Lunch? | Pizza | Sushi |  | Tacos", &host) else { panic!("expected a poll") };
+    assert_eq!(poll.question, "Lunch?");
+    assert_eq!(poll.options.iter().map(|o| (o.id, o.text.as_str())).collect::<Vec<_>>(), vec![(0, "Pizza"), (1, "Sushi"), (2, "Tacos")]);
+    assert!(matches!(parse("/poll Only one? | Yes", &host), Some(Err(CommandError::Usage(_)))));
+
+    assert_eq!(parse_duration("5m"), Some(300));
+    assert_eq!(parse_duration("1h30m"), Some(5400));
+    assert_eq!(parse_duration("90S"), Some(90));
+    assert_eq!(parse_duration("2"), Some(120));
+    assert_eq!(parse_duration("5x"), None);
+    assert_eq!(parse_duration("m"), None);
+    assert_eq!(parse("/timer 5m", &host), Some(Ok(CommandOutcome::Send(ClientMessage::SetTimer(300)))));
+    assert_eq!(parse("/timer off", &host), Some(Ok(CommandOutcome::Send(ClientMessage::SetTimer(0)))));
+    assert_eq!(parse("/timer 9h", &host), Some(Err(CommandError::InvalidDuration)));
+}
+
+#[test]
+fn test_poll_views() {
+    let mut poll = Poll {
+        id: "p1".to_string(),
+        question: "Color?".to_string(),
+        options: vec![
+            PollOption { id: 0, text: "Red".to_string(), votes: 1, correct: false },
+            PollOption { id: 1, text: "Blue".to_string(), votes: 1, correct: false },
+        ],
+        voters: ["alice".to_string(), "bob".to_string()].into_iter().collect(),
+        voter_count: 2,
+        ballots: [("alice".to_string(), vec![0]), ("bob".to_string(), vec![1])].into_iter().collect(),
+        ..Default::default()
+    };
+    assert_eq!(poll.public_view(), poll);
+
+    poll.hide_results = true;
+    let public = poll.public_view();
+    assert!(public.options.iter().all(|o| o.votes == 0));
+    assert!(public.ballots.is_empty());
+    assert_eq!(public.voters.len(), 2);
+    assert_eq!(poll.moderator_view(), poll);
+
+    poll.anonymous = true;
+    poll.closed = true;
+    let public = poll.public_view();
+    assert_eq!(public.options[1].votes, 1);
+    assert_eq!(public.voter_count, 2);
+    assert!(public.voters.is_empty() && public.ballots.is_empty());
+}
+
+#[test]
+fn test_quiz_answers() {
+    let mut poll = Poll {
+        quiz: true,
+        options: vec![
+            PollOption { id: 0, text: "3".to_string(), votes: 0, correct: false },
+            PollOption { id: 1, text: "4".to_string(), votes: 0, correct: true },
+            PollOption { id: 2, text: "2 + 2".to_string(), votes: 0, correct: true },
+        ],
+        ..Default::default()
+    };
+    assert!(poll.is_correct(&[1]) && poll.is_correct(&[2]));
+    assert!(!poll.is_correct(&[0]) && !poll.is_correct(&[1, 2]));
+    poll.multiple_choice = true;
+    assert!(poll.is_correct(&[1, 2]));
+    assert!(!poll.is_correct(&[1]) && !poll.is_correct(&[0, 1, 2]));
+
+    assert!(poll.redacted());
+    assert!(poll.public_view().options.iter().all(|o| !o.correct));
+    assert_eq!(poll.moderator_view(), poll);
+    poll.revealed = true;
+    assert_eq!(poll.public_view(), poll);
+}
+
+#[test]
+fn test_sort_questions() {
+    let question = |id: &str, upvotes: usize, answered: bool, pinned: bool, created_at: u64| Question {
+        id: id.to_string(),
+        upvoters: (0..upvotes).map(|i| i.to_string()).collect(),
+        answered,
+        pinned,
+        created_at,
+        ..Default::default()
+    };
+    let mut questions = vec![
+        question("answered", 9, true, false, 1),
+        question("old", 2, false, false, 1),
+        question("popular", 5, false, false, 3),
+        question("new", 2, false, false, 2),
+        question("pinned", 0, true, true, 4),
+    ];
+    sort_questions(&mut questions);
+    let order: Vec<&str> = questions.iter().map(|q| q.id.as_str()).collect();
+    assert_eq!(order, vec!["pinned", "popular", "old", "new", "answered"]);
+}
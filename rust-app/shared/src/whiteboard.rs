@@ -0,0 +1,214 @@
+// Elements drawn on the shared whiteboard. The server gives each one an ID when it is
+// added, so it can later be selected, moved, edited or deleted by anyone in the room.
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ELEMENTS: usize = 5000;
+pub const MAX_PATH_POINTS: usize = 5000;
+pub const MAX_TEXT_CHARS: usize = 1000;
+pub const MAX_STROKE_WIDTH: f64 = 50.0;
+pub const STROKE_WIDTHS: [f64; 5] = [1.0, 2.0, 4.0, 8.0, 16.0];
+pub const STICKY_NOTE_SIZE: f64 = 160.0;
+pub const STICKY_NOTE_FONT_SIZE: f64 = 14.0;
+const MIN_FONT_SIZE: f64 = 8.0;
+const MAX_FONT_SIZE: f64 = 96.0;
+// Rough width of a character relative to the font size, for text bounds
+const CHAR_WIDTH_RATIO: f64 = 0.6;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    fn offset(self, dx: f64, dy: f64) -> Self {
+        Self { x: self.x + dx, y: self.y + dy }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    Path { points: Vec<Point> }, // Freehand stroke
+    Rect { from: Point, to: Point }, // Opposite corners
+    Ellipse { from: Point, to: Point }, // Corners of the bounding box
+    Arrow { from: Point, to: Point }, // Head at `to`
+    Text { at: Point, text: String, font_size: f64 }, // `at` is the top left corner
+    StickyNote { at: Point, text: String },
+}
+
+impl Shape {
+    /// Top left and bottom right corners of the area the shape covers, ignoring stroke width.
+    pub fn bounds(&self) -> (Point, Point) {
+        let corners = |a: Point, b: Point| (Point::new(a.x.min(b.x), a.y.min(b.y)), Point::new(a.x.max(b.x), a.y.max(b.y)));
+        match self {
+            Shape::Path { points } => {
+                let first = points.first().copied().unwrap_or_default();
+                points.iter().fold((first, first), |(min, max), p| {
+                    (Point::new(min.x.min(p.x), min.y.min(p.y)), Point::new(max.x.max(p.x), max.y.max(p.y)))
+                })
+            },
+            Shape::Rect { from, to } | Shape::Ellipse { from, to } | Shape::Arrow { from, to } => corners(*from, *to),
+            Shape::Text { at, text, font_size } => {
+                let lines = text.lines().count().max(1) as f64;
+                let longest = text.lines().map(|l| l.chars().count()).max().unwrap_or(0) as f64;
+                (*at, at.offset(longest * font_size * CHAR_WIDTH_RATIO, lines * font_size * 1.2))
+            },
+            Shape::StickyNote { at, .. } => (*at, at.offset(STICKY_NOTE_SIZE, STICKY_NOTE_SIZE)),
+        }
+    }
+
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        match self {
+            Shape::Path { points } => {
+                for p in points {
+                    *p = p.offset(dx, dy);
+                }
+            },
+            Shape::Rect { from, to } | Shape::Ellipse { from, to } | Shape::Arrow { from, to } => {
+                *from = from.offset(dx, dy);
+                *to = to.offset(dx, dy);
+            },
+            Shape::Text { at, .. } | Shape::StickyNote { at, .. } => *at = at.offset(dx, dy),
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Shape::Text { text, .. } | Shape::StickyNote { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn text_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Shape::Text { text, .. } | Shape::StickyNote { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    fn points(&self) -> Vec<Point> {
+        match self {
+            Shape::Path { points } => points.clone(),
+            Shape::Rect { from, to } | Shape::Ellipse { from, to } | Shape::Arrow { from, to } => vec![*from, *to],
+            Shape::Text { at, .. } | Shape::StickyNote { at, .. } => vec![*at],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WhiteboardElement {
+    pub id: String, // Assigned by the server
+    pub shape: Shape,
+    pub color: String,
+    pub width: f64, // Stroke width
+    #[serde(default)]
+    pub sender_id: String, // Who drew it
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WhiteboardError {
+    Invalid,
+    Full,
+    NotFound,
+}
+
+impl WhiteboardError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            WhiteboardError::Invalid => "That can't be drawn on the whiteboard",
+            WhiteboardError::Full => "The whiteboard is full, delete something first",
+            WhiteboardError::NotFound => "That was already removed from the whiteboard",
+        }
+    }
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 { 0.0 } else { (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_sq).clamp(0.0, 1.0) };
+    let (cx, cy) = (a.x + t * dx, a.y + t * dy);
+    ((p.x - cx).powi(2) + (p.y - cy).powi(2)).sqrt()
+}
+
+impl WhiteboardElement {
+    /// Whether `p` touches the element: within `tolerance` of a line or arrow,
+    /// or inside (give or take `tolerance`) any other shape.
+    pub fn hit(&self, p: Point, tolerance: f64) -> bool {
+        let reach = tolerance + self.width / 2.0;
+        match &self.shape {
+            Shape::Path { points } => match points.as_slice() {
+                [only] => distance_to_segment(p, *only, *only) <= reach,
+                points => points.windows(2).any(|w| distance_to_segment(p, w[0], w[1]) <= reach),
+            },
+            Shape::Arrow { from, to } => distance_to_segment(p, *from, *to) <= reach,
+            Shape::Ellipse { from, to } => {
+                let (rx, ry) = (((to.x - from.x).abs() / 2.0) + reach, ((to.y - from.y).abs() / 2.0) + reach);
+                let (cx, cy) = ((from.x + to.x) / 2.0, (from.y + to.y) / 2.0);
+                ((p.x - cx) / rx).powi(2) + ((p.y - cy) / ry).powi(2) <= 1.0
+            },
+            shape => {
+                let (min, max) = shape.bounds();
+                p.x >= min.x - reach && p.x <= max.x + reach && p.y >= min.y - reach && p.y <= max.y + reach
+            },
+        }
+    }
+
+    /// Checks what a client sent before it is stored: finite coordinates, sane sizes and text.
+    pub fn validate(&self) -> Result<(), WhiteboardError> {
+        let points = self.shape.points();
+        let valid = points.iter().all(|p| p.x.is_finite() && p.y.is_finite())
+            && self.width > 0.0
+            && self.width <= MAX_STROKE_WIDTH
+            && !self.color.is_empty()
+            && self.color.len() <= 32
+            && match &self.shape {
+                Shape::Path { points } => !points.is_empty() && points.len() <= MAX_PATH_POINTS,
+                Shape::Text { text, font_size, .. } => {
+                    !text.trim().is_empty() && text.chars().count() <= MAX_TEXT_CHARS && (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(font_size)
+                },
+                Shape::StickyNote { text, .. } => text.chars().count() <= MAX_TEXT_CHARS,
+                _ => true,
+            };
+        if valid {
+            Ok(())
+        } else {
+            Err(WhiteboardError::Invalid)
+        }
+    }
+}
+
+/// The topmost element under `p`, i.e. the last one drawn.
+pub fn element_at(elements: &[WhiteboardElement], p: Point, tolerance: f64) -> Option<&WhiteboardElement> {
+    elements.iter().rev().find(|e| e.hit(p, tolerance))
+}
+
+/// Splits sticky note text into lines that fit the note, breaking between words where possible.
+pub fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let fits = line.chars().count() + word.chars().count() + usize::from(!line.is_empty()) <= max_chars;
+            if !line.is_empty() && !fits {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            // A single word longer than a line is cut
+            while line.chars().count() > max_chars {
+                let rest: String = line.chars().skip(max_chars).collect();
+                line = line.chars().take(max_chars).collect();
+                lines.push(std::mem::replace(&mut line, rest));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}